@@ -1,12 +1,15 @@
 use crate::error::ContractError;
 use crate::execute::{
     execute_create_pool, execute_register_pool_type, execute_update_admin,
-    execute_update_default_pool_logic_code_id,
+    execute_update_default_pool_logic_code_id, execute_update_fee_tiers,
+    execute_update_flash_loan_fee, execute_update_protocol_fee,
 };
 use crate::msg::{ExecuteMsg, InstantiateMsg, QueryMsg};
-use crate::query::{query_config, query_pool_address};
+use crate::query::{query_config, query_flash_loan_fee, query_pool_address, query_protocol_fee};
 use crate::reply::handle_lp_instantiate_reply;
-use crate::state::{Config, CONFIG, CONTRACT_NAME, CONTRACT_VERSION};
+use crate::state::{
+    format_fee_tiers, validate_fee_tiers, Config, CONFIG, CONTRACT_NAME, CONTRACT_VERSION,
+};
 use cosmwasm_std::{
     entry_point, Binary, Deps, DepsMut, Env, MessageInfo, Reply, Response, StdResult,
 };
@@ -19,12 +22,17 @@ pub fn instantiate(
     _env: Env,
     _info: MessageInfo,
     msg: InstantiateMsg,
-) -> StdResult<Response> {
+) -> Result<Response, ContractError> {
     let admin_addr = deps.api.addr_validate(&msg.admin)?;
+    validate_fee_tiers(&msg.fee_tiers)?;
 
     let cfg = Config {
         default_pool_logic_code_id: msg.default_pool_logic_code_id,
         admin: admin_addr.clone(),
+        fee_tiers: msg.fee_tiers,
+        treasury: None,
+        protocol_fee_share_bps: 0,
+        flash_loan_fee_bps: 0,
     };
 
     CONFIG.save(deps.storage, &cfg)?;
@@ -35,7 +43,8 @@ pub fn instantiate(
         .add_attribute(
             "default_pool_logic_code_id",
             cfg.default_pool_logic_code_id.to_string(),
-        ))
+        )
+        .add_attribute("fee_tiers", format_fee_tiers(&cfg.fee_tiers)))
 }
 
 #[entry_point]
@@ -50,7 +59,16 @@ pub fn execute(
             denom_a,
             denom_b,
             pool_logic_code_id,
-        } => execute_create_pool(deps, env, info, denom_a, denom_b, pool_logic_code_id),
+            fee_bps,
+        } => execute_create_pool(
+            deps,
+            env,
+            info,
+            denom_a,
+            denom_b,
+            pool_logic_code_id,
+            fee_bps,
+        ),
         ExecuteMsg::RegisterPoolType { pool_logic_code_id } => {
             execute_register_pool_type(deps, info, pool_logic_code_id)
         }
@@ -58,6 +76,14 @@ pub fn execute(
         ExecuteMsg::UpdateDefaultLpCodeId { new_code_id } => {
             execute_update_default_pool_logic_code_id(deps, info, new_code_id)
         }
+        ExecuteMsg::UpdateFeeTiers { fee_tiers } => execute_update_fee_tiers(deps, info, fee_tiers),
+        ExecuteMsg::UpdateProtocolFee {
+            treasury,
+            share_bps,
+        } => execute_update_protocol_fee(deps, info, treasury, share_bps),
+        ExecuteMsg::UpdateFlashLoanFee { fee_bps } => {
+            execute_update_flash_loan_fee(deps, info, fee_bps)
+        }
     }
 }
 
@@ -73,7 +99,10 @@ pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
             denom_a,
             denom_b,
             pool_logic_code_id,
-        } => query_pool_address(deps, denom_a, denom_b, pool_logic_code_id),
+            fee_bps,
+        } => query_pool_address(deps, denom_a, denom_b, pool_logic_code_id, fee_bps),
         QueryMsg::Config {} => query_config(deps),
+        QueryMsg::ProtocolFee {} => query_protocol_fee(deps),
+        QueryMsg::FlashLoanFee {} => query_flash_loan_fee(deps),
     }
 }
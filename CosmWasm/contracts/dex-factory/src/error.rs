@@ -49,8 +49,24 @@ pub enum ContractError {
     #[error("Denom A and Denom B must be different")]
     IdenticalDenoms {},
 
-    #[error("Pool already exists for denoms {denom1} and {denom2}")]
-    PoolAlreadyExists { denom1: String, denom2: String },
+    #[error("Pool already exists for denoms {denom1} and {denom2} with fee {fee_bps} bps")]
+    PoolAlreadyExists {
+        denom1: String,
+        denom2: String,
+        fee_bps: u16,
+    },
+
+    #[error("Fee tier {fee_bps} bps is not allowed by the factory")]
+    FeeTierNotAllowed { fee_bps: u16 },
+
+    #[error("Invalid fee tier {fee_bps} bps: tiers must be unique and below 10000")]
+    InvalidFeeTier { fee_bps: u16 },
+
+    #[error("Invalid protocol fee share {share_bps} bps: must not exceed 10000")]
+    InvalidProtocolFeeShare { share_bps: u16 },
+
+    #[error("Invalid flash loan fee {fee_bps} bps: must be below 10000")]
+    InvalidFlashLoanFee { fee_bps: u16 },
 
     #[error("Pool not found for denoms {denom1} and {denom2}")]
     PoolNotFound { denom1: String, denom2: String },
@@ -1,8 +1,9 @@
 use crate::error::ContractError;
 use crate::msg::PoolContractInstantiateMsg;
 use crate::state::{
-    get_ordered_denoms as get_ordered_denoms_state, CONFIG, INSTANTIATE_POOL_REPLY_ID,
-    PENDING_POOL_INSTANCE, POOLS,
+    format_fee_tiers, get_ordered_denoms as get_ordered_denoms_state, validate_fee_tiers, CONFIG,
+    INSTANTIATE_POOL_REPLY_ID, MAX_FEE_BPS, MAX_PROTOCOL_FEE_SHARE_BPS, PENDING_POOL_INSTANCE,
+    POOLS,
 };
 use cosmwasm_std::{to_json_binary, DepsMut, Env, MessageInfo, Response, SubMsg, WasmMsg};
 
@@ -15,22 +16,27 @@ pub(crate) fn execute_create_pool(
     denom_a: String,
     denom_b: String,
     pool_logic_code_id: u64,
+    fee_bps: u16,
 ) -> Result<Response, ContractError> {
     if denom_a == denom_b {
         return Err(ContractError::IdenticalDenoms {});
     }
     let pool_key_denoms = get_ordered_denoms_state(denom_a.clone(), denom_b.clone());
     let cfg = CONFIG.load(deps.storage)?;
+    if !cfg.fee_tiers.contains(&fee_bps) {
+        return Err(ContractError::FeeTierNotAllowed { fee_bps });
+    }
     let pool_key = (
         pool_key_denoms.0.clone(),
         pool_key_denoms.1.clone(),
-        pool_logic_code_id,
+        (pool_logic_code_id, fee_bps),
     );
 
     if POOLS.may_load(deps.storage, pool_key.clone())?.is_some() {
         return Err(ContractError::PoolAlreadyExists {
             denom1: pool_key.0,
             denom2: pool_key.1,
+            fee_bps,
         });
     }
     if PENDING_POOL_INSTANCE.may_load(deps.storage)?.is_some() {
@@ -46,6 +52,7 @@ pub(crate) fn execute_create_pool(
         denom_b: pool_key.1.clone(),
         lp_token_code_id: cfg.default_pool_logic_code_id,
         factory_addr: env.contract.address.clone(),
+        fee_bps,
     };
 
     let submsg = SubMsg::reply_on_success(
@@ -55,8 +62,8 @@ pub(crate) fn execute_create_pool(
             msg: to_json_binary(&instantiate_pool_msg)?,
             funds: vec![],
             label: format!(
-                "DEX Pool-{}-{} (Logic {})",
-                pool_key.0, pool_key.1, pool_logic_code_id
+                "DEX Pool-{}-{} (Logic {}, Fee {} bps)",
+                pool_key.0, pool_key.1, pool_logic_code_id, fee_bps
             ),
         },
         INSTANTIATE_POOL_REPLY_ID,
@@ -68,6 +75,7 @@ pub(crate) fn execute_create_pool(
         .add_submessage(submsg)
         .add_attribute("action", "create_pool_instance")
         .add_attribute("pool_logic_code_id", pool_logic_code_id.to_string())
+        .add_attribute("fee_bps", fee_bps.to_string())
         .add_attribute("denom_a", pool_key.0)
         .add_attribute("denom_b", pool_key.1))
 }
@@ -126,3 +134,68 @@ pub(crate) fn execute_update_default_pool_logic_code_id(
         .add_attribute("action", "update_default_pool_logic_code_id")
         .add_attribute("new_code_id", new_code_id.to_string()))
 }
+
+pub(crate) fn execute_update_fee_tiers(
+    deps: DepsMut,
+    info: MessageInfo,
+    fee_tiers: Vec<u16>,
+) -> Result<Response, ContractError> {
+    let mut cfg = CONFIG.load(deps.storage)?;
+    if cfg.admin != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    validate_fee_tiers(&fee_tiers)?;
+    cfg.fee_tiers = fee_tiers;
+    CONFIG.save(deps.storage, &cfg)?;
+    Ok(Response::new()
+        .add_attribute("action", "update_fee_tiers")
+        .add_attribute("fee_tiers", format_fee_tiers(&cfg.fee_tiers)))
+}
+
+pub(crate) fn execute_update_protocol_fee(
+    deps: DepsMut,
+    info: MessageInfo,
+    treasury: Option<String>,
+    share_bps: u16,
+) -> Result<Response, ContractError> {
+    let mut cfg = CONFIG.load(deps.storage)?;
+    if cfg.admin != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    if share_bps > MAX_PROTOCOL_FEE_SHARE_BPS {
+        return Err(ContractError::InvalidProtocolFeeShare { share_bps });
+    }
+    cfg.treasury = treasury
+        .map(|addr| deps.api.addr_validate(&addr))
+        .transpose()?;
+    cfg.protocol_fee_share_bps = share_bps;
+    CONFIG.save(deps.storage, &cfg)?;
+    Ok(Response::new()
+        .add_attribute("action", "update_protocol_fee")
+        .add_attribute(
+            "treasury",
+            cfg.treasury
+                .map(|addr| addr.to_string())
+                .unwrap_or_default(),
+        )
+        .add_attribute("share_bps", share_bps.to_string()))
+}
+
+pub(crate) fn execute_update_flash_loan_fee(
+    deps: DepsMut,
+    info: MessageInfo,
+    fee_bps: u16,
+) -> Result<Response, ContractError> {
+    let mut cfg = CONFIG.load(deps.storage)?;
+    if cfg.admin != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+    if fee_bps >= MAX_FEE_BPS {
+        return Err(ContractError::InvalidFlashLoanFee { fee_bps });
+    }
+    cfg.flash_loan_fee_bps = fee_bps;
+    CONFIG.save(deps.storage, &cfg)?;
+    Ok(Response::new()
+        .add_attribute("action", "update_flash_loan_fee")
+        .add_attribute("fee_bps", fee_bps.to_string()))
+}
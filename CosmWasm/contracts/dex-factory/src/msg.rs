@@ -8,16 +8,20 @@ use crate::state::Config;
 pub struct InstantiateMsg {
     pub default_pool_logic_code_id: u64,
     pub admin: String,
+    /// Swap fees (in basis points) pools may be created with
+    pub fee_tiers: Vec<u16>,
 }
 
 /// Execute messages for the Factory contract.
 #[cw_serde]
 pub enum ExecuteMsg {
     /// Create a new liquidity pool instance using a specific pool logic contract.
+    /// `fee_bps` must be one of the configured fee tiers.
     CreatePool {
         denom_a: String,
         denom_b: String,
         pool_logic_code_id: u64,
+        fee_bps: u16,
     },
     /// Allows admin to register a new pool logic contract code ID.
     RegisterPoolType { pool_logic_code_id: u64 },
@@ -25,6 +29,16 @@ pub enum ExecuteMsg {
     UpdateAdmin { new_admin: Option<String> },
     /// Update default LP token code ID.
     UpdateDefaultLpCodeId { new_code_id: u64 },
+    /// Replace the list of allowed fee tiers. Existing pools keep their fee.
+    UpdateFeeTiers { fee_tiers: Vec<u16> },
+    /// Set the treasury and the share of swap fees it receives (in basis points).
+    /// The fee is off while `treasury` is `None` or `share_bps` is zero.
+    UpdateProtocolFee {
+        treasury: Option<String>,
+        share_bps: u16,
+    },
+    /// Set the fee pools charge on flash loans (in basis points). Must be below 10000.
+    UpdateFlashLoanFee { fee_bps: u16 },
 }
 
 #[cw_serde]
@@ -37,6 +51,7 @@ pub struct PoolContractInstantiateMsg {
     pub denom_b: String,
     pub lp_token_code_id: u64,
     pub factory_addr: Addr,
+    pub fee_bps: u16,
 }
 
 /// Factory Query Messages
@@ -49,8 +64,27 @@ pub enum QueryMsg {
         denom_a: String,
         denom_b: String,
         pool_logic_code_id: u64,
+        fee_bps: u16,
     },
     /// Get the factory configuration.
     #[returns(Config)]
     Config {},
-}
\ No newline at end of file
+    /// Get the protocol fee settings. Queried by the pools on liquidity events.
+    #[returns(ProtocolFeeResponse)]
+    ProtocolFee {},
+    /// Get the flash loan fee. Queried by the pools on each flash loan.
+    #[returns(FlashLoanFeeResponse)]
+    FlashLoanFee {},
+}
+
+#[cw_serde]
+pub struct ProtocolFeeResponse {
+    /// `None` while the protocol fee is off
+    pub treasury: Option<Addr>,
+    pub share_bps: u16,
+}
+
+#[cw_serde]
+pub struct FlashLoanFeeResponse {
+    pub fee_bps: u16,
+}
@@ -1,3 +1,4 @@
+use crate::msg::{FlashLoanFeeResponse, ProtocolFeeResponse};
 use crate::state::{get_ordered_denoms as get_ordered_denoms_state, Config, CONFIG, POOLS};
 use cosmwasm_std::{to_json_binary, Binary, Deps, StdResult};
 
@@ -8,9 +9,10 @@ pub(crate) fn query_pool_address(
     denom_a: String,
     denom_b: String,
     pool_logic_code_id: u64,
+    fee_bps: u16,
 ) -> StdResult<Binary> {
     let key_denoms = get_ordered_denoms_state(denom_a, denom_b);
-    let key = (key_denoms.0, key_denoms.1, pool_logic_code_id);
+    let key = (key_denoms.0, key_denoms.1, (pool_logic_code_id, fee_bps));
     let pool_addr = POOLS.load(deps.storage, key)?;
     to_json_binary(&pool_addr)
 }
@@ -20,8 +22,35 @@ pub(crate) fn query_config(deps: Deps) -> StdResult<Binary> {
     let resp = Config {
         admin: cfg.admin,
         default_pool_logic_code_id: cfg.default_pool_logic_code_id,
+        fee_tiers: cfg.fee_tiers,
+        treasury: cfg.treasury,
+        protocol_fee_share_bps: cfg.protocol_fee_share_bps,
+        flash_loan_fee_bps: cfg.flash_loan_fee_bps,
     };
     to_json_binary(&resp)
 }
 
+pub(crate) fn query_protocol_fee(deps: Deps) -> StdResult<Binary> {
+    let cfg = CONFIG.load(deps.storage)?;
+    // Only report a treasury while the fee is actually on
+    let resp = match cfg.treasury {
+        Some(treasury) if cfg.protocol_fee_share_bps > 0 => ProtocolFeeResponse {
+            treasury: Some(treasury),
+            share_bps: cfg.protocol_fee_share_bps,
+        },
+        _ => ProtocolFeeResponse {
+            treasury: None,
+            share_bps: 0,
+        },
+    };
+    to_json_binary(&resp)
+}
+
+pub(crate) fn query_flash_loan_fee(deps: Deps) -> StdResult<Binary> {
+    let cfg = CONFIG.load(deps.storage)?;
+    to_json_binary(&FlashLoanFeeResponse {
+        fee_bps: cfg.flash_loan_fee_bps,
+    })
+}
+
 // Removed old query_pool implementation
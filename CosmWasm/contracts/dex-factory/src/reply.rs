@@ -21,10 +21,13 @@ pub fn handle_lp_instantiate_reply(deps: DepsMut, msg: Reply) -> Result<Response
 
     PENDING_POOL_INSTANCE.remove(deps.storage);
 
+    let (denom_a, denom_b, (pool_logic_code_id, fee_bps)) = pool_key;
+
     Ok(Response::new()
         .add_attribute("action", "pool_instance_created")
         .add_attribute("pool_contract_address", pool_contract_addr.to_string())
-        .add_attribute("denom_a", pool_key.0)
-        .add_attribute("denom_b", pool_key.1)
-        .add_attribute("pool_logic_code_id", pool_key.2.to_string()))
+        .add_attribute("denom_a", denom_a)
+        .add_attribute("denom_b", denom_b)
+        .add_attribute("pool_logic_code_id", pool_logic_code_id.to_string())
+        .add_attribute("fee_bps", fee_bps.to_string()))
 }
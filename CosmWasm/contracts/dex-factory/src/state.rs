@@ -2,6 +2,8 @@ use cosmwasm_schema::cw_serde;
 use cosmwasm_std::Addr;
 use cw_storage_plus::{Item, Map};
 
+use crate::error::ContractError;
+
 pub const INSTANTIATE_POOL_REPLY_ID: u64 = 1;
 pub const CONTRACT_NAME: &str = "crates.io:cw-dex-factory";
 pub const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -12,15 +14,32 @@ pub struct Config {
     pub default_pool_logic_code_id: u64,
     /// Address with power to update the config
     pub admin: Addr,
+    /// Swap fees (in basis points) pools may be created with
+    pub fee_tiers: Vec<u16>,
+    /// Receives the protocol fee as LP tokens minted by the pools
+    pub treasury: Option<Addr>,
+    /// Share of the swap fees taken by the protocol, in basis points (0 = off)
+    pub protocol_fee_share_bps: u16,
+    /// Fee on flash loans in basis points, paid to the pool's LPs
+    pub flash_loan_fee_bps: u16,
 }
 
+/// Maximum fee in basis points (exclusive)
+pub const MAX_FEE_BPS: u16 = 10_000;
+
+/// Maximum protocol fee share in basis points (inclusive)
+pub const MAX_PROTOCOL_FEE_SHARE_BPS: u16 = 10_000;
+
+// Key: (denom_a, denom_b, (pool_logic_code_id, fee_bps))
+pub type PoolKey = (String, String, (u64, u16));
+
 // Temporary storage for pool key during pool contract instantiation reply
-pub type PendingPoolInstanceKey = (String, String, u64);
+pub type PendingPoolInstanceKey = PoolKey;
 pub const PENDING_POOL_INSTANCE: Item<PendingPoolInstanceKey> = Item::new("pending_pool_instance");
 
 pub const CONFIG: Item<Config> = Item::new("config");
-// Key: (denom_a, denom_b, pool_logic_code_id), Value: Addr of the pool contract instance
-pub const POOLS: Map<(String, String, u64), Addr> = Map::new("pools");
+// Key: (denom_a, denom_b, (pool_logic_code_id, fee_bps)), Value: Addr of the pool contract instance
+pub const POOLS: Map<PoolKey, Addr> = Map::new("pools");
 
 /// Returns denoms in a canonical (alphabetical) order.
 /// Keeping this here for pool key creation.
@@ -32,4 +51,23 @@ pub(crate) fn get_ordered_denoms(denom_a: String, denom_b: String) -> (String, S
     }
 }
 
+/// Fee tiers must be unique and below `MAX_FEE_BPS`.
+pub(crate) fn validate_fee_tiers(fee_tiers: &[u16]) -> Result<(), ContractError> {
+    for (i, fee_bps) in fee_tiers.iter().enumerate() {
+        if *fee_bps >= MAX_FEE_BPS || fee_tiers[..i].contains(fee_bps) {
+            return Err(ContractError::InvalidFeeTier { fee_bps: *fee_bps });
+        }
+    }
+    Ok(())
+}
+
+/// Formats fee tiers as a comma separated attribute value.
+pub(crate) fn format_fee_tiers(fee_tiers: &[u16]) -> String {
+    fee_tiers
+        .iter()
+        .map(|fee_bps| fee_bps.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
 // Removed other helpers
@@ -0,0 +1,62 @@
+use crate::error::ContractError;
+use crate::execute::execute_swap_operations;
+use crate::msg::{Config, ExecuteMsg, InstantiateMsg, QueryMsg};
+use crate::query::{query_config, query_simulate_swap_operations};
+use crate::reply::handle_swap_reply;
+use crate::state::{CONFIG, CONTRACT_NAME, CONTRACT_VERSION};
+use cosmwasm_std::{
+    entry_point, Binary, Deps, DepsMut, Env, MessageInfo, Reply, Response, StdResult,
+};
+
+// --- Entry Points ---
+
+#[entry_point]
+pub fn instantiate(
+    deps: DepsMut,
+    _env: Env,
+    _info: MessageInfo,
+    msg: InstantiateMsg,
+) -> Result<Response, ContractError> {
+    let factory_addr = deps.api.addr_validate(&msg.factory_addr)?;
+    CONFIG.save(
+        deps.storage,
+        &Config {
+            factory_addr: factory_addr.clone(),
+        },
+    )?;
+    cw2::set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    Ok(Response::new()
+        .add_attribute("action", "instantiate")
+        .add_attribute("factory_addr", factory_addr.to_string()))
+}
+
+#[entry_point]
+pub fn execute(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: ExecuteMsg,
+) -> Result<Response, ContractError> {
+    match msg {
+        ExecuteMsg::ExecuteSwapOperations {
+            operations,
+            minimum_receive,
+        } => execute_swap_operations(deps, env, info, operations, minimum_receive),
+    }
+}
+
+#[entry_point]
+pub fn reply(deps: DepsMut, env: Env, msg: Reply) -> Result<Response, ContractError> {
+    handle_swap_reply(deps, env, msg)
+}
+
+#[entry_point]
+pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::Config {} => query_config(deps),
+        QueryMsg::SimulateSwapOperations {
+            offer_amount,
+            operations,
+        } => query_simulate_swap_operations(deps, offer_amount, operations),
+    }
+}
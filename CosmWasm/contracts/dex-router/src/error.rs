@@ -0,0 +1,45 @@
+use cosmwasm_std::{OverflowError, StdError, Uint128};
+use cw_utils::PaymentError;
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum ContractError {
+    #[error("{0}")]
+    Std(#[from] StdError),
+
+    #[error("{0}")]
+    OverflowError(#[from] OverflowError),
+
+    #[error("{0}")]
+    Payment(#[from] PaymentError),
+
+    #[error("Unknown reply id: {id}")]
+    UnknownReplyId { id: u64 },
+
+    #[error("Must provide at least one swap operation")]
+    NoSwapOperations {},
+
+    #[error("Too many swap operations: {count} exceeds the limit of {max}")]
+    TooManySwapOperations { count: usize, max: usize },
+
+    #[error("Offer and ask denom must be different: {denom}")]
+    IdenticalDenoms { denom: String },
+
+    #[error("Operation offers {offer_denom} but the previous one returns {ask_denom}")]
+    DisconnectedOperations {
+        ask_denom: String,
+        offer_denom: String,
+    },
+
+    #[error("Sent {sent} but the first operation offers {offer_denom}")]
+    OfferDenomMismatch { sent: String, offer_denom: String },
+
+    #[error("Another swap route is already being executed")]
+    SwapInProgress {},
+
+    #[error("Output amount {output} less than minimum requested {min_receive}")]
+    SwapMinimumReceiveViolation {
+        output: Uint128,
+        min_receive: Uint128,
+    },
+}
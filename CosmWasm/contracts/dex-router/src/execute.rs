@@ -0,0 +1,160 @@
+use cosmwasm_std::{
+    to_json_binary, Addr, Coin, Deps, DepsMut, Env, MessageInfo, Response, StdResult, SubMsg,
+    Uint128, WasmMsg,
+};
+use cw_utils::one_coin;
+
+use crate::error::ContractError;
+use crate::msg::{FactoryQueryMsg, PoolExecuteMsg, SwapOperation};
+use crate::state::{
+    PendingSwap, ResolvedOperation, CONFIG, MAX_SWAP_OPERATIONS, PENDING_SWAP, SWAP_REPLY_ID,
+};
+
+pub(crate) fn execute_swap_operations(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    operations: Vec<SwapOperation>,
+    minimum_receive: Uint128,
+) -> Result<Response, ContractError> {
+    // Only set while a route is in flight, i.e. when a pool calls back into the router
+    if PENDING_SWAP.may_load(deps.storage)?.is_some() {
+        return Err(ContractError::SwapInProgress {});
+    }
+
+    validate_operations(&operations)?;
+    let offer = one_coin(&info)?;
+    if offer.denom != operations[0].offer_denom() {
+        return Err(ContractError::OfferDenomMismatch {
+            sent: offer.denom,
+            offer_denom: operations[0].offer_denom().to_string(),
+        });
+    }
+    let operations = resolve_operations(deps.as_ref(), operations)?;
+
+    let mut pending = PendingSwap {
+        recipient: info.sender.clone(),
+        operations,
+        hop: 0,
+        ask_balance_before: Uint128::zero(),
+        minimum_receive,
+    };
+    let swap_msg = prepare_hop(deps.as_ref(), &env, &mut pending, offer.amount)?;
+    PENDING_SWAP.save(deps.storage, &pending)?;
+
+    Ok(Response::new()
+        .add_submessage(swap_msg)
+        .add_attribute("action", "execute_swap_operations")
+        .add_attribute("sender", info.sender.to_string())
+        .add_attribute("offer_denom", offer.denom)
+        .add_attribute("offer_amount", offer.amount.to_string())
+        .add_attribute("operations", pending.operations.len().to_string()))
+}
+
+// --- Internal Helpers ---
+
+/// Checks that the route is non-empty, bounded, and that each hop offers what the previous
+/// one returns.
+pub(crate) fn validate_operations(operations: &[SwapOperation]) -> Result<(), ContractError> {
+    if operations.is_empty() {
+        return Err(ContractError::NoSwapOperations {});
+    }
+    if operations.len() > MAX_SWAP_OPERATIONS {
+        return Err(ContractError::TooManySwapOperations {
+            count: operations.len(),
+            max: MAX_SWAP_OPERATIONS,
+        });
+    }
+    for operation in operations {
+        if operation.offer_denom() == operation.ask_denom() {
+            return Err(ContractError::IdenticalDenoms {
+                denom: operation.offer_denom().to_string(),
+            });
+        }
+    }
+    for pair in operations.windows(2) {
+        if pair[0].ask_denom() != pair[1].offer_denom() {
+            return Err(ContractError::DisconnectedOperations {
+                ask_denom: pair[0].ask_denom().to_string(),
+                offer_denom: pair[1].offer_denom().to_string(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Resolves every hop to a pool address, looking factory pools up by denom pair.
+pub(crate) fn resolve_operations(
+    deps: Deps,
+    operations: Vec<SwapOperation>,
+) -> StdResult<Vec<ResolvedOperation>> {
+    let mut factory_addr = None;
+    operations
+        .into_iter()
+        .map(|operation| match operation {
+            SwapOperation::Pool {
+                pool_addr,
+                offer_denom,
+                ask_denom,
+            } => Ok(ResolvedOperation {
+                pool_addr: deps.api.addr_validate(&pool_addr)?,
+                offer_denom,
+                ask_denom,
+            }),
+            SwapOperation::Factory {
+                offer_denom,
+                ask_denom,
+                pool_logic_code_id,
+                fee_bps,
+            } => {
+                let factory = match &factory_addr {
+                    Some(addr) => addr,
+                    None => factory_addr.insert(CONFIG.load(deps.storage)?.factory_addr),
+                };
+                let pool_addr: Addr = deps.querier.query_wasm_smart(
+                    factory.as_str(),
+                    &FactoryQueryMsg::PoolAddress {
+                        denom_a: offer_denom.clone(),
+                        denom_b: ask_denom.clone(),
+                        pool_logic_code_id,
+                        fee_bps,
+                    },
+                )?;
+                Ok(ResolvedOperation {
+                    pool_addr,
+                    offer_denom,
+                    ask_denom,
+                })
+            }
+        })
+        .collect()
+}
+
+/// Builds the swap submessage for `pending.hop`, recording the router's ask denom balance
+/// so the reply can tell how much the pool returned.
+pub(crate) fn prepare_hop(
+    deps: Deps,
+    env: &Env,
+    pending: &mut PendingSwap,
+    offer_amount: Uint128,
+) -> Result<SubMsg, ContractError> {
+    let operation = &pending.operations[pending.hop];
+    pending.ask_balance_before = deps
+        .querier
+        .query_balance(&env.contract.address, &operation.ask_denom)?
+        .amount;
+
+    // Slippage is only enforced on the final output
+    let swap_msg = WasmMsg::Execute {
+        contract_addr: operation.pool_addr.to_string(),
+        msg: to_json_binary(&PoolExecuteMsg::Swap {
+            offer_denom: operation.offer_denom.clone(),
+            min_receive: Uint128::zero(),
+        })?,
+        funds: vec![Coin {
+            denom: operation.offer_denom.clone(),
+            amount: offer_amount,
+        }],
+    };
+    Ok(SubMsg::reply_on_success(swap_msg, SWAP_REPLY_ID))
+}
@@ -0,0 +1,112 @@
+use cosmwasm_schema::{cw_serde, QueryResponses};
+use cosmwasm_std::{Addr, Uint128};
+
+#[cw_serde]
+pub struct InstantiateMsg {
+    pub factory_addr: String, // Factory used to resolve `SwapOperation::Factory` pools
+}
+
+/// One hop of a route, swapping all of `offer_denom` received so far into `ask_denom`.
+#[cw_serde]
+pub enum SwapOperation {
+    /// Swap through the pool contract at `pool_addr`.
+    Pool {
+        pool_addr: String,
+        offer_denom: String,
+        ask_denom: String,
+    },
+    /// Swap through the factory pool registered for the denom pair, logic code and fee tier.
+    Factory {
+        offer_denom: String,
+        ask_denom: String,
+        pool_logic_code_id: u64,
+        fee_bps: u16,
+    },
+}
+
+impl SwapOperation {
+    pub fn offer_denom(&self) -> &str {
+        match self {
+            SwapOperation::Pool { offer_denom, .. } => offer_denom,
+            SwapOperation::Factory { offer_denom, .. } => offer_denom,
+        }
+    }
+
+    pub fn ask_denom(&self) -> &str {
+        match self {
+            SwapOperation::Pool { ask_denom, .. } => ask_denom,
+            SwapOperation::Factory { ask_denom, .. } => ask_denom,
+        }
+    }
+}
+
+#[cw_serde]
+pub enum ExecuteMsg {
+    /// Swaps the single coin sent through `operations` in order and sends the final
+    /// output back to the sender. Fails unless it is at least `minimum_receive`.
+    ExecuteSwapOperations {
+        operations: Vec<SwapOperation>,
+        minimum_receive: Uint128,
+    },
+}
+
+#[cw_serde]
+#[derive(QueryResponses)]
+pub enum QueryMsg {
+    #[returns(Config)]
+    Config {},
+    /// Quotes routing `offer_amount` through `operations` at the current reserves.
+    #[returns(SimulateSwapOperationsResponse)]
+    SimulateSwapOperations {
+        offer_amount: Uint128,
+        operations: Vec<SwapOperation>,
+    },
+}
+
+#[cw_serde]
+pub struct Config {
+    pub factory_addr: Addr,
+}
+
+#[cw_serde]
+pub struct SimulateSwapOperationsResponse {
+    /// Amount of the last ask denom returned by the route
+    pub amount: Uint128,
+}
+
+/// Subset of the pool execute messages used by the router.
+#[cw_serde]
+pub enum PoolExecuteMsg {
+    Swap {
+        offer_denom: String,
+        min_receive: Uint128,
+    },
+}
+
+/// Subset of the pool query messages used by the router.
+#[cw_serde]
+pub enum PoolQueryMsg {
+    SimulateSwap {
+        offer_denom: String,
+        offer_amount: Uint128,
+    },
+}
+
+/// Mirror of the pool `SimulationResponse`.
+#[cw_serde]
+pub struct SimulationResponse {
+    pub return_amount: Uint128,
+    pub commission_amount: Uint128,
+    pub spread_amount: Uint128,
+}
+
+/// Subset of the factory query messages used by the router.
+#[cw_serde]
+pub enum FactoryQueryMsg {
+    PoolAddress {
+        denom_a: String,
+        denom_b: String,
+        pool_logic_code_id: u64,
+        fee_bps: u16,
+    },
+}
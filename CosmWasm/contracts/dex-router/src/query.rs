@@ -0,0 +1,36 @@
+use cosmwasm_std::{to_json_binary, Binary, Deps, StdError, StdResult, Uint128};
+
+use crate::execute::{resolve_operations, validate_operations};
+use crate::msg::{PoolQueryMsg, SimulateSwapOperationsResponse, SimulationResponse, SwapOperation};
+use crate::state::CONFIG;
+
+// --- Query Handlers ---
+
+pub(crate) fn query_config(deps: Deps) -> StdResult<Binary> {
+    to_json_binary(&CONFIG.load(deps.storage)?)
+}
+
+pub(crate) fn query_simulate_swap_operations(
+    deps: Deps,
+    offer_amount: Uint128,
+    operations: Vec<SwapOperation>,
+) -> StdResult<Binary> {
+    validate_operations(&operations).map_err(|e| StdError::generic_err(e.to_string()))?;
+    let operations = resolve_operations(deps, operations)?;
+
+    // Each hop is quoted at the pool's current reserves, so routes visiting the same pool
+    // twice are quoted as if the earlier hop had not moved its price
+    let mut amount = offer_amount;
+    for operation in operations {
+        let simulation: SimulationResponse = deps.querier.query_wasm_smart(
+            operation.pool_addr,
+            &PoolQueryMsg::SimulateSwap {
+                offer_denom: operation.offer_denom,
+                offer_amount: amount,
+            },
+        )?;
+        amount = simulation.return_amount;
+    }
+
+    to_json_binary(&SimulateSwapOperationsResponse { amount })
+}
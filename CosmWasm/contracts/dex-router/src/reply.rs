@@ -0,0 +1,62 @@
+use cosmwasm_std::{BankMsg, Coin, DepsMut, Env, Reply, Response};
+
+use crate::error::ContractError;
+use crate::execute::prepare_hop;
+use crate::state::{PENDING_SWAP, SWAP_REPLY_ID};
+
+/// Forwards the output of the hop that just completed to the next pool, or pays it out to
+/// the recipient once the last hop is done.
+pub fn handle_swap_reply(deps: DepsMut, env: Env, msg: Reply) -> Result<Response, ContractError> {
+    if msg.id != SWAP_REPLY_ID {
+        return Err(ContractError::UnknownReplyId { id: msg.id });
+    }
+
+    let mut pending = PENDING_SWAP.load(deps.storage)?;
+    let operation = &pending.operations[pending.hop];
+    let ask_denom = operation.ask_denom.clone();
+    let pool_addr = operation.pool_addr.to_string();
+    let return_amount = deps
+        .querier
+        .query_balance(&env.contract.address, &ask_denom)?
+        .amount
+        .checked_sub(pending.ask_balance_before)?;
+
+    let hop_attributes = [
+        ("hop", pending.hop.to_string()),
+        ("pool", pool_addr),
+        ("return_amount", return_amount.to_string()),
+    ];
+
+    if pending.hop + 1 < pending.operations.len() {
+        pending.hop += 1;
+        let swap_msg = prepare_hop(deps.as_ref(), &env, &mut pending, return_amount)?;
+        PENDING_SWAP.save(deps.storage, &pending)?;
+        return Ok(Response::new()
+            .add_submessage(swap_msg)
+            .add_attribute("action", "swap_operation")
+            .add_attributes(hop_attributes));
+    }
+
+    PENDING_SWAP.remove(deps.storage);
+    if return_amount < pending.minimum_receive {
+        return Err(ContractError::SwapMinimumReceiveViolation {
+            output: return_amount,
+            min_receive: pending.minimum_receive,
+        });
+    }
+
+    let payout_msg = BankMsg::Send {
+        to_address: pending.recipient.to_string(),
+        amount: vec![Coin {
+            denom: ask_denom.clone(),
+            amount: return_amount,
+        }],
+    };
+
+    Ok(Response::new()
+        .add_message(payout_msg)
+        .add_attribute("action", "swap_operation")
+        .add_attributes(hop_attributes)
+        .add_attribute("recipient", pending.recipient.to_string())
+        .add_attribute("ask_denom", ask_denom))
+}
@@ -0,0 +1,36 @@
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{Addr, Uint128};
+use cw_storage_plus::Item;
+
+use crate::msg::Config;
+
+/// A route hop with its pool address resolved.
+#[cw_serde]
+pub struct ResolvedOperation {
+    pub pool_addr: Addr,
+    pub offer_denom: String,
+    pub ask_denom: String,
+}
+
+/// Route being executed, kept between the swap submessages.
+#[cw_serde]
+pub struct PendingSwap {
+    pub recipient: Addr,
+    pub operations: Vec<ResolvedOperation>,
+    /// Index of the hop whose swap is in flight
+    pub hop: usize,
+    /// Router balance of the in-flight hop's ask denom before the swap was sent
+    pub ask_balance_before: Uint128,
+    pub minimum_receive: Uint128,
+}
+
+/// Upper bound on the hops of a single route
+pub const MAX_SWAP_OPERATIONS: usize = 10;
+
+pub const CONFIG: Item<Config> = Item::new("config");
+pub const PENDING_SWAP: Item<PendingSwap> = Item::new("pending_swap");
+
+pub const SWAP_REPLY_ID: u64 = 1; // Local reply ID for this contract
+
+pub const CONTRACT_NAME: &str = "crates.io:cw-dex-router";
+pub const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
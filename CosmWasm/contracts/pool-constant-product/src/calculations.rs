@@ -1,4 +1,5 @@
 use crate::error::ContractError;
+use crate::state::MINIMUM_LIQUIDITY;
 use cosmwasm_std::{DivideByZeroError, Isqrt, Uint128, Uint256};
 
 /// Calculates the initial LP shares using the geometric mean: sqrt(a * b).
@@ -37,14 +38,94 @@ pub(crate) fn calculate_subsequent_lp_shares(
     Ok(std::cmp::min(share_a, share_b))
 }
 
+/// Portion of a deposit accepted by the pool and the LP shares it mints.
+#[derive(Debug, PartialEq)]
+pub(crate) struct DepositOutput {
+    /// Shares minted to the depositor
+    pub shares: Uint128,
+    /// Shares locked in the pool, only minted by the first deposit
+    pub locked_shares: Uint128,
+    /// Amount of token A added to the reserves
+    pub amount_a: Uint128,
+    /// Amount of token B added to the reserves
+    pub amount_b: Uint128,
+}
+
+impl DepositOutput {
+    /// Amounts of token A and B sent but not accepted, to be refunded.
+    pub fn refunds(&self, sent_a: Uint128, sent_b: Uint128) -> (Uint128, Uint128) {
+        (sent_a - self.amount_a, sent_b - self.amount_b)
+    }
+}
+
+/// Calculates the LP shares minted for a deposit of `amount_a` and `amount_b` into reserves
+/// that do not include the deposit yet. The first deposit locks `MINIMUM_LIQUIDITY` of its
+/// shares, so a dust deposit can't set the price of a share. Subsequent deposits only accept
+/// the largest pair matching the reserve ratio; the excess of the other side is left to be
+/// refunded.
+pub(crate) fn calculate_deposit_shares(
+    amount_a: Uint128,
+    amount_b: Uint128,
+    reserve_a: Uint128,
+    reserve_b: Uint128,
+    total_shares: Uint128,
+) -> Result<DepositOutput, ContractError> {
+    if total_shares.is_zero() {
+        let shares = calculate_initial_lp_shares(amount_a, amount_b)?
+            .checked_sub(MINIMUM_LIQUIDITY)
+            .ok()
+            .filter(|shares| !shares.is_zero())
+            .ok_or(ContractError::InitialLiquidityTooLow {})?;
+        return Ok(DepositOutput {
+            shares,
+            locked_shares: MINIMUM_LIQUIDITY,
+            amount_a,
+            amount_b,
+        });
+    }
+    if reserve_a.is_zero() || reserve_b.is_zero() {
+        return Err(ContractError::CalculateSharesWithZeroReserve {});
+    }
+    let required_b = amount_a.multiply_ratio(reserve_b, reserve_a);
+    let (amount_a, amount_b) = if required_b <= amount_b {
+        (amount_a, required_b)
+    } else {
+        (amount_b.multiply_ratio(reserve_a, reserve_b), amount_b)
+    };
+    Ok(DepositOutput {
+        shares: calculate_subsequent_lp_shares(
+            amount_a,
+            amount_b,
+            reserve_a,
+            reserve_b,
+            total_shares,
+        )?,
+        locked_shares: Uint128::zero(),
+        amount_a,
+        amount_b,
+    })
+}
+
+/// Breakdown of a swap against the pool.
+#[derive(Debug, PartialEq)]
+pub(crate) struct SwapOutput {
+    /// Amount sent to the trader, after the fee
+    pub return_amount: Uint128,
+    /// Fee deducted from the output
+    pub commission_amount: Uint128,
+    /// Output lost to price impact compared to the spot price
+    pub spread_amount: Uint128,
+}
+
 /// Calculates the swap output amount using the constant product formula and applies fees.
+/// `reserve_in` must not include the offer amount.
 pub(crate) fn calculate_swap_output(
     offer_amount: Uint128,
     reserve_in: Uint128,
     reserve_out: Uint128,
     fee_numerator: u64,
     fee_denominator: u64,
-) -> Result<Uint128, ContractError> {
+) -> Result<SwapOutput, ContractError> {
     if reserve_in.is_zero() || reserve_out.is_zero() {
         return Err(ContractError::SwapAgainstEmptyReserve {});
     }
@@ -53,11 +134,81 @@ pub(crate) fn calculate_swap_output(
         return Err(DivideByZeroError {}.into());
     }
     let output_amount_before_fee = reserve_out.multiply_ratio(offer_amount, reserve_in_plus_offer);
-    // TODO: Read fees from pool config or state if they become pool-specific
     let fee_amount = output_amount_before_fee
         .multiply_ratio(Uint128::from(fee_numerator), Uint128::from(fee_denominator));
     let output_amount = output_amount_before_fee.checked_sub(fee_amount)?;
-    Ok(output_amount)
+    let spot_output = offer_amount.multiply_ratio(reserve_out, reserve_in);
+    Ok(SwapOutput {
+        return_amount: output_amount,
+        commission_amount: fee_amount,
+        spread_amount: spot_output.saturating_sub(output_amount_before_fee),
+    })
+}
+
+/// Calculates the smallest offer amount for which `calculate_swap_output` returns
+/// at least `ask_amount`.
+pub(crate) fn calculate_swap_input(
+    ask_amount: Uint128,
+    reserve_in: Uint128,
+    reserve_out: Uint128,
+    fee_numerator: u64,
+    fee_denominator: u64,
+) -> Result<Uint128, ContractError> {
+    if reserve_in.is_zero() || reserve_out.is_zero() {
+        return Err(ContractError::SwapAgainstEmptyReserve {});
+    }
+    if ask_amount.is_zero() {
+        return Err(ContractError::ZeroAskAmount {});
+    }
+    // Smallest output before fee `b` with `b - floor(b * fee) >= ask_amount`
+    let fee_complement = fee_denominator
+        .checked_sub(fee_numerator)
+        .filter(|c| *c > 0)
+        .ok_or(DivideByZeroError {})?;
+    let output_before_fee = (ask_amount - Uint128::one())
+        .checked_multiply_ratio(fee_denominator, fee_complement)
+        .map_err(|_| ContractError::AskExceedsReserve {})?
+        .checked_add(Uint128::one())?;
+    if output_before_fee >= reserve_out {
+        return Err(ContractError::AskExceedsReserve {});
+    }
+    // Smallest offer `o` with `floor(reserve_out * o / (reserve_in + o)) >= b`
+    let remaining_out = Uint256::from(reserve_out - output_before_fee);
+    let offer_amount = Uint256::from(output_before_fee)
+        .checked_mul(Uint256::from(reserve_in))?
+        .checked_add(remaining_out - Uint256::one())?
+        .checked_div(remaining_out)?;
+    Ok(Uint128::try_from(offer_amount)?)
+}
+
+/// Calculates the LP shares minted to the treasury for the fees accrued since `k_last`
+/// (Uniswap v2 `_mintFee`, with the protocol taking `share_bps / 10000` of the fees):
+/// `s = S * share * (sqrt(k) - sqrt(k_last)) / ((1 - share) * sqrt(k) + share * sqrt(k_last))`.
+pub(crate) fn calculate_protocol_fee_shares(
+    reserve_a: Uint128,
+    reserve_b: Uint128,
+    k_last: Uint256,
+    total_shares: Uint128,
+    share_bps: u16,
+    bps_denominator: u64,
+) -> Result<Uint128, ContractError> {
+    if k_last.is_zero() || total_shares.is_zero() || share_bps == 0 {
+        return Ok(Uint128::zero());
+    }
+    let root_k = (Uint256::from(reserve_a) * Uint256::from(reserve_b)).isqrt();
+    let root_k_last = k_last.isqrt();
+    if root_k <= root_k_last {
+        return Ok(Uint128::zero());
+    }
+    let share = Uint256::from(share_bps);
+    let remaining_share = Uint256::from(bps_denominator).checked_sub(share)?;
+    let numerator = Uint256::from(total_shares)
+        .checked_mul(share)?
+        .checked_mul(root_k - root_k_last)?;
+    let denominator = root_k
+        .checked_mul(remaining_share)?
+        .checked_add(root_k_last.checked_mul(share)?)?;
+    Ok(Uint128::try_from(numerator.checked_div(denominator)?)?)
 }
 
 /// Calculates the amounts of token A and B to return for withdrawing a given amount of LP tokens.
@@ -75,10 +226,25 @@ pub(crate) fn calculate_withdraw_amounts(
     Ok((return_a, return_b))
 }
 
+/// Calculates the fee on a flash loan of `amount`, `fee_bps / 10000` of it rounded up.
+pub(crate) fn calculate_flash_loan_fee(
+    amount: Uint128,
+    fee_bps: u16,
+    bps_denominator: u64,
+) -> Result<Uint128, ContractError> {
+    let denominator = Uint256::from(bps_denominator);
+    let fee = Uint256::from(amount)
+        .checked_mul(Uint256::from(fee_bps))?
+        .checked_add(denominator - Uint256::one())?
+        .checked_div(denominator)?;
+    Ok(Uint128::try_from(fee)?)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*; // Import functions from parent module (calculations.rs)
     use crate::error::ContractError;
+    use crate::state::MINIMUM_LIQUIDITY;
     use cosmwasm_std::{Isqrt, Uint128, Uint256};
 
     #[test]
@@ -163,6 +329,89 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_calculate_deposit_shares() {
+        let total_shares = Uint128::new(1000);
+        let reserve_a = Uint128::new(100);
+        let reserve_b = Uint128::new(200);
+        // Excess B is not accepted
+        let output = calculate_deposit_shares(
+            Uint128::new(10),
+            Uint128::new(50),
+            reserve_a,
+            reserve_b,
+            total_shares,
+        )
+        .unwrap();
+        assert_eq!(
+            output,
+            DepositOutput {
+                shares: Uint128::new(100),
+                locked_shares: Uint128::zero(),
+                amount_a: Uint128::new(10),
+                amount_b: Uint128::new(20),
+            }
+        );
+        assert_eq!(
+            output.refunds(Uint128::new(10), Uint128::new(50)),
+            (Uint128::zero(), Uint128::new(30))
+        );
+        // Excess A is not accepted, rounding in favour of the pool
+        let output = calculate_deposit_shares(
+            Uint128::new(50),
+            Uint128::new(25),
+            reserve_a,
+            reserve_b,
+            total_shares,
+        )
+        .unwrap();
+        assert_eq!(
+            output,
+            DepositOutput {
+                shares: Uint128::new(120),
+                locked_shares: Uint128::zero(),
+                amount_a: Uint128::new(12),
+                amount_b: Uint128::new(25),
+            }
+        );
+        // The first deposit is accepted whole, less the locked minimum
+        let output = calculate_deposit_shares(
+            Uint128::new(1_000),
+            Uint128::new(4_000),
+            Uint128::zero(),
+            Uint128::zero(),
+            Uint128::zero(),
+        )
+        .unwrap();
+        assert_eq!(output.shares, Uint128::new(1_000));
+        assert_eq!(output.locked_shares, MINIMUM_LIQUIDITY);
+        assert_eq!(
+            output.refunds(Uint128::new(1_000), Uint128::new(4_000)),
+            (Uint128::zero(), Uint128::zero())
+        );
+        let err = calculate_deposit_shares(
+            Uint128::new(1_000),
+            Uint128::new(1_000),
+            Uint128::zero(),
+            Uint128::zero(),
+            Uint128::zero(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InitialLiquidityTooLow {}));
+        let err = calculate_deposit_shares(
+            Uint128::new(10),
+            Uint128::new(20),
+            Uint128::zero(),
+            reserve_b,
+            total_shares,
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::CalculateSharesWithZeroReserve {}
+        ));
+    }
+
     #[test]
     fn test_calculate_swap_output() {
         let reserve_in = Uint128::new(1000);
@@ -172,7 +421,14 @@ mod tests {
         let fee_den = 1000u64;
         let output =
             calculate_swap_output(offer, reserve_in, reserve_out, fee_num, fee_den).unwrap();
-        assert_eq!(output, Uint128::new(181));
+        assert_eq!(
+            output,
+            SwapOutput {
+                return_amount: Uint128::new(181),
+                commission_amount: Uint128::zero(),
+                spread_amount: Uint128::new(19),
+            }
+        );
         // Large numbers
         let reserve_in_large = Uint128::new(1_000_000_000);
         let reserve_out_large = Uint128::new(2_000_000_000);
@@ -185,13 +441,99 @@ mod tests {
             fee_den,
         )
         .unwrap();
-        assert_eq!(output_large, Uint128::new(19_742_575));
+        assert_eq!(output_large.return_amount, Uint128::new(19_742_575));
+        assert_eq!(output_large.commission_amount, Uint128::new(59_405));
         // Error zero reserves
         let err = calculate_swap_output(offer, Uint128::zero(), reserve_out, fee_num, fee_den)
             .unwrap_err();
         assert!(matches!(err, ContractError::SwapAgainstEmptyReserve {}));
     }
 
+    #[test]
+    fn test_calculate_swap_input() {
+        let reserve_in = Uint128::new(1000);
+        let reserve_out = Uint128::new(2000);
+        for (fee_num, fee_den) in [(0u64, 1000u64), (3, 1000), (100, 10_000)] {
+            for ask in 1..1500u128 {
+                let ask = Uint128::new(ask);
+                let offer =
+                    calculate_swap_input(ask, reserve_in, reserve_out, fee_num, fee_den).unwrap();
+                // Enough to receive the ask amount...
+                let output =
+                    calculate_swap_output(offer, reserve_in, reserve_out, fee_num, fee_den)
+                        .unwrap();
+                assert!(output.return_amount >= ask);
+                // ...and one less is not
+                let below = calculate_swap_output(
+                    offer - Uint128::one(),
+                    reserve_in,
+                    reserve_out,
+                    fee_num,
+                    fee_den,
+                )
+                .unwrap();
+                assert!(below.return_amount < ask);
+            }
+        }
+        // Cannot drain the pool
+        let err = calculate_swap_input(reserve_out, reserve_in, reserve_out, 3, 1000).unwrap_err();
+        assert!(matches!(err, ContractError::AskExceedsReserve {}));
+        let err =
+            calculate_swap_input(Uint128::zero(), reserve_in, reserve_out, 3, 1000).unwrap_err();
+        assert!(matches!(err, ContractError::ZeroAskAmount {}));
+    }
+
+    #[test]
+    fn test_calculate_protocol_fee_shares() {
+        let k_last = Uint256::from(1_000_000u128);
+        let total_shares = Uint128::new(1000);
+        // sqrt(k) grew from 1000 to 1100, protocol takes half of the fees
+        let shares = calculate_protocol_fee_shares(
+            Uint128::new(1100),
+            Uint128::new(1100),
+            k_last,
+            total_shares,
+            5_000,
+            10_000,
+        )
+        .unwrap();
+        assert_eq!(shares, Uint128::new(47));
+        // Protocol takes all the fees
+        let shares = calculate_protocol_fee_shares(
+            Uint128::new(1100),
+            Uint128::new(1100),
+            k_last,
+            total_shares,
+            10_000,
+            10_000,
+        )
+        .unwrap();
+        assert_eq!(shares, Uint128::new(100));
+        // Fee off, no growth
+        for (k, share_bps) in [(Uint256::zero(), 5_000), (k_last, 0)] {
+            let shares = calculate_protocol_fee_shares(
+                Uint128::new(1100),
+                Uint128::new(1100),
+                k,
+                total_shares,
+                share_bps,
+                10_000,
+            )
+            .unwrap();
+            assert!(shares.is_zero());
+        }
+        let shares = calculate_protocol_fee_shares(
+            Uint128::new(1000),
+            Uint128::new(1000),
+            k_last,
+            total_shares,
+            5_000,
+            10_000,
+        )
+        .unwrap();
+        assert!(shares.is_zero());
+    }
+
     #[test]
     fn test_calculate_withdraw_amounts() {
         let total_shares = Uint128::new(1000);
@@ -207,4 +549,16 @@ mod tests {
             .unwrap_err();
         assert!(matches!(err, ContractError::DivideByZeroError(..)));
     }
+
+    #[test]
+    fn test_calculate_flash_loan_fee() {
+        let fee = |amount: u128, fee_bps: u16| {
+            calculate_flash_loan_fee(Uint128::new(amount), fee_bps, 10_000).unwrap()
+        };
+        assert_eq!(fee(50_000, 9), Uint128::new(45));
+        // Rounded up, so small loans are never free
+        assert_eq!(fee(10_001, 9), Uint128::new(10));
+        assert_eq!(fee(1, 9), Uint128::new(1));
+        assert_eq!(fee(50_000, 0), Uint128::zero());
+    }
 }
@@ -1,6 +1,14 @@
-use crate::execute::{execute_add_liquidity, execute_cw20_receive, execute_swap};
-use crate::query::query_pool_state;
-use crate::reply::handle_lp_instantiate_reply;
+use crate::execute::{
+    execute_add_liquidity, execute_cw20_receive, execute_flash_loan, execute_skim, execute_swap,
+    execute_swap_exact_out, execute_sync,
+};
+use crate::query::{
+    query_cumulative_prices, query_pool_state, query_reverse_simulate_swap,
+    query_simulate_provide_liquidity, query_simulate_swap, query_simulate_withdraw_liquidity,
+    query_twap,
+};
+use crate::reply::{handle_flash_loan_reply, handle_lp_instantiate_reply};
+use crate::state::{FLASH_LOAN_REPLY_ID, PENDING_FLASH_LOAN};
 use cosmwasm_std::{
     entry_point, Binary, Deps, DepsMut, Env, MessageInfo, Reply, Response, StdResult,
 };
@@ -27,13 +35,47 @@ pub fn execute(
     info: MessageInfo,
     msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
+    // The pool must not be used while a flash loan is out, e.g. by the borrower's callback
+    if PENDING_FLASH_LOAN.exists(deps.storage) {
+        return Err(ContractError::FlashLoanInProgress {});
+    }
     match msg {
-        ExecuteMsg::AddLiquidity {} => execute_add_liquidity(deps, env, info),
+        ExecuteMsg::AddLiquidity {
+            min_lp_out,
+            deadline,
+            to,
+        } => execute_add_liquidity(deps, env, info, min_lp_out, deadline, to),
         ExecuteMsg::Swap {
             offer_denom,
             min_receive,
-        } => execute_swap(deps, env, info, offer_denom, min_receive),
+            belief_price,
+            max_spread,
+            deadline,
+            to,
+        } => execute_swap(
+            deps,
+            env,
+            info,
+            offer_denom,
+            min_receive,
+            belief_price,
+            max_spread,
+            deadline,
+            to,
+        ),
+        ExecuteMsg::SwapExactOut {
+            ask_denom,
+            ask_amount,
+            max_spend,
+            deadline,
+        } => execute_swap_exact_out(deps, env, info, ask_denom, ask_amount, max_spend, deadline),
         ExecuteMsg::Receive(cw20_msg) => execute_cw20_receive(deps, env, info, cw20_msg),
+        ExecuteMsg::Sync {} => execute_sync(deps, env),
+        ExecuteMsg::Skim { to } => execute_skim(deps, env, info, to),
+        ExecuteMsg::FlashLoan {
+            assets,
+            callback_msg,
+        } => execute_flash_loan(deps, env, info, assets, callback_msg),
     }
 }
 
@@ -41,10 +83,29 @@ pub fn execute(
 pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
         QueryMsg::PoolState {} => query_pool_state(deps, env),
+        QueryMsg::SimulateSwap {
+            offer_denom,
+            offer_amount,
+        } => query_simulate_swap(deps, offer_denom, offer_amount),
+        QueryMsg::ReverseSimulateSwap {
+            ask_denom,
+            ask_amount,
+        } => query_reverse_simulate_swap(deps, ask_denom, ask_amount),
+        QueryMsg::SimulateProvideLiquidity { assets } => {
+            query_simulate_provide_liquidity(deps, assets)
+        }
+        QueryMsg::SimulateWithdrawLiquidity { lp_amount } => {
+            query_simulate_withdraw_liquidity(deps, lp_amount)
+        }
+        QueryMsg::Twap { start_age, end_age } => query_twap(deps, env, start_age, end_age),
+        QueryMsg::CumulativePrices {} => query_cumulative_prices(deps, env),
     }
 }
 
 #[entry_point]
-pub fn reply(deps: DepsMut, _env: Env, msg: Reply) -> Result<Response, ContractError> {
-    handle_lp_instantiate_reply(deps, msg)
+pub fn reply(deps: DepsMut, env: Env, msg: Reply) -> Result<Response, ContractError> {
+    match msg.id {
+        FLASH_LOAN_REPLY_ID => handle_flash_loan_reply(deps, env, msg),
+        _ => handle_lp_instantiate_reply(deps, msg),
+    }
 }
@@ -1,4 +1,4 @@
-use cosmwasm_std::{Addr, DivideByZeroError, OverflowError, StdError, Uint128};
+use cosmwasm_std::{Addr, DivideByZeroError, OverflowError, StdError, Timestamp, Uint128};
 use cw_utils::ParseReplyError;
 use thiserror::Error;
 
@@ -33,7 +33,7 @@ pub enum ContractError {
     #[error("Initial liquidity amounts must be positive")]
     ZeroInitialLiquidity {},
 
-    #[error("Initial liquidity too low to mint LP tokens")]
+    #[error("Initial liquidity too low to mint LP tokens above the locked minimum")]
     InitialLiquidityTooLow {},
 
     #[error("Cannot calculate shares with zero total supply (should use initial calculation)")]
@@ -42,12 +42,6 @@ pub enum ContractError {
     #[error("Cannot calculate shares against zero reserves for existing pool")]
     CalculateSharesWithZeroReserve {},
 
-    #[error("Cannot validate ratio against zero reserves for existing pool")]
-    ValidateRatioWithZeroReserve {},
-
-    #[error("Deposit ratio mismatch exceeds slippage tolerance")]
-    DepositRatioMismatch {},
-
     #[error("Cannot swap against empty reserves")]
     SwapAgainstEmptyReserve {},
 
@@ -63,12 +57,48 @@ pub enum ContractError {
     #[error("Offer amount must be positive")]
     ZeroOfferAmount {},
 
+    #[error("Ask amount must be positive")]
+    ZeroAskAmount {},
+
+    #[error("Ask amount exceeds the pool reserve")]
+    AskExceedsReserve {},
+
+    #[error("Required offer {offer} exceeds maximum spend {max_spend}")]
+    SwapMaximumSpendViolation { offer: Uint128, max_spend: Uint128 },
+
+    #[error("Required offer {required} exceeds the {sent} sent")]
+    InsufficientOfferFunds { required: Uint128, sent: Uint128 },
+
+    #[error("Withdraw amount exceeds the LP token supply")]
+    WithdrawExceedsSupply {},
+
     #[error("Output amount {output} less than minimum requested {min_receive}")]
     SwapMinimumReceiveViolation {
         output: Uint128,
         min_receive: Uint128,
     },
 
+    #[error("Minted shares {shares} less than minimum requested {min_lp_out}")]
+    MinimumLpOutViolation {
+        shares: Uint128,
+        min_lp_out: Uint128,
+    },
+
+    #[error("Operation exceeds max spread limit")]
+    MaxSpreadAssertion {},
+
+    #[error("Belief price must be positive")]
+    InvalidBeliefPrice {},
+
+    #[error("Deadline {deadline} has passed (block time {block_time})")]
+    DeadlineExceeded {
+        deadline: Timestamp,
+        block_time: Timestamp,
+    },
+
+    #[error("Invalid fee: {fee_bps} bps must be below 10000")]
+    InvalidFee { fee_bps: u16 },
+
     #[error("Pool is not initialized with LP token address yet")]
     NotInitialized {},
 
@@ -86,4 +116,33 @@ pub enum ContractError {
 
     #[error("CW20 token query failed for contract {contract}: {error}")]
     TokenQueryFailed { contract: Addr, error: StdError },
+
+    #[error("Invalid TWAP window: start age {start_age} must exceed end age {end_age}")]
+    InvalidTwapWindow { start_age: u64, end_age: u64 },
+
+    #[error("No price history as far back as {time}")]
+    TwapHistoryUnavailable { time: Timestamp },
+
+    #[error("Flash loan must borrow at least one asset, each with a positive amount")]
+    EmptyFlashLoan {},
+
+    #[error("Flash loan lists denom {denom} more than once")]
+    DuplicateFlashLoanDenom { denom: String },
+
+    #[error("Flash loan of {amount} {denom} must be below the reserve of {reserve}")]
+    FlashLoanExceedsReserve {
+        denom: String,
+        amount: Uint128,
+        reserve: Uint128,
+    },
+
+    #[error("Flash loan not repaid: pool holds {balance} {denom}, {due} due")]
+    FlashLoanNotRepaid {
+        denom: String,
+        balance: Uint128,
+        due: Uint128,
+    },
+
+    #[error("A flash loan is in progress")]
+    FlashLoanInProgress {},
 }
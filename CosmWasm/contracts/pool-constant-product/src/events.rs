@@ -6,6 +6,7 @@ use cosmwasm_std::{Addr, Event, Uint128};
 #[cw_serde]
 pub struct LiquidityAddedEvent {
     pub sender: Addr,
+    pub receiver: Addr, // Receiver of the LP tokens
     pub denom_a_deposited: Uint128,
     pub denom_b_deposited: Uint128,
     pub shares_minted: Uint128,
@@ -15,6 +16,7 @@ impl From<LiquidityAddedEvent> for Event {
     fn from(val: LiquidityAddedEvent) -> Self {
         Event::new("liquidity_added")
             .add_attribute("sender", val.sender.into_string())
+            .add_attribute("receiver", val.receiver.into_string())
             .add_attribute("denom_a_deposited", val.denom_a_deposited.to_string())
             .add_attribute("denom_b_deposited", val.denom_b_deposited.to_string())
             .add_attribute("shares_minted", val.shares_minted.to_string())
@@ -23,7 +25,8 @@ impl From<LiquidityAddedEvent> for Event {
 
 #[cw_serde]
 pub struct LiquidityRemovedEvent {
-    pub sender: Addr,            // User receiving funds
+    pub sender: Addr,            // LP token holder
+    pub receiver: Addr,          // User receiving funds
     pub lp_token_contract: Addr, // LP token contract that was burned from
     pub withdrawn_share: Uint128,
     pub return_a: Uint128,
@@ -34,6 +37,7 @@ impl From<LiquidityRemovedEvent> for Event {
     fn from(val: LiquidityRemovedEvent) -> Self {
         Event::new("liquidity_removed")
             .add_attribute("sender", val.sender.into_string())
+            .add_attribute("receiver", val.receiver.into_string())
             .add_attribute("lp_token_contract", val.lp_token_contract.into_string())
             .add_attribute("withdrawn_share", val.withdrawn_share.to_string())
             .add_attribute("return_a", val.return_a.to_string())
@@ -44,6 +48,7 @@ impl From<LiquidityRemovedEvent> for Event {
 #[cw_serde]
 pub struct SwapEvent {
     pub sender: Addr,
+    pub receiver: Addr, // Receiver of the swap output
     pub offer_denom: String,
     pub ask_denom: String,
     pub offer_amount: Uint128,
@@ -54,6 +59,7 @@ impl From<SwapEvent> for Event {
     fn from(val: SwapEvent) -> Self {
         Event::new("swap")
             .add_attribute("sender", val.sender.into_string())
+            .add_attribute("receiver", val.receiver.into_string())
             .add_attribute("offer_denom", val.offer_denom)
             .add_attribute("ask_denom", val.ask_denom)
             .add_attribute("offer_amount", val.offer_amount.to_string())
@@ -73,6 +79,7 @@ mod tests {
 
         let added = LiquidityAddedEvent {
             sender: addr1.clone(),
+            receiver: addr1.clone(),
             denom_a_deposited: Uint128::new(50),
             denom_b_deposited: Uint128::new(100),
             shares_minted: Uint128::new(70),
@@ -86,6 +93,7 @@ mod tests {
 
         let removed = LiquidityRemovedEvent {
             sender: addr1.clone(),
+            receiver: addr2.clone(),
             lp_token_contract: addr2.clone(),
             withdrawn_share: Uint128::new(100),
             return_a: Uint128::new(50),
@@ -97,5 +105,6 @@ mod tests {
         assert!(event
             .attributes
             .contains(&("lp_token_contract", "addr2").into()));
+        assert!(event.attributes.contains(&("receiver", "addr2").into()));
     }
 }
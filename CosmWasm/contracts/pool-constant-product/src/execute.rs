@@ -1,15 +1,20 @@
 // contracts/pool-constant-product/src/execute.rs
 
 use cosmwasm_std::{
-    from_json, to_json_binary, Addr, BankMsg, Coin, Deps, DepsMut, Env, MessageInfo, QueryRequest,
-    Response, StdResult, Uint128, WasmQuery,
+    from_json, to_json_binary, Addr, BankMsg, Binary, Coin, Decimal, Deps, DepsMut, Env,
+    MessageInfo, QueryRequest, Response, StdResult, Storage, SubMsg, Timestamp, Uint128, Uint256,
+    WasmMsg, WasmQuery,
 };
 use cw20::Cw20ReceiveMsg;
 
 use crate::error::ContractError;
-use crate::msg::{Cw20HookMsg, InstantiateMsg};
+use crate::msg::{
+    Cw20HookMsg, FactoryQueryMsg, FlashLoanFeeResponse, InstantiateMsg, ProtocolFeeResponse,
+};
+use crate::oracle::{init_oracle, update_oracle};
 use crate::state::{
-    PoolConfig, CONTRACT_NAME, CONTRACT_VERSION, POOL_CONFIG, RESERVE_A, RESERVE_B,
+    PendingFlashLoan, PoolConfig, BPS_DENOMINATOR, CONTRACT_NAME, CONTRACT_VERSION,
+    FLASH_LOAN_REPLY_ID, K_LAST, PENDING_FLASH_LOAN, POOL_CONFIG, RESERVE_A, RESERVE_B,
 };
 
 // Import helpers from other modules for this contract
@@ -27,6 +32,11 @@ pub(crate) fn execute_instantiate(
     msg: InstantiateMsg,
 ) -> Result<Response, ContractError> {
     let factory_addr = deps.api.addr_validate(&msg.factory_addr)?;
+    if u64::from(msg.fee_bps) >= BPS_DENOMINATOR {
+        return Err(ContractError::InvalidFee {
+            fee_bps: msg.fee_bps,
+        });
+    }
     let (denom_a, denom_b) = {
         if msg.denom_a < msg.denom_b {
             (msg.denom_a.clone(), msg.denom_b.clone())
@@ -36,6 +46,8 @@ pub(crate) fn execute_instantiate(
     };
     RESERVE_A.save(deps.storage, &Uint128::zero())?;
     RESERVE_B.save(deps.storage, &Uint128::zero())?;
+    K_LAST.save(deps.storage, &Uint256::zero())?;
+    init_oracle(deps.storage, env.block.time)?;
 
     let sub_msg = create_lp_instantiate_submsg(msg.lp_token_code_id, &env, &denom_a, &denom_b)?;
 
@@ -44,6 +56,7 @@ pub(crate) fn execute_instantiate(
         denom_a: denom_a.clone(),
         denom_b: denom_b.clone(),
         lp_token_addr: Addr::unchecked(""),
+        fee_bps: msg.fee_bps,
     };
     POOL_CONFIG.save(deps.storage, &cfg)?;
     cw2::set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
@@ -54,7 +67,8 @@ pub(crate) fn execute_instantiate(
         .add_attribute("factory", msg.factory_addr)
         .add_attribute("denom_a", denom_a)
         .add_attribute("denom_b", denom_b)
-        .add_attribute("lp_token_code_id", msg.lp_token_code_id.to_string()))
+        .add_attribute("lp_token_code_id", msg.lp_token_code_id.to_string())
+        .add_attribute("fee_bps", msg.fee_bps.to_string()))
 }
 
 // --- Execute Handler Implementations ---
@@ -63,81 +77,131 @@ pub(crate) fn execute_add_liquidity(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
+    min_lp_out: Option<Uint128>,
+    deadline: Option<Timestamp>,
+    to: Option<String>,
 ) -> Result<Response, ContractError> {
+    validate_deadline(&env, deadline)?;
+    let receiver = validate_receiver(deps.api, to.as_deref(), info.sender.as_str())?;
     let cfg = POOL_CONFIG.load(deps.storage)?;
     if cfg.lp_token_addr == Addr::unchecked("") {
         return Err(ContractError::NotInitialized {});
     }
 
-    let current_reserve_a = query_bank_balance(deps.as_ref(), &env.contract.address, &cfg.denom_a)?;
-    let current_reserve_b = query_bank_balance(deps.as_ref(), &env.contract.address, &cfg.denom_b)?;
+    let (reserve_a_before, reserve_b_before) = load_reserves(deps.storage)?;
     let total_shares = query_cw20_total_supply(deps.as_ref(), &cfg.lp_token_addr)?;
+    let protocol_fee = query_protocol_fee(deps.as_ref(), &cfg.factory_addr)?;
 
     let (amount_a, amount_b) = validate_and_get_liquidity_funds(&info, &cfg.denom_a, &cfg.denom_b)?;
 
-    let shares_to_mint = if total_shares.is_zero() {
-        calculate_initial_lp_shares(amount_a, amount_b)?
-    } else {
-        let reserve_a_before = current_reserve_a.checked_sub(amount_a)?;
-        let reserve_b_before = current_reserve_b.checked_sub(amount_b)?;
-        validate_deposit_ratio(amount_a, amount_b, reserve_a_before, reserve_b_before)?;
-        calculate_subsequent_lp_shares(
-            amount_a,
-            amount_b,
-            reserve_a_before,
-            reserve_b_before,
-            total_shares,
-        )?
-    };
+    let (response, fee_shares) = mint_protocol_fee(
+        deps.storage,
+        Response::new(),
+        &cfg,
+        protocol_fee.as_ref(),
+        reserve_a_before,
+        reserve_b_before,
+        total_shares,
+    )?;
+    let deposit = calculate_deposit_shares(
+        amount_a,
+        amount_b,
+        reserve_a_before,
+        reserve_b_before,
+        total_shares.checked_add(fee_shares)?,
+    )?;
+    let shares_to_mint = deposit.shares;
+    if let Some(min_lp_out) = min_lp_out {
+        if shares_to_mint < min_lp_out {
+            return Err(ContractError::MinimumLpOutViolation {
+                shares: shares_to_mint,
+                min_lp_out,
+            });
+        }
+    }
 
-    let mint_msg =
-        create_mint_message(&cfg.lp_token_addr, info.sender.to_string(), shares_to_mint)?;
+    let mint_msg = create_mint_message(&cfg.lp_token_addr, receiver.to_string(), shares_to_mint)?;
+    let reserve_a = reserve_a_before.checked_add(deposit.amount_a)?;
+    let reserve_b = reserve_b_before.checked_add(deposit.amount_b)?;
+    save_reserves(deps.storage, &env, reserve_a, reserve_b)?;
+    update_k_last(deps.storage, protocol_fee.is_some(), reserve_a, reserve_b)?;
+
+    // The excess of the side exceeding the reserve ratio goes back to the depositor
+    let (refund_a, refund_b) = deposit.refunds(amount_a, amount_b);
+    let refund: Vec<Coin> = [(&cfg.denom_a, refund_a), (&cfg.denom_b, refund_b)]
+        .into_iter()
+        .filter(|(_, amount)| !amount.is_zero())
+        .map(|(denom, amount)| Coin {
+            denom: denom.clone(),
+            amount,
+        })
+        .collect();
+    let mut response = response.add_message(mint_msg);
+    if !deposit.locked_shares.is_zero() {
+        response = response.add_message(create_mint_message(
+            &cfg.lp_token_addr,
+            env.contract.address.to_string(),
+            deposit.locked_shares,
+        )?);
+    }
+    if !refund.is_empty() {
+        response = response.add_message(BankMsg::Send {
+            to_address: info.sender.to_string(),
+            amount: refund,
+        });
+    }
 
     // TODO: Add event emission
-    Ok(Response::new()
-        .add_message(mint_msg)
+    Ok(response
         .add_attribute("action", "add_liquidity")
         .add_attribute("sender", info.sender.to_string())
-        .add_attribute("denom_a_deposited", amount_a.to_string())
-        .add_attribute("denom_b_deposited", amount_b.to_string())
-        .add_attribute("shares_minted", shares_to_mint.to_string()))
+        .add_attribute("receiver", receiver.to_string())
+        .add_attribute("denom_a_deposited", deposit.amount_a.to_string())
+        .add_attribute("denom_b_deposited", deposit.amount_b.to_string())
+        .add_attribute("denom_a_refunded", refund_a.to_string())
+        .add_attribute("denom_b_refunded", refund_b.to_string())
+        .add_attribute("shares_minted", shares_to_mint.to_string())
+        .add_attribute("shares_locked", deposit.locked_shares.to_string()))
 }
 
+#[allow(clippy::too_many_arguments)]
 pub(crate) fn execute_swap(
     deps: DepsMut,
     env: Env,
     info: MessageInfo,
     offer_denom: String,
     min_receive: Uint128,
+    belief_price: Option<Decimal>,
+    max_spread: Option<Decimal>,
+    deadline: Option<Timestamp>,
+    to: Option<String>,
 ) -> Result<Response, ContractError> {
+    validate_deadline(&env, deadline)?;
+    let receiver = validate_receiver(deps.api, to.as_deref(), info.sender.as_str())?;
     let cfg = POOL_CONFIG.load(deps.storage)?;
     if cfg.lp_token_addr == Addr::unchecked("") {
         return Err(ContractError::NotInitialized {});
     }
 
     let offer_amount = get_offer_amount(&info, &offer_denom)?;
-    let current_reserve_a = query_bank_balance(deps.as_ref(), &env.contract.address, &cfg.denom_a)?;
-    let current_reserve_b = query_bank_balance(deps.as_ref(), &env.contract.address, &cfg.denom_b)?;
+    let (reserve_a, reserve_b) = load_reserves(deps.storage)?;
 
     let (ask_denom, reserve_in, reserve_out) = if offer_denom == cfg.denom_a {
-        (cfg.denom_b.clone(), current_reserve_a, current_reserve_b)
+        (cfg.denom_b.clone(), reserve_a, reserve_b)
     } else if offer_denom == cfg.denom_b {
-        (cfg.denom_a.clone(), current_reserve_b, current_reserve_a)
+        (cfg.denom_a.clone(), reserve_b, reserve_a)
     } else {
         return Err(ContractError::InvalidLiquidityDenom { denom: offer_denom });
     };
 
-    // TODO: Fee logic needs solidifying. Using placeholders.
-    let fee_numerator = 3u64;
-    let fee_denominator = 1000u64;
-
-    let output_amount = calculate_swap_output(
+    let swap_output = calculate_swap_output(
         offer_amount,
         reserve_in,
         reserve_out,
-        fee_numerator,
-        fee_denominator,
+        u64::from(cfg.fee_bps),
+        BPS_DENOMINATOR,
     )?;
+    let output_amount = swap_output.return_amount;
 
     if output_amount < min_receive {
         return Err(ContractError::SwapMinimumReceiveViolation {
@@ -145,9 +209,24 @@ pub(crate) fn execute_swap(
             min_receive,
         });
     }
+    assert_max_spread(
+        belief_price,
+        max_spread,
+        offer_amount,
+        output_amount.checked_add(swap_output.commission_amount)?,
+        swap_output.spread_amount,
+    )?;
+    save_swap_reserves(
+        deps.storage,
+        &env,
+        &cfg,
+        &offer_denom,
+        reserve_in.checked_add(offer_amount)?,
+        reserve_out.checked_sub(output_amount)?,
+    )?;
 
     let return_msg = BankMsg::Send {
-        to_address: info.sender.to_string(),
+        to_address: receiver.to_string(),
         amount: vec![Coin {
             denom: ask_denom.clone(),
             amount: output_amount,
@@ -159,10 +238,246 @@ pub(crate) fn execute_swap(
         .add_message(return_msg)
         .add_attribute("action", "swap")
         .add_attribute("sender", info.sender.to_string())
+        .add_attribute("receiver", receiver.to_string())
+        .add_attribute("offer_denom", offer_denom)
+        .add_attribute("ask_denom", ask_denom)
+        .add_attribute("offer_amount", offer_amount.to_string())
+        .add_attribute("return_amount", output_amount.to_string())
+        .add_attribute(
+            "commission_amount",
+            swap_output.commission_amount.to_string(),
+        )
+        .add_attribute("spread_amount", swap_output.spread_amount.to_string()))
+}
+
+pub(crate) fn execute_swap_exact_out(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    ask_denom: String,
+    ask_amount: Uint128,
+    max_spend: Uint128,
+    deadline: Option<Timestamp>,
+) -> Result<Response, ContractError> {
+    validate_deadline(&env, deadline)?;
+    let cfg = POOL_CONFIG.load(deps.storage)?;
+    if cfg.lp_token_addr == Addr::unchecked("") {
+        return Err(ContractError::NotInitialized {});
+    }
+
+    let offer_denom = if ask_denom == cfg.denom_a {
+        cfg.denom_b.clone()
+    } else if ask_denom == cfg.denom_b {
+        cfg.denom_a.clone()
+    } else {
+        return Err(ContractError::InvalidLiquidityDenom { denom: ask_denom });
+    };
+    let sent_amount = get_offer_amount(&info, &offer_denom)?;
+    let (reserve_a, reserve_b) = load_reserves(deps.storage)?;
+    let (reserve_in, reserve_out) = if offer_denom == cfg.denom_a {
+        (reserve_a, reserve_b)
+    } else {
+        (reserve_b, reserve_a)
+    };
+
+    let fee_numerator = u64::from(cfg.fee_bps);
+    let offer_amount = calculate_swap_input(
+        ask_amount,
+        reserve_in,
+        reserve_out,
+        fee_numerator,
+        BPS_DENOMINATOR,
+    )?;
+    if offer_amount > max_spend {
+        return Err(ContractError::SwapMaximumSpendViolation {
+            offer: offer_amount,
+            max_spend,
+        });
+    }
+    if offer_amount > sent_amount {
+        return Err(ContractError::InsufficientOfferFunds {
+            required: offer_amount,
+            sent: sent_amount,
+        });
+    }
+    // The offer may buy slightly more than the ask; the difference stays in the pool
+    let swap_output = calculate_swap_output(
+        offer_amount,
+        reserve_in,
+        reserve_out,
+        fee_numerator,
+        BPS_DENOMINATOR,
+    )?;
+    save_swap_reserves(
+        deps.storage,
+        &env,
+        &cfg,
+        &offer_denom,
+        reserve_in.checked_add(offer_amount)?,
+        reserve_out.checked_sub(ask_amount)?,
+    )?;
+
+    let mut response = Response::new().add_message(BankMsg::Send {
+        to_address: info.sender.to_string(),
+        amount: vec![Coin {
+            denom: ask_denom.clone(),
+            amount: ask_amount,
+        }],
+    });
+    let refund_amount = sent_amount - offer_amount;
+    if !refund_amount.is_zero() {
+        response = response.add_message(BankMsg::Send {
+            to_address: info.sender.to_string(),
+            amount: vec![Coin {
+                denom: offer_denom.clone(),
+                amount: refund_amount,
+            }],
+        });
+    }
+
+    Ok(response
+        .add_attribute("action", "swap_exact_out")
+        .add_attribute("sender", info.sender.to_string())
         .add_attribute("offer_denom", offer_denom)
         .add_attribute("ask_denom", ask_denom)
         .add_attribute("offer_amount", offer_amount.to_string())
-        .add_attribute("return_amount", output_amount.to_string()))
+        .add_attribute("return_amount", ask_amount.to_string())
+        .add_attribute("refund_amount", refund_amount.to_string())
+        .add_attribute(
+            "commission_amount",
+            swap_output.commission_amount.to_string(),
+        )
+        .add_attribute("spread_amount", swap_output.spread_amount.to_string()))
+}
+
+/// Adopts the pool's bank balances as its reserves, e.g. after tokens were sent to it directly.
+pub(crate) fn execute_sync(deps: DepsMut, env: Env) -> Result<Response, ContractError> {
+    let cfg = POOL_CONFIG.load(deps.storage)?;
+    let balance_a = query_bank_balance(deps.as_ref(), &env.contract.address, &cfg.denom_a)?;
+    let balance_b = query_bank_balance(deps.as_ref(), &env.contract.address, &cfg.denom_b)?;
+    save_reserves(deps.storage, &env, balance_a, balance_b)?;
+    Ok(Response::new()
+        .add_attribute("action", "sync")
+        .add_attribute("reserve_a", balance_a.to_string())
+        .add_attribute("reserve_b", balance_b.to_string()))
+}
+
+/// Sends the pool's bank balances in excess of its reserves to `to`, or the sender.
+pub(crate) fn execute_skim(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    to: Option<String>,
+) -> Result<Response, ContractError> {
+    let receiver = validate_receiver(deps.api, to.as_deref(), info.sender.as_str())?;
+    let cfg = POOL_CONFIG.load(deps.storage)?;
+    let (reserve_a, reserve_b) = load_reserves(deps.storage)?;
+    let balance_a = query_bank_balance(deps.as_ref(), &env.contract.address, &cfg.denom_a)?;
+    let balance_b = query_bank_balance(deps.as_ref(), &env.contract.address, &cfg.denom_b)?;
+    let skim_a = balance_a.saturating_sub(reserve_a);
+    let skim_b = balance_b.saturating_sub(reserve_b);
+
+    let surplus: Vec<Coin> = [(&cfg.denom_a, skim_a), (&cfg.denom_b, skim_b)]
+        .into_iter()
+        .filter(|(_, amount)| !amount.is_zero())
+        .map(|(denom, amount)| Coin {
+            denom: denom.clone(),
+            amount,
+        })
+        .collect();
+    let mut response = Response::new();
+    if !surplus.is_empty() {
+        response = response.add_message(BankMsg::Send {
+            to_address: receiver.to_string(),
+            amount: surplus,
+        });
+    }
+    Ok(response
+        .add_attribute("action", "skim")
+        .add_attribute("receiver", receiver.to_string())
+        .add_attribute("skim_a", skim_a.to_string())
+        .add_attribute("skim_b", skim_b.to_string()))
+}
+
+/// Sends `assets` to the sender with `callback_msg`, recording the balances the pool must
+/// hold once the callback returns. Repayment is checked in `handle_flash_loan_reply`.
+pub(crate) fn execute_flash_loan(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    mut assets: Vec<Coin>,
+    callback_msg: Binary,
+) -> Result<Response, ContractError> {
+    let cfg = POOL_CONFIG.load(deps.storage)?;
+    let (reserve_a, reserve_b) = load_reserves(deps.storage)?;
+    if assets.is_empty() || assets.iter().any(|coin| coin.amount.is_zero()) {
+        return Err(ContractError::EmptyFlashLoan {});
+    }
+    assets.sort_by(|a, b| a.denom.cmp(&b.denom));
+    if let Some(pair) = assets
+        .windows(2)
+        .find(|pair| pair[0].denom == pair[1].denom)
+    {
+        return Err(ContractError::DuplicateFlashLoanDenom {
+            denom: pair[0].denom.clone(),
+        });
+    }
+
+    let fee_bps = query_flash_loan_fee(deps.as_ref(), &cfg.factory_addr)?;
+    let mut balances_due = Vec::with_capacity(assets.len());
+    let mut fees = Vec::with_capacity(assets.len());
+    for coin in &assets {
+        let reserve = if coin.denom == cfg.denom_a {
+            reserve_a
+        } else if coin.denom == cfg.denom_b {
+            reserve_b
+        } else {
+            return Err(ContractError::InvalidLiquidityDenom {
+                denom: coin.denom.clone(),
+            });
+        };
+        // The reserve can't be lent out in full
+        if coin.amount >= reserve {
+            return Err(ContractError::FlashLoanExceedsReserve {
+                denom: coin.denom.clone(),
+                amount: coin.amount,
+                reserve,
+            });
+        }
+        let fee = calculate_flash_loan_fee(coin.amount, fee_bps, BPS_DENOMINATOR)?;
+        let balance = query_bank_balance(deps.as_ref(), &env.contract.address, &coin.denom)?;
+        balances_due.push(Coin::new(balance.checked_add(fee)?, coin.denom.clone()));
+        fees.push(Coin::new(fee, coin.denom.clone()));
+    }
+
+    PENDING_FLASH_LOAN.save(
+        deps.storage,
+        &PendingFlashLoan {
+            borrower: info.sender.clone(),
+            balances_due,
+            fees,
+        },
+    )?;
+    let callback = SubMsg::reply_on_success(
+        WasmMsg::Execute {
+            contract_addr: info.sender.to_string(),
+            msg: callback_msg,
+            funds: assets.clone(),
+        },
+        FLASH_LOAN_REPLY_ID,
+    );
+    Ok(Response::new()
+        .add_submessage(callback)
+        .add_attribute("action", "flash_loan")
+        .add_attribute("borrower", info.sender.to_string())
+        .add_attribute(
+            "assets",
+            assets
+                .iter()
+                .map(|coin| coin.to_string())
+                .collect::<Vec<_>>()
+                .join(","),
+        ))
 }
 
 pub(crate) fn execute_cw20_receive(
@@ -179,27 +494,40 @@ pub(crate) fn execute_cw20_receive(
     }
 
     match from_json(&cw20_msg.msg)? {
-        Cw20HookMsg::WithdrawLiquidity {} => {
+        Cw20HookMsg::WithdrawLiquidity { deadline, to } => {
+            validate_deadline(&env, deadline)?;
+            let receiver = validate_receiver(deps.api, to.as_deref(), &cw20_msg.sender)?;
             if cw20_msg.amount.is_zero() {
                 return Err(ContractError::ZeroWithdrawAmount {});
             }
 
-            let current_reserve_a =
-                query_bank_balance(deps.as_ref(), &env.contract.address, &cfg.denom_a)?;
-            let current_reserve_b =
-                query_bank_balance(deps.as_ref(), &env.contract.address, &cfg.denom_b)?;
+            let (current_reserve_a, current_reserve_b) = load_reserves(deps.storage)?;
             let total_shares = query_cw20_total_supply(deps.as_ref(), &cfg.lp_token_addr)?;
+            let protocol_fee = query_protocol_fee(deps.as_ref(), &cfg.factory_addr)?;
 
+            let (response, fee_shares) = mint_protocol_fee(
+                deps.storage,
+                Response::new(),
+                &cfg,
+                protocol_fee.as_ref(),
+                current_reserve_a,
+                current_reserve_b,
+                total_shares,
+            )?;
             let (return_a, return_b) = calculate_withdraw_amounts(
                 cw20_msg.amount,
                 current_reserve_a,
                 current_reserve_b,
-                total_shares,
+                total_shares.checked_add(fee_shares)?,
             )?;
+            let reserve_a = current_reserve_a.checked_sub(return_a)?;
+            let reserve_b = current_reserve_b.checked_sub(return_b)?;
+            save_reserves(deps.storage, &env, reserve_a, reserve_b)?;
+            update_k_last(deps.storage, protocol_fee.is_some(), reserve_a, reserve_b)?;
 
             let burn_msg = create_burn_message(&cfg.lp_token_addr, cw20_msg.amount)?;
             let return_funds_msg = BankMsg::Send {
-                to_address: cw20_msg.sender.clone(),
+                to_address: receiver.to_string(),
                 amount: vec![
                     Coin {
                         denom: cfg.denom_a.clone(),
@@ -213,11 +541,12 @@ pub(crate) fn execute_cw20_receive(
             };
 
             // TODO: Add event emission
-            Ok(Response::new()
+            Ok(response
                 .add_message(burn_msg)
                 .add_message(return_funds_msg)
                 .add_attribute("action", "withdraw_liquidity")
-                .add_attribute("sender", cw20_msg.sender) // User receiving funds
+                .add_attribute("sender", cw20_msg.sender) // LP token holder
+                .add_attribute("receiver", receiver.to_string()) // User receiving funds
                 .add_attribute("lp_token_contract", info.sender.to_string()) // LP token burned
                 .add_attribute("withdrawn_share", cw20_msg.amount.to_string())
                 .add_attribute("return_a", return_a.to_string())
@@ -228,12 +557,132 @@ pub(crate) fn execute_cw20_receive(
 
 // --- Internal Helpers ---
 
+/// Loads the tracked reserves.
+pub(crate) fn load_reserves(storage: &dyn Storage) -> StdResult<(Uint128, Uint128)> {
+    Ok((RESERVE_A.load(storage)?, RESERVE_B.load(storage)?))
+}
+
+/// Saves the tracked reserves, first accruing the cumulative prices at the old ones.
+pub(crate) fn save_reserves(
+    storage: &mut dyn Storage,
+    env: &Env,
+    reserve_a: Uint128,
+    reserve_b: Uint128,
+) -> StdResult<()> {
+    update_oracle(storage, env.block.time)?;
+    RESERVE_A.save(storage, &reserve_a)?;
+    RESERVE_B.save(storage, &reserve_b)
+}
+
+/// Saves the reserves after a swap offering `offer_denom`.
+fn save_swap_reserves(
+    storage: &mut dyn Storage,
+    env: &Env,
+    cfg: &PoolConfig,
+    offer_denom: &str,
+    reserve_in: Uint128,
+    reserve_out: Uint128,
+) -> StdResult<()> {
+    if offer_denom == cfg.denom_a {
+        save_reserves(storage, env, reserve_in, reserve_out)
+    } else {
+        save_reserves(storage, env, reserve_out, reserve_in)
+    }
+}
+
 /// Helper function to query bank balance using query_balance method.
-fn query_bank_balance(deps: Deps, contract_addr: &Addr, denom: &str) -> StdResult<Uint128> {
+pub(crate) fn query_bank_balance(
+    deps: Deps,
+    contract_addr: &Addr,
+    denom: &str,
+) -> StdResult<Uint128> {
     let balance: Coin = deps.querier.query_balance(contract_addr, denom)?;
     Ok(balance.amount)
 }
 
+/// Queries the factory for the protocol fee, returning the treasury and share while it is on.
+pub(crate) fn query_protocol_fee(
+    deps: Deps,
+    factory_addr: &Addr,
+) -> StdResult<Option<(Addr, u16)>> {
+    let resp: ProtocolFeeResponse = deps
+        .querier
+        .query_wasm_smart(factory_addr, &FactoryQueryMsg::ProtocolFee {})?;
+    Ok(resp
+        .treasury
+        .filter(|_| resp.share_bps > 0)
+        .map(|treasury| (treasury, resp.share_bps)))
+}
+
+/// Queries the factory for the flash loan fee in basis points.
+pub(crate) fn query_flash_loan_fee(deps: Deps, factory_addr: &Addr) -> StdResult<u16> {
+    let resp: FlashLoanFeeResponse = deps
+        .querier
+        .query_wasm_smart(factory_addr, &FactoryQueryMsg::FlashLoanFee {})?;
+    Ok(resp.fee_bps)
+}
+
+/// Protocol fee shares owed to the treasury for the fees accrued since the last liquidity event.
+pub(crate) fn pending_protocol_fee_shares(
+    storage: &dyn Storage,
+    protocol_fee: Option<&(Addr, u16)>,
+    reserve_a: Uint128,
+    reserve_b: Uint128,
+    total_shares: Uint128,
+) -> Result<Uint128, ContractError> {
+    let Some((_, share_bps)) = protocol_fee else {
+        return Ok(Uint128::zero());
+    };
+    calculate_protocol_fee_shares(
+        reserve_a,
+        reserve_b,
+        K_LAST.load(storage)?,
+        total_shares,
+        *share_bps,
+        BPS_DENOMINATOR,
+    )
+}
+
+/// Adds a message minting the protocol fee accrued since the last liquidity event
+/// to the treasury. Returns the response along with the minted shares.
+fn mint_protocol_fee(
+    storage: &dyn Storage,
+    response: Response,
+    cfg: &PoolConfig,
+    protocol_fee: Option<&(Addr, u16)>,
+    reserve_a: Uint128,
+    reserve_b: Uint128,
+    total_shares: Uint128,
+) -> Result<(Response, Uint128), ContractError> {
+    let fee_shares =
+        pending_protocol_fee_shares(storage, protocol_fee, reserve_a, reserve_b, total_shares)?;
+    let Some((treasury, _)) = protocol_fee.filter(|_| !fee_shares.is_zero()) else {
+        return Ok((response, fee_shares));
+    };
+    let mint_msg = create_mint_message(&cfg.lp_token_addr, treasury.to_string(), fee_shares)?;
+    Ok((
+        response
+            .add_message(mint_msg)
+            .add_attribute("protocol_fee_shares", fee_shares.to_string()),
+        fee_shares,
+    ))
+}
+
+/// Records `reserve_a * reserve_b` after a liquidity event while the protocol fee is on.
+fn update_k_last(
+    storage: &mut dyn Storage,
+    fee_on: bool,
+    reserve_a: Uint128,
+    reserve_b: Uint128,
+) -> StdResult<()> {
+    let k_last = if fee_on {
+        Uint256::from(reserve_a) * Uint256::from(reserve_b)
+    } else {
+        Uint256::zero()
+    };
+    K_LAST.save(storage, &k_last)
+}
+
 /// Helper function to query CW20 total supply using a WasmQuery.
 fn query_cw20_total_supply(deps: Deps, token_addr: &Addr) -> StdResult<Uint128> {
     use cw20::{Cw20QueryMsg, TokenInfoResponse};
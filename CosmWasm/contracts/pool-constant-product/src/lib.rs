@@ -5,6 +5,7 @@ pub mod events;
 pub mod execute;
 pub mod messaging;
 pub mod msg;
+pub mod oracle;
 pub mod query;
 pub mod reply;
 pub mod state;
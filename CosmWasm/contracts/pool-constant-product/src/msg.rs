@@ -1,5 +1,5 @@
 use cosmwasm_schema::{cw_serde, QueryResponses};
-use cosmwasm_std::{Addr, Uint128};
+use cosmwasm_std::{Addr, Binary, Coin, Decimal, Decimal256, Timestamp, Uint128, Uint256};
 use cw20::Cw20ReceiveMsg;
 
 /// Message sent by the factory to instantiate this pool logic contract.
@@ -9,17 +9,60 @@ pub struct InstantiateMsg {
     pub denom_b: String,
     pub lp_token_code_id: u64, // Code ID for the LP token this pool should use
     pub factory_addr: String,  // Address of the factory contract
-                               // Potentially add fee info if pool controls fees
+    pub fee_bps: u16,          // Swap fee in basis points, charged on the output
 }
 
 #[cw_serde]
 pub enum ExecuteMsg {
-    AddLiquidity {},
+    /// Deposits the attached tokens. Past the first deposit, the excess of the side
+    /// exceeding the reserve ratio is refunded to the sender.
+    AddLiquidity {
+        /// Fails if fewer LP shares would be minted
+        min_lp_out: Option<Uint128>,
+        /// Fails once the block time is past this
+        deadline: Option<Timestamp>,
+        /// Receiver of the LP tokens, defaults to the sender
+        to: Option<String>,
+    },
     Swap {
         offer_denom: String, // Must match sent funds
         min_receive: Uint128,
+        /// Price the trader expects, in offer units per ask unit. Checked against
+        /// `max_spread` instead of the pool spot price when set.
+        belief_price: Option<Decimal>,
+        /// Largest spread the trader accepts, as a fraction of the expected return
+        max_spread: Option<Decimal>,
+        /// Fails once the block time is past this
+        deadline: Option<Timestamp>,
+        /// Receiver of the swap output, defaults to the sender
+        to: Option<String>,
+    },
+    /// Buys exactly `ask_amount` of `ask_denom`, paying with the other pool denom.
+    /// The funds sent must cover the required offer; any excess is refunded.
+    SwapExactOut {
+        ask_denom: String,
+        ask_amount: Uint128,
+        max_spend: Uint128, // Upper bound on the offer actually spent
+        /// Fails once the block time is past this
+        deadline: Option<Timestamp>,
     },
     Receive(Cw20ReceiveMsg),
+    /// Sets the tracked reserves to the pool's bank balances.
+    Sync {},
+    /// Sends the pool's bank balances in excess of the tracked reserves.
+    Skim {
+        /// Receiver of the surplus, defaults to the sender
+        to: Option<String>,
+    },
+    /// Lends `assets` to the sender, which must be a contract: they are sent along with
+    /// `callback_msg` executed on the sender. Once it returns, the pool balances must have
+    /// grown by the factory's flash loan fee on each borrowed amount, else the whole
+    /// transaction reverts. The fee accrues to the LPs. Each amount must be below the
+    /// tracked reserve.
+    FlashLoan {
+        assets: Vec<Coin>,
+        callback_msg: Binary,
+    },
 }
 
 #[cw_serde]
@@ -27,23 +70,129 @@ pub enum ExecuteMsg {
 pub enum QueryMsg {
     #[returns(PoolStateResponse)]
     PoolState {},
-    // Would be useful to add SimulateSwap query later
-    // #[returns(SimulateSwapResponse)]
-    // SimulateSwap { offer_amount: Uint128, offer_denom: String },
+    /// Quotes a swap of `offer_amount` of `offer_denom` at the current reserves.
+    #[returns(SimulationResponse)]
+    SimulateSwap {
+        offer_denom: String,
+        offer_amount: Uint128,
+    },
+    /// Quotes the smallest offer that returns at least `ask_amount` of `ask_denom`.
+    #[returns(ReverseSimulationResponse)]
+    ReverseSimulateSwap {
+        ask_denom: String,
+        ask_amount: Uint128,
+    },
+    /// Quotes the LP shares `AddLiquidity` would mint for `assets`.
+    #[returns(SimulateProvideLiquidityResponse)]
+    SimulateProvideLiquidity { assets: Vec<Coin> },
+    /// Quotes the tokens returned for withdrawing `lp_amount` LP tokens.
+    #[returns(SimulateWithdrawLiquidityResponse)]
+    SimulateWithdrawLiquidity { lp_amount: Uint128 },
+    /// Time-weighted average prices from `start_age` to `end_age` seconds before the
+    /// current block. Fails when the window reaches past the stored observations.
+    #[returns(TwapResponse)]
+    Twap { start_age: u64, end_age: u64 },
+    /// Cumulative prices as of the current block, for callers keeping their own history.
+    #[returns(CumulativePricesResponse)]
+    CumulativePrices {},
+}
+
+#[cw_serde]
+pub struct SimulationResponse {
+    pub return_amount: Uint128,
+    pub commission_amount: Uint128,
+    pub spread_amount: Uint128,
+}
+
+#[cw_serde]
+pub struct ReverseSimulationResponse {
+    pub offer_amount: Uint128,
+    /// Amount the swap of `offer_amount` actually returns, at least the ask amount
+    pub return_amount: Uint128,
+    pub commission_amount: Uint128,
+    pub spread_amount: Uint128,
 }
 
 #[cw_serde]
 pub struct PoolStateResponse {
     pub denom_a: String,
     pub denom_b: String,
+    /// Reserves priced by the pool, updated by each operation
     pub reserve_a: Uint128,
     pub reserve_b: Uint128,
+    /// Bank balances of the pool, including tokens sent to it directly
+    pub balance_a: Uint128,
+    pub balance_b: Uint128,
     pub total_lp_shares: Uint128,
     pub lp_token_address: Addr,
+    pub fee_bps: u16,
+}
+
+#[cw_serde]
+pub struct SimulateProvideLiquidityResponse {
+    pub shares: Uint128,
+    /// Tokens sent back to the depositor: the excess of the side exceeding the reserve ratio
+    pub refund_assets: Vec<Coin>,
+    /// Fraction of the LP supply held by the minted shares after the deposit
+    pub share_of_pool: Decimal,
+}
+
+#[cw_serde]
+pub struct SimulateWithdrawLiquidityResponse {
+    pub assets: Vec<Coin>,
+    /// Fraction of the LP supply being withdrawn
+    pub share_of_pool: Decimal,
+}
+
+#[cw_serde]
+pub struct TwapResponse {
+    /// Average price of denom A in denom B
+    pub price_a: Decimal256,
+    /// Average price of denom B in denom A
+    pub price_b: Decimal256,
+    pub start_time: Timestamp,
+    pub end_time: Timestamp,
+}
+
+#[cw_serde]
+pub struct CumulativePricesResponse {
+    pub reserve_a: Uint128,
+    pub reserve_b: Uint128,
+    /// Sum of the price of denom A in denom B times seconds, as `Decimal256` atomics.
+    /// Wraps on overflow; only differences between two readings are meaningful.
+    pub price_a_cumulative: Uint256,
+    /// Same as `price_a_cumulative` for the price of denom B in denom A
+    pub price_b_cumulative: Uint256,
+    pub timestamp: Timestamp,
+}
+
+/// Subset of the factory query messages used by the pool.
+#[cw_serde]
+pub enum FactoryQueryMsg {
+    ProtocolFee {},
+    FlashLoanFee {},
+}
+
+/// Mirror of the factory `ProtocolFeeResponse`.
+#[cw_serde]
+pub struct ProtocolFeeResponse {
+    pub treasury: Option<Addr>,
+    pub share_bps: u16,
+}
+
+/// Mirror of the factory `FlashLoanFeeResponse`.
+#[cw_serde]
+pub struct FlashLoanFeeResponse {
+    pub fee_bps: u16,
 }
 
 // Hook message for receiving LP tokens
 #[cw_serde]
 pub enum Cw20HookMsg {
-    WithdrawLiquidity {},
+    WithdrawLiquidity {
+        /// Fails once the block time is past this
+        deadline: Option<Timestamp>,
+        /// Receiver of the withdrawn tokens, defaults to the LP token sender
+        to: Option<String>,
+    },
 }
@@ -0,0 +1,251 @@
+//! Cumulative prices and the observation buffer behind the `Twap` and `CumulativePrices`
+//! queries (Uniswap v2 style).
+//!
+//! The price of denom A is `reserve_b / reserve_a` as `Decimal256` atomics. The cumulative
+//! prices add up `price * seconds` since instantiation and wrap on overflow, so only the
+//! difference between two observations is meaningful.
+
+use cosmwasm_std::{Decimal256, StdResult, Storage, Timestamp, Uint128, Uint256};
+
+use crate::error::ContractError;
+use crate::state::{
+    Observation, OBSERVATIONS, OBSERVATION_CAPACITY, PRICE_CUMULATIVE, RESERVE_A, RESERVE_B,
+};
+
+/// `reserve_out / reserve_in` as `Decimal256` atomics. `None` while either reserve is empty.
+pub fn price(reserve_in: Uint128, reserve_out: Uint128) -> Option<Uint256> {
+    if reserve_in.is_zero() || reserve_out.is_zero() {
+        return None;
+    }
+    Some(Decimal256::from_ratio(reserve_out, reserve_in).atomics())
+}
+
+/// `last` carried forward to `now` at the given reserves.
+pub fn accrue(
+    last: &Observation,
+    reserve_a: Uint128,
+    reserve_b: Uint128,
+    now: Timestamp,
+) -> Observation {
+    let elapsed = Uint256::from(now.seconds().saturating_sub(last.timestamp.seconds()));
+    let accrued = |price: Option<Uint256>| price.unwrap_or_default().wrapping_mul(elapsed);
+    Observation {
+        timestamp: now.max(last.timestamp),
+        price_a_cumulative: last
+            .price_a_cumulative
+            .wrapping_add(accrued(price(reserve_a, reserve_b))),
+        price_b_cumulative: last
+            .price_b_cumulative
+            .wrapping_add(accrued(price(reserve_b, reserve_a))),
+    }
+}
+
+/// Cumulative prices at `time`, between `before` and `after`. Exact as long as the reserves
+/// did not change in between, which holds for consecutive observations.
+pub fn interpolate(
+    before: &Observation,
+    after: &Observation,
+    time: Timestamp,
+) -> Result<Observation, ContractError> {
+    let span = after.timestamp.seconds() - before.timestamp.seconds();
+    let offset = time.seconds() - before.timestamp.seconds();
+    if offset == 0 {
+        return Ok(before.clone());
+    }
+    let step = |before: Uint256, after: Uint256| -> Result<Uint256, ContractError> {
+        let delta = after
+            .wrapping_sub(before)
+            .checked_mul(Uint256::from(offset))?
+            .checked_div(Uint256::from(span))?;
+        Ok(before.wrapping_add(delta))
+    };
+    Ok(Observation {
+        timestamp: time,
+        price_a_cumulative: step(before.price_a_cumulative, after.price_a_cumulative)?,
+        price_b_cumulative: step(before.price_b_cumulative, after.price_b_cumulative)?,
+    })
+}
+
+/// Average `(price_a, price_b)` from `start` to `end`.
+pub fn twap(
+    start: &Observation,
+    end: &Observation,
+) -> Result<(Decimal256, Decimal256), ContractError> {
+    let seconds = Uint256::from(end.timestamp.seconds() - start.timestamp.seconds());
+    let average = |start: Uint256, end: Uint256| -> Result<Decimal256, ContractError> {
+        Ok(Decimal256::new(
+            end.wrapping_sub(start).checked_div(seconds)?,
+        ))
+    };
+    Ok((
+        average(start.price_a_cumulative, end.price_a_cumulative)?,
+        average(start.price_b_cumulative, end.price_b_cumulative)?,
+    ))
+}
+
+/// Starts the cumulative prices at zero as of `now`.
+pub fn init_oracle(storage: &mut dyn Storage, now: Timestamp) -> StdResult<()> {
+    let first = Observation {
+        timestamp: now,
+        price_a_cumulative: Uint256::zero(),
+        price_b_cumulative: Uint256::zero(),
+    };
+    PRICE_CUMULATIVE.save(storage, &first)?;
+    OBSERVATIONS.push_back(storage, &first)
+}
+
+/// Accrues the cumulative prices up to `now` at the stored reserves and records an
+/// observation. Must run before the reserves change.
+pub fn update_oracle(storage: &mut dyn Storage, now: Timestamp) -> StdResult<()> {
+    let current = current_cumulative(storage, now)?;
+    PRICE_CUMULATIVE.save(storage, &current)?;
+    if OBSERVATIONS
+        .back(storage)?
+        .is_some_and(|latest| latest.timestamp >= current.timestamp)
+    {
+        return Ok(());
+    }
+    if OBSERVATIONS.len(storage)? >= OBSERVATION_CAPACITY {
+        OBSERVATIONS.pop_front(storage)?;
+    }
+    OBSERVATIONS.push_back(storage, &current)
+}
+
+/// Cumulative prices as of `now`, including the stored reserves' price since the last update.
+pub fn current_cumulative(storage: &dyn Storage, now: Timestamp) -> StdResult<Observation> {
+    Ok(accrue(
+        &PRICE_CUMULATIVE.load(storage)?,
+        RESERVE_A.load(storage)?,
+        RESERVE_B.load(storage)?,
+        now,
+    ))
+}
+
+/// Cumulative prices at `time`, no later than `current`, from the stored observations.
+pub fn cumulative_at(
+    storage: &dyn Storage,
+    current: &Observation,
+    time: Timestamp,
+) -> Result<Observation, ContractError> {
+    let mut after = current.clone();
+    if time >= after.timestamp {
+        return Ok(after);
+    }
+    for observation in OBSERVATIONS.iter(storage)?.rev() {
+        let observation = observation?;
+        if observation.timestamp <= time {
+            return interpolate(&observation, &after, time);
+        }
+        after = observation;
+    }
+    Err(ContractError::TwapHistoryUnavailable { time })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmwasm_std::testing::MockStorage;
+
+    fn observation(seconds: u64, price_a_cumulative: u128) -> Observation {
+        Observation {
+            timestamp: Timestamp::from_seconds(seconds),
+            price_a_cumulative: Uint256::from(price_a_cumulative),
+            price_b_cumulative: Uint256::zero(),
+        }
+    }
+
+    fn one() -> Uint256 {
+        Decimal256::one().atomics()
+    }
+
+    #[test]
+    fn test_accrue() {
+        let start = observation(100, 0);
+        let reserves = (Uint128::new(1_000), Uint128::new(2_000));
+
+        let later = accrue(&start, reserves.0, reserves.1, Timestamp::from_seconds(110));
+        assert_eq!(later.timestamp, Timestamp::from_seconds(110));
+        assert_eq!(later.price_a_cumulative, one() * Uint256::from(20u32));
+        assert_eq!(later.price_b_cumulative, one() * Uint256::from(5u32));
+
+        // Empty reserves accrue nothing, and time never runs backwards
+        let empty = accrue(
+            &start,
+            Uint128::zero(),
+            reserves.1,
+            Timestamp::from_seconds(110),
+        );
+        assert_eq!(empty.price_a_cumulative, Uint256::zero());
+        let earlier = accrue(&later, reserves.0, reserves.1, Timestamp::from_seconds(105));
+        assert_eq!(earlier, later);
+    }
+
+    #[test]
+    fn test_interpolate_and_twap() {
+        let start = observation(100, 0);
+        let end = Observation {
+            timestamp: Timestamp::from_seconds(140),
+            price_a_cumulative: one() * Uint256::from(80u32),
+            price_b_cumulative: one() * Uint256::from(20u32),
+        };
+        let middle = interpolate(&start, &end, Timestamp::from_seconds(130)).unwrap();
+        assert_eq!(middle.price_a_cumulative, one() * Uint256::from(60u32));
+        assert_eq!(
+            interpolate(&start, &end, start.timestamp).unwrap(),
+            start.clone()
+        );
+
+        let (price_a, price_b) = twap(&start, &end).unwrap();
+        assert_eq!(price_a, Decimal256::from_ratio(2u32, 1u32));
+        assert_eq!(price_b, Decimal256::from_ratio(1u32, 2u32));
+        assert!(twap(&end, &end).is_err());
+    }
+
+    #[test]
+    fn test_twap_across_wrap() {
+        let start = Observation {
+            timestamp: Timestamp::from_seconds(0),
+            price_a_cumulative: Uint256::MAX - one() + Uint256::one(),
+            price_b_cumulative: Uint256::zero(),
+        };
+        let end = Observation {
+            timestamp: Timestamp::from_seconds(2),
+            price_a_cumulative: one(),
+            price_b_cumulative: Uint256::zero(),
+        };
+        assert_eq!(twap(&start, &end).unwrap().0, Decimal256::one());
+    }
+
+    #[test]
+    fn test_observation_buffer() {
+        let mut storage = MockStorage::new();
+        RESERVE_A.save(&mut storage, &Uint128::new(1_000)).unwrap();
+        RESERVE_B.save(&mut storage, &Uint128::new(1_000)).unwrap();
+        init_oracle(&mut storage, Timestamp::from_seconds(10)).unwrap();
+
+        // One observation per block time
+        update_oracle(&mut storage, Timestamp::from_seconds(10)).unwrap();
+        assert_eq!(OBSERVATIONS.len(&storage).unwrap(), 1);
+
+        // Once full, the oldest are dropped
+        for seconds in 1..=OBSERVATION_CAPACITY as u64 {
+            update_oracle(&mut storage, Timestamp::from_seconds(10 + seconds * 10)).unwrap();
+        }
+        assert_eq!(OBSERVATIONS.len(&storage).unwrap(), OBSERVATION_CAPACITY);
+        let now = Timestamp::from_seconds(10 + OBSERVATION_CAPACITY as u64 * 10 + 5);
+        let current = current_cumulative(&storage, now).unwrap();
+        assert_eq!(
+            cumulative_at(&storage, &current, Timestamp::from_seconds(25))
+                .unwrap()
+                .price_a_cumulative,
+            one() * Uint256::from(15u32)
+        );
+        assert_eq!(
+            cumulative_at(&storage, &current, Timestamp::from_seconds(19)),
+            Err(ContractError::TwapHistoryUnavailable {
+                time: Timestamp::from_seconds(19)
+            })
+        );
+        assert_eq!(cumulative_at(&storage, &current, now).unwrap(), current);
+    }
+}
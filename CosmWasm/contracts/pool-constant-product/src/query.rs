@@ -1,7 +1,20 @@
-use crate::msg::PoolStateResponse;
-use crate::state::POOL_CONFIG;
+use crate::calculations::{
+    calculate_deposit_shares, calculate_swap_input, calculate_swap_output,
+    calculate_withdraw_amounts,
+};
+use crate::error::ContractError;
+use crate::execute::{load_reserves, pending_protocol_fee_shares, query_protocol_fee};
+use crate::msg::{
+    CumulativePricesResponse, PoolStateResponse, ReverseSimulationResponse,
+    SimulateProvideLiquidityResponse, SimulateWithdrawLiquidityResponse, SimulationResponse,
+    TwapResponse,
+};
+use crate::oracle::{cumulative_at, current_cumulative, twap};
+use crate::state::{PoolConfig, BPS_DENOMINATOR, POOL_CONFIG};
+use crate::validation::validate_liquidity_assets;
 use cosmwasm_std::{
-    to_json_binary, Addr, Binary, Deps, Env, QueryRequest, StdResult, Uint128, WasmQuery,
+    coin, to_json_binary, Addr, Binary, Coin, Decimal, Deps, Env, QueryRequest, StdError,
+    StdResult, Timestamp, Uint128, WasmQuery,
 };
 
 // --- Query Handler Implementations ---
@@ -10,8 +23,9 @@ pub(crate) fn query_pool_state(deps: Deps, env: Env) -> StdResult<Binary> {
     let cfg = POOL_CONFIG.load(deps.storage)?;
 
     // Use internal helpers to get current state
-    let reserve_a = query_bank_balance(deps, &env.contract.address, &cfg.denom_a)?;
-    let reserve_b = query_bank_balance(deps, &env.contract.address, &cfg.denom_b)?;
+    let (reserve_a, reserve_b) = load_reserves(deps.storage)?;
+    let balance_a = query_bank_balance(deps, &env.contract.address, &cfg.denom_a)?;
+    let balance_b = query_bank_balance(deps, &env.contract.address, &cfg.denom_b)?;
     let total_shares = query_cw20_total_supply(deps, &cfg.lp_token_addr)?;
 
     let resp = PoolStateResponse {
@@ -19,14 +33,209 @@ pub(crate) fn query_pool_state(deps: Deps, env: Env) -> StdResult<Binary> {
         denom_b: cfg.denom_b,
         reserve_a,
         reserve_b,
+        balance_a,
+        balance_b,
         total_lp_shares: total_shares,
         lp_token_address: cfg.lp_token_addr,
+        fee_bps: cfg.fee_bps,
     };
     to_json_binary(&resp)
 }
 
+pub(crate) fn query_simulate_swap(
+    deps: Deps,
+    offer_denom: String,
+    offer_amount: Uint128,
+) -> StdResult<Binary> {
+    let cfg = POOL_CONFIG.load(deps.storage)?;
+    let (reserve_a, reserve_b) = load_reserves(deps.storage)?;
+    let (reserve_in, reserve_out) = if offer_denom == cfg.denom_a {
+        (reserve_a, reserve_b)
+    } else if offer_denom == cfg.denom_b {
+        (reserve_b, reserve_a)
+    } else {
+        return Err(to_std_err(ContractError::InvalidLiquidityDenom {
+            denom: offer_denom,
+        }));
+    };
+
+    let output = calculate_swap_output(
+        offer_amount,
+        reserve_in,
+        reserve_out,
+        u64::from(cfg.fee_bps),
+        BPS_DENOMINATOR,
+    )
+    .map_err(to_std_err)?;
+    to_json_binary(&SimulationResponse {
+        return_amount: output.return_amount,
+        commission_amount: output.commission_amount,
+        spread_amount: output.spread_amount,
+    })
+}
+
+pub(crate) fn query_reverse_simulate_swap(
+    deps: Deps,
+    ask_denom: String,
+    ask_amount: Uint128,
+) -> StdResult<Binary> {
+    let cfg = POOL_CONFIG.load(deps.storage)?;
+    let (reserve_a, reserve_b) = load_reserves(deps.storage)?;
+    let (reserve_in, reserve_out) = if ask_denom == cfg.denom_b {
+        (reserve_a, reserve_b)
+    } else if ask_denom == cfg.denom_a {
+        (reserve_b, reserve_a)
+    } else {
+        return Err(to_std_err(ContractError::InvalidLiquidityDenom {
+            denom: ask_denom,
+        }));
+    };
+
+    let fee_numerator = u64::from(cfg.fee_bps);
+    let offer_amount = calculate_swap_input(
+        ask_amount,
+        reserve_in,
+        reserve_out,
+        fee_numerator,
+        BPS_DENOMINATOR,
+    )
+    .map_err(to_std_err)?;
+    // Quote the forward swap of that offer so the breakdown matches execution
+    let output = calculate_swap_output(
+        offer_amount,
+        reserve_in,
+        reserve_out,
+        fee_numerator,
+        BPS_DENOMINATOR,
+    )
+    .map_err(to_std_err)?;
+    to_json_binary(&ReverseSimulationResponse {
+        offer_amount,
+        return_amount: output.return_amount,
+        commission_amount: output.commission_amount,
+        spread_amount: output.spread_amount,
+    })
+}
+
+pub(crate) fn query_simulate_provide_liquidity(deps: Deps, assets: Vec<Coin>) -> StdResult<Binary> {
+    let cfg = POOL_CONFIG.load(deps.storage)?;
+    let (amount_a, amount_b) =
+        validate_liquidity_assets(&assets, &cfg.denom_a, &cfg.denom_b).map_err(to_std_err)?;
+    let (reserve_a, reserve_b) = load_reserves(deps.storage)?;
+    let total_shares = total_shares_with_protocol_fee(deps, &cfg, reserve_a, reserve_b)?;
+
+    let deposit = calculate_deposit_shares(amount_a, amount_b, reserve_a, reserve_b, total_shares)
+        .map_err(to_std_err)?;
+    let (refund_a, refund_b) = deposit.refunds(amount_a, amount_b);
+    let refund_assets = [(cfg.denom_a, refund_a), (cfg.denom_b, refund_b)]
+        .into_iter()
+        .filter(|(_, amount)| !amount.is_zero())
+        .map(|(denom, amount)| Coin { denom, amount })
+        .collect();
+    to_json_binary(&SimulateProvideLiquidityResponse {
+        shares: deposit.shares,
+        refund_assets,
+        share_of_pool: Decimal::from_ratio(
+            deposit.shares,
+            total_shares
+                .checked_add(deposit.shares)?
+                .checked_add(deposit.locked_shares)?,
+        ),
+    })
+}
+
+pub(crate) fn query_simulate_withdraw_liquidity(
+    deps: Deps,
+    lp_amount: Uint128,
+) -> StdResult<Binary> {
+    let cfg = POOL_CONFIG.load(deps.storage)?;
+    if lp_amount.is_zero() {
+        return Err(to_std_err(ContractError::ZeroWithdrawAmount {}));
+    }
+    let (reserve_a, reserve_b) = load_reserves(deps.storage)?;
+    let total_shares = total_shares_with_protocol_fee(deps, &cfg, reserve_a, reserve_b)?;
+    if lp_amount > total_shares {
+        return Err(to_std_err(ContractError::WithdrawExceedsSupply {}));
+    }
+
+    let (return_a, return_b) =
+        calculate_withdraw_amounts(lp_amount, reserve_a, reserve_b, total_shares)
+            .map_err(to_std_err)?;
+    to_json_binary(&SimulateWithdrawLiquidityResponse {
+        assets: vec![
+            coin(return_a.u128(), &cfg.denom_a),
+            coin(return_b.u128(), &cfg.denom_b),
+        ],
+        share_of_pool: Decimal::from_ratio(lp_amount, total_shares),
+    })
+}
+
+pub(crate) fn query_twap(deps: Deps, env: Env, start_age: u64, end_age: u64) -> StdResult<Binary> {
+    if start_age <= end_age {
+        return Err(to_std_err(ContractError::InvalidTwapWindow {
+            start_age,
+            end_age,
+        }));
+    }
+    let now = env.block.time;
+    let ago = |age: u64| {
+        Timestamp::from_nanos(
+            now.nanos()
+                .saturating_sub(age.saturating_mul(1_000_000_000)),
+        )
+    };
+    let (start_time, end_time) = (ago(start_age), ago(end_age));
+
+    let current = current_cumulative(deps.storage, now)?;
+    let start = cumulative_at(deps.storage, &current, start_time).map_err(to_std_err)?;
+    let end = cumulative_at(deps.storage, &current, end_time).map_err(to_std_err)?;
+    let (price_a, price_b) = twap(&start, &end).map_err(to_std_err)?;
+    to_json_binary(&TwapResponse {
+        price_a,
+        price_b,
+        start_time,
+        end_time,
+    })
+}
+
+pub(crate) fn query_cumulative_prices(deps: Deps, env: Env) -> StdResult<Binary> {
+    let (reserve_a, reserve_b) = load_reserves(deps.storage)?;
+    let current = current_cumulative(deps.storage, env.block.time)?;
+    to_json_binary(&CumulativePricesResponse {
+        reserve_a,
+        reserve_b,
+        price_a_cumulative: current.price_a_cumulative,
+        price_b_cumulative: current.price_b_cumulative,
+        timestamp: current.timestamp,
+    })
+}
+
 // --- Internal Helpers (Copied from execute.rs) ---
 
+/// LP supply once the pending protocol fee is minted, as the next liquidity event would.
+fn total_shares_with_protocol_fee(
+    deps: Deps,
+    cfg: &PoolConfig,
+    reserve_a: Uint128,
+    reserve_b: Uint128,
+) -> StdResult<Uint128> {
+    let total_shares = query_cw20_total_supply(deps, &cfg.lp_token_addr)?;
+    let protocol_fee = query_protocol_fee(deps, &cfg.factory_addr)?;
+    let fee_shares = pending_protocol_fee_shares(
+        deps.storage,
+        protocol_fee.as_ref(),
+        reserve_a,
+        reserve_b,
+        total_shares,
+    )
+    .map_err(to_std_err)?;
+    Ok(total_shares.checked_add(fee_shares)?)
+}
+
+fn to_std_err(err: ContractError) -> StdError {
+    StdError::generic_err(err.to_string())
+}
+
 /// Helper function to query bank balance using query_balance method.
 fn query_bank_balance(deps: Deps, contract_addr: &Addr, denom: &str) -> StdResult<Uint128> {
     use cosmwasm_std::Coin; // Add specific import needed here
@@ -44,5 +253,3 @@ fn query_cw20_total_supply(deps: Deps, token_addr: &Addr) -> StdResult<Uint128>
         }))?;
     Ok(token_info.total_supply)
 }
-
-// TODO: Add simulate_swap query implementation if needed
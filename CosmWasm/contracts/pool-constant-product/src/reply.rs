@@ -1,8 +1,9 @@
-use cosmwasm_std::{Addr, DepsMut, Reply, Response, StdError, StdResult};
+use cosmwasm_std::{Addr, DepsMut, Env, Reply, Response, StdError, StdResult};
 use cw_utils::parse_instantiate_response_data;
 
 use crate::error::ContractError;
-use crate::state::{INSTANTIATE_LP_REPLY_ID, POOL_CONFIG};
+use crate::execute::{load_reserves, query_bank_balance, save_reserves};
+use crate::state::{FLASH_LOAN_REPLY_ID, INSTANTIATE_LP_REPLY_ID, PENDING_FLASH_LOAN, POOL_CONFIG};
 
 pub fn handle_lp_instantiate_reply(deps: DepsMut, msg: Reply) -> Result<Response, ContractError> {
     if msg.id != INSTANTIATE_LP_REPLY_ID {
@@ -38,3 +39,52 @@ pub fn handle_lp_instantiate_reply(deps: DepsMut, msg: Reply) -> Result<Response
         .add_attribute("action", "lp_token_instantiated")
         .add_attribute("lp_token_address", lp_token_addr))
 }
+
+/// Checks that the borrower's callback returned the flash loan with its fees, and adds the
+/// fees to the reserves. Any error reverts the loan along with the callback.
+pub fn handle_flash_loan_reply(
+    deps: DepsMut,
+    env: Env,
+    msg: Reply,
+) -> Result<Response, ContractError> {
+    if msg.id != FLASH_LOAN_REPLY_ID {
+        return Err(ContractError::UnknownReplyId { id: msg.id });
+    }
+
+    let pending = PENDING_FLASH_LOAN.load(deps.storage)?;
+    PENDING_FLASH_LOAN.remove(deps.storage);
+    for due in &pending.balances_due {
+        let balance = query_bank_balance(deps.as_ref(), &env.contract.address, &due.denom)?;
+        if balance < due.amount {
+            return Err(ContractError::FlashLoanNotRepaid {
+                denom: due.denom.clone(),
+                balance,
+                due: due.amount,
+            });
+        }
+    }
+
+    let cfg = POOL_CONFIG.load(deps.storage)?;
+    let (mut reserve_a, mut reserve_b) = load_reserves(deps.storage)?;
+    for fee in &pending.fees {
+        if fee.denom == cfg.denom_a {
+            reserve_a = reserve_a.checked_add(fee.amount)?;
+        } else {
+            reserve_b = reserve_b.checked_add(fee.amount)?;
+        }
+    }
+    save_reserves(deps.storage, &env, reserve_a, reserve_b)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "flash_loan_repaid")
+        .add_attribute("borrower", pending.borrower)
+        .add_attribute(
+            "fees",
+            pending
+                .fees
+                .iter()
+                .map(|coin| coin.to_string())
+                .collect::<Vec<_>>()
+                .join(","),
+        ))
+}
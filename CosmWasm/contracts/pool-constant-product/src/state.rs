@@ -1,6 +1,6 @@
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{Addr, Uint128};
-use cw_storage_plus::Item;
+use cosmwasm_std::{Addr, Coin, Timestamp, Uint128, Uint256};
+use cw_storage_plus::{Deque, Item};
 
 #[cw_serde]
 pub struct PoolConfig {
@@ -8,14 +8,56 @@ pub struct PoolConfig {
     pub denom_a: String,
     pub denom_b: String,
     pub lp_token_addr: Addr,
+    /// Swap fee in basis points
+    pub fee_bps: u16,
 }
 
+/// Denominator of fees expressed in basis points
+pub const BPS_DENOMINATOR: u64 = 10_000;
+/// LP shares of the first deposit minted to the pool itself, where they stay locked
+pub const MINIMUM_LIQUIDITY: Uint128 = Uint128::new(1_000);
+
 // Store reserves directly
 pub const RESERVE_A: Item<Uint128> = Item::new("reserve_a");
 pub const RESERVE_B: Item<Uint128> = Item::new("reserve_b");
 pub const POOL_CONFIG: Item<PoolConfig> = Item::new("pool_config");
+// reserve_a * reserve_b after the last liquidity event while the protocol fee was on, else zero
+pub const K_LAST: Item<Uint256> = Item::new("k_last");
+
+/// Cumulative prices at a point in time, see `crate::oracle`.
+#[cw_serde]
+pub struct Observation {
+    pub timestamp: Timestamp,
+    /// Sum of the price of denom A in denom B times seconds, as `Decimal256` atomics
+    pub price_a_cumulative: Uint256,
+    /// Sum of the price of denom B in denom A times seconds, as `Decimal256` atomics
+    pub price_b_cumulative: Uint256,
+}
+
+/// Observations kept in `OBSERVATIONS`; the oldest is dropped beyond this
+pub const OBSERVATION_CAPACITY: u32 = 256;
+
+// Cumulative prices as of the last reserve change
+pub const PRICE_CUMULATIVE: Item<Observation> = Item::new("price_cumulative");
+// At most one observation per block time, oldest first
+pub const OBSERVATIONS: Deque<Observation> = Deque::new("observations");
+
+/// Flash loan awaiting repayment, set while the borrower's callback runs.
+#[cw_serde]
+pub struct PendingFlashLoan {
+    pub borrower: Addr,
+    /// Pool bank balances required once the callback returns: the balances before the
+    /// loan plus the fees
+    pub balances_due: Vec<Coin>,
+    /// Fee charged per borrowed denom, added to the reserves on repayment
+    pub fees: Vec<Coin>,
+}
+
+// Blocks every other execute message until the flash loan reply has run
+pub const PENDING_FLASH_LOAN: Item<PendingFlashLoan> = Item::new("pending_flash_loan");
 
 pub const INSTANTIATE_LP_REPLY_ID: u64 = 1; // Local reply ID for this contract
+pub const FLASH_LOAN_REPLY_ID: u64 = 2;
 
 // Contract name and version (optional, but good practice)
 pub const CONTRACT_NAME: &str = "crates.io:cw-dex-pool-constant-product";
@@ -1,5 +1,5 @@
 use crate::error::ContractError;
-use cosmwasm_std::{Decimal, MessageInfo, Uint128};
+use cosmwasm_std::{Addr, Api, Coin, Decimal, Env, MessageInfo, StdResult, Timestamp, Uint128};
 
 /// Validates that the MessageInfo contains funds for the two pool denoms and returns the amounts.
 /// Errors if funds are missing, zero, or contain invalid denoms.
@@ -7,10 +7,19 @@ pub(crate) fn validate_and_get_liquidity_funds(
     info: &MessageInfo,
     pool_denom_a: &str,
     pool_denom_b: &str,
+) -> Result<(Uint128, Uint128), ContractError> {
+    validate_liquidity_assets(&info.funds, pool_denom_a, pool_denom_b)
+}
+
+/// Same as `validate_and_get_liquidity_funds` for a list of assets.
+pub(crate) fn validate_liquidity_assets(
+    assets: &[Coin],
+    pool_denom_a: &str,
+    pool_denom_b: &str,
 ) -> Result<(Uint128, Uint128), ContractError> {
     let mut amount_a = Uint128::zero();
     let mut amount_b = Uint128::zero();
-    for coin in info.funds.iter() {
+    for coin in assets.iter() {
         if coin.denom == pool_denom_a {
             amount_a = coin.amount;
         } else if coin.denom == pool_denom_b {
@@ -46,27 +55,55 @@ pub(crate) fn get_offer_amount(
     Ok(offer_coin.amount)
 }
 
-/// Validates if the ratio of deposited amounts matches the reserve ratio within 1% slippage.
-pub(crate) fn validate_deposit_ratio(
-    amount_a: Uint128,
-    amount_b: Uint128,
-    reserve_a: Uint128,
-    reserve_b: Uint128,
+/// Resolves the receiver of an operation's output: `to` when given, else `sender`.
+pub(crate) fn validate_receiver(api: &dyn Api, to: Option<&str>, sender: &str) -> StdResult<Addr> {
+    api.addr_validate(to.unwrap_or(sender))
+}
+
+/// Rejects the message once the block time is past `deadline`.
+pub(crate) fn validate_deadline(
+    env: &Env,
+    deadline: Option<Timestamp>,
 ) -> Result<(), ContractError> {
-    if reserve_a.is_zero() || reserve_b.is_zero() {
-        return Err(ContractError::ValidateRatioWithZeroReserve {});
+    match deadline {
+        Some(deadline) if env.block.time > deadline => Err(ContractError::DeadlineExceeded {
+            deadline,
+            block_time: env.block.time,
+        }),
+        _ => Ok(()),
     }
-    // TODO: Make slippage configurable?
-    let slippage = Decimal::percent(1);
-    let ratio_a = Decimal::from_ratio(amount_a, reserve_a);
-    let ratio_b = Decimal::from_ratio(amount_b, reserve_b);
-    let diff = if ratio_a > ratio_b {
-        ratio_a - ratio_b
-    } else {
-        ratio_b - ratio_a
+}
+
+/// Checks the spread of a swap against `max_spread`, Astroport style. `return_amount` is
+/// the output before the commission. With a `belief_price` the spread is measured from the
+/// return that price implies, otherwise from the pool spot price (`spread_amount`).
+pub(crate) fn assert_max_spread(
+    belief_price: Option<Decimal>,
+    max_spread: Option<Decimal>,
+    offer_amount: Uint128,
+    return_amount: Uint128,
+    spread_amount: Uint128,
+) -> Result<(), ContractError> {
+    let Some(max_spread) = max_spread else {
+        return Ok(());
     };
-    if diff > slippage {
-        return Err(ContractError::DepositRatioMismatch {});
+    if let Some(belief_price) = belief_price {
+        let expected_return = Decimal::one()
+            .checked_div(belief_price)
+            .ok()
+            .and_then(|inverse| offer_amount.checked_mul_floor(inverse).ok())
+            .ok_or(ContractError::InvalidBeliefPrice {})?;
+        let spread = expected_return.saturating_sub(return_amount);
+        if return_amount < expected_return
+            && Decimal::from_ratio(spread, expected_return) > max_spread
+        {
+            return Err(ContractError::MaxSpreadAssertion {});
+        }
+    } else {
+        let total = return_amount.checked_add(spread_amount)?;
+        if !total.is_zero() && Decimal::from_ratio(spread_amount, total) > max_spread {
+            return Err(ContractError::MaxSpreadAssertion {});
+        }
     }
     Ok(())
 }
@@ -75,7 +112,7 @@ pub(crate) fn validate_deposit_ratio(
 mod tests {
     use super::*;
     use crate::error::ContractError;
-    use cosmwasm_std::testing::message_info;
+    use cosmwasm_std::testing::{message_info, mock_env};
     use cosmwasm_std::{coin, Addr, Uint128};
 
     const USER1: &str = "user_address_111";
@@ -153,34 +190,85 @@ mod tests {
     }
 
     #[test]
-    fn test_validate_deposit_ratio() {
-        let reserve_a = Uint128::new(1000);
-        let reserve_b = Uint128::new(2000);
-        assert!(
-            validate_deposit_ratio(Uint128::new(10), Uint128::new(20), reserve_a, reserve_b)
-                .is_ok()
+    fn test_validate_deadline() {
+        let env = mock_env();
+        let now = env.block.time;
+        assert!(validate_deadline(&env, None).is_ok());
+        assert!(validate_deadline(&env, Some(now)).is_ok());
+        assert!(validate_deadline(&env, Some(now.plus_seconds(1))).is_ok());
+        let err = validate_deadline(&env, Some(now.minus_seconds(1))).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::DeadlineExceeded {
+                deadline: now.minus_seconds(1),
+                block_time: now,
+            }
         );
-        assert!(validate_deposit_ratio(
-            Uint128::new(100),
-            Uint128::from(201u128),
-            reserve_a,
-            reserve_b
+    }
+
+    #[test]
+    fn test_assert_max_spread() {
+        let offer = Uint128::new(1_000);
+        // No max_spread, no check
+        assert!(assert_max_spread(None, None, offer, Uint128::new(1), Uint128::new(999)).is_ok());
+
+        // Spot spread of 10% of 1_000
+        let max_spread = Some(Decimal::percent(10));
+        assert!(assert_max_spread(
+            None,
+            max_spread,
+            offer,
+            Uint128::new(900),
+            Uint128::new(100)
+        )
+        .is_ok());
+        let err = assert_max_spread(
+            None,
+            max_spread,
+            offer,
+            Uint128::new(899),
+            Uint128::new(101),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::MaxSpreadAssertion {});
+
+        // Belief price of 0.5 offer per ask expects 2_000 back
+        let belief_price = Some(Decimal::percent(50));
+        assert!(assert_max_spread(
+            belief_price,
+            max_spread,
+            offer,
+            Uint128::new(1_800),
+            Uint128::zero()
         )
         .is_ok());
-        let err =
-            validate_deposit_ratio(Uint128::new(100), Uint128::new(250), reserve_a, reserve_b)
-                .unwrap_err();
-        assert!(matches!(err, ContractError::DepositRatioMismatch {}));
-        let err_zero = validate_deposit_ratio(
-            Uint128::new(10),
-            Uint128::new(20),
+        let err = assert_max_spread(
+            belief_price,
+            max_spread,
+            offer,
+            Uint128::new(1_799),
             Uint128::zero(),
-            reserve_b,
         )
         .unwrap_err();
-        assert!(matches!(
-            err_zero,
-            ContractError::ValidateRatioWithZeroReserve {}
-        ));
+        assert_eq!(err, ContractError::MaxSpreadAssertion {});
+        // A better return than believed always passes
+        assert!(assert_max_spread(
+            belief_price,
+            Some(Decimal::zero()),
+            offer,
+            Uint128::new(2_500),
+            Uint128::zero()
+        )
+        .is_ok());
+
+        let err = assert_max_spread(
+            Some(Decimal::zero()),
+            max_spread,
+            offer,
+            Uint128::new(1),
+            Uint128::zero(),
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::InvalidBeliefPrice {});
     }
 }
@@ -0,0 +1,69 @@
+//! Flash loan borrower used by the multitests: takes a loan from a pool and sends back
+//! whatever its callback is told to, so tests can both repay and default.
+
+use cosmwasm_std::{
+    to_json_binary, BankMsg, Binary, Coin, Deps, DepsMut, Empty, Env, MessageInfo, Response,
+    StdError, StdResult, WasmMsg,
+};
+use cw_multi_test::{Contract, ContractWrapper};
+use pool_constant_product::msg as PoolMsg;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecuteMsg {
+    /// Borrows `assets` from `pool`, returning `repay` from the callback.
+    Borrow {
+        pool: String,
+        assets: Vec<Coin>,
+        repay: Vec<Coin>,
+        /// Also calls `Sync` on the pool from the callback
+        sync_pool: bool,
+    },
+    /// Called back by the pool with the borrowed assets.
+    Callback { repay: Vec<Coin>, sync_pool: bool },
+}
+
+fn instantiate(_deps: DepsMut, _env: Env, _info: MessageInfo, _msg: Empty) -> StdResult<Response> {
+    Ok(Response::new())
+}
+
+fn execute(_deps: DepsMut, _env: Env, info: MessageInfo, msg: ExecuteMsg) -> StdResult<Response> {
+    match msg {
+        ExecuteMsg::Borrow {
+            pool,
+            assets,
+            repay,
+            sync_pool,
+        } => Ok(Response::new().add_message(WasmMsg::Execute {
+            contract_addr: pool,
+            msg: to_json_binary(&PoolMsg::ExecuteMsg::FlashLoan {
+                assets,
+                callback_msg: to_json_binary(&ExecuteMsg::Callback { repay, sync_pool })?,
+            })?,
+            funds: vec![],
+        })),
+        ExecuteMsg::Callback { repay, sync_pool } => {
+            let mut response = Response::new().add_message(BankMsg::Send {
+                to_address: info.sender.to_string(),
+                amount: repay,
+            });
+            if sync_pool {
+                response = response.add_message(WasmMsg::Execute {
+                    contract_addr: info.sender.to_string(),
+                    msg: to_json_binary(&PoolMsg::ExecuteMsg::Sync {})?,
+                    funds: vec![],
+                });
+            }
+            Ok(response)
+        }
+    }
+}
+
+fn query(_deps: Deps, _env: Env, _msg: Empty) -> StdResult<Binary> {
+    Err(StdError::generic_err("no queries"))
+}
+
+pub fn contract() -> Box<dyn Contract<Empty>> {
+    Box::new(ContractWrapper::new(execute, instantiate, query))
+}
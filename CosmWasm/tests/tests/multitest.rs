@@ -1,12 +1,19 @@
-use cosmwasm_std::{to_json_binary, Addr, Api, Coin, Uint128};
+use cosmwasm_std::{to_json_binary, Addr, Api, Coin, Decimal, Decimal256, Isqrt, Uint128, Uint256};
 use cw20::{BalanceResponse, TokenInfoResponse};
 use cw_multi_test::{App, BankSudo, Contract, ContractWrapper, Executor};
 use dex_factory::msg as FactoryMsg;
+use dex_router::msg as RouterMsg;
 use pool_constant_product::msg as PoolMsg;
 use pool_constant_product::msg::{Cw20HookMsg, PoolStateResponse};
+use pool_constant_product::state::MINIMUM_LIQUIDITY;
+
+mod mock_borrower;
 
 const TOKEN_A: &str = "tokenA";
 const TOKEN_B: &str = "tokenB";
+const TOKEN_C: &str = "tokenC";
+const FEE_TIERS: [u16; 4] = [1, 5, 30, 100];
+const DEFAULT_FEE_BPS: u16 = 30;
 
 // Helper to create contract wrapper for the Factory contract
 fn factory_contract() -> Box<dyn Contract<cosmwasm_std::Empty>> {
@@ -40,6 +47,17 @@ fn cw20_contract() -> Box<dyn Contract<cosmwasm_std::Empty>> {
     Box::new(contract)
 }
 
+// Helper to create contract wrapper for the Router contract
+fn router_contract() -> Box<dyn Contract<cosmwasm_std::Empty>> {
+    let contract = ContractWrapper::new(
+        dex_router::contract::execute,
+        dex_router::contract::instantiate,
+        dex_router::contract::query,
+    )
+    .with_reply(dex_router::contract::reply);
+    Box::new(contract)
+}
+
 /// Sets up app, users, balances, and instantiates DEX Factory
 /// Returns: `(App, Factory Address, Factory Code ID, Pool Code ID, Owner Addr, User1 Addr, User2 Addr)`
 fn setup_app() -> (App, Addr, u64, u64, Addr, Addr, Addr) {
@@ -91,6 +109,7 @@ fn setup_app() -> (App, Addr, u64, u64, Addr, Addr, Addr) {
                 // Corrected fields for factory instantiation
                 default_pool_logic_code_id: cw20_code_id,
                 admin: owner.to_string(),
+                fee_tiers: FEE_TIERS.to_vec(),
             },
             &[],
             "DexFactoryContract",
@@ -117,11 +136,60 @@ fn create_basic_pool(
     pool_code_id: u64,
     user1: &Addr,
 ) -> (Addr, Addr) {
-    // Step 1: Create the pool structure via the factory
+    create_pool_with_fee(app, factory_addr, pool_code_id, user1, DEFAULT_FEE_BPS)
+}
+
+/// Same as `create_basic_pool` for a given fee tier
+/// Returns: `(Pool Address, LP Token Address)`
+fn create_pool_with_fee(
+    app: &mut App,
+    factory_addr: &Addr,
+    pool_code_id: u64,
+    user1: &Addr,
+    fee_bps: u16,
+) -> (Addr, Addr) {
+    let (pool_addr, lp_token_addr) =
+        create_empty_pool(app, factory_addr, pool_code_id, user1, fee_bps);
+
+    // Provide initial liquidity directly to the new pool contract
+    let initial_a = Uint128::new(100_000);
+    let initial_b = Uint128::new(200_000);
+    // AddLiquidity in pool takes no args, amounts from funds
+    let provide_msg = PoolMsg::ExecuteMsg::AddLiquidity {
+        min_lp_out: None,
+        deadline: None,
+        to: None,
+    };
+
+    app.execute_contract(
+        user1.clone(),
+        pool_addr.clone(),
+        &provide_msg,
+        &[
+            cosmwasm_std::coin(initial_a.u128(), TOKEN_A),
+            cosmwasm_std::coin(initial_b.u128(), TOKEN_B),
+        ],
+    )
+    .unwrap();
+
+    (pool_addr, lp_token_addr)
+}
+
+/// Creates an A/B pool via the Factory without any liquidity
+/// Returns: `(Pool Address, LP Token Address)`
+fn create_empty_pool(
+    app: &mut App,
+    factory_addr: &Addr,
+    pool_code_id: u64,
+    user1: &Addr,
+    fee_bps: u16,
+) -> (Addr, Addr) {
+    // Create the pool structure via the factory
     let create_msg = FactoryMsg::ExecuteMsg::CreatePool {
         pool_logic_code_id: pool_code_id,
         denom_a: TOKEN_A.to_string(),
         denom_b: TOKEN_B.to_string(),
+        fee_bps,
     };
     let res_create = app
         .execute_contract(user1.clone(), factory_addr.clone(), &create_msg, &[])
@@ -148,24 +216,6 @@ fn create_basic_pool(
 
     let pool_addr = app.api().addr_validate(&pool_addr_str).unwrap();
     let lp_token_addr = app.api().addr_validate(&lp_token_addr_str).unwrap();
-
-    // Step 2: Provide initial liquidity directly to the new pool contract
-    let initial_a = Uint128::new(100_000);
-    let initial_b = Uint128::new(200_000);
-    // AddLiquidity in pool takes no args, amounts from funds
-    let provide_msg = PoolMsg::ExecuteMsg::AddLiquidity {};
-
-    app.execute_contract(
-        user1.clone(),
-        pool_addr.clone(),
-        &provide_msg,
-        &[
-            cosmwasm_std::coin(initial_a.u128(), TOKEN_A),
-            cosmwasm_std::coin(initial_b.u128(), TOKEN_B),
-        ],
-    )
-    .unwrap();
-
     (pool_addr, lp_token_addr)
 }
 
@@ -219,10 +269,28 @@ fn test_full_flow_cosmwasm() {
             },
         )
         .unwrap();
-    assert_eq!(lp_balance.balance, total_supply.total_supply);
+    assert_eq!(
+        lp_balance.balance + MINIMUM_LIQUIDITY,
+        total_supply.total_supply
+    );
+    // The locked minimum is held by the pool itself
+    let locked: BalanceResponse = app
+        .wrap()
+        .query_wasm_smart(
+            lp_token_addr.clone(),
+            &cw20::Cw20QueryMsg::Balance {
+                address: pool_addr.to_string(),
+            },
+        )
+        .unwrap();
+    assert_eq!(locked.balance, MINIMUM_LIQUIDITY);
 
     // --- Add liquidity by user2 (Execute on the pool contract) ---
-    let add_msg = PoolMsg::ExecuteMsg::AddLiquidity {};
+    let add_msg = PoolMsg::ExecuteMsg::AddLiquidity {
+        min_lp_out: None,
+        deadline: None,
+        to: None,
+    };
     let add_a = Uint128::new(50_000);
     let add_b = Uint128::new(100_000);
     let _res2 = app
@@ -279,6 +347,10 @@ fn test_full_flow_cosmwasm() {
         offer_denom: TOKEN_A.into(),
         // ask_denom is inferred by the pool
         min_receive: Uint128::new(1),
+        belief_price: None,
+        max_spread: None,
+        deadline: None,
+        to: None,
     };
     let offer_amount = Uint128::new(10_000);
     let balance_user2_before = app
@@ -320,7 +392,10 @@ fn test_full_flow_cosmwasm() {
 
     // --- Withdraw liquidity by user1 (Send LP tokens to the pool contract) ---
     let user1_lp_balance = lp_balance.balance;
-    let withdraw_hook = Cw20HookMsg::WithdrawLiquidity {};
+    let withdraw_hook = Cw20HookMsg::WithdrawLiquidity {
+        deadline: None,
+        to: None,
+    };
     let user1_tokena_before_withdraw = app
         .wrap()
         .query_balance(user1.clone(), TOKEN_A)
@@ -409,6 +484,7 @@ fn test_create_pool_errors() {
         pool_logic_code_id: pool_code_id,
         denom_a: TOKEN_A.to_string(),
         denom_b: TOKEN_B.to_string(),
+        fee_bps: DEFAULT_FEE_BPS,
     };
     let err = app
         .execute_contract(user1.clone(), factory_addr.clone(), &create_msg, &[])
@@ -420,6 +496,7 @@ fn test_create_pool_errors() {
         pool_logic_code_id: pool_code_id,
         denom_a: TOKEN_A.to_string(),
         denom_b: TOKEN_A.to_string(),
+        fee_bps: DEFAULT_FEE_BPS,
     };
     let err_same = app
         .execute_contract(
@@ -439,6 +516,7 @@ fn test_create_pool_errors() {
         pool_logic_code_id: pool_code_id,
         denom_a: "tokenC".to_string(),
         denom_b: "tokenD".to_string(),
+        fee_bps: DEFAULT_FEE_BPS,
     };
     app.sudo(cw_multi_test::SudoMsg::Bank(BankSudo::Mint {
         to_address: user1.to_string(),
@@ -460,122 +538,1407 @@ fn test_create_pool_errors() {
 }
 
 #[test]
-fn test_add_liquidity_errors() {
-    let (mut app, factory_addr, _factory_code_id, pool_code_id, _owner, user1, _user2) =
-        setup_app();
-    let (pool_addr, _lp_token_addr) =
-        create_basic_pool(&mut app, &factory_addr, pool_code_id, &user1);
+fn test_fee_tiers() {
+    let (mut app, factory_addr, _factory_code_id, pool_code_id, owner, user1, user2) = setup_app();
 
-    // --- Test Add Zero Amount ---
-    let add_msg_zero = PoolMsg::ExecuteMsg::AddLiquidity {};
-    let err_zero_a = app
-        .execute_contract(
-            user1.clone(),
+    // Same pair at two fee tiers gives two distinct pools
+    let (pool_30, _) = create_basic_pool(&mut app, &factory_addr, pool_code_id, &user1);
+    let (pool_1, _) = create_pool_with_fee(&mut app, &factory_addr, pool_code_id, &user1, 1);
+    assert_ne!(pool_30, pool_1);
+
+    for (fee_bps, pool_addr) in [(30u16, &pool_30), (1, &pool_1)] {
+        let queried: Addr = app
+            .wrap()
+            .query_wasm_smart(
+                factory_addr.clone(),
+                &FactoryMsg::QueryMsg::PoolAddress {
+                    denom_a: TOKEN_B.to_string(),
+                    denom_b: TOKEN_A.to_string(),
+                    pool_logic_code_id: pool_code_id,
+                    fee_bps,
+                },
+            )
+            .unwrap();
+        assert_eq!(&queried, pool_addr);
+        let pool_resp: PoolStateResponse = app
+            .wrap()
+            .query_wasm_smart(pool_addr.clone(), &PoolMsg::QueryMsg::PoolState {})
+            .unwrap();
+        assert_eq!(pool_resp.fee_bps, fee_bps);
+    }
+
+    // Swapping the same amount returns more from the cheaper pool
+    let offer = cosmwasm_std::coin(10_000u128, TOKEN_A);
+    let swap_msg = PoolMsg::ExecuteMsg::Swap {
+        offer_denom: TOKEN_A.into(),
+        min_receive: Uint128::one(),
+        belief_price: None,
+        max_spread: None,
+        deadline: None,
+        to: None,
+    };
+    let mut outputs = vec![];
+    for pool_addr in [&pool_30, &pool_1] {
+        let before = app.wrap().query_balance(&user2, TOKEN_B).unwrap().amount;
+        app.execute_contract(
+            user2.clone(),
             pool_addr.clone(),
-            &add_msg_zero,
-            &[
-                cosmwasm_std::coin(0u128, TOKEN_A),
-                cosmwasm_std::coin(100u128, TOKEN_B),
-            ], // Send 0 A
+            &swap_msg,
+            std::slice::from_ref(&offer),
         )
+        .unwrap();
+        let after = app.wrap().query_balance(&user2, TOKEN_B).unwrap().amount;
+        outputs.push(after - before);
+    }
+    // Both pools have the same reserves, so the same pre-fee output of 18_181
+    assert_eq!(outputs[0], Uint128::new(18_181 - 54)); // 30 bps
+    assert_eq!(outputs[1], Uint128::new(18_181 - 1)); // 1 bps
+
+    // Fee tier outside the allowed list is rejected
+    let create_msg = FactoryMsg::ExecuteMsg::CreatePool {
+        pool_logic_code_id: pool_code_id,
+        denom_a: TOKEN_A.to_string(),
+        denom_b: TOKEN_B.to_string(),
+        fee_bps: 50,
+    };
+    let err = app
+        .execute_contract(user1.clone(), factory_addr.clone(), &create_msg, &[])
         .unwrap_err();
-    assert!(err_zero_a
-        .root_cause()
-        .to_string()
-        .contains("Must provide both tokens"));
+    assert_eq!(
+        err.root_cause().to_string(),
+        "Fee tier 50 bps is not allowed by the factory"
+    );
 
-    let err_zero_b = app
-        .execute_contract(
-            user1.clone(),
+    // Only the admin can update fee tiers
+    let update_msg = FactoryMsg::ExecuteMsg::UpdateFeeTiers {
+        fee_tiers: vec![5, 50],
+    };
+    let err = app
+        .execute_contract(user1.clone(), factory_addr.clone(), &update_msg, &[])
+        .unwrap_err();
+    assert_eq!(err.root_cause().to_string(), "Unauthorized");
+    app.execute_contract(owner.clone(), factory_addr.clone(), &update_msg, &[])
+        .unwrap();
+    app.execute_contract(user1.clone(), factory_addr.clone(), &create_msg, &[])
+        .unwrap();
+
+    // Duplicate or out of range tiers are rejected
+    for fee_tiers in [vec![5, 5], vec![10_000]] {
+        let err = app
+            .execute_contract(
+                owner.clone(),
+                factory_addr.clone(),
+                &FactoryMsg::ExecuteMsg::UpdateFeeTiers { fee_tiers },
+                &[],
+            )
+            .unwrap_err();
+        assert!(err.root_cause().to_string().contains("Invalid fee tier"));
+    }
+}
+
+#[test]
+fn test_simulate_swap() {
+    let (mut app, factory_addr, _factory_code_id, pool_code_id, _owner, user1, user2) = setup_app();
+    let (pool_addr, _lp_token_addr) =
+        create_basic_pool(&mut app, &factory_addr, pool_code_id, &user1);
+
+    // Reserves 100_000 A / 200_000 B, 30 bps fee
+    let sim: PoolMsg::SimulationResponse = app
+        .wrap()
+        .query_wasm_smart(
             pool_addr.clone(),
-            &add_msg_zero,
-            &[
-                cosmwasm_std::coin(100u128, TOKEN_A),
-                cosmwasm_std::coin(0u128, TOKEN_B),
-            ], // Send 0 B
+            &PoolMsg::QueryMsg::SimulateSwap {
+                offer_denom: TOKEN_A.into(),
+                offer_amount: Uint128::new(10_000),
+            },
         )
-        .unwrap_err();
-    assert!(err_zero_b
-        .root_cause()
-        .to_string()
-        .contains("Must provide both tokens"));
+        .unwrap();
+    assert_eq!(sim.return_amount, Uint128::new(18_127));
+    assert_eq!(sim.commission_amount, Uint128::new(54));
+    assert_eq!(sim.spread_amount, Uint128::new(20_000 - 18_181));
 
-    // --- Test Add Only One Token ---
-    let add_msg_one = PoolMsg::ExecuteMsg::AddLiquidity {};
-    let err_one = app
-        .execute_contract(
-            user1.clone(),
+    // Execution matches the quote
+    let before = app.wrap().query_balance(&user2, TOKEN_B).unwrap().amount;
+    app.execute_contract(
+        user2.clone(),
+        pool_addr.clone(),
+        &PoolMsg::ExecuteMsg::Swap {
+            offer_denom: TOKEN_A.into(),
+            min_receive: sim.return_amount,
+            belief_price: None,
+            max_spread: None,
+            deadline: None,
+            to: None,
+        },
+        &[cosmwasm_std::coin(10_000u128, TOKEN_A)],
+    )
+    .unwrap();
+    let after = app.wrap().query_balance(&user2, TOKEN_B).unwrap().amount;
+    assert_eq!(after - before, sim.return_amount);
+
+    // Reverse quote: offering the returned amount yields at least the ask
+    let ask_amount = Uint128::new(5_000);
+    let reverse: PoolMsg::ReverseSimulationResponse = app
+        .wrap()
+        .query_wasm_smart(
             pool_addr.clone(),
-            &add_msg_one,
-            &[cosmwasm_std::coin(100u128, TOKEN_A)], // Only send A
+            &PoolMsg::QueryMsg::ReverseSimulateSwap {
+                ask_denom: TOKEN_A.into(),
+                ask_amount,
+            },
         )
-        .unwrap_err();
-    assert!(err_one
-        .root_cause()
-        .to_string()
-        .contains("Must provide both tokens"));
+        .unwrap();
+    assert!(reverse.return_amount >= ask_amount);
+    let sim: PoolMsg::SimulationResponse = app
+        .wrap()
+        .query_wasm_smart(
+            pool_addr.clone(),
+            &PoolMsg::QueryMsg::SimulateSwap {
+                offer_denom: TOKEN_B.into(),
+                offer_amount: reverse.offer_amount,
+            },
+        )
+        .unwrap();
+    assert_eq!(sim.return_amount, reverse.return_amount);
+    assert_eq!(sim.commission_amount, reverse.commission_amount);
+    assert_eq!(sim.spread_amount, reverse.spread_amount);
+    let sim_below: PoolMsg::SimulationResponse = app
+        .wrap()
+        .query_wasm_smart(
+            pool_addr.clone(),
+            &PoolMsg::QueryMsg::SimulateSwap {
+                offer_denom: TOKEN_B.into(),
+                offer_amount: reverse.offer_amount - Uint128::one(),
+            },
+        )
+        .unwrap();
+    assert!(sim_below.return_amount < ask_amount);
 
-    // --- Test Ratio Mismatch ---
-    let add_msg_slippage = PoolMsg::ExecuteMsg::AddLiquidity {};
+    let before = app.wrap().query_balance(&user2, TOKEN_A).unwrap().amount;
     app.execute_contract(
-        user1.clone(),
+        user2.clone(),
         pool_addr.clone(),
-        &add_msg_slippage,
-        &[
-            cosmwasm_std::coin(1000u128, TOKEN_A),
-            cosmwasm_std::coin(1000u128, TOKEN_B),
-        ],
+        &PoolMsg::ExecuteMsg::Swap {
+            offer_denom: TOKEN_B.into(),
+            min_receive: ask_amount,
+            belief_price: None,
+            max_spread: None,
+            deadline: None,
+            to: None,
+        },
+        &[cosmwasm_std::coin(reverse.offer_amount.u128(), TOKEN_B)],
     )
     .unwrap();
+    let after = app.wrap().query_balance(&user2, TOKEN_A).unwrap().amount;
+    assert_eq!(after - before, reverse.return_amount);
+
+    // Unknown denom and asking for the whole reserve are rejected
+    let err = app
+        .wrap()
+        .query_wasm_smart::<PoolMsg::SimulationResponse>(
+            pool_addr.clone(),
+            &PoolMsg::QueryMsg::SimulateSwap {
+                offer_denom: "tokenC".into(),
+                offer_amount: Uint128::new(10),
+            },
+        )
+        .unwrap_err();
+    assert!(err.to_string().contains("Invalid denom received: tokenC"));
+    let reserve_a = app
+        .wrap()
+        .query_balance(&pool_addr, TOKEN_A)
+        .unwrap()
+        .amount;
+    let err = app
+        .wrap()
+        .query_wasm_smart::<PoolMsg::ReverseSimulationResponse>(
+            pool_addr.clone(),
+            &PoolMsg::QueryMsg::ReverseSimulateSwap {
+                ask_denom: TOKEN_A.into(),
+                ask_amount: reserve_a,
+            },
+        )
+        .unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("Ask amount exceeds the pool reserve"));
 }
 
 #[test]
-fn test_swap_errors() {
-    let (mut app, factory_addr, _factory_code_id, pool_code_id, _owner, user1, _user2) =
-        setup_app();
+fn test_swap_exact_out() {
+    let (mut app, factory_addr, _factory_code_id, pool_code_id, _owner, user1, user2) = setup_app();
     let (pool_addr, _lp_token_addr) =
         create_basic_pool(&mut app, &factory_addr, pool_code_id, &user1);
 
-    // --- Test Swap with Non-Pool Token ---
-    let swap_msg_wrong_offer = PoolMsg::ExecuteMsg::Swap {
-        offer_denom: "tokenC".into(),
-        min_receive: Uint128::one(),
-    };
-    let err_wrong_offer = app
+    // Buy exactly 5_000 B, over-funding with 10_000 A
+    let ask_amount = Uint128::new(5_000);
+    let reverse: PoolMsg::ReverseSimulationResponse = app
+        .wrap()
+        .query_wasm_smart(
+            pool_addr.clone(),
+            &PoolMsg::QueryMsg::ReverseSimulateSwap {
+                ask_denom: TOKEN_B.into(),
+                ask_amount,
+            },
+        )
+        .unwrap();
+    let user_a_before = app.wrap().query_balance(&user2, TOKEN_A).unwrap().amount;
+    let user_b_before = app.wrap().query_balance(&user2, TOKEN_B).unwrap().amount;
+    let res = app
         .execute_contract(
-            user1.clone(),
+            user2.clone(),
             pool_addr.clone(),
-            &swap_msg_wrong_offer,
-            &[cosmwasm_std::coin(100u128, "tokenC")],
+            &PoolMsg::ExecuteMsg::SwapExactOut {
+                ask_denom: TOKEN_B.into(),
+                ask_amount,
+                max_spend: reverse.offer_amount,
+                deadline: None,
+            },
+            &[cosmwasm_std::coin(10_000u128, TOKEN_A)],
         )
-        .unwrap_err();
-    assert!(err_wrong_offer
-        .root_cause()
-        .to_string()
-        .contains("Cannot Sub with given operands"));
+        .unwrap();
+    let refund = Uint128::new(10_000) - reverse.offer_amount;
+    assert!(res.events.iter().any(|e| e
+        .attributes
+        .iter()
+        .any(|a| a.key == "refund_amount" && a.value == refund.to_string())));
 
-    // --- Test Swap Zero Offer ---
-    let swap_msg_zero = PoolMsg::ExecuteMsg::Swap {
-        offer_denom: TOKEN_A.into(),
-        min_receive: Uint128::one(),
-    };
-    let err_zero = app
+    let user_a_after = app.wrap().query_balance(&user2, TOKEN_A).unwrap().amount;
+    let user_b_after = app.wrap().query_balance(&user2, TOKEN_B).unwrap().amount;
+    assert_eq!(user_a_before - user_a_after, reverse.offer_amount);
+    assert_eq!(user_b_after - user_b_before, ask_amount);
+    let pool_state: PoolStateResponse = app
+        .wrap()
+        .query_wasm_smart(pool_addr.clone(), &PoolMsg::QueryMsg::PoolState {})
+        .unwrap();
+    assert_eq!(
+        pool_state.reserve_a,
+        Uint128::new(100_000) + reverse.offer_amount
+    );
+    assert_eq!(pool_state.reserve_b, Uint128::new(200_000) - ask_amount);
+
+    // Exact funding leaves nothing to refund
+    let reverse: PoolMsg::ReverseSimulationResponse = app
+        .wrap()
+        .query_wasm_smart(
+            pool_addr.clone(),
+            &PoolMsg::QueryMsg::ReverseSimulateSwap {
+                ask_denom: TOKEN_A.into(),
+                ask_amount,
+            },
+        )
+        .unwrap();
+    let res = app
         .execute_contract(
-            user1.clone(),
+            user2.clone(),
             pool_addr.clone(),
-            &swap_msg_zero,
-            &[cosmwasm_std::coin(0u128, TOKEN_A)],
+            &PoolMsg::ExecuteMsg::SwapExactOut {
+                ask_denom: TOKEN_A.into(),
+                ask_amount,
+                max_spend: reverse.offer_amount,
+                deadline: None,
+            },
+            &[cosmwasm_std::coin(reverse.offer_amount.u128(), TOKEN_B)],
         )
-        .unwrap_err();
-    assert!(err_zero
-        .root_cause()
-        .to_string()
-        .contains("Cannot transfer empty coins amount"));
+        .unwrap();
+    assert!(res.events.iter().any(|e| e
+        .attributes
+        .iter()
+        .any(|a| a.key == "refund_amount" && a.value == "0")));
 
-    // --- Test Swap Wrong Denom Sent (Funds mismatch message) ---
+    // The required offer above max_spend is rejected
+    let reverse: PoolMsg::ReverseSimulationResponse = app
+        .wrap()
+        .query_wasm_smart(
+            pool_addr.clone(),
+            &PoolMsg::QueryMsg::ReverseSimulateSwap {
+                ask_denom: TOKEN_B.into(),
+                ask_amount,
+            },
+        )
+        .unwrap();
+    let err = app
+        .execute_contract(
+            user2.clone(),
+            pool_addr.clone(),
+            &PoolMsg::ExecuteMsg::SwapExactOut {
+                ask_denom: TOKEN_B.into(),
+                ask_amount,
+                max_spend: reverse.offer_amount - Uint128::one(),
+                deadline: None,
+            },
+            &[cosmwasm_std::coin(10_000u128, TOKEN_A)],
+        )
+        .unwrap_err();
+    assert!(err
+        .root_cause()
+        .to_string()
+        .contains("exceeds maximum spend"));
+
+    // Under-funding is rejected
+    let err = app
+        .execute_contract(
+            user2.clone(),
+            pool_addr.clone(),
+            &PoolMsg::ExecuteMsg::SwapExactOut {
+                ask_denom: TOKEN_B.into(),
+                ask_amount,
+                max_spend: Uint128::new(10_000),
+                deadline: None,
+            },
+            &[cosmwasm_std::coin(
+                (reverse.offer_amount - Uint128::one()).u128(),
+                TOKEN_A,
+            )],
+        )
+        .unwrap_err();
+    assert_eq!(
+        err.root_cause().to_string(),
+        format!(
+            "Required offer {} exceeds the {} sent",
+            reverse.offer_amount,
+            reverse.offer_amount - Uint128::one()
+        )
+    );
+
+    // Asking for a non-pool denom is rejected
+    let err = app
+        .execute_contract(
+            user2.clone(),
+            pool_addr.clone(),
+            &PoolMsg::ExecuteMsg::SwapExactOut {
+                ask_denom: "tokenC".into(),
+                ask_amount,
+                max_spend: Uint128::new(10_000),
+                deadline: None,
+            },
+            &[cosmwasm_std::coin(10_000u128, TOKEN_A)],
+        )
+        .unwrap_err();
+    assert!(err
+        .root_cause()
+        .to_string()
+        .contains("Invalid denom received: tokenC"));
+}
+
+#[test]
+fn test_simulate_liquidity() {
+    let (mut app, factory_addr, _factory_code_id, pool_code_id, owner, user1, user2) = setup_app();
+    let (pool_addr, lp_token_addr) =
+        create_basic_pool(&mut app, &factory_addr, pool_code_id, &user1);
+    let lp_balance = |app: &App, addr: &Addr| -> Uint128 {
+        let resp: BalanceResponse = app
+            .wrap()
+            .query_wasm_smart(
+                lp_token_addr.clone(),
+                &cw20::Cw20QueryMsg::Balance {
+                    address: addr.to_string(),
+                },
+            )
+            .unwrap();
+        resp.balance
+    };
+
+    // Protocol fee on, and swap fees accrued, so quotes must account for the fee mint
+    app.execute_contract(
+        owner.clone(),
+        factory_addr.clone(),
+        &FactoryMsg::ExecuteMsg::UpdateProtocolFee {
+            treasury: Some(owner.to_string()),
+            share_bps: 5_000,
+        },
+        &[],
+    )
+    .unwrap();
+    app.execute_contract(
+        user1.clone(),
+        pool_addr.clone(),
+        &PoolMsg::ExecuteMsg::AddLiquidity {
+            min_lp_out: None,
+            deadline: None,
+            to: None,
+        },
+        &[
+            cosmwasm_std::coin(10_000u128, TOKEN_A),
+            cosmwasm_std::coin(20_000u128, TOKEN_B),
+        ],
+    )
+    .unwrap();
+    app.execute_contract(
+        user2.clone(),
+        pool_addr.clone(),
+        &PoolMsg::ExecuteMsg::Swap {
+            offer_denom: TOKEN_A.into(),
+            min_receive: Uint128::one(),
+            belief_price: None,
+            max_spread: None,
+            deadline: None,
+            to: None,
+        },
+        &[cosmwasm_std::coin(20_000u128, TOKEN_A)],
+    )
+    .unwrap();
+
+    // Provide
+    let reserve_a = app
+        .wrap()
+        .query_balance(&pool_addr, TOKEN_A)
+        .unwrap()
+        .amount;
+    let reserve_b = app
+        .wrap()
+        .query_balance(&pool_addr, TOKEN_B)
+        .unwrap()
+        .amount;
+    let assets = vec![
+        cosmwasm_std::coin(reserve_a.u128() / 10, TOKEN_A),
+        cosmwasm_std::coin(reserve_b.u128() / 10, TOKEN_B),
+    ];
+    let sim: PoolMsg::SimulateProvideLiquidityResponse = app
+        .wrap()
+        .query_wasm_smart(
+            pool_addr.clone(),
+            &PoolMsg::QueryMsg::SimulateProvideLiquidity {
+                assets: assets.clone(),
+            },
+        )
+        .unwrap();
+    assert!(sim.refund_assets.is_empty());
+    let before = lp_balance(&app, &user2);
+    app.execute_contract(
+        user2.clone(),
+        pool_addr.clone(),
+        &PoolMsg::ExecuteMsg::AddLiquidity {
+            min_lp_out: None,
+            deadline: None,
+            to: None,
+        },
+        &assets,
+    )
+    .unwrap();
+    assert_eq!(lp_balance(&app, &user2) - before, sim.shares);
+    let supply: TokenInfoResponse = app
+        .wrap()
+        .query_wasm_smart(lp_token_addr.clone(), &cw20::Cw20QueryMsg::TokenInfo {})
+        .unwrap();
+    assert_eq!(
+        sim.share_of_pool,
+        Decimal::from_ratio(sim.shares, supply.total_supply)
+    );
+
+    // Excess of one side is quoted as a refund, matching the execution
+    let assets = vec![
+        cosmwasm_std::coin(20_000u128, TOKEN_A),
+        cosmwasm_std::coin(1_000u128, TOKEN_B),
+    ];
+    let sim: PoolMsg::SimulateProvideLiquidityResponse = app
+        .wrap()
+        .query_wasm_smart(
+            pool_addr.clone(),
+            &PoolMsg::QueryMsg::SimulateProvideLiquidity {
+                assets: assets.clone(),
+            },
+        )
+        .unwrap();
+    assert_eq!(sim.refund_assets.len(), 1);
+    assert_eq!(sim.refund_assets[0].denom, TOKEN_A);
+    let refund_a = sim.refund_assets[0].amount;
+    let before = lp_balance(&app, &user2);
+    let before_a = app.wrap().query_balance(&user2, TOKEN_A).unwrap().amount;
+    let before_b = app.wrap().query_balance(&user2, TOKEN_B).unwrap().amount;
+    let res = app
+        .execute_contract(
+            user2.clone(),
+            pool_addr.clone(),
+            &PoolMsg::ExecuteMsg::AddLiquidity {
+                min_lp_out: None,
+                deadline: None,
+                to: None,
+            },
+            &assets,
+        )
+        .unwrap();
+    assert_eq!(lp_balance(&app, &user2) - before, sim.shares);
+    assert_eq!(
+        before_a - app.wrap().query_balance(&user2, TOKEN_A).unwrap().amount,
+        Uint128::new(20_000) - refund_a
+    );
+    assert_eq!(
+        before_b - app.wrap().query_balance(&user2, TOKEN_B).unwrap().amount,
+        Uint128::new(1_000)
+    );
+    assert!(res.events.iter().any(|e| e
+        .attributes
+        .iter()
+        .any(|a| a.key == "denom_a_refunded" && a.value == refund_a.to_string())));
+
+    // Withdraw, after more fees accrue
+    app.execute_contract(
+        user2.clone(),
+        pool_addr.clone(),
+        &PoolMsg::ExecuteMsg::Swap {
+            offer_denom: TOKEN_B.into(),
+            min_receive: Uint128::one(),
+            belief_price: None,
+            max_spread: None,
+            deadline: None,
+            to: None,
+        },
+        &[cosmwasm_std::coin(30_000u128, TOKEN_B)],
+    )
+    .unwrap();
+    let lp_amount = lp_balance(&app, &user1) / Uint128::new(2);
+    let sim: PoolMsg::SimulateWithdrawLiquidityResponse = app
+        .wrap()
+        .query_wasm_smart(
+            pool_addr.clone(),
+            &PoolMsg::QueryMsg::SimulateWithdrawLiquidity { lp_amount },
+        )
+        .unwrap();
+    let before_a = app.wrap().query_balance(&user1, TOKEN_A).unwrap().amount;
+    let before_b = app.wrap().query_balance(&user1, TOKEN_B).unwrap().amount;
+    let owner_lp_before = lp_balance(&app, &owner);
+    let supply_before: TokenInfoResponse = app
+        .wrap()
+        .query_wasm_smart(lp_token_addr.clone(), &cw20::Cw20QueryMsg::TokenInfo {})
+        .unwrap();
+    app.execute_contract(
+        user1.clone(),
+        lp_token_addr.clone(),
+        &cw20::Cw20ExecuteMsg::Send {
+            contract: pool_addr.to_string(),
+            amount: lp_amount,
+            msg: to_json_binary(&Cw20HookMsg::WithdrawLiquidity {
+                deadline: None,
+                to: None,
+            })
+            .unwrap(),
+        },
+        &[],
+    )
+    .unwrap();
+    let received_a = app.wrap().query_balance(&user1, TOKEN_A).unwrap().amount - before_a;
+    let received_b = app.wrap().query_balance(&user1, TOKEN_B).unwrap().amount - before_b;
+    assert_eq!(
+        sim.assets,
+        vec![
+            cosmwasm_std::coin(received_a.u128(), TOKEN_A),
+            cosmwasm_std::coin(received_b.u128(), TOKEN_B),
+        ]
+    );
+    let fee_shares = lp_balance(&app, &owner) - owner_lp_before;
+    assert!(!fee_shares.is_zero());
+    assert_eq!(
+        sim.share_of_pool,
+        Decimal::from_ratio(lp_amount, supply_before.total_supply + fee_shares)
+    );
+
+    let err = app
+        .wrap()
+        .query_wasm_smart::<PoolMsg::SimulateWithdrawLiquidityResponse>(
+            pool_addr.clone(),
+            &PoolMsg::QueryMsg::SimulateWithdrawLiquidity {
+                lp_amount: Uint128::MAX,
+            },
+        )
+        .unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("Withdraw amount exceeds the LP token supply"));
+}
+
+#[test]
+fn test_protocol_fee() {
+    let (mut app, factory_addr, _factory_code_id, pool_code_id, owner, user1, user2) = setup_app();
+    let treasury = app.api().addr_make("treasury");
+
+    // Only the admin can switch the protocol fee on
+    let update_msg = FactoryMsg::ExecuteMsg::UpdateProtocolFee {
+        treasury: Some(treasury.to_string()),
+        share_bps: 1_667,
+    };
+    let err = app
+        .execute_contract(user1.clone(), factory_addr.clone(), &update_msg, &[])
+        .unwrap_err();
+    assert_eq!(err.root_cause().to_string(), "Unauthorized");
+    let err = app
+        .execute_contract(
+            owner.clone(),
+            factory_addr.clone(),
+            &FactoryMsg::ExecuteMsg::UpdateProtocolFee {
+                treasury: Some(treasury.to_string()),
+                share_bps: 10_001,
+            },
+            &[],
+        )
+        .unwrap_err();
+    assert!(err
+        .root_cause()
+        .to_string()
+        .contains("Invalid protocol fee share"));
+    app.execute_contract(owner.clone(), factory_addr.clone(), &update_msg, &[])
+        .unwrap();
+    let fee: FactoryMsg::ProtocolFeeResponse = app
+        .wrap()
+        .query_wasm_smart(factory_addr.clone(), &FactoryMsg::QueryMsg::ProtocolFee {})
+        .unwrap();
+    assert_eq!(fee.treasury, Some(treasury.clone()));
+    assert_eq!(fee.share_bps, 1_667);
+
+    // Initial deposit of 100_000 A / 200_000 B records k_last, nothing is owed yet
+    let (pool_addr, lp_token_addr) =
+        create_basic_pool(&mut app, &factory_addr, pool_code_id, &user1);
+    let treasury_lp = |app: &App| -> Uint128 {
+        let resp: BalanceResponse = app
+            .wrap()
+            .query_wasm_smart(
+                lp_token_addr.clone(),
+                &cw20::Cw20QueryMsg::Balance {
+                    address: treasury.to_string(),
+                },
+            )
+            .unwrap();
+        resp.balance
+    };
+    assert!(treasury_lp(&app).is_zero());
+
+    // Swap fees grow k
+    app.execute_contract(
+        user2.clone(),
+        pool_addr.clone(),
+        &PoolMsg::ExecuteMsg::Swap {
+            offer_denom: TOKEN_A.into(),
+            min_receive: Uint128::one(),
+            belief_price: None,
+            max_spread: None,
+            deadline: None,
+            to: None,
+        },
+        &[cosmwasm_std::coin(10_000u128, TOKEN_A)],
+    )
+    .unwrap();
+    let reserve_a = app
+        .wrap()
+        .query_balance(&pool_addr, TOKEN_A)
+        .unwrap()
+        .amount;
+    let reserve_b = app
+        .wrap()
+        .query_balance(&pool_addr, TOKEN_B)
+        .unwrap()
+        .amount;
+    let supply: TokenInfoResponse = app
+        .wrap()
+        .query_wasm_smart(lp_token_addr.clone(), &cw20::Cw20QueryMsg::TokenInfo {})
+        .unwrap();
+    let root_k = (reserve_a.full_mul(reserve_b)).isqrt();
+    let root_k_last = (Uint128::new(100_000).full_mul(Uint128::new(200_000))).isqrt();
+    let expected_fee_shares = Uint128::try_from(
+        cosmwasm_std::Uint256::from(supply.total_supply)
+            * cosmwasm_std::Uint256::from(1_667u16)
+            * (root_k - root_k_last)
+            / (root_k * cosmwasm_std::Uint256::from(8_333u16)
+                + root_k_last * cosmwasm_std::Uint256::from(1_667u16)),
+    )
+    .unwrap();
+    assert!(!expected_fee_shares.is_zero());
+
+    // The next liquidity event mints the accrued fee to the treasury
+    app.execute_contract(
+        user1.clone(),
+        lp_token_addr.clone(),
+        &cw20::Cw20ExecuteMsg::Send {
+            contract: pool_addr.to_string(),
+            amount: Uint128::new(1_000),
+            msg: to_json_binary(&Cw20HookMsg::WithdrawLiquidity {
+                deadline: None,
+                to: None,
+            })
+            .unwrap(),
+        },
+        &[],
+    )
+    .unwrap();
+    assert_eq!(treasury_lp(&app), expected_fee_shares);
+    let supply_after: TokenInfoResponse = app
+        .wrap()
+        .query_wasm_smart(lp_token_addr.clone(), &cw20::Cw20QueryMsg::TokenInfo {})
+        .unwrap();
+    assert_eq!(
+        supply_after.total_supply,
+        supply.total_supply + expected_fee_shares - Uint128::new(1_000)
+    );
+
+    // Switching the fee off stops minting
+    app.execute_contract(
+        owner.clone(),
+        factory_addr.clone(),
+        &FactoryMsg::ExecuteMsg::UpdateProtocolFee {
+            treasury: None,
+            share_bps: 0,
+        },
+        &[],
+    )
+    .unwrap();
+    app.execute_contract(
+        user2.clone(),
+        pool_addr.clone(),
+        &PoolMsg::ExecuteMsg::Swap {
+            offer_denom: TOKEN_B.into(),
+            min_receive: Uint128::one(),
+            belief_price: None,
+            max_spread: None,
+            deadline: None,
+            to: None,
+        },
+        &[cosmwasm_std::coin(10_000u128, TOKEN_B)],
+    )
+    .unwrap();
+    app.execute_contract(
+        user1.clone(),
+        lp_token_addr.clone(),
+        &cw20::Cw20ExecuteMsg::Send {
+            contract: pool_addr.to_string(),
+            amount: Uint128::new(1_000),
+            msg: to_json_binary(&Cw20HookMsg::WithdrawLiquidity {
+                deadline: None,
+                to: None,
+            })
+            .unwrap(),
+        },
+        &[],
+    )
+    .unwrap();
+    assert_eq!(treasury_lp(&app), expected_fee_shares);
+}
+
+#[test]
+fn test_add_liquidity_errors() {
+    let (mut app, factory_addr, _factory_code_id, pool_code_id, _owner, user1, _user2) =
+        setup_app();
+    let (pool_addr, _lp_token_addr) =
+        create_basic_pool(&mut app, &factory_addr, pool_code_id, &user1);
+
+    // --- Test Add Zero Amount ---
+    let add_msg_zero = PoolMsg::ExecuteMsg::AddLiquidity {
+        min_lp_out: None,
+        deadline: None,
+        to: None,
+    };
+    let err_zero_a = app
+        .execute_contract(
+            user1.clone(),
+            pool_addr.clone(),
+            &add_msg_zero,
+            &[
+                cosmwasm_std::coin(0u128, TOKEN_A),
+                cosmwasm_std::coin(100u128, TOKEN_B),
+            ], // Send 0 A
+        )
+        .unwrap_err();
+    assert!(err_zero_a
+        .root_cause()
+        .to_string()
+        .contains("Must provide both tokens"));
+
+    let err_zero_b = app
+        .execute_contract(
+            user1.clone(),
+            pool_addr.clone(),
+            &add_msg_zero,
+            &[
+                cosmwasm_std::coin(100u128, TOKEN_A),
+                cosmwasm_std::coin(0u128, TOKEN_B),
+            ], // Send 0 B
+        )
+        .unwrap_err();
+    assert!(err_zero_b
+        .root_cause()
+        .to_string()
+        .contains("Must provide both tokens"));
+
+    // --- Test Add Only One Token ---
+    let add_msg_one = PoolMsg::ExecuteMsg::AddLiquidity {
+        min_lp_out: None,
+        deadline: None,
+        to: None,
+    };
+    let err_one = app
+        .execute_contract(
+            user1.clone(),
+            pool_addr.clone(),
+            &add_msg_one,
+            &[cosmwasm_std::coin(100u128, TOKEN_A)], // Only send A
+        )
+        .unwrap_err();
+    assert!(err_one
+        .root_cause()
+        .to_string()
+        .contains("Must provide both tokens"));
+
+    // --- Test Ratio Mismatch ---
+    let add_msg_slippage = PoolMsg::ExecuteMsg::AddLiquidity {
+        min_lp_out: None,
+        deadline: None,
+        to: None,
+    };
+    app.execute_contract(
+        user1.clone(),
+        pool_addr.clone(),
+        &add_msg_slippage,
+        &[
+            cosmwasm_std::coin(1000u128, TOKEN_A),
+            cosmwasm_std::coin(1000u128, TOKEN_B),
+        ],
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_first_deposit_inflation_attack() {
+    let (mut app, factory_addr, _factory_code_id, pool_code_id, _owner, attacker, victim) =
+        setup_app();
+    let (pool_addr, lp_token_addr) = create_empty_pool(
+        &mut app,
+        &factory_addr,
+        pool_code_id,
+        &attacker,
+        DEFAULT_FEE_BPS,
+    );
+    let add_msg = PoolMsg::ExecuteMsg::AddLiquidity {
+        min_lp_out: None,
+        deadline: None,
+        to: None,
+    };
+    let lp_balance = |app: &App, addr: &Addr| -> Uint128 {
+        let resp: BalanceResponse = app
+            .wrap()
+            .query_wasm_smart(
+                lp_token_addr.clone(),
+                &cw20::Cw20QueryMsg::Balance {
+                    address: addr.to_string(),
+                },
+            )
+            .unwrap();
+        resp.balance
+    };
+    let withdraw_all = |app: &mut App, user: &Addr| -> Uint128 {
+        let before = app.wrap().query_balance(user, TOKEN_A).unwrap().amount;
+        app.execute_contract(
+            user.clone(),
+            lp_token_addr.clone(),
+            &cw20::Cw20ExecuteMsg::Send {
+                contract: pool_addr.to_string(),
+                amount: lp_balance(app, user),
+                msg: to_json_binary(&Cw20HookMsg::WithdrawLiquidity {
+                    deadline: None,
+                    to: None,
+                })
+                .unwrap(),
+            },
+            &[],
+        )
+        .unwrap();
+        app.wrap().query_balance(user, TOKEN_A).unwrap().amount - before
+    };
+
+    // A dust first deposit worth a single share is no longer possible
+    for amount in [1u128, MINIMUM_LIQUIDITY.u128()] {
+        let err = app
+            .execute_contract(
+                attacker.clone(),
+                pool_addr.clone(),
+                &add_msg,
+                &[
+                    cosmwasm_std::coin(amount, TOKEN_A),
+                    cosmwasm_std::coin(amount, TOKEN_B),
+                ],
+            )
+            .unwrap_err();
+        assert_eq!(
+            err.downcast::<pool_constant_product::ContractError>()
+                .unwrap(),
+            pool_constant_product::ContractError::InitialLiquidityTooLow {}
+        );
+    }
+
+    // The smallest allowed first deposit mints one share
+    let first = MINIMUM_LIQUIDITY.u128() + 1;
+    app.execute_contract(
+        attacker.clone(),
+        pool_addr.clone(),
+        &add_msg,
+        &[
+            cosmwasm_std::coin(first, TOKEN_A),
+            cosmwasm_std::coin(first, TOKEN_B),
+        ],
+    )
+    .unwrap();
+    assert_eq!(lp_balance(&app, &attacker), Uint128::one());
+
+    // Donating to the pool and syncing inflates the share price...
+    let donation = 500_000u128;
+    app.send_tokens(
+        attacker.clone(),
+        pool_addr.clone(),
+        &[
+            cosmwasm_std::coin(donation, TOKEN_A),
+            cosmwasm_std::coin(donation, TOKEN_B),
+        ],
+    )
+    .unwrap();
+    app.execute_contract(
+        attacker.clone(),
+        pool_addr.clone(),
+        &PoolMsg::ExecuteMsg::Sync {},
+        &[],
+    )
+    .unwrap();
+
+    // ...but the victim's deposit still mints shares worth about what it paid
+    let deposit = 600_000u128;
+    app.execute_contract(
+        victim.clone(),
+        pool_addr.clone(),
+        &add_msg,
+        &[
+            cosmwasm_std::coin(deposit, TOKEN_A),
+            cosmwasm_std::coin(deposit, TOKEN_B),
+        ],
+    )
+    .unwrap();
+    assert!(!lp_balance(&app, &victim).is_zero());
+    let victim_received = withdraw_all(&mut app, &victim);
+    assert!(victim_received.u128() >= deposit - deposit / 1_000);
+
+    // The attacker loses nearly all of the donation to the locked shares
+    let attacker_received = withdraw_all(&mut app, &attacker);
+    assert!(attacker_received.u128() < donation / 100);
+}
+
+#[test]
+fn test_sync_and_skim() {
+    let (mut app, factory_addr, _factory_code_id, pool_code_id, _owner, user1, user2) = setup_app();
+    let (pool_addr, _lp_token_addr) =
+        create_basic_pool(&mut app, &factory_addr, pool_code_id, &user1);
+    let pool_state = |app: &App| -> PoolStateResponse {
+        app.wrap()
+            .query_wasm_smart(pool_addr.clone(), &PoolMsg::QueryMsg::PoolState {})
+            .unwrap()
+    };
+    let simulate = |app: &App| -> PoolMsg::SimulationResponse {
+        app.wrap()
+            .query_wasm_smart(
+                pool_addr.clone(),
+                &PoolMsg::QueryMsg::SimulateSwap {
+                    offer_denom: TOKEN_A.into(),
+                    offer_amount: Uint128::new(1_000),
+                },
+            )
+            .unwrap()
+    };
+    let before = pool_state(&app);
+    let quote_before = simulate(&app);
+
+    // Tokens sent directly show in the balances but don't move the price
+    app.send_tokens(
+        user1.clone(),
+        pool_addr.clone(),
+        &[cosmwasm_std::coin(50_000u128, TOKEN_B)],
+    )
+    .unwrap();
+    let donated = pool_state(&app);
+    assert_eq!(donated.reserve_a, before.reserve_a);
+    assert_eq!(donated.reserve_b, before.reserve_b);
+    assert_eq!(donated.balance_a, before.reserve_a);
+    assert_eq!(donated.balance_b, before.reserve_b + Uint128::new(50_000));
+    assert_eq!(simulate(&app), quote_before);
+
+    // Swaps keep the tracked reserves in step with the tokens moved
+    let res = app
+        .execute_contract(
+            user2.clone(),
+            pool_addr.clone(),
+            &PoolMsg::ExecuteMsg::Swap {
+                offer_denom: TOKEN_A.into(),
+                min_receive: Uint128::one(),
+                belief_price: None,
+                max_spread: None,
+                deadline: None,
+                to: None,
+            },
+            &[cosmwasm_std::coin(1_000u128, TOKEN_A)],
+        )
+        .unwrap();
+    assert!(res
+        .events
+        .iter()
+        .any(|e| e.attributes.iter().any(
+            |a| a.key == "return_amount" && a.value == quote_before.return_amount.to_string()
+        )));
+    let swapped = pool_state(&app);
+    assert_eq!(swapped.reserve_a, before.reserve_a + Uint128::new(1_000));
+    assert_eq!(
+        swapped.reserve_b,
+        before.reserve_b - quote_before.return_amount
+    );
+    assert_eq!(swapped.balance_b - swapped.reserve_b, Uint128::new(50_000));
+
+    // Skim sends the surplus away
+    let user2_b = app.wrap().query_balance(&user2, TOKEN_B).unwrap().amount;
+    app.execute_contract(
+        user1.clone(),
+        pool_addr.clone(),
+        &PoolMsg::ExecuteMsg::Skim {
+            to: Some(user2.to_string()),
+        },
+        &[],
+    )
+    .unwrap();
+    assert_eq!(
+        app.wrap().query_balance(&user2, TOKEN_B).unwrap().amount - user2_b,
+        Uint128::new(50_000)
+    );
+    let skimmed = pool_state(&app);
+    assert_eq!(skimmed.balance_a, skimmed.reserve_a);
+    assert_eq!(skimmed.balance_b, skimmed.reserve_b);
+
+    // Sync adopts the balances as reserves
+    app.send_tokens(
+        user1.clone(),
+        pool_addr.clone(),
+        &[cosmwasm_std::coin(20_000u128, TOKEN_A)],
+    )
+    .unwrap();
+    app.execute_contract(
+        user2.clone(),
+        pool_addr.clone(),
+        &PoolMsg::ExecuteMsg::Sync {},
+        &[],
+    )
+    .unwrap();
+    let synced = pool_state(&app);
+    assert_eq!(synced.reserve_a, skimmed.reserve_a + Uint128::new(20_000));
+    assert_eq!(synced.reserve_a, synced.balance_a);
+    assert_eq!(synced.reserve_b, synced.balance_b);
+    assert!(simulate(&app).return_amount < quote_before.return_amount);
+}
+
+#[test]
+fn test_twap_oracle() {
+    let (mut app, factory_addr, _factory_code_id, pool_code_id, _owner, user1, user2) = setup_app();
+    let (pool_addr, _lp_token_addr) =
+        create_basic_pool(&mut app, &factory_addr, pool_code_id, &user1);
+    let advance = |app: &mut App, seconds: u64| {
+        app.update_block(|block| {
+            block.time = block.time.plus_seconds(seconds);
+            block.height += 1;
+        })
+    };
+    let twap = |app: &App, start_age: u64, end_age: u64| {
+        app.wrap().query_wasm_smart::<PoolMsg::TwapResponse>(
+            pool_addr.clone(),
+            &PoolMsg::QueryMsg::Twap { start_age, end_age },
+        )
+    };
+    let initial_price = Decimal256::from_ratio(200_000u128, 100_000u128);
+
+    // 100 seconds at the initial price, then a swap moves it
+    advance(&mut app, 100);
+    app.execute_contract(
+        user2.clone(),
+        pool_addr.clone(),
+        &PoolMsg::ExecuteMsg::Swap {
+            offer_denom: TOKEN_A.into(),
+            min_receive: Uint128::one(),
+            belief_price: None,
+            max_spread: None,
+            deadline: None,
+            to: None,
+        },
+        &[cosmwasm_std::coin(10_000u128, TOKEN_A)],
+    )
+    .unwrap();
+    let state: PoolStateResponse = app
+        .wrap()
+        .query_wasm_smart(pool_addr.clone(), &PoolMsg::QueryMsg::PoolState {})
+        .unwrap();
+    let swapped_price = Decimal256::from_ratio(state.reserve_b, state.reserve_a);
+    assert!(swapped_price < initial_price);
+
+    // 300 seconds at the new price; a donation does not count
+    advance(&mut app, 300);
+    app.send_tokens(
+        user1.clone(),
+        pool_addr.clone(),
+        &[cosmwasm_std::coin(50_000u128, TOKEN_B)],
+    )
+    .unwrap();
+
+    let cumulative: PoolMsg::CumulativePricesResponse = app
+        .wrap()
+        .query_wasm_smart(pool_addr.clone(), &PoolMsg::QueryMsg::CumulativePrices {})
+        .unwrap();
+    assert_eq!(cumulative.timestamp, app.block_info().time);
+    assert_eq!(cumulative.reserve_b, state.reserve_b);
+    assert_eq!(
+        cumulative.price_a_cumulative,
+        initial_price.atomics() * Uint256::from(100u32)
+            + swapped_price.atomics() * Uint256::from(300u32)
+    );
+
+    let recent = twap(&app, 300, 0).unwrap();
+    assert_eq!(recent.price_a, swapped_price);
+    assert_eq!(recent.end_time, app.block_info().time);
+    assert_eq!(twap(&app, 400, 300).unwrap().price_a, initial_price);
+    // Windows between observations are interpolated
+    let spanning = twap(&app, 350, 50).unwrap();
+    assert_eq!(
+        spanning.price_a.atomics(),
+        (initial_price.atomics() * Uint256::from(50u32)
+            + swapped_price.atomics() * Uint256::from(250u32))
+            / Uint256::from(300u32)
+    );
+    assert_eq!(spanning.price_b, {
+        let price_b = |price: Decimal256| Decimal256::one() / price;
+        let a = price_b(initial_price).atomics() * Uint256::from(50u32);
+        let b = Decimal256::from_ratio(state.reserve_a, state.reserve_b).atomics()
+            * Uint256::from(250u32);
+        Decimal256::new((a + b) / Uint256::from(300u32))
+    });
+
+    // Nothing before the pool existed, and the window must not be empty
+    assert!(twap(&app, 401, 0).is_err());
+    assert!(twap(&app, 10, 10).is_err());
+}
+
+#[test]
+fn test_flash_loan() {
+    let (mut app, factory_addr, _factory_code_id, pool_code_id, owner, user1, user2) = setup_app();
+
+    // Only the admin can set the flash loan fee, and it must be below 100%
+    let update_msg = FactoryMsg::ExecuteMsg::UpdateFlashLoanFee { fee_bps: 9 };
+    let err = app
+        .execute_contract(user1.clone(), factory_addr.clone(), &update_msg, &[])
+        .unwrap_err();
+    assert_eq!(err.root_cause().to_string(), "Unauthorized");
+    let err = app
+        .execute_contract(
+            owner.clone(),
+            factory_addr.clone(),
+            &FactoryMsg::ExecuteMsg::UpdateFlashLoanFee { fee_bps: 10_000 },
+            &[],
+        )
+        .unwrap_err();
+    assert!(err
+        .root_cause()
+        .to_string()
+        .contains("Invalid flash loan fee"));
+    app.execute_contract(owner.clone(), factory_addr.clone(), &update_msg, &[])
+        .unwrap();
+    let fee: FactoryMsg::FlashLoanFeeResponse = app
+        .wrap()
+        .query_wasm_smart(factory_addr.clone(), &FactoryMsg::QueryMsg::FlashLoanFee {})
+        .unwrap();
+    assert_eq!(fee.fee_bps, 9);
+
+    // Pool of 100_000 A / 200_000 B, and a borrower holding enough to pay the fees
+    let (pool_addr, _lp_token_addr) =
+        create_basic_pool(&mut app, &factory_addr, pool_code_id, &user1);
+    let borrower_code_id = app.store_code(mock_borrower::contract());
+    let borrower = app
+        .instantiate_contract(
+            borrower_code_id,
+            user2.clone(),
+            &cosmwasm_std::Empty {},
+            &[],
+            "MockBorrower",
+            None,
+        )
+        .unwrap();
+    app.sudo(cw_multi_test::SudoMsg::Bank(BankSudo::Mint {
+        to_address: borrower.to_string(),
+        amount: vec![
+            cosmwasm_std::coin(1_000u128, TOKEN_A),
+            cosmwasm_std::coin(1_000u128, TOKEN_B),
+        ],
+    }))
+    .unwrap();
+    let pool_state = |app: &App| -> PoolStateResponse {
+        app.wrap()
+            .query_wasm_smart(pool_addr.clone(), &PoolMsg::QueryMsg::PoolState {})
+            .unwrap()
+    };
+    let borrower_balances = |app: &App| -> (Uint128, Uint128) {
+        (
+            app.wrap().query_balance(&borrower, TOKEN_A).unwrap().amount,
+            app.wrap().query_balance(&borrower, TOKEN_B).unwrap().amount,
+        )
+    };
+    let borrow = |app: &mut App, repay_a: u128, repay_b: u128, sync_pool: bool| {
+        app.execute_contract(
+            user2.clone(),
+            borrower.clone(),
+            &mock_borrower::ExecuteMsg::Borrow {
+                pool: pool_addr.to_string(),
+                assets: vec![
+                    cosmwasm_std::coin(10_000u128, TOKEN_B),
+                    cosmwasm_std::coin(50_000u128, TOKEN_A),
+                ],
+                repay: vec![
+                    cosmwasm_std::coin(repay_a, TOKEN_A),
+                    cosmwasm_std::coin(repay_b, TOKEN_B),
+                ],
+                sync_pool,
+            },
+            &[],
+        )
+    };
+
+    // Repaying the loan plus 9 bps, rounded up, grows the reserves by the fees
+    let res = borrow(&mut app, 50_045, 10_009, false).unwrap();
+    assert!(res.events.iter().any(|e| e
+        .attributes
+        .iter()
+        .any(|a| a.key == "fees" && a.value == "45tokenA,9tokenB")));
+    let repaid = pool_state(&app);
+    assert_eq!(repaid.reserve_a, Uint128::new(100_045));
+    assert_eq!(repaid.reserve_b, Uint128::new(200_009));
+    assert_eq!(repaid.balance_a, repaid.reserve_a);
+    assert_eq!(repaid.balance_b, repaid.reserve_b);
+    assert_eq!(
+        borrower_balances(&app),
+        (Uint128::new(955), Uint128::new(991))
+    );
+
+    // Falling short of the fee by one reverts the loan along with the callback
+    let err = borrow(&mut app, 50_044, 10_009, false).unwrap_err();
+    assert_eq!(
+        err.root_cause().to_string(),
+        "Flash loan not repaid: pool holds 100089 tokenA, 100090 due"
+    );
+    assert_eq!(pool_state(&app), repaid);
+    assert_eq!(
+        borrower_balances(&app),
+        (Uint128::new(955), Uint128::new(991))
+    );
+
+    // The pool can't be used from the callback
+    let err = borrow(&mut app, 50_045, 10_009, true).unwrap_err();
+    assert_eq!(err.root_cause().to_string(), "A flash loan is in progress");
+    assert_eq!(pool_state(&app), repaid);
+
+    // Nothing is left pending after a reverted loan
+    app.execute_contract(
+        user1.clone(),
+        pool_addr.clone(),
+        &PoolMsg::ExecuteMsg::Sync {},
+        &[],
+    )
+    .unwrap();
+
+    // Invalid loans are rejected before any funds move
+    let flash_loan_err = |app: &mut App, assets: Vec<Coin>| -> String {
+        app.execute_contract(
+            user1.clone(),
+            pool_addr.clone(),
+            &PoolMsg::ExecuteMsg::FlashLoan {
+                assets,
+                callback_msg: to_json_binary(&cosmwasm_std::Empty {}).unwrap(),
+            },
+            &[],
+        )
+        .unwrap_err()
+        .root_cause()
+        .to_string()
+    };
+    assert_eq!(
+        flash_loan_err(&mut app, vec![cosmwasm_std::coin(100_046u128, TOKEN_A)]),
+        "Flash loan of 100046 tokenA must be below the reserve of 100045"
+    );
+    // Not even the full reserve can be borrowed
+    assert_eq!(
+        flash_loan_err(&mut app, vec![cosmwasm_std::coin(100_045u128, TOKEN_A)]),
+        "Flash loan of 100045 tokenA must be below the reserve of 100045"
+    );
+    assert_eq!(
+        flash_loan_err(&mut app, vec![cosmwasm_std::coin(1u128, TOKEN_C)]),
+        format!("Invalid denom received: {TOKEN_C}")
+    );
+    assert_eq!(
+        flash_loan_err(
+            &mut app,
+            vec![
+                cosmwasm_std::coin(1u128, TOKEN_A),
+                cosmwasm_std::coin(2u128, TOKEN_A)
+            ]
+        ),
+        format!("Flash loan lists denom {TOKEN_A} more than once")
+    );
+    for assets in [vec![], vec![cosmwasm_std::coin(0u128, TOKEN_A)]] {
+        assert!(flash_loan_err(&mut app, assets).contains("at least one asset"));
+    }
+}
+
+#[test]
+fn test_swap_errors() {
+    let (mut app, factory_addr, _factory_code_id, pool_code_id, _owner, user1, _user2) =
+        setup_app();
+    let (pool_addr, _lp_token_addr) =
+        create_basic_pool(&mut app, &factory_addr, pool_code_id, &user1);
+
+    // --- Test Swap with Non-Pool Token ---
+    let swap_msg_wrong_offer = PoolMsg::ExecuteMsg::Swap {
+        offer_denom: "tokenC".into(),
+        min_receive: Uint128::one(),
+        belief_price: None,
+        max_spread: None,
+        deadline: None,
+        to: None,
+    };
+    let err_wrong_offer = app
+        .execute_contract(
+            user1.clone(),
+            pool_addr.clone(),
+            &swap_msg_wrong_offer,
+            &[cosmwasm_std::coin(100u128, "tokenC")],
+        )
+        .unwrap_err();
+    assert!(err_wrong_offer
+        .root_cause()
+        .to_string()
+        .contains("Cannot Sub with given operands"));
+
+    // --- Test Swap Zero Offer ---
+    let swap_msg_zero = PoolMsg::ExecuteMsg::Swap {
+        offer_denom: TOKEN_A.into(),
+        min_receive: Uint128::one(),
+        belief_price: None,
+        max_spread: None,
+        deadline: None,
+        to: None,
+    };
+    let err_zero = app
+        .execute_contract(
+            user1.clone(),
+            pool_addr.clone(),
+            &swap_msg_zero,
+            &[cosmwasm_std::coin(0u128, TOKEN_A)],
+        )
+        .unwrap_err();
+    assert!(err_zero
+        .root_cause()
+        .to_string()
+        .contains("Cannot transfer empty coins amount"));
+
+    // --- Test Swap Wrong Denom Sent (Funds mismatch message) ---
     let swap_msg_wrong_denom = PoolMsg::ExecuteMsg::Swap {
         offer_denom: TOKEN_A.into(),
         min_receive: Uint128::one(),
+        belief_price: None,
+        max_spread: None,
+        deadline: None,
+        to: None,
     };
     let err_wrong_denom = app
         .execute_contract(
@@ -594,6 +1957,10 @@ fn test_swap_errors() {
     let swap_msg_min_recv = PoolMsg::ExecuteMsg::Swap {
         offer_denom: TOKEN_A.into(),
         min_receive: Uint128::new(200000),
+        belief_price: None,
+        max_spread: None,
+        deadline: None,
+        to: None,
     };
     let err_min_recv = app
         .execute_contract(
@@ -613,6 +1980,296 @@ fn test_swap_errors() {
         .contains(" less than minimum requested "));
 }
 
+#[test]
+fn test_execute_guards() {
+    let (mut app, factory_addr, _factory_code_id, pool_code_id, _owner, user1, user2) = setup_app();
+    let (pool_addr, lp_token_addr) =
+        create_basic_pool(&mut app, &factory_addr, pool_code_id, &user1);
+    let now = app.block_info().time;
+
+    // --- Deadlines ---
+    let err = app
+        .execute_contract(
+            user2.clone(),
+            pool_addr.clone(),
+            &PoolMsg::ExecuteMsg::Swap {
+                offer_denom: TOKEN_A.into(),
+                min_receive: Uint128::one(),
+                belief_price: None,
+                max_spread: None,
+                deadline: Some(now.minus_seconds(1)),
+                to: None,
+            },
+            &[cosmwasm_std::coin(1_000u128, TOKEN_A)],
+        )
+        .unwrap_err();
+    assert!(err.root_cause().to_string().contains("has passed"));
+    let err = app
+        .execute_contract(
+            user2.clone(),
+            pool_addr.clone(),
+            &PoolMsg::ExecuteMsg::AddLiquidity {
+                min_lp_out: None,
+                deadline: Some(now.minus_seconds(1)),
+                to: None,
+            },
+            &[
+                cosmwasm_std::coin(1_000u128, TOKEN_A),
+                cosmwasm_std::coin(2_000u128, TOKEN_B),
+            ],
+        )
+        .unwrap_err();
+    assert!(err.root_cause().to_string().contains("has passed"));
+    let err = app
+        .execute_contract(
+            user1.clone(),
+            lp_token_addr.clone(),
+            &cw20::Cw20ExecuteMsg::Send {
+                contract: pool_addr.to_string(),
+                amount: Uint128::new(1_000),
+                msg: to_json_binary(&Cw20HookMsg::WithdrawLiquidity {
+                    deadline: Some(now.minus_seconds(1)),
+                    to: None,
+                })
+                .unwrap(),
+            },
+            &[],
+        )
+        .unwrap_err();
+    assert!(err.root_cause().to_string().contains("has passed"));
+    // A deadline at the current block time still executes
+    app.execute_contract(
+        user2.clone(),
+        pool_addr.clone(),
+        &PoolMsg::ExecuteMsg::Swap {
+            offer_denom: TOKEN_A.into(),
+            min_receive: Uint128::one(),
+            belief_price: None,
+            max_spread: None,
+            deadline: Some(now),
+            to: None,
+        },
+        &[cosmwasm_std::coin(1_000u128, TOKEN_A)],
+    )
+    .unwrap();
+
+    // --- Max spread ---
+    // 10_000 A into ~101_000 A / ~198_000 B moves the price by roughly 9%
+    let swap = |belief_price: Option<Decimal>, max_spread: Decimal| PoolMsg::ExecuteMsg::Swap {
+        offer_denom: TOKEN_A.into(),
+        min_receive: Uint128::one(),
+        belief_price,
+        max_spread: Some(max_spread),
+        deadline: None,
+        to: None,
+    };
+    let err = app
+        .execute_contract(
+            user2.clone(),
+            pool_addr.clone(),
+            &swap(None, Decimal::percent(5)),
+            &[cosmwasm_std::coin(10_000u128, TOKEN_A)],
+        )
+        .unwrap_err();
+    assert!(err
+        .root_cause()
+        .to_string()
+        .contains("exceeds max spread limit"));
+    // Believing in 0.4 A per B expects 25_000 B back, far above what the pool pays
+    let err = app
+        .execute_contract(
+            user2.clone(),
+            pool_addr.clone(),
+            &swap(Some(Decimal::percent(40)), Decimal::percent(15)),
+            &[cosmwasm_std::coin(10_000u128, TOKEN_A)],
+        )
+        .unwrap_err();
+    assert!(err
+        .root_cause()
+        .to_string()
+        .contains("exceeds max spread limit"));
+    // At 0.5 A per B the ~17_800 B returned is within 15% of the 20_000 expected
+    let res = app
+        .execute_contract(
+            user2.clone(),
+            pool_addr.clone(),
+            &swap(Some(Decimal::percent(50)), Decimal::percent(15)),
+            &[cosmwasm_std::coin(10_000u128, TOKEN_A)],
+        )
+        .unwrap();
+    assert!(res.events.iter().any(|e| e
+        .attributes
+        .iter()
+        .any(|a| a.key == "spread_amount" && a.value != "0")));
+
+    // --- Minimum LP out ---
+    // Deposit at the current reserve ratio, which the swaps above moved
+    let pool_state: PoolStateResponse = app
+        .wrap()
+        .query_wasm_smart(pool_addr.clone(), &PoolMsg::QueryMsg::PoolState {})
+        .unwrap();
+    let deposit_a = Uint128::new(10_000);
+    let deposit_b = deposit_a.multiply_ratio(pool_state.reserve_b, pool_state.reserve_a);
+    let assets = vec![
+        cosmwasm_std::coin(deposit_a.u128(), TOKEN_A),
+        cosmwasm_std::coin(deposit_b.u128(), TOKEN_B),
+    ];
+    let sim: PoolMsg::SimulateProvideLiquidityResponse = app
+        .wrap()
+        .query_wasm_smart(
+            pool_addr.clone(),
+            &PoolMsg::QueryMsg::SimulateProvideLiquidity {
+                assets: assets.clone(),
+            },
+        )
+        .unwrap();
+    let err = app
+        .execute_contract(
+            user1.clone(),
+            pool_addr.clone(),
+            &PoolMsg::ExecuteMsg::AddLiquidity {
+                min_lp_out: Some(sim.shares + Uint128::one()),
+                deadline: None,
+                to: None,
+            },
+            &assets,
+        )
+        .unwrap_err();
+    assert!(err
+        .root_cause()
+        .to_string()
+        .contains("less than minimum requested"));
+    app.execute_contract(
+        user1.clone(),
+        pool_addr.clone(),
+        &PoolMsg::ExecuteMsg::AddLiquidity {
+            min_lp_out: Some(sim.shares),
+            deadline: Some(now),
+            to: None,
+        },
+        &assets,
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_receiver() {
+    let (mut app, factory_addr, _factory_code_id, pool_code_id, _owner, user1, user2) = setup_app();
+    let (pool_addr, lp_token_addr) =
+        create_basic_pool(&mut app, &factory_addr, pool_code_id, &user1);
+    let vault = app.api().addr_make("vault");
+    let lp_balance = |app: &App, address: &Addr| -> Uint128 {
+        let resp: BalanceResponse = app
+            .wrap()
+            .query_wasm_smart(
+                lp_token_addr.clone(),
+                &cw20::Cw20QueryMsg::Balance {
+                    address: address.to_string(),
+                },
+            )
+            .unwrap();
+        resp.balance
+    };
+
+    // An invalid receiver is rejected
+    let err = app
+        .execute_contract(
+            user2.clone(),
+            pool_addr.clone(),
+            &PoolMsg::ExecuteMsg::Swap {
+                offer_denom: TOKEN_A.into(),
+                min_receive: Uint128::one(),
+                belief_price: None,
+                max_spread: None,
+                deadline: None,
+                to: Some("Not An Address".into()),
+            },
+            &[cosmwasm_std::coin(1_000u128, TOKEN_A)],
+        )
+        .unwrap_err();
+    assert!(err
+        .root_cause()
+        .to_string()
+        .contains("Error decoding bech32"));
+
+    // --- Swap output to the vault ---
+    let user2_b_before = app.wrap().query_balance(&user2, TOKEN_B).unwrap().amount;
+    let res = app
+        .execute_contract(
+            user2.clone(),
+            pool_addr.clone(),
+            &PoolMsg::ExecuteMsg::Swap {
+                offer_denom: TOKEN_A.into(),
+                min_receive: Uint128::one(),
+                belief_price: None,
+                max_spread: None,
+                deadline: None,
+                to: Some(vault.to_string()),
+            },
+            &[cosmwasm_std::coin(1_000u128, TOKEN_A)],
+        )
+        .unwrap();
+    assert!(res.events.iter().any(|e| e
+        .attributes
+        .iter()
+        .any(|a| a.key == "receiver" && a.value == vault.as_str())));
+    let vault_b = app.wrap().query_balance(&vault, TOKEN_B).unwrap().amount;
+    assert!(!vault_b.is_zero());
+    assert_eq!(
+        app.wrap().query_balance(&user2, TOKEN_B).unwrap().amount,
+        user2_b_before
+    );
+
+    // --- LP tokens minted to the vault ---
+    let pool_state: PoolStateResponse = app
+        .wrap()
+        .query_wasm_smart(pool_addr.clone(), &PoolMsg::QueryMsg::PoolState {})
+        .unwrap();
+    let deposit_a = Uint128::new(10_000);
+    let deposit_b = deposit_a.multiply_ratio(pool_state.reserve_b, pool_state.reserve_a);
+    app.execute_contract(
+        user2.clone(),
+        pool_addr.clone(),
+        &PoolMsg::ExecuteMsg::AddLiquidity {
+            min_lp_out: None,
+            deadline: None,
+            to: Some(vault.to_string()),
+        },
+        &[
+            cosmwasm_std::coin(deposit_a.u128(), TOKEN_A),
+            cosmwasm_std::coin(deposit_b.u128(), TOKEN_B),
+        ],
+    )
+    .unwrap();
+    assert!(lp_balance(&app, &user2).is_zero());
+    assert!(!lp_balance(&app, &vault).is_zero());
+
+    // --- Withdrawal paid to the vault ---
+    let lp_amount = Uint128::new(1_000);
+    let vault_a_before = app.wrap().query_balance(&vault, TOKEN_A).unwrap().amount;
+    let user1_a_before = app.wrap().query_balance(&user1, TOKEN_A).unwrap().amount;
+    app.execute_contract(
+        user1.clone(),
+        lp_token_addr.clone(),
+        &cw20::Cw20ExecuteMsg::Send {
+            contract: pool_addr.to_string(),
+            amount: lp_amount,
+            msg: to_json_binary(&Cw20HookMsg::WithdrawLiquidity {
+                deadline: None,
+                to: Some(vault.to_string()),
+            })
+            .unwrap(),
+        },
+        &[],
+    )
+    .unwrap();
+    assert!(app.wrap().query_balance(&vault, TOKEN_A).unwrap().amount > vault_a_before);
+    assert_eq!(
+        app.wrap().query_balance(&user1, TOKEN_A).unwrap().amount,
+        user1_a_before
+    );
+}
+
 #[test]
 fn test_withdraw_errors() {
     let (mut app, factory_addr, _factory_code_id, pool_code_id, owner, user1, _user2) = setup_app();
@@ -639,7 +2296,10 @@ fn test_withdraw_errors() {
         )
         .unwrap();
 
-    let withdraw_hook = Cw20HookMsg::WithdrawLiquidity {};
+    let withdraw_hook = Cw20HookMsg::WithdrawLiquidity {
+        deadline: None,
+        to: None,
+    };
     let send_msg_wrong_lp = cw20::Cw20ExecuteMsg::Send {
         contract: pool_addr.to_string(),
         amount: Uint128::new(100),
@@ -654,7 +2314,10 @@ fn test_withdraw_errors() {
         .contains("Cannot Sub with given operands"));
 
     // --- Test Withdraw Zero Amount (Pool hook should reject) ---
-    let withdraw_hook_zero = Cw20HookMsg::WithdrawLiquidity {};
+    let withdraw_hook_zero = Cw20HookMsg::WithdrawLiquidity {
+        deadline: None,
+        to: None,
+    };
     let send_msg_zero = cw20::Cw20ExecuteMsg::Send {
         contract: pool_addr.to_string(),
         amount: Uint128::zero(),
@@ -668,3 +2331,215 @@ fn test_withdraw_errors() {
         .to_string()
         .contains("Withdraw amount cannot be zero"));
 }
+
+#[test]
+fn test_router_multi_hop() {
+    let (mut app, factory_addr, _factory_code_id, pool_code_id, _owner, user1, user2) = setup_app();
+    let (pool_ab, _lp_ab) = create_basic_pool(&mut app, &factory_addr, pool_code_id, &user1);
+
+    // Second pool B/C, seeded with 200_000 B and 100_000 C
+    app.sudo(cw_multi_test::SudoMsg::Bank(BankSudo::Mint {
+        to_address: user1.to_string(),
+        amount: vec![cosmwasm_std::coin(1_000_000u128, TOKEN_C)],
+    }))
+    .unwrap();
+    let res_create = app
+        .execute_contract(
+            user1.clone(),
+            factory_addr.clone(),
+            &FactoryMsg::ExecuteMsg::CreatePool {
+                pool_logic_code_id: pool_code_id,
+                denom_a: TOKEN_B.to_string(),
+                denom_b: TOKEN_C.to_string(),
+                fee_bps: DEFAULT_FEE_BPS,
+            },
+            &[],
+        )
+        .unwrap();
+    let pool_bc = res_create
+        .events
+        .iter()
+        .find_map(|e| {
+            e.attributes
+                .iter()
+                .find(|a| a.key == "pool_contract_address")
+        })
+        .map(|a| Addr::unchecked(a.value.clone()))
+        .unwrap();
+    app.execute_contract(
+        user1.clone(),
+        pool_bc.clone(),
+        &PoolMsg::ExecuteMsg::AddLiquidity {
+            min_lp_out: None,
+            deadline: None,
+            to: None,
+        },
+        &[
+            cosmwasm_std::coin(200_000u128, TOKEN_B),
+            cosmwasm_std::coin(100_000u128, TOKEN_C),
+        ],
+    )
+    .unwrap();
+
+    let router_code_id = app.store_code(router_contract());
+    let router_addr = app
+        .instantiate_contract(
+            router_code_id,
+            user1.clone(),
+            &RouterMsg::InstantiateMsg {
+                factory_addr: factory_addr.to_string(),
+            },
+            &[],
+            "DexRouterContract",
+            None,
+        )
+        .unwrap();
+
+    // A -> B through the factory lookup, B -> C by address
+    let operations = vec![
+        RouterMsg::SwapOperation::Factory {
+            offer_denom: TOKEN_A.into(),
+            ask_denom: TOKEN_B.into(),
+            pool_logic_code_id: pool_code_id,
+            fee_bps: DEFAULT_FEE_BPS,
+        },
+        RouterMsg::SwapOperation::Pool {
+            pool_addr: pool_bc.to_string(),
+            offer_denom: TOKEN_B.into(),
+            ask_denom: TOKEN_C.into(),
+        },
+    ];
+    let offer_amount = Uint128::new(10_000);
+
+    // The route quote chains the single-pool quotes
+    let first_hop: PoolMsg::SimulationResponse = app
+        .wrap()
+        .query_wasm_smart(
+            pool_ab.clone(),
+            &PoolMsg::QueryMsg::SimulateSwap {
+                offer_denom: TOKEN_A.into(),
+                offer_amount,
+            },
+        )
+        .unwrap();
+    let second_hop: PoolMsg::SimulationResponse = app
+        .wrap()
+        .query_wasm_smart(
+            pool_bc.clone(),
+            &PoolMsg::QueryMsg::SimulateSwap {
+                offer_denom: TOKEN_B.into(),
+                offer_amount: first_hop.return_amount,
+            },
+        )
+        .unwrap();
+    let simulation: RouterMsg::SimulateSwapOperationsResponse = app
+        .wrap()
+        .query_wasm_smart(
+            router_addr.clone(),
+            &RouterMsg::QueryMsg::SimulateSwapOperations {
+                offer_amount,
+                operations: operations.clone(),
+            },
+        )
+        .unwrap();
+    assert_eq!(simulation.amount, second_hop.return_amount);
+    assert!(!simulation.amount.is_zero());
+
+    let user_a_before = app.wrap().query_balance(&user2, TOKEN_A).unwrap().amount;
+    let user_b_before = app.wrap().query_balance(&user2, TOKEN_B).unwrap().amount;
+    app.execute_contract(
+        user2.clone(),
+        router_addr.clone(),
+        &RouterMsg::ExecuteMsg::ExecuteSwapOperations {
+            operations: operations.clone(),
+            minimum_receive: simulation.amount,
+        },
+        &[cosmwasm_std::coin(offer_amount.u128(), TOKEN_A)],
+    )
+    .unwrap();
+
+    let user_a_after = app.wrap().query_balance(&user2, TOKEN_A).unwrap().amount;
+    let user_b_after = app.wrap().query_balance(&user2, TOKEN_B).unwrap().amount;
+    let user_c_after = app.wrap().query_balance(&user2, TOKEN_C).unwrap().amount;
+    assert_eq!(user_a_before - user_a_after, offer_amount);
+    assert_eq!(user_b_after, user_b_before);
+    assert_eq!(user_c_after, simulation.amount);
+    // Nothing is left behind in the router
+    for denom in [TOKEN_A, TOKEN_B, TOKEN_C] {
+        let balance = app.wrap().query_balance(&router_addr, denom).unwrap();
+        assert!(balance.amount.is_zero());
+    }
+    let pool_state: PoolStateResponse = app
+        .wrap()
+        .query_wasm_smart(pool_bc.clone(), &PoolMsg::QueryMsg::PoolState {})
+        .unwrap();
+    assert_eq!(
+        pool_state.reserve_a,
+        Uint128::new(200_000) + first_hop.return_amount
+    );
+
+    // The final output is checked against minimum_receive and the whole route reverts
+    let simulation: RouterMsg::SimulateSwapOperationsResponse = app
+        .wrap()
+        .query_wasm_smart(
+            router_addr.clone(),
+            &RouterMsg::QueryMsg::SimulateSwapOperations {
+                offer_amount,
+                operations: operations.clone(),
+            },
+        )
+        .unwrap();
+    let err = app
+        .execute_contract(
+            user2.clone(),
+            router_addr.clone(),
+            &RouterMsg::ExecuteMsg::ExecuteSwapOperations {
+                operations: operations.clone(),
+                minimum_receive: simulation.amount + Uint128::one(),
+            },
+            &[cosmwasm_std::coin(offer_amount.u128(), TOKEN_A)],
+        )
+        .unwrap_err();
+    assert!(err
+        .root_cause()
+        .to_string()
+        .contains(" less than minimum requested "));
+    assert_eq!(
+        app.wrap().query_balance(&user2, TOKEN_A).unwrap().amount,
+        user_a_after
+    );
+
+    // Hops must connect
+    let err = app
+        .execute_contract(
+            user2.clone(),
+            router_addr.clone(),
+            &RouterMsg::ExecuteMsg::ExecuteSwapOperations {
+                operations: vec![operations[1].clone(), operations[0].clone()],
+                minimum_receive: Uint128::zero(),
+            },
+            &[cosmwasm_std::coin(offer_amount.u128(), TOKEN_B)],
+        )
+        .unwrap_err();
+    assert!(err
+        .root_cause()
+        .to_string()
+        .contains("but the previous one returns"));
+
+    // The coin sent must be the first offer denom
+    let err = app
+        .execute_contract(
+            user2.clone(),
+            router_addr.clone(),
+            &RouterMsg::ExecuteMsg::ExecuteSwapOperations {
+                operations,
+                minimum_receive: Uint128::zero(),
+            },
+            &[cosmwasm_std::coin(offer_amount.u128(), TOKEN_B)],
+        )
+        .unwrap_err();
+    assert!(err
+        .root_cause()
+        .to_string()
+        .contains("but the first operation offers"));
+}
@@ -1,23 +1,85 @@
-use borsh::{BorshDeserialize, BorshSerialize};
+use borsh::BorshDeserialize;
+use dex_plugin_interface::instruction::DISCRIMINATOR_LEN;
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
 
-#[derive(BorshSerialize, BorshDeserialize, Debug)]
-pub enum PluginInstruction {
-    ComputeAddLiquidity {
-        reserve_a: u64,
-        reserve_b: u64,
-        deposit_a: u64,
-        deposit_b: u64,
-        total_lp_supply: u64,
-    },
-    ComputeRemoveLiquidity {
-        reserve_a: u64,
-        reserve_b: u64,
-        total_lp_supply: u64,
-        lp_amount_burning: u64,
-    },
-    ComputeSwap {
-        reserve_in: u64,
-        reserve_out: u64,
-        amount_in: u64,
+/// Discriminator of `InitializePluginState`:
+/// first 8 bytes of `sha256("constant-product-plugin:initialize_plugin_state")`.
+pub const INITIALIZE_PLUGIN_STATE: [u8; DISCRIMINATOR_LEN] =
+    [174, 153, 203, 70, 220, 222, 249, 191];
+
+/// Instructions specific to the constant product plugin, on top of the plugin interface.
+///
+/// Encoding: `discriminator (8 bytes) || little-endian arguments`.
+#[derive(Debug, PartialEq)]
+pub enum ConstantProductInstruction {
+    /// Writes the swap fee configuration into a freshly created plugin state account.
+    /// The fee is charged on the swap input as `fee_numerator / fee_denominator`.
+    ///
+    /// Accounts:
+    /// 0. [writable, signer] plugin state: Uninitialized account owned by this program,
+    ///    with at least `PluginState::LEN` bytes of data
+    InitializePluginState {
+        fee_numerator: u64,
+        fee_denominator: u64,
     },
 }
+
+impl ConstantProductInstruction {
+    /// Serializes the instruction into its wire format.
+    pub fn pack(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(DISCRIMINATOR_LEN + 2 * 8);
+        match self {
+            Self::InitializePluginState {
+                fee_numerator,
+                fee_denominator,
+            } => {
+                buf.extend_from_slice(&INITIALIZE_PLUGIN_STATE);
+                buf.extend_from_slice(&fee_numerator.to_le_bytes());
+                buf.extend_from_slice(&fee_denominator.to_le_bytes());
+            }
+        }
+        buf
+    }
+
+    /// Deserializes instruction data, returning `None` for a foreign discriminator.
+    pub fn unpack(input: &[u8]) -> Result<Option<Self>, ProgramError> {
+        if input.len() < DISCRIMINATOR_LEN {
+            return Ok(None);
+        }
+        let (disc, mut rest) = input.split_at(DISCRIMINATOR_LEN);
+        if disc != INITIALIZE_PLUGIN_STATE {
+            return Ok(None);
+        }
+        let (fee_numerator, fee_denominator) = <(u64, u64)>::deserialize(&mut rest)
+            .map_err(|_| ProgramError::InvalidInstructionData)?;
+        if !rest.is_empty() {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        Ok(Some(Self::InitializePluginState {
+            fee_numerator,
+            fee_denominator,
+        }))
+    }
+}
+
+/// Builds an `InitializePluginState` instruction.
+pub fn initialize_plugin_state(
+    program_id: &Pubkey,
+    plugin_state: &Pubkey,
+    fee_numerator: u64,
+    fee_denominator: u64,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        accounts: vec![AccountMeta::new(*plugin_state, true)],
+        data: ConstantProductInstruction::InitializePluginState {
+            fee_numerator,
+            fee_denominator,
+        }
+        .pack(),
+    }
+}
@@ -1,9 +1,11 @@
 pub mod instruction;
 pub mod processor;
+pub mod state;
 
 #[cfg(not(feature = "no-entrypoint"))]
 pub mod entrypoint;
 
+pub use dex_plugin_interface;
 pub use solana_program;
 
 #[cfg(test)]
@@ -1,37 +1,49 @@
 use borsh::{BorshDeserialize, BorshSerialize};
+use dex_plugin_interface::{
+    error::PluginInterfaceError, instruction::PluginInstruction, result::PluginCalcResult,
+    INTERFACE_VERSION,
+};
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
     entrypoint::ProgramResult,
     msg,
+    program::set_return_data,
     program_error::ProgramError,
     pubkey::Pubkey,
 };
-use spl_math::{checked_ceil_div::CheckedCeilDiv, uint::U192};
+use spl_math::{
+    approximations::sqrt,
+    checked_ceil_div::CheckedCeilDiv,
+    uint::{U192, U256},
+};
 use std::convert::TryInto;
 
-use crate::instruction::PluginInstruction;
-
-/// We'll store the plugin's computed results in the plugin state account.
-/// The pool program reads them after the CPI call.
-#[derive(BorshDeserialize, BorshSerialize, Debug, Default)]
-pub struct PluginCalcResult {
-    pub actual_a: u64,
-    pub actual_b: u64,
-    pub shares_to_mint: u64,
-    pub withdraw_a: u64,
-    pub withdraw_b: u64,
-    pub amount_out: u64,
-}
+use crate::instruction::ConstantProductInstruction;
+use crate::state::PluginState;
 
 pub struct Processor;
 impl Processor {
     pub fn process(
-        _program_id: &Pubkey,
+        program_id: &Pubkey,
         accounts: &[AccountInfo],
         instr_data: &[u8],
     ) -> ProgramResult {
-        let instruction = PluginInstruction::try_from_slice(instr_data)
-            .map_err(|_| ProgramError::InvalidInstructionData)?;
+        // Plugin-specific instructions first, then the shared plugin interface
+        if let Some(instruction) = ConstantProductInstruction::unpack(instr_data)? {
+            return match instruction {
+                ConstantProductInstruction::InitializePluginState {
+                    fee_numerator,
+                    fee_denominator,
+                } => Self::process_initialize_plugin_state(
+                    program_id,
+                    accounts,
+                    fee_numerator,
+                    fee_denominator,
+                ),
+            };
+        }
+
+        let instruction = PluginInstruction::unpack(instr_data)?;
         msg!("Plugin: Deserialized instruction successfully.");
 
         match instruction {
@@ -42,7 +54,6 @@ impl Processor {
                 deposit_b,
                 total_lp_supply,
             } => Self::compute_add_liquidity(
-                accounts,
                 reserve_a,
                 reserve_b,
                 deposit_a,
@@ -55,7 +66,6 @@ impl Processor {
                 total_lp_supply,
                 lp_amount_burning,
             } => Self::compute_remove_liquidity(
-                accounts,
                 reserve_a,
                 reserve_b,
                 total_lp_supply,
@@ -65,24 +75,146 @@ impl Processor {
                 reserve_in,
                 reserve_out,
                 amount_in,
-            } => Self::compute_swap(accounts, reserve_in, reserve_out, amount_in),
+            } => Self::compute_swap(program_id, accounts, reserve_in, reserve_out, amount_in),
+            PluginInstruction::CheckInterfaceVersion { version } => {
+                Self::check_interface_version(program_id, accounts, version)
+            }
+            PluginInstruction::ComputeSwapExactOut {
+                reserve_in,
+                reserve_out,
+                amount_out,
+            } => Self::compute_swap_exact_out(
+                program_id,
+                accounts,
+                reserve_in,
+                reserve_out,
+                amount_out,
+            ),
+            PluginInstruction::CheckSwapInvariant {
+                reserve_a,
+                reserve_b,
+                balance_a,
+                balance_b,
+                amount_a_in,
+                amount_b_in,
+            } => Self::check_swap_invariant(
+                program_id,
+                accounts,
+                (reserve_a, reserve_b),
+                (balance_a, balance_b),
+                (amount_a_in, amount_b_in),
+            ),
         }
     }
 
-    pub fn compute_add_liquidity(
+    pub fn process_initialize_plugin_state(
+        program_id: &Pubkey,
         accounts: &[AccountInfo],
+        fee_numerator: u64,
+        fee_denominator: u64,
+    ) -> ProgramResult {
+        let state_acc = next_account_info(&mut accounts.iter())?;
+        if state_acc.owner != program_id {
+            msg!("Plugin: State account not owned by the plugin");
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if !state_acc.is_signer {
+            msg!("Plugin: State account must sign its initialization");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if !state_acc.is_writable {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if state_acc.data_len() < PluginState::LEN {
+            msg!("Plugin: State account too small");
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+        let current = PluginState::deserialize(&mut &state_acc.data.borrow()[..])
+            .map_err(|_| ProgramError::InvalidAccountData)?;
+        if current.is_initialized {
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        if fee_denominator == 0 || fee_numerator >= fee_denominator {
+            msg!("Plugin: Invalid fee {}/{}", fee_numerator, fee_denominator);
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let state = PluginState {
+            is_initialized: true,
+            fee_numerator,
+            fee_denominator,
+        };
+        state.serialize(&mut &mut state_acc.data.borrow_mut()[..])?;
+        msg!(
+            "Plugin: State initialized with fee {}/{}",
+            fee_numerator,
+            fee_denominator
+        );
+        Ok(())
+    }
+
+    /// Reads the initialized plugin state passed as the first account.
+    fn load_plugin_state(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> Result<PluginState, ProgramError> {
+        let state_acc = next_account_info(&mut accounts.iter())?;
+        if state_acc.owner != program_id {
+            msg!("Plugin: State account not owned by the plugin");
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let state = PluginState::deserialize(&mut &state_acc.data.borrow()[..])
+            .map_err(|_| ProgramError::InvalidAccountData)?;
+        if !state.is_initialized {
+            msg!("Plugin: State account not initialized");
+            return Err(ProgramError::UninitializedAccount);
+        }
+        Ok(state)
+    }
+
+    /// Accepts the handshake only for the interface version this plugin was built against
+    /// and for a plugin state this plugin has initialized.
+    pub fn check_interface_version(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        version: u16,
+    ) -> ProgramResult {
+        if version != INTERFACE_VERSION {
+            msg!(
+                "Plugin: Unsupported interface version {}, expected {}",
+                version,
+                INTERFACE_VERSION
+            );
+            return Err(PluginInterfaceError::UnsupportedInterfaceVersion.into());
+        }
+        Self::load_plugin_state(program_id, accounts)?;
+        Ok(())
+    }
+
+    pub fn compute_add_liquidity(
         reserve_a: u64,
         reserve_b: u64,
         deposit_a: u64,
         deposit_b: u64,
         total_lp_supply: u64,
     ) -> ProgramResult {
-        // We store results in the first (and only) writable account => plugin state
-        let state_acc = next_account_info(&mut accounts.iter())?;
-        if !state_acc.is_writable {
-            return Err(ProgramError::InvalidAccountData);
-        }
+        let result = Self::calculate_add_liquidity(
+            reserve_a,
+            reserve_b,
+            deposit_a,
+            deposit_b,
+            total_lp_supply,
+        )?;
+        Self::return_result(&result)
+    }
 
+    pub fn calculate_add_liquidity(
+        reserve_a: u64,
+        reserve_b: u64,
+        deposit_a: u64,
+        deposit_b: u64,
+        total_lp_supply: u64,
+    ) -> Result<PluginCalcResult, ProgramError> {
         let mut result = PluginCalcResult::default();
         msg!(
             "Plugin: Computing Add Liquidity. Reserves: ({}, {}), Deposit: ({}, {}), Total LP: {}",
@@ -96,7 +228,7 @@ impl Processor {
         if total_lp_supply == 0 {
             // first deposit => geometric mean
             let prod = (deposit_a as u128).saturating_mul(deposit_b as u128);
-            let minted = integer_sqrt(prod);
+            let minted = sqrt(prod).ok_or(ProgramError::ArithmeticOverflow)?;
             if minted == 0 {
                 return Err(ProgramError::InvalidArgument);
             }
@@ -137,20 +269,30 @@ impl Processor {
             result.shares_to_mint
         );
 
-        result.serialize(&mut *state_acc.data.borrow_mut())?;
-        msg!("Plugin: Serialization successful.");
-
-        Ok(())
+        Ok(result)
     }
 
     pub fn compute_remove_liquidity(
-        accounts: &[AccountInfo],
         reserve_a: u64,
         reserve_b: u64,
         total_lp_supply: u64,
         lp_amount_burning: u64,
     ) -> ProgramResult {
-        let state_acc = next_account_info(&mut accounts.iter())?;
+        let result = Self::calculate_remove_liquidity(
+            reserve_a,
+            reserve_b,
+            total_lp_supply,
+            lp_amount_burning,
+        )?;
+        Self::return_result(&result)
+    }
+
+    pub fn calculate_remove_liquidity(
+        reserve_a: u64,
+        reserve_b: u64,
+        total_lp_supply: u64,
+        lp_amount_burning: u64,
+    ) -> Result<PluginCalcResult, ProgramError> {
         if lp_amount_burning == 0 || lp_amount_burning > total_lp_supply {
             return Err(ProgramError::InvalidArgument);
         }
@@ -177,17 +319,34 @@ impl Processor {
             result.withdraw_b
         );
 
-        result.serialize(&mut *state_acc.data.borrow_mut())?;
-        Ok(())
+        Ok(result)
     }
 
     pub fn compute_swap(
+        program_id: &Pubkey,
         accounts: &[AccountInfo],
         reserve_in: u64,
         reserve_out: u64,
         amount_in: u64,
     ) -> ProgramResult {
-        let state_acc = next_account_info(&mut accounts.iter())?;
+        let state = Self::load_plugin_state(program_id, accounts)?;
+        let result = Self::calculate_swap(
+            reserve_in,
+            reserve_out,
+            amount_in,
+            state.fee_numerator,
+            state.fee_denominator,
+        )?;
+        Self::return_result(&result)
+    }
+
+    pub fn calculate_swap(
+        reserve_in: u64,
+        reserve_out: u64,
+        amount_in: u64,
+        fee_numerator: u64,
+        fee_denominator: u64,
+    ) -> Result<PluginCalcResult, ProgramError> {
         if reserve_in == 0 || reserve_out == 0 || amount_in == 0 {
             // Allow amount_in = 0? Or return specific error?
             // For now, follow spl-token-swap pattern which seems to allow it
@@ -200,12 +359,10 @@ impl Processor {
 
         let mut result = PluginCalcResult::default();
 
-        // Calculate effective input after 0.3% fee (floor division)
-        let fee_num = 3u64;
-        let fee_den = 1000u64;
+        // Calculate effective input after the configured fee (floor division)
         let effective_in = (amount_in as u128)
-            .checked_mul(fee_den.saturating_sub(fee_num) as u128)
-            .and_then(|num| num.checked_div(fee_den as u128))
+            .checked_mul(fee_denominator.saturating_sub(fee_numerator) as u128)
+            .and_then(|num| num.checked_div(fee_denominator as u128))
             .unwrap_or(0);
 
         if effective_in == 0 && amount_in > 0 {
@@ -264,17 +421,147 @@ impl Processor {
             result.amount_out
         );
 
-        result.serialize(&mut *state_acc.data.borrow_mut())?;
+        Ok(result)
+    }
+
+    pub fn compute_swap_exact_out(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        reserve_in: u64,
+        reserve_out: u64,
+        amount_out: u64,
+    ) -> ProgramResult {
+        let state = Self::load_plugin_state(program_id, accounts)?;
+        let result = Self::calculate_swap_exact_out(
+            reserve_in,
+            reserve_out,
+            amount_out,
+            state.fee_numerator,
+            state.fee_denominator,
+        )?;
+        Self::return_result(&result)
+    }
+
+    /// Smallest input for which `calculate_swap` returns at least `amount_out`.
+    /// Both steps round up, so the pool never receives less than the exact-in price.
+    pub fn calculate_swap_exact_out(
+        reserve_in: u64,
+        reserve_out: u64,
+        amount_out: u64,
+        fee_numerator: u64,
+        fee_denominator: u64,
+    ) -> Result<PluginCalcResult, ProgramError> {
+        // The output reserve can't be drained
+        if reserve_in == 0 || amount_out == 0 || amount_out >= reserve_out {
+            return Err(ProgramError::InvalidArgument);
+        }
+        let fee_complement = fee_denominator.saturating_sub(fee_numerator);
+        if fee_complement == 0 {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        // Effective input restoring the invariant:
+        // effective_in = ceil(reserve_in * reserve_out / (reserve_out - amount_out)) - reserve_in
+        let invariant = (reserve_in as u128) * (reserve_out as u128);
+        let new_reserve_out = (reserve_out - amount_out) as u128;
+        let new_reserve_in = ceil_div(invariant, new_reserve_out).ok_or_else(|| {
+            msg!("Error: New reserve_in overflowed u128");
+            ProgramError::InvalidInstructionData
+        })?;
+        let effective_in = new_reserve_in - reserve_in as u128;
+
+        // Gross up for the fee: amount_in = ceil(effective_in * fee_den / (fee_den - fee_num))
+        let amount_in = effective_in
+            .checked_mul(fee_denominator as u128)
+            .and_then(|num| ceil_div(num, fee_complement as u128))
+            .ok_or_else(|| {
+                msg!("Error: Gross amount_in overflowed u128");
+                ProgramError::InvalidInstructionData
+            })?;
+        let amount_in: u64 = amount_in.try_into().map_err(|_| {
+            msg!("Error: Failed to downcast amount_in (u128) to u64");
+            ProgramError::InvalidInstructionData
+        })?;
+
+        let result = PluginCalcResult {
+            amount_in,
+            amount_out,
+            ..Default::default()
+        };
+        msg!(
+            "Plugin Swap Exact Out Calculated: amount_in={}, amount_out={}",
+            result.amount_in,
+            result.amount_out
+        );
+
+        Ok(result)
+    }
+
+    pub fn check_swap_invariant(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        reserves: (u64, u64),
+        balances: (u64, u64),
+        amounts_in: (u64, u64),
+    ) -> ProgramResult {
+        let state = Self::load_plugin_state(program_id, accounts)?;
+        if !Self::swap_invariant_holds(
+            reserves,
+            balances,
+            amounts_in,
+            state.fee_numerator,
+            state.fee_denominator,
+        )? {
+            msg!(
+                "Plugin: Balances {:?} with {:?} paid in break the invariant of reserves {:?}",
+                balances,
+                amounts_in,
+                reserves
+            );
+            return Err(PluginInterfaceError::SwapInvariantViolated.into());
+        }
+        Ok(())
+    }
+
+    /// Whether `balances` keep the product of `reserves` once the fee is taken off the
+    /// `amounts_in`, as for a regular swap:
+    /// `(balance_a - fee_a) * (balance_b - fee_b) >= reserve_a * reserve_b`.
+    pub fn swap_invariant_holds(
+        reserves: (u64, u64),
+        balances: (u64, u64),
+        amounts_in: (u64, u64),
+        fee_numerator: u64,
+        fee_denominator: u64,
+    ) -> Result<bool, ProgramError> {
+        // Scaled by fee_denominator to keep the fee exact
+        let net_of_fee = |balance: u64, amount_in: u64| {
+            if amount_in > balance {
+                msg!("Error: Amount in {} exceeds balance {}", amount_in, balance);
+                return Err(ProgramError::InvalidArgument);
+            }
+            Ok(balance as u128 * fee_denominator as u128
+                - amount_in as u128 * fee_numerator as u128)
+        };
+        let adjusted = U256::from(net_of_fee(balances.0, amounts_in.0)?)
+            * U256::from(net_of_fee(balances.1, amounts_in.1)?);
+        let required = U256::from(reserves.0 as u128 * reserves.1 as u128)
+            * U256::from(fee_denominator as u128 * fee_denominator as u128);
+        Ok(adjusted >= required)
+    }
+
+    /// Hands the computed result back to the calling pool via return data.
+    fn return_result(result: &PluginCalcResult) -> ProgramResult {
+        set_return_data(&result.try_to_vec()?);
         Ok(())
     }
 }
 
-fn integer_sqrt(v: u128) -> u128 {
-    let mut x = v;
-    let mut z = (v >> 1) + 1;
-    while z < x {
-        x = z;
-        z = ((v / z) + z) >> 1;
+/// Division rounding up. `None` on division by zero or overflow.
+fn ceil_div(dividend: u128, divisor: u128) -> Option<u128> {
+    let quotient = dividend.checked_div(divisor)?;
+    if dividend.checked_rem(divisor)? > 0 {
+        quotient.checked_add(1)
+    } else {
+        Some(quotient)
     }
-    x
 }
@@ -1,16 +1,19 @@
 #[cfg(test)]
 mod tests {
-    use crate::processor::{PluginCalcResult, Processor};
+    use crate::instruction::ConstantProductInstruction;
+    use crate::processor::Processor;
+    use crate::state::PluginState;
     use borsh::BorshDeserialize;
+    use dex_plugin_interface::instruction::PluginInstruction;
     use solana_program::{
         account_info::AccountInfo, clock::Epoch, program_error::ProgramError, pubkey::Pubkey,
     };
-    use std::mem;
 
     /// Helper to create a basic AccountInfo for testing state accounts
     /// Lifetimes need to be specified for references within AccountInfo
     fn create_state_account_info<'a>(
         key: &'a Pubkey,
+        is_signer: bool,
         is_writable: bool,
         lamports: &'a mut u64,
         data: &'a mut [u8],
@@ -18,7 +21,7 @@ mod tests {
     ) -> AccountInfo<'a> {
         AccountInfo::new(
             key,
-            false, // is_signer
+            is_signer,
             is_writable,
             lamports,
             data,
@@ -29,22 +32,7 @@ mod tests {
     }
 
     #[test]
-    fn test_compute_add_liquidity_first_deposit() {
-        let owner_program_id = Pubkey::new_unique(); // Dummy owner for account
-        let state_key = Pubkey::new_unique();
-        let mut lamports: u64 = 0;
-        // Size needs to be sufficient for PluginCalcResult serialization
-        let mut data: Vec<u8> = vec![0; mem::size_of::<PluginCalcResult>()];
-        let state_acc_info = create_state_account_info(
-            &state_key,
-            true, // Writable
-            &mut lamports,
-            &mut data,
-            &owner_program_id,
-        );
-        // Pass as a slice, as the processor expects &[AccountInfo]
-        let accounts = [state_acc_info];
-
+    fn test_calculate_add_liquidity_first_deposit() {
         let reserve_a = 0u64;
         let reserve_b = 0u64;
         let deposit_a = 100u64;
@@ -54,8 +42,7 @@ mod tests {
         // Expected shares = sqrt(deposit_a * deposit_b) = sqrt(100 * 400) = sqrt(40000) = 200
         let expected_shares = 200u64;
 
-        let result = Processor::compute_add_liquidity(
-            &accounts,
+        let result = Processor::calculate_add_liquidity(
             reserve_a,
             reserve_b,
             deposit_a,
@@ -65,13 +52,11 @@ mod tests {
 
         assert!(
             result.is_ok(),
-            "compute_add_liquidity failed: {:?}",
+            "calculate_add_liquidity failed: {:?}",
             result.err()
         );
 
-        // Check the state account data for results
-        // Use `deserialize` which handles reading from the start of the buffer
-        let calc_result = PluginCalcResult::deserialize(&mut &data[..]).unwrap();
+        let calc_result = result.unwrap();
 
         assert_eq!(calc_result.actual_a, deposit_a, "actual_a mismatch");
         assert_eq!(calc_result.actual_b, deposit_b, "actual_b mismatch");
@@ -86,20 +71,7 @@ mod tests {
     }
 
     #[test]
-    fn test_compute_add_liquidity_existing_pool() {
-        let owner_program_id = Pubkey::new_unique();
-        let state_key = Pubkey::new_unique();
-        let mut lamports: u64 = 0;
-        let mut data: Vec<u8> = vec![0; mem::size_of::<PluginCalcResult>()];
-        let state_acc_info = create_state_account_info(
-            &state_key,
-            true,
-            &mut lamports,
-            &mut data,
-            &owner_program_id,
-        );
-        let accounts = [state_acc_info];
-
+    fn test_calculate_add_liquidity_existing_pool() {
         // Existing pool state
         let reserve_a = 1000u64;
         let reserve_b = 5000u64; // Price: 5 B per A
@@ -121,8 +93,7 @@ mod tests {
         //                = 10000 * 100 / 1000 = 1000
         let expected_shares = 1000u64;
 
-        let result = Processor::compute_add_liquidity(
-            &accounts,
+        let result = Processor::calculate_add_liquidity(
             reserve_a,
             reserve_b,
             deposit_a,
@@ -131,11 +102,11 @@ mod tests {
         );
         assert!(
             result.is_ok(),
-            "compute_add_liquidity (existing) failed: {:?}",
+            "calculate_add_liquidity (existing) failed: {:?}",
             result.err()
         );
 
-        let calc_result = PluginCalcResult::deserialize(&mut &data[..]).unwrap();
+        let calc_result = result.unwrap();
         assert_eq!(
             calc_result.actual_a, expected_actual_a,
             "existing actual_a mismatch"
@@ -151,20 +122,7 @@ mod tests {
     }
 
     #[test]
-    fn test_compute_remove_liquidity() {
-        let owner_program_id = Pubkey::new_unique();
-        let state_key = Pubkey::new_unique();
-        let mut lamports: u64 = 0;
-        let mut data: Vec<u8> = vec![0; mem::size_of::<PluginCalcResult>()];
-        let state_acc_info = create_state_account_info(
-            &state_key,
-            true,
-            &mut lamports,
-            &mut data,
-            &owner_program_id,
-        );
-        let accounts = [state_acc_info];
-
+    fn test_calculate_remove_liquidity() {
         let reserve_a = 1000u64;
         let reserve_b = 5000u64;
         let total_lp_supply = 10000u64;
@@ -180,8 +138,7 @@ mod tests {
         assert_eq!(expected_withdraw_a, 200);
         assert_eq!(expected_withdraw_b, 1000);
 
-        let result = Processor::compute_remove_liquidity(
-            &accounts,
+        let result = Processor::calculate_remove_liquidity(
             reserve_a,
             reserve_b,
             total_lp_supply,
@@ -189,11 +146,11 @@ mod tests {
         );
         assert!(
             result.is_ok(),
-            "compute_remove_liquidity failed: {:?}",
+            "calculate_remove_liquidity failed: {:?}",
             result.err()
         );
 
-        let calc_result = PluginCalcResult::deserialize(&mut &data[..]).unwrap();
+        let calc_result = result.unwrap();
         assert_eq!(
             calc_result.withdraw_a, expected_withdraw_a,
             "remove withdraw_a mismatch"
@@ -210,20 +167,7 @@ mod tests {
     }
 
     #[test]
-    fn test_compute_swap() {
-        let owner_program_id = Pubkey::new_unique();
-        let state_key = Pubkey::new_unique();
-        let mut lamports: u64 = 0;
-        let mut data: Vec<u8> = vec![0; mem::size_of::<PluginCalcResult>()];
-        let state_acc_info = create_state_account_info(
-            &state_key,
-            true,
-            &mut lamports,
-            &mut data,
-            &owner_program_id,
-        );
-        let accounts = [state_acc_info];
-
+    fn test_calculate_swap() {
         let reserve_in = 10000u64; // Token A
         let reserve_out = 20000u64; // Token B
         let amount_in = 1000u64; // Swap 1000 A
@@ -236,10 +180,10 @@ mod tests {
         //            = 20000 * 997 / 10997 = 19940000 / 10997 = 1813 (integer division)
         let expected_amount_out = 1813u64;
 
-        let result = Processor::compute_swap(&accounts, reserve_in, reserve_out, amount_in);
-        assert!(result.is_ok(), "compute_swap failed: {:?}", result.err());
+        let result = Processor::calculate_swap(reserve_in, reserve_out, amount_in, 3, 1000);
+        assert!(result.is_ok(), "calculate_swap failed: {:?}", result.err());
 
-        let calc_result = PluginCalcResult::deserialize(&mut &data[..]).unwrap();
+        let calc_result = result.unwrap();
         assert_eq!(
             calc_result.amount_out, expected_amount_out,
             "swap amount_out mismatch"
@@ -253,46 +197,20 @@ mod tests {
     }
 
     #[test]
-    fn test_compute_add_liquidity_zero_deposit() {
-        let owner_program_id = Pubkey::new_unique();
-        let state_key = Pubkey::new_unique();
-        let mut lamports: u64 = 0;
-        let mut data: Vec<u8> = vec![0; mem::size_of::<PluginCalcResult>()];
-        let state_acc_info = create_state_account_info(
-            &state_key,
-            true,
-            &mut lamports,
-            &mut data,
-            &owner_program_id,
-        );
-        let accounts = [state_acc_info];
-
+    fn test_calculate_add_liquidity_zero_deposit() {
         // Scenario 1: First deposit, zero amounts
-        let result1 = Processor::compute_add_liquidity(&accounts, 0, 0, 0, 0, 0);
+        let result1 = Processor::calculate_add_liquidity(0, 0, 0, 0, 0);
         // Expect error because sqrt(0*0) = 0 shares
         assert_eq!(result1.err(), Some(ProgramError::InvalidArgument));
 
         // Scenario 2: Existing pool, zero amounts
-        let result2 = Processor::compute_add_liquidity(&accounts, 1000, 1000, 0, 0, 1000);
+        let result2 = Processor::calculate_add_liquidity(1000, 1000, 0, 0, 1000);
         // Expect error because shares calculated will be 0
         assert_eq!(result2.err(), Some(ProgramError::InvalidArgument));
     }
 
     #[test]
-    fn test_compute_add_liquidity_large_numbers() {
-        let owner_program_id = Pubkey::new_unique();
-        let state_key = Pubkey::new_unique();
-        let mut lamports: u64 = 0;
-        let mut data: Vec<u8> = vec![0; mem::size_of::<PluginCalcResult>()];
-        let state_acc_info = create_state_account_info(
-            &state_key,
-            true,
-            &mut lamports,
-            &mut data,
-            &owner_program_id,
-        );
-        let accounts = [state_acc_info];
-
+    fn test_calculate_add_liquidity_large_numbers() {
         let reserve_a = u64::MAX / 2;
         let reserve_b = u64::MAX / 2;
         let deposit_a = u64::MAX / 4; // Will be limited by ratio
@@ -308,8 +226,7 @@ mod tests {
         // Shares = total_lp * actual_a / reserve_a = (MAX/2) * (MAX/4) / (MAX/2) = MAX/4
         let expected_shares = u64::MAX / 4;
 
-        let result = Processor::compute_add_liquidity(
-            &accounts,
+        let result = Processor::calculate_add_liquidity(
             reserve_a,
             reserve_b,
             deposit_a,
@@ -318,11 +235,11 @@ mod tests {
         );
         assert!(
             result.is_ok(),
-            "compute_add_liquidity (large) failed: {:?}",
+            "calculate_add_liquidity (large) failed: {:?}",
             result.err()
         );
 
-        let calc_result = PluginCalcResult::deserialize(&mut &data[..]).unwrap();
+        let calc_result = result.unwrap();
         assert_eq!(
             calc_result.actual_a, expected_actual_a,
             "large actual_a mismatch"
@@ -338,20 +255,7 @@ mod tests {
     }
 
     #[test]
-    fn test_compute_remove_liquidity_burn_all() {
-        let owner_program_id = Pubkey::new_unique();
-        let state_key = Pubkey::new_unique();
-        let mut lamports: u64 = 0;
-        let mut data: Vec<u8> = vec![0; mem::size_of::<PluginCalcResult>()];
-        let state_acc_info = create_state_account_info(
-            &state_key,
-            true,
-            &mut lamports,
-            &mut data,
-            &owner_program_id,
-        );
-        let accounts = [state_acc_info];
-
+    fn test_calculate_remove_liquidity_burn_all() {
         let reserve_a = 12345u64;
         let reserve_b = 54321u64;
         let total_lp_supply = 10000u64;
@@ -360,8 +264,7 @@ mod tests {
         let expected_withdraw_a = reserve_a; // Should get all reserves back
         let expected_withdraw_b = reserve_b;
 
-        let result = Processor::compute_remove_liquidity(
-            &accounts,
+        let result = Processor::calculate_remove_liquidity(
             reserve_a,
             reserve_b,
             total_lp_supply,
@@ -369,11 +272,11 @@ mod tests {
         );
         assert!(
             result.is_ok(),
-            "compute_remove_liquidity (burn all) failed: {:?}",
+            "calculate_remove_liquidity (burn all) failed: {:?}",
             result.err()
         );
 
-        let calc_result = PluginCalcResult::deserialize(&mut &data[..]).unwrap();
+        let calc_result = result.unwrap();
         assert_eq!(
             calc_result.withdraw_a, expected_withdraw_a,
             "burn all withdraw_a mismatch"
@@ -385,27 +288,13 @@ mod tests {
     }
 
     #[test]
-    fn test_compute_remove_liquidity_burn_zero() {
-        let owner_program_id = Pubkey::new_unique();
-        let state_key = Pubkey::new_unique();
-        let mut lamports: u64 = 0;
-        let mut data: Vec<u8> = vec![0; mem::size_of::<PluginCalcResult>()];
-        let state_acc_info = create_state_account_info(
-            &state_key,
-            true,
-            &mut lamports,
-            &mut data,
-            &owner_program_id,
-        );
-        let accounts = [state_acc_info];
-
+    fn test_calculate_remove_liquidity_burn_zero() {
         let reserve_a = 1000u64;
         let reserve_b = 5000u64;
         let total_lp_supply = 10000u64;
         let lp_amount_burning = 0u64; // Burn zero
 
-        let result = Processor::compute_remove_liquidity(
-            &accounts,
+        let result = Processor::calculate_remove_liquidity(
             reserve_a,
             reserve_b,
             total_lp_supply,
@@ -416,37 +305,296 @@ mod tests {
     }
 
     #[test]
-    fn test_compute_swap_zero_input() {
-        let owner_program_id = Pubkey::new_unique();
+    fn test_calculate_swap_zero_input() {
+        let reserve_in = 10000u64;
+        let reserve_out = 20000u64;
+        let amount_in = 0u64;
+
+        let expected_amount_out = 0u64;
+
+        let result = Processor::calculate_swap(reserve_in, reserve_out, amount_in, 3, 1000);
+        assert!(
+            result.is_ok(),
+            "calculate_swap (zero input) failed: {:?}",
+            result.err()
+        );
+
+        let calc_result = result.unwrap();
+        assert_eq!(
+            calc_result.amount_out, expected_amount_out,
+            "swap zero input amount_out mismatch"
+        );
+    }
+
+    #[test]
+    fn test_calculate_swap_fee_tiers() {
+        let reserve_in = 1_000_000u64;
+        let reserve_out = 1_000_000u64;
+        let amount_in = 10_000u64;
+
+        // (fee_num, fee_den, expected_out)
+        // effective_in = 10000 * (den - num) / den
+        // amount_out = floor(1e6 * eff / (1e6 + eff))
+        let tiers = [
+            (1u64, 10_000u64, 9_900u64), // 1 bp: eff = 9999
+            (5, 10_000, 9_896),          // 5 bp: eff = 9995
+            (30, 10_000, 9_871),         // 30 bp: eff = 9970
+            (100, 10_000, 9_802),        // 100 bp: eff = 9900
+        ];
+        for (fee_num, fee_den, expected_out) in tiers {
+            let calc_result =
+                Processor::calculate_swap(reserve_in, reserve_out, amount_in, fee_num, fee_den)
+                    .unwrap();
+            assert_eq!(
+                calc_result.amount_out, expected_out,
+                "amount_out mismatch for fee {}/{}",
+                fee_num, fee_den
+            );
+        }
+    }
+
+    #[test]
+    fn test_calculate_swap_exact_out() {
+        // Inverse of test_calculate_swap:
+        // new_reserve_in = ceil(10000 * 20000 / (20000 - 1813)) = 10997
+        // effective_in = 997, amount_in = ceil(997 * 1000 / 997) = 1000
+        let calc_result = Processor::calculate_swap_exact_out(10000, 20000, 1813, 3, 1000).unwrap();
+        assert_eq!(calc_result.amount_in, 1000);
+        assert_eq!(calc_result.amount_out, 1813);
+        assert_eq!(calc_result.shares_to_mint, 0);
+    }
+
+    #[test]
+    fn test_calculate_swap_exact_out_rounds_for_pool() {
+        let (reserve_in, reserve_out) = (123_456u64, 654_321u64);
+        for (fee_num, fee_den) in [(3u64, 1000u64), (1, 10_000), (0, 1000)] {
+            for amount_out in [1u64, 7, 1_000, 59_999, 654_320] {
+                let amount_in = Processor::calculate_swap_exact_out(
+                    reserve_in,
+                    reserve_out,
+                    amount_out,
+                    fee_num,
+                    fee_den,
+                )
+                .unwrap()
+                .amount_in;
+                // Paying amount_in buys at least amount_out...
+                let out =
+                    Processor::calculate_swap(reserve_in, reserve_out, amount_in, fee_num, fee_den)
+                        .unwrap()
+                        .amount_out;
+                assert!(out >= amount_out, "{} in bought only {}", amount_in, out);
+                // ...and one unit less does not
+                let out = Processor::calculate_swap(
+                    reserve_in,
+                    reserve_out,
+                    amount_in - 1,
+                    fee_num,
+                    fee_den,
+                )
+                .unwrap()
+                .amount_out;
+                assert!(
+                    out < amount_out,
+                    "{} in already bought {}",
+                    amount_in - 1,
+                    out
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_calculate_swap_exact_out_invalid() {
+        // Zero output
+        assert_eq!(
+            Processor::calculate_swap_exact_out(10000, 20000, 0, 3, 1000).err(),
+            Some(ProgramError::InvalidArgument)
+        );
+        // Draining the output reserve
+        assert_eq!(
+            Processor::calculate_swap_exact_out(10000, 20000, 20000, 3, 1000).err(),
+            Some(ProgramError::InvalidArgument)
+        );
+        // Empty input reserve
+        assert_eq!(
+            Processor::calculate_swap_exact_out(0, 20000, 1, 3, 1000).err(),
+            Some(ProgramError::InvalidArgument)
+        );
+        // Required input does not fit in u64
+        assert_eq!(
+            Processor::calculate_swap_exact_out(u64::MAX, u64::MAX, u64::MAX - 1, 3, 1000).err(),
+            Some(ProgramError::InvalidInstructionData)
+        );
+    }
+
+    #[test]
+    fn test_swap_invariant_holds() {
+        let reserves = (10000, 20000);
+        // Repaying in the other token: the exact-out price of test_calculate_swap_exact_out
+        let repaid_a = |amount_in: u64| {
+            Processor::swap_invariant_holds(
+                reserves,
+                (10000 + amount_in, 20000 - 1813),
+                (amount_in, 0),
+                3,
+                1000,
+            )
+            .unwrap()
+        };
+        assert!(repaid_a(1000));
+        assert!(!repaid_a(999));
+
+        // Repaying in the borrowed token: 1000 out needs ceil(1000 * 1000 / 997) back
+        let repaid_b = |amount_in: u64| {
+            Processor::swap_invariant_holds(
+                reserves,
+                (10000, 20000 - 1000 + amount_in),
+                (0, amount_in),
+                3,
+                1000,
+            )
+            .unwrap()
+        };
+        assert!(repaid_b(1004));
+        assert!(!repaid_b(1003));
+        assert!(!repaid_b(0));
+
+        // Without a fee, restoring the balances is enough
+        assert!(Processor::swap_invariant_holds(reserves, reserves, (0, 1000), 0, 1000).unwrap());
+        // The amount paid in can't exceed the balance
+        assert_eq!(
+            Processor::swap_invariant_holds(reserves, (10000, 500), (0, 1000), 3, 1000).err(),
+            Some(ProgramError::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_initialize_plugin_state() {
+        let program_id = Pubkey::new_unique();
         let state_key = Pubkey::new_unique();
         let mut lamports: u64 = 0;
-        let mut data: Vec<u8> = vec![0; mem::size_of::<PluginCalcResult>()];
+        let mut data: Vec<u8> = vec![0; PluginState::LEN];
         let state_acc_info = create_state_account_info(
             &state_key,
             true,
+            true,
             &mut lamports,
             &mut data,
-            &owner_program_id,
+            &program_id,
         );
         let accounts = [state_acc_info];
 
-        let reserve_in = 10000u64;
-        let reserve_out = 20000u64;
-        let amount_in = 0u64;
+        // The pool's handshake rejects the state until it is initialized
+        let handshake_data = PluginInstruction::CheckInterfaceVersion {
+            version: dex_plugin_interface::INTERFACE_VERSION,
+        }
+        .pack();
+        assert_eq!(
+            Processor::process(&program_id, &accounts, &handshake_data).err(),
+            Some(ProgramError::UninitializedAccount)
+        );
+
+        let init_data = ConstantProductInstruction::InitializePluginState {
+            fee_numerator: 5,
+            fee_denominator: 10_000,
+        }
+        .pack();
+        Processor::process(&program_id, &accounts, &init_data).unwrap();
+        assert!(Processor::process(&program_id, &accounts, &handshake_data).is_ok());
+
+        // Swaps read the fee from the initialized state
+        let swap_data = PluginInstruction::ComputeSwap {
+            reserve_in: 1_000,
+            reserve_out: 1_000,
+            amount_in: 10,
+        }
+        .pack();
+        assert!(Processor::process(&program_id, &accounts, &swap_data).is_ok());
+
+        // Second initialization is rejected
+        assert_eq!(
+            Processor::process(&program_id, &accounts, &init_data).err(),
+            Some(ProgramError::AccountAlreadyInitialized)
+        );
 
-        let expected_amount_out = 0u64;
+        drop(accounts);
+        let state = PluginState::deserialize(&mut &data[..]).unwrap();
+        assert_eq!(
+            state,
+            PluginState {
+                is_initialized: true,
+                fee_numerator: 5,
+                fee_denominator: 10_000,
+            }
+        );
+    }
 
-        let result = Processor::compute_swap(&accounts, reserve_in, reserve_out, amount_in);
-        assert!(
-            result.is_ok(),
-            "compute_swap (zero input) failed: {:?}",
-            result.err()
+    #[test]
+    fn test_initialize_plugin_state_invalid() {
+        let program_id = Pubkey::new_unique();
+        let state_key = Pubkey::new_unique();
+        let mut lamports: u64 = 0;
+        let mut data: Vec<u8> = vec![0; PluginState::LEN];
+        let state_acc_info = create_state_account_info(
+            &state_key,
+            true,
+            true,
+            &mut lamports,
+            &mut data,
+            &program_id,
         );
+        let accounts = [state_acc_info];
 
-        let calc_result = PluginCalcResult::deserialize(&mut &data[..]).unwrap();
+        // Fee of 100% or a zero denominator is rejected
+        for (fee_numerator, fee_denominator) in [(1000u64, 1000u64), (0, 0)] {
+            let init_data = ConstantProductInstruction::InitializePluginState {
+                fee_numerator,
+                fee_denominator,
+            }
+            .pack();
+            assert_eq!(
+                Processor::process(&program_id, &accounts, &init_data).err(),
+                Some(ProgramError::InvalidArgument)
+            );
+        }
+
+        // Swaps against an uninitialized state are rejected
+        let swap_data = PluginInstruction::ComputeSwap {
+            reserve_in: 1_000,
+            reserve_out: 1_000,
+            amount_in: 10,
+        }
+        .pack();
         assert_eq!(
-            calc_result.amount_out, expected_amount_out,
-            "swap zero input amount_out mismatch"
+            Processor::process(&program_id, &accounts, &swap_data).err(),
+            Some(ProgramError::UninitializedAccount)
+        );
+
+        // State owned by another program is rejected
+        assert_eq!(
+            Processor::process(&Pubkey::new_unique(), &accounts, &swap_data).err(),
+            Some(ProgramError::IncorrectProgramId)
+        );
+
+        // The state account must sign its own initialization
+        drop(accounts);
+        let unsigned_acc_info = create_state_account_info(
+            &state_key,
+            false,
+            true,
+            &mut lamports,
+            &mut data,
+            &program_id,
+        );
+        let init_data = ConstantProductInstruction::InitializePluginState {
+            fee_numerator: 5,
+            fee_denominator: 10_000,
+        }
+        .pack();
+        assert_eq!(
+            Processor::process(&program_id, &[unsigned_acc_info], &init_data).err(),
+            Some(ProgramError::MissingRequiredSignature)
         );
     }
 
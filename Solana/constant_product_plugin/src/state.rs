@@ -0,0 +1,18 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+
+/// Configuration stored in the plugin state account of a pool.
+/// Pools are keyed on the plugin state account, so each fee tier gets its own pool.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default, PartialEq)]
+pub struct PluginState {
+    /// Set once by `InitializePluginState`
+    pub is_initialized: bool,
+    /// Swap fee numerator, charged on the input amount
+    pub fee_numerator: u64,
+    /// Swap fee denominator
+    pub fee_denominator: u64,
+}
+
+impl PluginState {
+    /// Serialized size of the state.
+    pub const LEN: usize = 1 + 8 + 8;
+}
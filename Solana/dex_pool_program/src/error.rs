@@ -123,6 +123,66 @@ pub enum PoolError {
     /// Provided vault account is not the correct ATA
     #[error("Incorrect vault ATA provided")]
     IncorrectVaultATA,
+
+    /// Provided config account is not the protocol config PDA
+    #[error("Incorrect protocol config PDA")]
+    IncorrectConfigPDA,
+
+    /// Protocol config was already initialized
+    #[error("Protocol config already initialized")]
+    ConfigAlreadyInitialized,
+
+    /// Signer is not the protocol config admin
+    #[error("Signer is not the protocol admin")]
+    Unauthorized,
+
+    /// Protocol fee share above 100%
+    #[error("Protocol fee share must not exceed 10000 bps")]
+    InvalidProtocolFee,
+
+    /// Treasury LP account is not an LP account of the treasury
+    #[error("Treasury LP account mismatch")]
+    TreasuryMismatch,
+
+    /// Swap route has no hops, too many hops, or the same pool twice in a row
+    #[error("Invalid swap route")]
+    InvalidRoute,
+
+    /// The instruction's deadline has passed
+    #[error("Deadline exceeded")]
+    DeadlineExceeded,
+
+    /// The first deposit does not mint more than `MINIMUM_LIQUIDITY` shares
+    #[error("Initial liquidity below minimum")]
+    InsufficientInitialLiquidity,
+
+    /// Provided oracle account is not the pool's oracle PDA
+    #[error("Incorrect oracle PDA")]
+    IncorrectOraclePDA,
+
+    /// The pool's oracle account already exists
+    #[error("Oracle already initialized")]
+    OracleAlreadyInitialized,
+
+    /// The oracle holds no observation old enough for the requested window
+    #[error("Insufficient oracle history")]
+    InsufficientOracleHistory,
+
+    /// The pool is in the middle of a flash swap
+    #[error("Pool is locked by a flash swap")]
+    PoolLocked,
+
+    /// Plugin state account is not owned by the plugin program
+    #[error("Invalid plugin state account")]
+    InvalidPluginState,
+
+    /// Provided account is not the program's ProgramData account
+    #[error("Incorrect program data account")]
+    IncorrectProgramData,
+
+    /// Signer is not the program's upgrade authority
+    #[error("Signer is not the upgrade authority")]
+    NotUpgradeAuthority,
 }
 
 impl From<PoolError> for ProgramError {
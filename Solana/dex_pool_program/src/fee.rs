@@ -0,0 +1,100 @@
+use spl_math::{approximations::sqrt, uint::U192};
+
+/// Basis points in 100%.
+pub const MAX_BPS: u16 = 10_000;
+
+/// LP shares to mint to the treasury for the fees accrued since `k_last`
+/// (Uniswap v2 `_mintFee`, generalised to a share `φ = share_bps / 10000`):
+///
+/// `s = S * φ * (√k - √k_last) / ((1 - φ) * √k + φ * √k_last)`
+///
+/// Returns `None` on overflow, `Some(0)` when there is nothing to mint.
+pub fn calculate_protocol_fee_shares(
+    reserve_a: u64,
+    reserve_b: u64,
+    k_last: u128,
+    total_lp_supply: u64,
+    protocol_fee_share_bps: u16,
+) -> Option<u64> {
+    if k_last == 0 || total_lp_supply == 0 || protocol_fee_share_bps == 0 {
+        return Some(0);
+    }
+    let root_k = sqrt((reserve_a as u128) * (reserve_b as u128))?;
+    let root_k_last = sqrt(k_last)?;
+    if root_k <= root_k_last {
+        return Some(0);
+    }
+
+    let share = U192::from(protocol_fee_share_bps);
+    let remaining_share = U192::from(MAX_BPS.checked_sub(protocol_fee_share_bps)?);
+    let numerator = U192::from(total_lp_supply)
+        .checked_mul(share)?
+        .checked_mul(U192::from(root_k - root_k_last))?;
+    let denominator = U192::from(root_k)
+        .checked_mul(remaining_share)?
+        .checked_add(U192::from(root_k_last).checked_mul(share)?)?;
+    if denominator.is_zero() {
+        return None;
+    }
+    let shares = numerator.checked_div(denominator)?;
+    if shares > U192::from(u64::MAX) {
+        return None;
+    }
+    Some(shares.as_u64())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_protocol_fee_off_or_no_growth() {
+        // Fee was off at the last liquidity event
+        assert_eq!(
+            calculate_protocol_fee_shares(2_000, 2_000, 0, 1_000, 1_667),
+            Some(0)
+        );
+        // Zero share
+        assert_eq!(
+            calculate_protocol_fee_shares(2_000, 2_000, 1_000_000, 1_000, 0),
+            Some(0)
+        );
+        // k did not grow
+        assert_eq!(
+            calculate_protocol_fee_shares(1_000, 1_000, 1_000_000, 1_000, 1_667),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn test_protocol_fee_shares() {
+        // √k grew from 1000 to 1100 with S = 1000.
+        // φ = 1/2: s = 1000 * 0.5 * 100 / (0.5 * 1100 + 0.5 * 1000) = 47.6
+        assert_eq!(
+            calculate_protocol_fee_shares(1_100, 1_100, 1_000_000, 1_000, 5_000),
+            Some(47)
+        );
+        // φ = 1: the treasury gets the entire growth, S * (√k - √k_last) / √k_last = 100
+        assert_eq!(
+            calculate_protocol_fee_shares(1_100, 1_100, 1_000_000, 1_000, 10_000),
+            Some(100)
+        );
+        // Share above 100% is rejected
+        assert_eq!(
+            calculate_protocol_fee_shares(1_100, 1_100, 1_000_000, 1_000, 10_001),
+            None
+        );
+    }
+
+    #[test]
+    fn test_protocol_fee_large_values() {
+        let shares = calculate_protocol_fee_shares(
+            u64::MAX,
+            u64::MAX,
+            (u64::MAX as u128 / 2) * (u64::MAX as u128 / 2),
+            u64::MAX,
+            1_667,
+        );
+        assert!(shares.is_some());
+    }
+}
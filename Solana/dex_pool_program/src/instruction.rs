@@ -1,11 +1,49 @@
 #![allow(clippy::doc_lazy_continuation)]
 use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{clock::Clock, program_error::ProgramError, pubkey::Pubkey};
+
+use crate::error::PoolError;
+
+/// Maximum number of pools a `SwapRoute` may go through.
+pub const MAX_ROUTE_HOPS: u8 = 4;
+
+/// Number of accounts passed for each hop of a `SwapRoute`.
+pub const ROUTE_HOP_ACCOUNTS: usize = 5;
+
+/// Most trailing optional fields any instruction gained after its introduction.
+const MAX_TRAILING_OPTIONS: usize = 3;
+
+/// Last moment at which a user-facing instruction may still execute, read from the Clock sysvar.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Deadline {
+    /// Latest `Clock::unix_timestamp`, inclusive
+    UnixTimestamp(i64),
+    /// Latest `Clock::slot`, inclusive
+    Slot(u64),
+}
+
+impl Deadline {
+    pub fn has_passed(&self, clock: &Clock) -> bool {
+        match *self {
+            Deadline::UnixTimestamp(timestamp) => clock.unix_timestamp > timestamp,
+            Deadline::Slot(slot) => clock.slot > slot,
+        }
+    }
+}
 
 /// Defines the instructions available in the Pool program.
-#[derive(BorshSerialize, BorshDeserialize, Debug)]
+///
+/// Every user-facing instruction carries an optional `deadline`; the program rejects it
+/// with `PoolError::DeadlineExceeded` once the deadline has passed.
+///
+/// Fields added after an instruction was introduced are trailing `Option`s, so data encoded
+/// before they existed still decodes (see `PoolInstruction::unpack`).
+#[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq)]
 pub enum PoolInstruction {
     /// Initializes a new pool.
     /// Creates the pool state account, vaults, and LP mint.
+    /// The plugin must accept the `CheckInterfaceVersion` handshake for
+    /// `dex_plugin_interface::INTERFACE_VERSION` and the given plugin state.
     ///
     /// Accounts (expected):
     /// 0. [signer] payer: Account funding the new pool
@@ -15,8 +53,8 @@ pub enum PoolInstruction {
     /// 4. [writable] LP mint: Mint account for the pool's liquidity provider tokens
     /// 5. [read]   token mint A: Mint of token A
     /// 6. [read]   token mint B: Mint of token B
-    /// 7. [read]   plugin program: The executable plugin program ID
-    /// 8. [writable] plugin state: The state account for the plugin program
+    /// 7. [read]   plugin program: The executable plugin program ID (implements the plugin interface)
+    /// 8. [read]   plugin state: An initialized state account owned by the plugin program
     /// 9. [read]   system_program: Solana System Program
     /// 10. [read]  token_program: SPL Token Program
     /// 11. [read]  rent sysvar: Solana Rent Sysvar
@@ -36,12 +74,23 @@ pub enum PoolInstruction {
     /// 7. [writable] user LP: User's destination LP token account
     /// 8. [read]   token_program: SPL Token Program
     /// 9. [read]   plugin program: The executable plugin program ID
-    /// 10.[writable] plugin state: The state account for the plugin program
+    /// 10.[read]  plugin state: The state account for the plugin program
+    /// 11.[read]  protocol config: The `[b"config"]` PDA (may be uninitialized)
+    /// 12.[writable] treasury LP (fee on): Treasury's LP token account, required while the fee is on
+    /// 13.[writable] oracle (optional): The pool's `[b"oracle", pool]` PDA, to record an observation
+    /// 14.[writable] locked LP (first deposit): The pool PDA's LP token ATA, which receives
+    ///    the `MINIMUM_LIQUIDITY` shares locked by the first deposit
+    ///
+    /// Pass this program's ID in place of an optional account followed by one that is passed.
     AddLiquidity {
         /// Max amount of token A to deposit
         amount_a: u64,
         /// Max amount of token B to deposit
         amount_b: u64,
+        /// Reject the instruction after this point (`None` = no expiry)
+        deadline: Option<Deadline>,
+        /// Minimum LP tokens to mint, checked after the plugin computation (`None` = no minimum)
+        min_shares_out: Option<u64>,
     },
 
     /// Removes liquidity from the pool.
@@ -58,10 +107,21 @@ pub enum PoolInstruction {
     /// 7. [writable] user LP: User's source LP token account (to burn from)
     /// 8. [read]   token_program: SPL Token Program
     /// 9. [read]   plugin program: The executable plugin program ID
-    /// 10.[writable] plugin state: The state account for the plugin program
+    /// 10.[read]  plugin state: The state account for the plugin program
+    /// 11.[read]  protocol config: The `[b"config"]` PDA (may be uninitialized)
+    /// 12.[writable] treasury LP (fee on): Treasury's LP token account, required while the fee is on
+    /// 13.[writable] oracle (optional): The pool's `[b"oracle", pool]` PDA, to record an observation
+    ///
+    /// Pass this program's ID in place of an optional account followed by one that is passed.
     RemoveLiquidity {
         /// Amount of LP tokens to burn
         amount_lp: u64,
+        /// Reject the instruction after this point (`None` = no expiry)
+        deadline: Option<Deadline>,
+        /// Minimum amount of token A to receive (`None` = no minimum)
+        min_a: Option<u64>,
+        /// Minimum amount of token B to receive (`None` = no minimum)
+        min_b: Option<u64>,
     },
 
     /// Swaps one token for another in the pool.
@@ -76,11 +136,396 @@ pub enum PoolInstruction {
     /// 5. [writable] user dst token: User's destination token account (receiving from pool)
     /// 6. [read]   token_program: SPL Token Program
     /// 7. [read]   plugin program: The executable plugin program ID
-    /// 8. [writable] plugin state: The state account for the plugin program
+    /// 8. [read]   plugin state: The state account for the plugin program
+    /// 9. [writable] oracle (optional): The pool's `[b"oracle", pool]` PDA, to record an observation
     Swap {
         /// Amount of the input token to swap
         amount_in: u64,
         /// Minimum amount of the output token the user must receive (slippage protection)
         min_out: u64,
+        /// Reject the instruction after this point (`None` = no expiry)
+        deadline: Option<Deadline>,
+    },
+
+    /// Creates the global protocol config PDA. Only the program's upgrade authority may
+    /// call it, and it becomes the config admin.
+    ///
+    /// Accounts:
+    /// 0. [signer, writable] admin: The upgrade authority; pays for and administers the config
+    /// 1. [writable] protocol config: The `[b"config"]` PDA
+    /// 2. [read]   system_program: Solana System Program
+    /// 3. [read]   program data: This program's ProgramData account (upgradeable loader)
+    InitializeConfig {
+        /// Wallet receiving the protocol fee
+        treasury: Pubkey,
+        /// Share of the swap fees credited to the treasury, in basis points (0 = off)
+        protocol_fee_share_bps: u16,
+    },
+
+    /// Updates the treasury and protocol fee share.
+    /// A pool settles the fee at its next liquidity event, so the new share also applies to
+    /// the growth accrued before the change. Turning the fee off forfeits that growth;
+    /// turning it on starts accrual at each pool's next liquidity event.
+    ///
+    /// Accounts:
+    /// 0. [signer] admin: The config admin
+    /// 1. [writable] protocol config: The `[b"config"]` PDA
+    SetProtocolFee {
+        /// Wallet receiving the protocol fee
+        treasury: Pubkey,
+        /// Share of the swap fees credited to the treasury, in basis points (0 = off)
+        protocol_fee_share_bps: u16,
+    },
+
+    /// Swaps one token for an exact amount of the other.
+    /// The plugin computes the input needed, rounded up in the pool's favour.
+    ///
+    /// Accounts: same as `Swap`.
+    SwapExactOut {
+        /// Exact amount of the output token the user receives
+        amount_out: u64,
+        /// Maximum amount of the input token the user is willing to spend (slippage protection)
+        max_in: u64,
+        /// Reject the instruction after this point (`None` = no expiry)
+        deadline: Option<Deadline>,
+    },
+
+    /// Swaps through a chain of pools (e.g. A -> B -> C) in one instruction.
+    /// Each hop's output moves straight from its vault into the next pool's vault,
+    /// and only the final output is checked against `min_final_out`.
+    /// The hops accrue their pools' cumulative prices but record no oracle observations.
+    ///
+    /// Accounts:
+    /// 0. [signer] user: The user performing the swap
+    /// 1. [writable] user src token: User's token account of the route's input mint
+    /// 2. [writable] user dst token: User's token account of the route's output mint
+    /// 3. [read]   token_program: SPL Token Program
+    ///
+    /// Followed by `ROUTE_HOP_ACCOUNTS` accounts per hop, in route order:
+    /// 0. [writable] pool state: The hop's pool state account
+    /// 1. [writable] vault A: The hop's token A vault
+    /// 2. [writable] vault B: The hop's token B vault
+    /// 3. [read]   plugin program: The hop's plugin program ID
+    /// 4. [read]   plugin state: The hop's plugin state account
+    SwapRoute {
+        /// Amount of the route's input token to swap
+        amount_in: u64,
+        /// Minimum amount of the route's output token the user must receive
+        min_final_out: u64,
+        /// Number of pools in the route, at most `MAX_ROUTE_HOPS`
+        hops: u8,
+        /// Reject the instruction after this point (`None` = no expiry)
+        deadline: Option<Deadline>,
+    },
+
+    /// Same as `Swap`, but delivers the output to any token account of the output mint.
+    /// Only the source account must belong to the signer.
+    ///
+    /// Accounts: same as `Swap`, with 5 being the recipient's token account.
+    SwapTo {
+        /// Amount of the input token to swap
+        amount_in: u64,
+        /// Minimum amount of the output token the recipient must receive (slippage protection)
+        min_out: u64,
+        /// Reject the instruction after this point (`None` = no expiry)
+        deadline: Option<Deadline>,
     },
+
+    /// Same as `RemoveLiquidity`, but pays tokens A and B to any token accounts of those
+    /// mints. Only the LP account burned from must belong to the signer.
+    ///
+    /// Accounts: same as `RemoveLiquidity`, with 5 and 6 being the recipient's token
+    /// A and token B accounts.
+    RemoveLiquidityTo {
+        /// Amount of LP tokens to burn
+        amount_lp: u64,
+        /// Reject the instruction after this point (`None` = no expiry)
+        deadline: Option<Deadline>,
+        /// Minimum amount of token A to receive (`None` = no minimum)
+        min_a: Option<u64>,
+        /// Minimum amount of token B to receive (`None` = no minimum)
+        min_b: Option<u64>,
+    },
+
+    /// Sets the pool's tracked reserves to its vault balances, taking in any tokens sent
+    /// straight to the vaults. Anyone may call it.
+    ///
+    /// Accounts:
+    /// 0. [writable] pool state: The pool's state account
+    /// 1. [read]   vault A: Pool's token A vault
+    /// 2. [read]   vault B: Pool's token B vault
+    /// 3. [writable] oracle (optional): The pool's `[b"oracle", pool]` PDA, to record an observation
+    Sync,
+
+    /// Transfers the vault balances above the pool's tracked reserves to the given token
+    /// accounts. Anyone may call it.
+    ///
+    /// Accounts:
+    /// 0. [read]   pool state: The pool's state account
+    /// 1. [writable] vault A: Pool's token A vault
+    /// 2. [writable] vault B: Pool's token B vault
+    /// 3. [writable] dst token A: Token A account receiving the surplus
+    /// 4. [writable] dst token B: Token B account receiving the surplus
+    /// 5. [read]   token_program: SPL Token Program
+    Skim,
+
+    /// Creates a pool's oracle account, a ring buffer of `oracle::OBSERVATION_CAPACITY`
+    /// cumulative price observations, seeded with the current one. Anyone may call it.
+    ///
+    /// Accounts:
+    /// 0. [signer, writable] payer: Pays for the oracle account
+    /// 1. [read]   pool state: The pool's state account
+    /// 2. [writable] oracle: The pool's `[b"oracle", pool]` PDA
+    /// 3. [read]   system_program: Solana System Program
+    InitializeOracle,
+
+    /// Sets an `oracle::TwapPrice` as return data: the pool's average prices from the latest
+    /// observation made at least `seconds_ago` seconds ago until now. Meant to be called
+    /// through CPI; it changes no accounts.
+    ///
+    /// Accounts:
+    /// 0. [read]   pool state: The pool's state account
+    /// 1. [read]   oracle: The pool's `[b"oracle", pool]` PDA
+    ObservePrice {
+        /// Minimum length of the averaging window, in seconds
+        seconds_ago: u32,
+    },
+
+    /// Lends `amount_out` of one token, then invokes a callback program that must pay the
+    /// pool back before it returns. The vault balances afterwards must keep the plugin's
+    /// invariant, with the fee charged on whatever was paid in (`CheckSwapInvariant`), and
+    /// become the new reserves. Repayment may be in either token or both.
+    ///
+    /// The pool is locked while the callback runs, so it can't re-enter the pool to change
+    /// its reserves or vaults. It accrues the pool's cumulative prices but records no
+    /// oracle observation.
+    ///
+    /// Accounts:
+    /// 0. [signer] user: The user performing the flash swap
+    /// 1. [writable] pool state: The pool's state account
+    /// 2. [writable] vault A: Pool's token A vault
+    /// 3. [writable] vault B: Pool's token B vault
+    /// 4. [writable] borrower token: Token account receiving `amount_out`; its mint is the
+    ///    token borrowed
+    /// 5. [read]   token_program: SPL Token Program
+    /// 6. [read]   plugin program: The executable plugin program ID
+    /// 7. [read]   plugin state: The state account for the plugin program
+    /// 8. [read]   callback program: The program invoked once the tokens are lent
+    ///
+    /// Followed by the accounts passed to the callback program, in order, with the
+    /// signer and writable flags they have here.
+    FlashSwap {
+        /// Amount of the borrowed token sent to the borrower token account
+        amount_out: u64,
+        /// Instruction data the callback program is invoked with
+        callback_data: Vec<u8>,
+        /// Reject the instruction after this point (`None` = no expiry)
+        deadline: Option<Deadline>,
+    },
+}
+
+impl PoolInstruction {
+    /// Decodes instruction data. Data encoded before trailing optional fields were added
+    /// stops right before their `Option` tags, so the missing fields are read as `None`.
+    pub fn unpack(data: &[u8]) -> Result<Self, ProgramError> {
+        let mut padded = data.to_vec();
+        for missing in 0..=MAX_TRAILING_OPTIONS {
+            if let Ok(instruction) = Self::try_from_slice(&padded) {
+                // Padding must only stand in for absent fields, not complete a truncated value
+                if instruction.trailing_none_fields() >= missing {
+                    return Ok(instruction);
+                }
+            }
+            padded.push(0); // `None` tag
+        }
+        Err(PoolError::InvalidInstructionData.into())
+    }
+
+    /// Number of trailing optional fields, counted from the end, that are `None`.
+    fn trailing_none_fields(&self) -> usize {
+        let trailing: &[bool] = match self {
+            PoolInstruction::AddLiquidity {
+                deadline,
+                min_shares_out,
+                ..
+            } => &[deadline.is_none(), min_shares_out.is_none()],
+            PoolInstruction::RemoveLiquidity {
+                deadline,
+                min_a,
+                min_b,
+                ..
+            }
+            | PoolInstruction::RemoveLiquidityTo {
+                deadline,
+                min_a,
+                min_b,
+                ..
+            } => &[deadline.is_none(), min_a.is_none(), min_b.is_none()],
+            PoolInstruction::Swap { deadline, .. }
+            | PoolInstruction::SwapExactOut { deadline, .. }
+            | PoolInstruction::SwapRoute { deadline, .. }
+            | PoolInstruction::SwapTo { deadline, .. }
+            | PoolInstruction::FlashSwap { deadline, .. } => &[deadline.is_none()],
+            PoolInstruction::InitializePool
+            | PoolInstruction::InitializeConfig { .. }
+            | PoolInstruction::SetProtocolFee { .. }
+            | PoolInstruction::Sync
+            | PoolInstruction::Skim
+            | PoolInstruction::InitializeOracle
+            | PoolInstruction::ObservePrice { .. } => &[],
+        };
+        trailing.iter().rev().take_while(|none| **none).count()
+    }
+
+    /// The deadline of a user-facing instruction, if one was set.
+    pub fn deadline(&self) -> Option<Deadline> {
+        match self {
+            PoolInstruction::AddLiquidity { deadline, .. }
+            | PoolInstruction::RemoveLiquidity { deadline, .. }
+            | PoolInstruction::Swap { deadline, .. }
+            | PoolInstruction::SwapExactOut { deadline, .. }
+            | PoolInstruction::SwapRoute { deadline, .. }
+            | PoolInstruction::SwapTo { deadline, .. }
+            | PoolInstruction::RemoveLiquidityTo { deadline, .. }
+            | PoolInstruction::FlashSwap { deadline, .. } => *deadline,
+            PoolInstruction::InitializePool
+            | PoolInstruction::InitializeConfig { .. }
+            | PoolInstruction::SetProtocolFee { .. }
+            | PoolInstruction::Sync
+            | PoolInstruction::Skim
+            | PoolInstruction::InitializeOracle
+            | PoolInstruction::ObservePrice { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unpack_legacy_encoding() {
+        // Swap { amount_in: 7, min_out: 5 } as encoded before deadlines existed
+        let mut legacy = vec![3u8];
+        legacy.extend_from_slice(&7u64.to_le_bytes());
+        legacy.extend_from_slice(&5u64.to_le_bytes());
+        assert_eq!(
+            PoolInstruction::unpack(&legacy).unwrap(),
+            PoolInstruction::Swap {
+                amount_in: 7,
+                min_out: 5,
+                deadline: None,
+            }
+        );
+
+        // Instructions without a deadline are not padded into validity
+        assert_eq!(
+            PoolInstruction::unpack(&[0]).unwrap(),
+            PoolInstruction::InitializePool
+        );
+        assert!(PoolInstruction::unpack(&[0, 0]).is_err());
+        assert!(PoolInstruction::unpack(&legacy[..9]).is_err());
+    }
+
+    #[test]
+    fn test_unpack_with_deadline() {
+        let instructions = [
+            PoolInstruction::AddLiquidity {
+                amount_a: 1,
+                amount_b: 2,
+                deadline: Some(Deadline::Slot(3)),
+                min_shares_out: None,
+            },
+            PoolInstruction::RemoveLiquidity {
+                amount_lp: 1,
+                deadline: Some(Deadline::UnixTimestamp(1_700_000_000)),
+                min_a: Some(4),
+                min_b: None,
+            },
+            PoolInstruction::SwapExactOut {
+                amount_out: 1,
+                max_in: 2,
+                deadline: None,
+            },
+            PoolInstruction::SwapRoute {
+                amount_in: 1,
+                min_final_out: 2,
+                hops: 2,
+                deadline: Some(Deadline::Slot(9)),
+            },
+            PoolInstruction::FlashSwap {
+                amount_out: 1,
+                callback_data: vec![0, 1, 2],
+                deadline: Some(Deadline::UnixTimestamp(1_700_000_000)),
+            },
+            PoolInstruction::FlashSwap {
+                amount_out: 1,
+                callback_data: vec![],
+                deadline: None,
+            },
+        ];
+        for instruction in instructions {
+            let data = instruction.try_to_vec().unwrap();
+            let unpacked = PoolInstruction::unpack(&data).unwrap();
+            assert_eq!(unpacked.deadline(), instruction.deadline());
+            assert_eq!(unpacked, instruction);
+        }
+    }
+
+    #[test]
+    fn test_unpack_without_slippage_limits() {
+        // AddLiquidity { amount_a: 1, amount_b: 2, deadline: Some(Slot(3)) } as encoded
+        // before minimum shares existed
+        let mut add = vec![1u8];
+        add.extend_from_slice(&1u64.to_le_bytes());
+        add.extend_from_slice(&2u64.to_le_bytes());
+        add.extend_from_slice(&[1, 1]);
+        add.extend_from_slice(&3u64.to_le_bytes());
+        assert_eq!(
+            PoolInstruction::unpack(&add).unwrap(),
+            PoolInstruction::AddLiquidity {
+                amount_a: 1,
+                amount_b: 2,
+                deadline: Some(Deadline::Slot(3)),
+                min_shares_out: None,
+            }
+        );
+
+        // RemoveLiquidity { amount_lp: 5 } as encoded before deadlines existed
+        let mut remove = vec![2u8];
+        remove.extend_from_slice(&5u64.to_le_bytes());
+        assert_eq!(
+            PoolInstruction::unpack(&remove).unwrap(),
+            PoolInstruction::RemoveLiquidity {
+                amount_lp: 5,
+                deadline: None,
+                min_a: None,
+                min_b: None,
+            }
+        );
+
+        // A truncated minimum is rejected rather than completed with padding
+        let full = PoolInstruction::RemoveLiquidity {
+            amount_lp: 5,
+            deadline: None,
+            min_a: None,
+            min_b: Some(256),
+        }
+        .try_to_vec()
+        .unwrap();
+        assert!(PoolInstruction::unpack(&full[..full.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_deadline_has_passed() {
+        let clock = Clock {
+            slot: 100,
+            unix_timestamp: 1_700_000_000,
+            ..Clock::default()
+        };
+        assert!(!Deadline::Slot(100).has_passed(&clock));
+        assert!(Deadline::Slot(99).has_passed(&clock));
+        assert!(!Deadline::UnixTimestamp(1_700_000_000).has_passed(&clock));
+        assert!(Deadline::UnixTimestamp(1_699_999_999).has_passed(&clock));
+    }
 }
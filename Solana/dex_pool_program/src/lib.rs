@@ -1,6 +1,8 @@
 pub mod entrypoint;
 pub mod error;
+pub mod fee;
 pub mod instruction;
+pub mod oracle;
 pub mod pda;
 pub mod processor;
 pub mod state;
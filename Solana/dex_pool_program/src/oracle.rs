@@ -0,0 +1,267 @@
+//! Time-weighted average prices from cumulative prices (Uniswap v2 style).
+//!
+//! Prices are UQ64.64 fixed point numbers: the price of token A is `reserve_b / reserve_a`
+//! scaled by `2^64`. The cumulative prices add up `price * seconds` and are meant to wrap:
+//! the difference between two observations stays correct as long as it fits in a `u128`.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::pubkey::Pubkey;
+
+use crate::state::PoolState;
+
+/// Observations kept by a pool's oracle account.
+pub const OBSERVATION_CAPACITY: usize = 64;
+
+/// `reserve_out / reserve_in` as UQ64.64. `None` while either reserve is empty.
+pub fn price_q64(reserve_in: u64, reserve_out: u64) -> Option<u128> {
+    if reserve_in == 0 || reserve_out == 0 {
+        return None;
+    }
+    Some(((reserve_out as u128) << 64) / reserve_in as u128)
+}
+
+/// The pool's `(price_a_cumulative, price_b_cumulative)` as of `now`, including the
+/// current reserves' price since `last_update_timestamp`.
+pub fn cumulative_prices(pool: &PoolState, now: i64) -> (u128, u128) {
+    let elapsed = now.saturating_sub(pool.last_update_timestamp).max(0) as u128;
+    let accrued = |price: Option<u128>| price.unwrap_or(0).wrapping_mul(elapsed);
+    (
+        pool.price_a_cumulative
+            .wrapping_add(accrued(price_q64(pool.reserve_a, pool.reserve_b))),
+        pool.price_b_cumulative
+            .wrapping_add(accrued(price_q64(pool.reserve_b, pool.reserve_a))),
+    )
+}
+
+/// Brings the cumulative prices up to `now`. Must run before the reserves change.
+pub fn accrue(pool: &mut PoolState, now: i64) {
+    (pool.price_a_cumulative, pool.price_b_cumulative) = cumulative_prices(pool, now);
+    pool.last_update_timestamp = pool.last_update_timestamp.max(now);
+}
+
+/// Cumulative prices at a point in time.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default, Clone, Copy, PartialEq)]
+pub struct Observation {
+    /// `Clock::unix_timestamp` of the observation
+    pub timestamp: i64,
+    pub price_a_cumulative: u128,
+    pub price_b_cumulative: u128,
+}
+
+impl Observation {
+    /// The pool's cumulative prices as of `now`.
+    pub fn of_pool(pool: &PoolState, now: i64) -> Self {
+        let (price_a_cumulative, price_b_cumulative) = cumulative_prices(pool, now);
+        Self {
+            timestamp: now,
+            price_a_cumulative,
+            price_b_cumulative,
+        }
+    }
+}
+
+/// Ring buffer of a pool's observations, stored in the `[b"oracle", pool]` PDA.
+/// Liquidity events, swaps and `Sync` record one when the account is passed to them.
+#[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq)]
+pub struct ObservationBuffer {
+    /// The pool observed.
+    pub pool: Pubkey,
+    /// Slot the next observation is written to.
+    pub next_index: u16,
+    /// Number of slots written so far, at most `OBSERVATION_CAPACITY`.
+    pub len: u16,
+    /// The bump seed used to derive the oracle PDA.
+    pub bump: u8,
+    /// Always `OBSERVATION_CAPACITY` slots; kept on the heap, as the on-chain stack is small.
+    pub observations: Vec<Observation>,
+}
+
+impl ObservationBuffer {
+    /// Serialized size of the buffer.
+    pub const LEN: usize = 32 + 2 + 2 + 1 + 4 + OBSERVATION_CAPACITY * (8 + 16 + 16);
+
+    /// A buffer holding `first` only.
+    pub fn new(pool: Pubkey, bump: u8, first: Observation) -> Self {
+        let mut buffer = Self {
+            pool,
+            next_index: 0,
+            len: 0,
+            bump,
+            observations: vec![Observation::default(); OBSERVATION_CAPACITY],
+        };
+        buffer.record(first);
+        buffer
+    }
+
+    /// Records `observation`, overwriting the oldest one once the buffer is full.
+    /// Skipped when the latest observation is not older.
+    pub fn record(&mut self, observation: Observation) {
+        if self
+            .latest()
+            .is_some_and(|latest| latest.timestamp >= observation.timestamp)
+        {
+            return;
+        }
+        self.observations[self.next_index as usize] = observation;
+        self.next_index = ((self.next_index as usize + 1) % OBSERVATION_CAPACITY) as u16;
+        self.len = (self.len + 1).min(OBSERVATION_CAPACITY as u16);
+    }
+
+    /// Most recent observation.
+    pub fn latest(&self) -> Option<&Observation> {
+        self.newest_first().next()
+    }
+
+    /// Most recent observation made at or before `timestamp`.
+    pub fn at_or_before(&self, timestamp: i64) -> Option<&Observation> {
+        self.newest_first()
+            .find(|observation| observation.timestamp <= timestamp)
+    }
+
+    fn newest_first(&self) -> impl Iterator<Item = &Observation> {
+        let next = self.next_index as usize;
+        (1..=self.len as usize).map(move |back| {
+            &self.observations[(next + OBSERVATION_CAPACITY - back) % OBSERVATION_CAPACITY]
+        })
+    }
+}
+
+/// Average prices between two observations, returned by `ObservePrice`.
+#[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq)]
+pub struct TwapPrice {
+    /// Average price of token A in token B, UQ64.64.
+    pub price_a: u128,
+    /// Average price of token B in token A, UQ64.64.
+    pub price_b: u128,
+    /// Seconds the average covers.
+    pub window_seconds: u64,
+}
+
+/// Average prices from `start` to `end`. `None` unless `end` is later than `start`.
+pub fn twap(start: &Observation, end: &Observation) -> Option<TwapPrice> {
+    let window = end.timestamp.checked_sub(start.timestamp)?;
+    if window <= 0 {
+        return None;
+    }
+    Some(TwapPrice {
+        price_a: end
+            .price_a_cumulative
+            .wrapping_sub(start.price_a_cumulative)
+            / window as u128,
+        price_b: end
+            .price_b_cumulative
+            .wrapping_sub(start.price_b_cumulative)
+            / window as u128,
+        window_seconds: window as u64,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ONE: u128 = 1 << 64;
+
+    fn pool_state(reserve_a: u64, reserve_b: u64, last_update_timestamp: i64) -> PoolState {
+        PoolState {
+            token_mint_a: Pubkey::new_unique(),
+            token_mint_b: Pubkey::new_unique(),
+            vault_a: Pubkey::new_unique(),
+            vault_b: Pubkey::new_unique(),
+            lp_mint: Pubkey::new_unique(),
+            total_lp_supply: 0,
+            bump: 255,
+            plugin_program_id: Pubkey::new_unique(),
+            plugin_state_pubkey: Pubkey::new_unique(),
+            k_last: 0,
+            reserve_a,
+            reserve_b,
+            price_a_cumulative: 0,
+            price_b_cumulative: 0,
+            last_update_timestamp,
+            locked: false,
+        }
+    }
+
+    fn observation(timestamp: i64) -> Observation {
+        Observation {
+            timestamp,
+            ..Observation::default()
+        }
+    }
+
+    #[test]
+    fn test_price_q64() {
+        assert_eq!(price_q64(1_000, 2_000), Some(2 * ONE));
+        assert_eq!(price_q64(2_000, 1_000), Some(ONE / 2));
+        assert_eq!(price_q64(0, 1_000), None);
+        assert_eq!(price_q64(u64::MAX, u64::MAX), Some(ONE));
+    }
+
+    #[test]
+    fn test_accrue_and_twap() {
+        let mut pool = pool_state(1_000, 2_000, 100);
+        let start = Observation::of_pool(&pool, 100);
+
+        // 10 seconds at a price of 2, then 30 seconds at 4
+        accrue(&mut pool, 110);
+        pool.reserve_b = 4_000;
+        accrue(&mut pool, 140);
+        assert_eq!(pool.price_a_cumulative, 20 * ONE + 120 * ONE);
+        assert_eq!(pool.last_update_timestamp, 140);
+
+        let end = Observation::of_pool(&pool, 140);
+        let average = twap(&start, &end).unwrap();
+        assert_eq!(average.price_a, 140 * ONE / 40);
+        assert_eq!(average.price_b, (10 * ONE / 2 + 30 * ONE / 4) / 40);
+        assert_eq!(average.window_seconds, 40);
+        assert_eq!(twap(&end, &start), None);
+        assert_eq!(twap(&end, &end), None);
+
+        // Empty reserves accrue nothing
+        let mut empty = pool_state(0, 0, 0);
+        accrue(&mut empty, 50);
+        assert_eq!((empty.price_a_cumulative, empty.price_b_cumulative), (0, 0));
+        assert_eq!(empty.last_update_timestamp, 50);
+    }
+
+    #[test]
+    fn test_twap_across_wrap() {
+        let start = Observation {
+            timestamp: 0,
+            price_a_cumulative: u128::MAX - ONE + 1,
+            price_b_cumulative: 0,
+        };
+        let end = Observation {
+            timestamp: 2,
+            price_a_cumulative: ONE,
+            price_b_cumulative: 0,
+        };
+        assert_eq!(twap(&start, &end).unwrap().price_a, ONE);
+    }
+
+    #[test]
+    fn test_observation_buffer() {
+        let mut buffer = ObservationBuffer::new(Pubkey::new_unique(), 255, observation(10));
+        assert_eq!(buffer.try_to_vec().unwrap().len(), ObservationBuffer::LEN);
+
+        // Observations must move forward in time
+        buffer.record(observation(10));
+        buffer.record(observation(5));
+        assert_eq!(buffer.len, 1);
+        assert_eq!(buffer.at_or_before(9), None);
+        assert_eq!(buffer.at_or_before(10), Some(&observation(10)));
+
+        // Once full, the oldest are overwritten
+        for timestamp in 1..=OBSERVATION_CAPACITY as i64 {
+            buffer.record(observation(10 + timestamp * 10));
+        }
+        assert_eq!(buffer.len as usize, OBSERVATION_CAPACITY);
+        assert_eq!(buffer.at_or_before(19), None);
+        assert_eq!(buffer.at_or_before(25), Some(&observation(20)));
+        assert_eq!(
+            buffer.latest(),
+            Some(&observation(10 + OBSERVATION_CAPACITY as i64 * 10))
+        );
+    }
+}
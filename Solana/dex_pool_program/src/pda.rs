@@ -44,6 +44,27 @@ pub fn find_pool_address(
     )
 }
 
+/// Seed of the global protocol config PDA
+pub const CONFIG_SEED: &[u8] = b"config";
+
+/// Get the protocol config PDA and bump seed
+pub fn find_config_address(program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[CONFIG_SEED], program_id)
+}
+
+/// Seed of a pool's oracle PDA, followed by the pool address
+pub const ORACLE_SEED: &[u8] = b"oracle";
+
+/// Get a pool's oracle PDA and bump seed
+pub fn find_oracle_address(program_id: &Pubkey, pool: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[ORACLE_SEED, pool.as_ref()], program_id)
+}
+
+/// Get the ProgramData account the upgradeable loader keeps for a program
+pub fn find_program_data_address(program_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[program_id.as_ref()], &bpf_loader_upgradeable::id()).0
+}
+
 /// Get the pool seeds with bump for signing
 pub fn get_pool_seeds<'a>(
     mint_a: &'a Pubkey,
@@ -156,6 +177,37 @@ pub fn validate_token_account_basic(
     account_info: &AccountInfo,
     expected_owner: &Pubkey,
     expected_mint: &Pubkey,
+) -> Result<TokenAccount, ProgramError> {
+    let token_account_data = unpack_initialized_token_account(account_info)?;
+
+    // Check owner field inside the token account data
+    if &token_account_data.owner != expected_owner {
+        msg!(
+            "Token Account Error: Account {} owner {} does not match expected owner {}",
+            account_info.key,
+            token_account_data.owner,
+            expected_owner
+        );
+        return Err(PoolError::InvalidAccountData.into());
+    }
+
+    check_token_account_mint(account_info, &token_account_data, expected_mint)?;
+    Ok(token_account_data)
+}
+
+/// Validates an SPL Token account whatever its internal owner, e.g. a third-party recipient.
+/// Checks: Token Program owner, Initialized, Mint.
+pub fn validate_token_account_mint(
+    account_info: &AccountInfo,
+    expected_mint: &Pubkey,
+) -> Result<TokenAccount, ProgramError> {
+    let token_account_data = unpack_initialized_token_account(account_info)?;
+    check_token_account_mint(account_info, &token_account_data, expected_mint)?;
+    Ok(token_account_data)
+}
+
+fn unpack_initialized_token_account(
+    account_info: &AccountInfo,
 ) -> Result<TokenAccount, ProgramError> {
     // Check ownership by Token Program
     if account_info.owner != &TOKEN_PROGRAM_ID {
@@ -180,19 +232,14 @@ pub fn validate_token_account_basic(
         );
         return Err(PoolError::InvalidAccountData.into());
     }
+    Ok(token_account_data)
+}
 
-    // Check owner field inside the token account data
-    if &token_account_data.owner != expected_owner {
-        msg!(
-            "Token Account Error: Account {} owner {} does not match expected owner {}",
-            account_info.key,
-            token_account_data.owner,
-            expected_owner
-        );
-        return Err(PoolError::InvalidAccountData.into());
-    }
-
-    // Check mint
+fn check_token_account_mint(
+    account_info: &AccountInfo,
+    token_account_data: &TokenAccount,
+    expected_mint: &Pubkey,
+) -> Result<(), ProgramError> {
     if &token_account_data.mint != expected_mint {
         msg!(
             "Token Account Error: Account {} mint {} does not match expected mint {}",
@@ -202,8 +249,7 @@ pub fn validate_token_account_basic(
         );
         return Err(PoolError::TokenMintMismatch.into());
     }
-
-    Ok(token_account_data)
+    Ok(())
 }
 
 /// Validates basic properties of an SPL Mint account.
@@ -290,6 +336,45 @@ pub fn validate_program_id(
     }
 }
 
+/// Validates that `authority` is the upgrade authority recorded in the program's ProgramData.
+/// Checks: ProgramData derivation, Upgradeable Loader owner, Authority.
+pub fn validate_upgrade_authority(
+    program_id: &Pubkey,
+    program_data_info: &AccountInfo,
+    authority: &Pubkey,
+) -> Result<(), ProgramError> {
+    if program_data_info.key != &find_program_data_address(program_id)
+        || program_data_info.owner != &bpf_loader_upgradeable::id()
+    {
+        msg!(
+            "ProgramData Error: {} is not the ProgramData of {}",
+            program_data_info.key,
+            program_id
+        );
+        return Err(PoolError::IncorrectProgramData.into());
+    }
+
+    // Bincode `UpgradeableLoaderState::ProgramData { slot, upgrade_authority_address }`:
+    // u32 variant tag (3), u64 slot, then the `Option<Pubkey>` tag and key
+    let data = program_data_info.try_borrow_data()?;
+    if data.len() < 45 || data[..4] != 3u32.to_le_bytes() {
+        msg!(
+            "ProgramData Error: {} is not a ProgramData account",
+            program_data_info.key
+        );
+        return Err(PoolError::IncorrectProgramData.into());
+    }
+    if data[12] != 1 || &data[13..45] != authority.as_ref() {
+        msg!(
+            "ProgramData Error: {} is not the upgrade authority",
+            authority
+        );
+        return Err(PoolError::NotUpgradeAuthority.into());
+    }
+
+    Ok(())
+}
+
 /// Validates that the provided account is executable and owned by a BPF loader.
 pub fn validate_executable(account_info: &AccountInfo) -> Result<(), ProgramError> {
     if !account_info.executable {
@@ -1,42 +1,31 @@
 use borsh::{BorshDeserialize, BorshSerialize};
+use dex_plugin_interface::{instruction as plugin_instruction, result::PluginCalcResult};
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
     entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
     msg,
-    program::{invoke, invoke_signed},
+    program::{get_return_data, invoke, invoke_signed, set_return_data},
+    program_error::ProgramError,
     program_pack::Pack,
     pubkey::Pubkey,
     system_instruction,
-    sysvar::{rent::Rent, Sysvar},
+    sysvar::{clock::Clock, rent::Rent, Sysvar},
 };
 use spl_token::state::Account as TokenAccount;
 
 use crate::error::PoolError;
-use crate::instruction::PoolInstruction;
+use crate::fee::{calculate_protocol_fee_shares, MAX_BPS};
+use crate::instruction::{PoolInstruction, MAX_ROUTE_HOPS};
+use crate::oracle::{self, Observation, ObservationBuffer};
 use crate::pda::{
-    find_pool_address, validate_executable, validate_lp_mint_properties,
-    validate_lp_mint_zero_supply, validate_mint_basic, validate_pool_vault, validate_program_id,
-    validate_rent_exemption, validate_token_account_basic,
+    find_config_address, find_oracle_address, find_pool_address, get_pool_seeds,
+    validate_executable, validate_lp_mint_properties, validate_lp_mint_zero_supply,
+    validate_mint_basic, validate_pool_vault, validate_program_id, validate_rent_exemption,
+    validate_token_account_basic, validate_token_account_mint, validate_upgrade_authority,
+    CONFIG_SEED, ORACLE_SEED,
 };
-use crate::state::PoolState;
-
-/// For plugin <-> pool communication
-/// We'll reuse a struct for reading plugin's computed results.
-#[derive(BorshDeserialize, BorshSerialize, Debug, Default)]
-pub struct PluginCalcResult {
-    /// Actual amount of token A deposited/withdrawn (relevant for Add/Remove Liquidity)
-    pub actual_a: u64,
-    /// Actual amount of token B deposited/withdrawn (relevant for Add/Remove Liquidity)
-    pub actual_b: u64,
-    /// Number of LP shares minted (relevant for Add Liquidity)
-    pub shares_to_mint: u64,
-    /// Amount of token A withdrawn (relevant for Remove Liquidity)
-    pub withdraw_a: u64,
-    /// Amount of token B withdrawn (relevant for Remove Liquidity)
-    pub withdraw_b: u64,
-    /// Amount of output token calculated (relevant for Swap)
-    pub amount_out: u64,
-}
+use crate::state::{PoolState, ProtocolConfig, MINIMUM_LIQUIDITY};
 
 /// Processes instructions for the Pool program.
 pub struct Processor;
@@ -47,20 +36,113 @@ impl Processor {
         accounts: &[AccountInfo],
         instr_data: &[u8],
     ) -> ProgramResult {
-        let instruction = PoolInstruction::try_from_slice(instr_data)
-            .map_err(|_| PoolError::InvalidInstructionData)?;
+        let instruction = PoolInstruction::unpack(instr_data)?;
+        if let Some(deadline) = instruction.deadline() {
+            if deadline.has_passed(&Clock::get()?) {
+                msg!("Deadline {:?} has passed", deadline);
+                return Err(PoolError::DeadlineExceeded.into());
+            }
+        }
 
         match instruction {
             PoolInstruction::InitializePool => Self::process_initialize_pool(program_id, accounts),
-            PoolInstruction::AddLiquidity { amount_a, amount_b } => {
-                Self::process_add_liquidity(program_id, accounts, amount_a, amount_b)
-            }
-            PoolInstruction::RemoveLiquidity { amount_lp } => {
-                Self::process_remove_liquidity(program_id, accounts, amount_lp)
+            PoolInstruction::AddLiquidity {
+                amount_a,
+                amount_b,
+                min_shares_out,
+                ..
+            } => Self::process_add_liquidity(
+                program_id,
+                accounts,
+                amount_a,
+                amount_b,
+                min_shares_out.unwrap_or(0),
+            ),
+            PoolInstruction::RemoveLiquidity {
+                amount_lp,
+                min_a,
+                min_b,
+                ..
+            } => Self::process_remove_liquidity(
+                program_id,
+                accounts,
+                amount_lp,
+                (min_a.unwrap_or(0), min_b.unwrap_or(0)),
+                Recipient::Signer,
+            ),
+            PoolInstruction::RemoveLiquidityTo {
+                amount_lp,
+                min_a,
+                min_b,
+                ..
+            } => Self::process_remove_liquidity(
+                program_id,
+                accounts,
+                amount_lp,
+                (min_a.unwrap_or(0), min_b.unwrap_or(0)),
+                Recipient::Any,
+            ),
+            PoolInstruction::Swap {
+                amount_in, min_out, ..
+            } => Self::process_swap(
+                program_id,
+                accounts,
+                SwapAmount::ExactIn { amount_in, min_out },
+                Recipient::Signer,
+            ),
+            PoolInstruction::SwapTo {
+                amount_in, min_out, ..
+            } => Self::process_swap(
+                program_id,
+                accounts,
+                SwapAmount::ExactIn { amount_in, min_out },
+                Recipient::Any,
+            ),
+            PoolInstruction::SwapExactOut {
+                amount_out, max_in, ..
+            } => Self::process_swap(
+                program_id,
+                accounts,
+                SwapAmount::ExactOut { amount_out, max_in },
+                Recipient::Signer,
+            ),
+            PoolInstruction::SwapRoute {
+                amount_in,
+                min_final_out,
+                hops,
+                ..
+            } => Self::process_swap_route(program_id, accounts, amount_in, min_final_out, hops),
+            PoolInstruction::InitializeConfig {
+                treasury,
+                protocol_fee_share_bps,
+            } => Self::process_initialize_config(
+                program_id,
+                accounts,
+                treasury,
+                protocol_fee_share_bps,
+            ),
+            PoolInstruction::SetProtocolFee {
+                treasury,
+                protocol_fee_share_bps,
+            } => Self::process_set_protocol_fee(
+                program_id,
+                accounts,
+                treasury,
+                protocol_fee_share_bps,
+            ),
+            PoolInstruction::Sync => Self::process_sync(program_id, accounts),
+            PoolInstruction::Skim => Self::process_skim(program_id, accounts),
+            PoolInstruction::InitializeOracle => {
+                Self::process_initialize_oracle(program_id, accounts)
             }
-            PoolInstruction::Swap { amount_in, min_out } => {
-                Self::process_swap(program_id, accounts, amount_in, min_out)
+            PoolInstruction::ObservePrice { seconds_ago } => {
+                Self::process_observe_price(program_id, accounts, seconds_ago)
             }
+            PoolInstruction::FlashSwap {
+                amount_out,
+                callback_data,
+                ..
+            } => Self::process_flash_swap(program_id, accounts, amount_out, callback_data),
         }
     }
 
@@ -82,29 +164,47 @@ impl Processor {
 
         // --- Initial Validations ---
         msg!("Pool Init: Validating accounts...");
-        // 0. Payer must sign
+        // 1. Payer must sign
         if !payer_acc.is_signer {
             msg!("Payer did not sign");
             return Err(PoolError::MissingRequiredSignature.into());
         }
 
-        // 9. System Program ID
+        // 2. System Program ID
         validate_program_id(system_acc, &solana_program::system_program::id())?;
 
-        // 10. Rent Sysvar ID
+        // 3. Rent Sysvar ID
         validate_program_id(rent_acc, &solana_program::sysvar::rent::id())?;
         let rent = Rent::from_account_info(rent_acc)?;
 
-        // 11. Token Program ID
+        // 4. Token Program ID
         validate_program_id(token_prog_acc, &spl_token::id())?;
 
-        // 7. Plugin Program Account (Executable? Owned by Loader?)
+        // 5. Plugin Program Account (Executable? Owned by Loader?)
         validate_executable(plugin_prog_acc)?;
 
-        // 8. Plugin State Account (Rent-exempt?)
+        // 6. Plugin State Account (Owned by the plugin? Rent-exempt?)
+        if plugin_state_acc.owner != plugin_prog_acc.key {
+            msg!("Plugin state account not owned by the plugin program");
+            return Err(PoolError::InvalidPluginState.into());
+        }
         validate_rent_exemption(plugin_state_acc, &rent)?;
 
-        // 5 & 6: Mint A & B must be different
+        // 7. Plugin must speak our interface version and recognise the state as initialized
+        msg!(
+            "Pool Init: Checking plugin interface version {}...",
+            dex_plugin_interface::INTERFACE_VERSION
+        );
+        invoke(
+            &plugin_instruction::check_interface_version(
+                plugin_prog_acc.key,
+                plugin_state_acc.key,
+                dex_plugin_interface::INTERFACE_VERSION,
+            ),
+            &[plugin_state_acc.clone(), plugin_prog_acc.clone()],
+        )?;
+
+        // 8. Mint A & B must be different
         if mint_a_acc.key == mint_b_acc.key {
             msg!("Mint A and Mint B cannot be the same");
             return Err(PoolError::MintsMustBeDifferent.into());
@@ -139,25 +239,25 @@ impl Processor {
 
         // --- Mint & Vault Validations (using PDA and Rent) ---
         msg!("Pool Init: Validating Mints and Vaults...");
-        // 5. Mint A (Basic Mint Checks + Rent)
+        // 9. Mint A (Basic Mint Checks + Rent)
         let _mint_a_data = validate_mint_basic(mint_a_acc)?;
         validate_rent_exemption(mint_a_acc, &rent)?;
 
-        // 6. Mint B (Basic Mint Checks + Rent)
+        // 10. Mint B (Basic Mint Checks + Rent)
         let _mint_b_data = validate_mint_basic(mint_b_acc)?;
         validate_rent_exemption(mint_b_acc, &rent)?;
 
-        // 4. LP Mint (Specific LP Mint Checks + Rent)
+        // 11. LP Mint (Specific LP Mint Checks + Rent)
         let lp_mint_data = validate_mint_basic(lp_mint_acc)?;
         validate_lp_mint_properties(&lp_mint_data, &expected_pool_pda)?;
         validate_lp_mint_zero_supply(&lp_mint_data)?;
         validate_rent_exemption(lp_mint_acc, &rent)?;
 
-        // 2. Vault A
+        // 12. Vault A
         validate_pool_vault(vault_a_acc, &expected_pool_pda, mint_a_acc.key)?;
         // Rent implicitly checked by ATA creation on client, not checked here
 
-        // 3. Vault B
+        // 13. Vault B
         validate_pool_vault(vault_b_acc, &expected_pool_pda, mint_b_acc.key)?;
         // Rent implicitly checked by ATA creation on client, not checked here
         msg!("Pool Init: All account validations passed.");
@@ -176,6 +276,13 @@ impl Processor {
             bump,
             plugin_program_id: *plugin_prog_acc.key,
             plugin_state_pubkey: *plugin_state_acc.key,
+            k_last: 0,
+            reserve_a: 0,
+            reserve_b: 0,
+            price_a_cumulative: 0,
+            price_b_cumulative: 0,
+            last_update_timestamp: Clock::get()?.unix_timestamp,
+            locked: false,
         };
         let pool_data_bytes = initial_pool_data.try_to_vec()?;
         let pool_space = pool_data_bytes.len(); // Use serialized length
@@ -222,11 +329,92 @@ impl Processor {
         Ok(())
     }
 
+    fn process_initialize_config(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        treasury: Pubkey,
+        protocol_fee_share_bps: u16,
+    ) -> ProgramResult {
+        msg!("Pool InitConfig: Processing");
+        let acc_iter = &mut accounts.iter();
+        let admin_acc = next_account_info(acc_iter)?; // 0
+        let config_acc = next_account_info(acc_iter)?; // 1
+        let system_acc = next_account_info(acc_iter)?; // 2
+        let program_data_acc = next_account_info(acc_iter)?; // 3
+
+        if !admin_acc.is_signer {
+            return Err(PoolError::MissingRequiredSignature.into());
+        }
+        validate_program_id(system_acc, &solana_program::system_program::id())?;
+        validate_upgrade_authority(program_id, program_data_acc, admin_acc.key)?;
+        if protocol_fee_share_bps > MAX_BPS {
+            return Err(PoolError::InvalidProtocolFee.into());
+        }
+
+        let (expected_config, bump) = find_config_address(program_id);
+        if &expected_config != config_acc.key {
+            return Err(PoolError::IncorrectConfigPDA.into());
+        }
+        if config_acc.owner == program_id {
+            return Err(PoolError::ConfigAlreadyInitialized.into());
+        }
+
+        let config = ProtocolConfig {
+            is_initialized: true,
+            admin: *admin_acc.key,
+            treasury,
+            protocol_fee_share_bps,
+            bump,
+        };
+        create_pda_account(
+            program_id,
+            admin_acc,
+            config_acc,
+            system_acc,
+            ProtocolConfig::LEN,
+            &[CONFIG_SEED, &[bump]],
+        )?;
+        config.serialize(&mut *config_acc.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    fn process_set_protocol_fee(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        treasury: Pubkey,
+        protocol_fee_share_bps: u16,
+    ) -> ProgramResult {
+        msg!("Pool SetProtocolFee: Processing");
+        let acc_iter = &mut accounts.iter();
+        let admin_acc = next_account_info(acc_iter)?; // 0
+        let config_acc = next_account_info(acc_iter)?; // 1
+
+        if !admin_acc.is_signer {
+            return Err(PoolError::MissingRequiredSignature.into());
+        }
+        if protocol_fee_share_bps > MAX_BPS {
+            return Err(PoolError::InvalidProtocolFee.into());
+        }
+        let mut config = load_protocol_config(program_id, config_acc)?
+            .ok_or(ProgramError::UninitializedAccount)?;
+        if &config.admin != admin_acc.key {
+            return Err(PoolError::Unauthorized.into());
+        }
+
+        config.treasury = treasury;
+        config.protocol_fee_share_bps = protocol_fee_share_bps;
+        config.serialize(&mut *config_acc.data.borrow_mut())?;
+
+        Ok(())
+    }
+
     fn process_add_liquidity(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
         amount_a: u64,
         amount_b: u64,
+        min_shares_out: u64,
     ) -> ProgramResult {
         msg!("Pool AddLiq: Processing");
         let acc_iter = &mut accounts.iter();
@@ -241,12 +429,17 @@ impl Processor {
         let token_prog_acc = next_account_info(acc_iter)?; // 8
         let plugin_prog_acc = next_account_info(acc_iter)?; // 9
         let plugin_state_acc = next_account_info(acc_iter)?; // 10
+        let config_acc = next_account_info(acc_iter)?; // 11
+        let treasury_lp_acc = next_optional_account(acc_iter, program_id); // 12 (fee on)
+        let oracle_acc = next_optional_account(acc_iter, program_id); // 13 (optional)
+        let locked_lp_acc = next_optional_account(acc_iter, program_id); // 14 (first deposit)
 
         // --- Load State & Basic Checks ---
         if !user_acc.is_signer {
             return Err(PoolError::MissingRequiredSignature.into());
         }
         let mut pool_data = PoolState::try_from_slice(&pool_state_acc.data.borrow())?;
+        check_unlocked(&pool_data)?;
         validate_program_id(token_prog_acc, &spl_token::id())?;
 
         // --- PDA Re-derivation & Pool State Check ---
@@ -293,11 +486,23 @@ impl Processor {
             validate_token_account_basic(user_lp_acc, user_acc.key, &pool_data.lp_mint)?;
         // Plugin accounts are implicitly checked by CPI
 
-        // --- Get Reserves (safe after validation) ---
-        let vault_a_data = TokenAccount::unpack(&vault_a_acc.data.borrow())?;
-        let vault_b_data = TokenAccount::unpack(&vault_b_acc.data.borrow())?;
-        let reserve_a = vault_a_data.amount;
-        let reserve_b = vault_b_data.amount;
+        // --- Get Reserves (tracked, ignoring tokens sent straight to the vaults) ---
+        let reserve_a = pool_data.reserve_a;
+        let reserve_b = pool_data.reserve_b;
+
+        // --- Protocol Fee (accrued since the last liquidity event) ---
+        let protocol_fee = ProtocolFee::load(
+            program_id,
+            config_acc,
+            treasury_lp_acc,
+            &pool_data,
+            reserve_a,
+            reserve_b,
+        )?;
+        let total_lp_supply = pool_data
+            .total_lp_supply
+            .checked_add(protocol_fee.shares())
+            .ok_or(PoolError::ArithmeticOverflow)?;
 
         // Log keys before CPI setup
         msg!("Pool->Plugin CPI Prep: Pool PDA: {}", pool_state_acc.key);
@@ -314,42 +519,59 @@ impl Processor {
             plugin_prog_acc.key
         );
 
-        // CPI to plugin -- Inlined
-        let ix_data =
-            constant_product_plugin::instruction::PluginInstruction::ComputeAddLiquidity {
-                reserve_a,
-                reserve_b,
-                deposit_a: amount_a, // Use original amount_a
-                deposit_b: amount_b, // Use original amount_b
-                total_lp_supply: pool_data.total_lp_supply,
-            }
-            .try_to_vec()?;
-        let ix = solana_program::instruction::Instruction {
-            program_id: pool_data.plugin_program_id,
-            accounts: vec![
-                // Mark as writable (implicit via accounts list), NOT signer (false)
-                solana_program::instruction::AccountMeta::new(*plugin_state_acc.key, false),
-            ],
-            data: ix_data,
-        };
+        // CPI to plugin
+        let ix = plugin_instruction::compute_add_liquidity(
+            &pool_data.plugin_program_id,
+            plugin_state_acc.key,
+            reserve_a,
+            reserve_b,
+            amount_a, // Use original amount_a
+            amount_b, // Use original amount_b
+            total_lp_supply,
+        );
         msg!("Pool: About to invoke plugin for AddLiquidity");
-        invoke(
-            &ix,
-            &[
-                plugin_prog_acc.clone(),
-                plugin_state_acc.clone(), // Writable passed here
-            ],
-        )?;
+        let plugin_calc = invoke_plugin(&ix, plugin_prog_acc, plugin_state_acc)?;
         msg!("Pool: Plugin invoke successful (returned Ok)");
 
-        // Read the plugin result from plugin_state
-        let plugin_calc = PluginCalcResult::deserialize(&mut &plugin_state_acc.data.borrow()[..])?;
         let actual_a = plugin_calc.actual_a;
         let actual_b = plugin_calc.actual_b;
-        let shares_to_mint = plugin_calc.shares_to_mint;
+        // The first deposit locks `MINIMUM_LIQUIDITY` of its shares for good
+        // in the pool PDA's own LP token account
+        let locked_lp_acc = if total_lp_supply == 0 {
+            let locked_lp_acc = locked_lp_acc.ok_or_else(|| {
+                msg!("The first deposit needs the pool's LP token account");
+                ProgramError::NotEnoughAccountKeys
+            })?;
+            validate_pool_vault(locked_lp_acc, &expected_pda, &pool_data.lp_mint)?;
+            Some(locked_lp_acc)
+        } else {
+            None
+        };
+        let locked_shares = if locked_lp_acc.is_some() {
+            MINIMUM_LIQUIDITY
+        } else {
+            0
+        };
+        if total_lp_supply == 0 && plugin_calc.shares_to_mint <= MINIMUM_LIQUIDITY {
+            msg!(
+                "Initial shares {} do not exceed the locked minimum {}",
+                plugin_calc.shares_to_mint,
+                MINIMUM_LIQUIDITY
+            );
+            return Err(PoolError::InsufficientInitialLiquidity.into());
+        }
+        let shares_to_mint = plugin_calc.shares_to_mint - locked_shares;
         if shares_to_mint == 0 {
             return Err(PoolError::ZeroAmount.into());
         }
+        if shares_to_mint < min_shares_out {
+            msg!(
+                "Shares to mint {} below minimum {}",
+                shares_to_mint,
+                min_shares_out
+            );
+            return Err(PoolError::SlippageLimitExceeded.into());
+        }
 
         // Transfer actual_a from user -> vaultA
         let transfer_a_ix = spl_token::instruction::transfer(
@@ -418,12 +640,40 @@ impl Processor {
             ],
             &[sign_seeds],
         )?;
+        if let Some(locked_lp_acc) = locked_lp_acc {
+            let lock_ix = spl_token::instruction::mint_to(
+                token_prog_acc.key,
+                &pool_data.lp_mint,
+                locked_lp_acc.key,
+                pool_state_acc.key,
+                &[],
+                locked_shares,
+            )?;
+            invoke_signed(
+                &lock_ix,
+                &[
+                    lp_mint_acc.clone(),
+                    locked_lp_acc.clone(),
+                    pool_state_acc.clone(),
+                    token_prog_acc.clone(),
+                ],
+                &[sign_seeds],
+            )?;
+        }
+        protocol_fee.mint(lp_mint_acc, pool_state_acc, token_prog_acc, sign_seeds)?;
 
+        update_oracle(program_id, pool_state_acc, &mut pool_data, oracle_acc)?;
         // Update total_lp_supply
-        pool_data.total_lp_supply = pool_data
-            .total_lp_supply
-            .checked_add(shares_to_mint)
+        pool_data.total_lp_supply = total_lp_supply
+            .checked_add(plugin_calc.shares_to_mint)
+            .ok_or(PoolError::ArithmeticOverflow)?;
+        pool_data.reserve_a = reserve_a
+            .checked_add(actual_a)
             .ok_or(PoolError::ArithmeticOverflow)?;
+        pool_data.reserve_b = reserve_b
+            .checked_add(actual_b)
+            .ok_or(PoolError::ArithmeticOverflow)?;
+        pool_data.k_last = protocol_fee.k_last(pool_data.reserve_a, pool_data.reserve_b);
         pool_data.serialize(&mut *pool_state_acc.data.borrow_mut())?;
 
         Ok(())
@@ -433,6 +683,8 @@ impl Processor {
         program_id: &Pubkey,
         accounts: &[AccountInfo],
         amount_lp: u64,
+        (min_a, min_b): (u64, u64),
+        recipient: Recipient,
     ) -> ProgramResult {
         msg!("Pool RemLiq: Processing");
         let acc_iter = &mut accounts.iter();
@@ -447,12 +699,16 @@ impl Processor {
         let token_prog_acc = next_account_info(acc_iter)?; // 8
         let plugin_prog_acc = next_account_info(acc_iter)?; // 9
         let plugin_state_acc = next_account_info(acc_iter)?; // 10
+        let config_acc = next_account_info(acc_iter)?; // 11
+        let treasury_lp_acc = next_optional_account(acc_iter, program_id); // 12 (fee on)
+        let oracle_acc = next_optional_account(acc_iter, program_id); // 13 (optional)
 
         // --- Load State & Basic Checks ---
         if !user_acc.is_signer {
             return Err(PoolError::MissingRequiredSignature.into());
         }
         let mut pool_data = PoolState::try_from_slice(&pool_state_acc.data.borrow())?;
+        check_unlocked(&pool_data)?;
         validate_program_id(token_prog_acc, &spl_token::id())?;
 
         // --- PDA Re-derivation & Pool State Check ---
@@ -500,10 +756,8 @@ impl Processor {
         validate_lp_mint_properties(&lp_mint_data, &expected_pda)?;
         // Note: We already check amount_lp <= total_lp_supply earlier
 
-        let _user_token_a_data =
-            validate_token_account_basic(user_token_a_acc, user_acc.key, &pool_data.token_mint_a)?;
-        let _user_token_b_data =
-            validate_token_account_basic(user_token_b_acc, user_acc.key, &pool_data.token_mint_b)?;
+        recipient.validate(user_token_a_acc, user_acc.key, &pool_data.token_mint_a)?;
+        recipient.validate(user_token_b_acc, user_acc.key, &pool_data.token_mint_b)?;
         let user_lp_data =
             validate_token_account_basic(user_lp_acc, user_acc.key, &pool_data.lp_mint)?;
         if user_lp_data.amount < amount_lp {
@@ -516,42 +770,49 @@ impl Processor {
         }
         // Plugin accounts are implicitly checked by CPI
 
-        // --- Get Reserves (safe after validation) ---
-        let vault_a_data = TokenAccount::unpack(&vault_a_acc.data.borrow())?;
-        let vault_b_data = TokenAccount::unpack(&vault_b_acc.data.borrow())?;
-        let reserve_a = vault_a_data.amount;
-        let reserve_b = vault_b_data.amount;
-
-        // plugin cpi -- Inlined
-        let ix_data =
-            constant_product_plugin::instruction::PluginInstruction::ComputeRemoveLiquidity {
-                reserve_a,
-                reserve_b,
-                total_lp_supply: pool_data.total_lp_supply,
-                lp_amount_burning: amount_lp,
-            }
-            .try_to_vec()?;
-        let ix = solana_program::instruction::Instruction {
-            program_id: pool_data.plugin_program_id,
-            accounts: vec![solana_program::instruction::AccountMeta::new(
-                *plugin_state_acc.key,
-                false,
-            )],
-            data: ix_data,
-        };
-        msg!("Pool: About to invoke plugin for RemoveLiquidity");
-        invoke(
-            &ix,
-            &[
-                plugin_prog_acc.clone(),
-                plugin_state_acc.clone(), // Writable passed here
-            ],
+        // --- Get Reserves (tracked, ignoring tokens sent straight to the vaults) ---
+        let reserve_a = pool_data.reserve_a;
+        let reserve_b = pool_data.reserve_b;
+
+        // --- Protocol Fee (accrued since the last liquidity event) ---
+        let protocol_fee = ProtocolFee::load(
+            program_id,
+            config_acc,
+            treasury_lp_acc,
+            &pool_data,
+            reserve_a,
+            reserve_b,
         )?;
+        let total_lp_supply = pool_data
+            .total_lp_supply
+            .checked_add(protocol_fee.shares())
+            .ok_or(PoolError::ArithmeticOverflow)?;
+
+        // plugin cpi
+        let ix = plugin_instruction::compute_remove_liquidity(
+            &pool_data.plugin_program_id,
+            plugin_state_acc.key,
+            reserve_a,
+            reserve_b,
+            total_lp_supply,
+            amount_lp,
+        );
+        msg!("Pool: About to invoke plugin for RemoveLiquidity");
+        let plugin_calc = invoke_plugin(&ix, plugin_prog_acc, plugin_state_acc)?;
         msg!("Pool: Plugin invoke successful (returned Ok)");
 
-        let plugin_calc = PluginCalcResult::deserialize(&mut &plugin_state_acc.data.borrow()[..])?;
         let withdraw_a = plugin_calc.withdraw_a;
         let withdraw_b = plugin_calc.withdraw_b;
+        if withdraw_a < min_a || withdraw_b < min_b {
+            msg!(
+                "Withdrawal {}/{} below minimum {}/{}",
+                withdraw_a,
+                withdraw_b,
+                min_a,
+                min_b
+            );
+            return Err(PoolError::SlippageLimitExceeded.into());
+        }
 
         // Burn user's LP - User must authorize this
         let burn_ix = spl_token::instruction::burn(
@@ -583,6 +844,7 @@ impl Processor {
             pool_data.plugin_state_pubkey.as_ref(),
             &[pool_data.bump],
         ];
+        protocol_fee.mint(lp_mint_acc, pool_state_acc, token_prog_acc, sign_seeds)?;
         let transfer_a_ix = spl_token::instruction::transfer(
             token_prog_acc.key,
             vault_a_acc.key,      // Source (Pool's vault)
@@ -622,11 +884,18 @@ impl Processor {
             &[sign_seeds],
         )?;
 
+        update_oracle(program_id, pool_state_acc, &mut pool_data, oracle_acc)?;
         // Update supply
-        pool_data.total_lp_supply = pool_data
-            .total_lp_supply
+        pool_data.total_lp_supply = total_lp_supply
             .checked_sub(amount_lp)
             .ok_or(PoolError::ArithmeticOverflow)?;
+        pool_data.reserve_a = reserve_a
+            .checked_sub(withdraw_a)
+            .ok_or(PoolError::ArithmeticOverflow)?;
+        pool_data.reserve_b = reserve_b
+            .checked_sub(withdraw_b)
+            .ok_or(PoolError::ArithmeticOverflow)?;
+        pool_data.k_last = protocol_fee.k_last(pool_data.reserve_a, pool_data.reserve_b);
         pool_data.serialize(&mut *pool_state_acc.data.borrow_mut())?;
 
         Ok(())
@@ -635,8 +904,8 @@ impl Processor {
     fn process_swap(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
-        amount_in: u64,
-        min_out: u64,
+        swap_amount: SwapAmount,
+        recipient: Recipient,
     ) -> ProgramResult {
         msg!("Pool Swap: Processing");
         let acc_iter = &mut accounts.iter();
@@ -649,14 +918,16 @@ impl Processor {
         let token_prog_acc = next_account_info(acc_iter)?; // 6
         let plugin_prog_acc = next_account_info(acc_iter)?; // 7
         let plugin_state_acc = next_account_info(acc_iter)?; // 8
+        let oracle_acc = acc_iter.next(); // 9 (optional)
 
         // --- Load State & Basic Checks ---
         if !user_acc.is_signer {
             return Err(PoolError::MissingRequiredSignature.into());
         }
-        let pool_data = PoolState::try_from_slice(&pool_state_acc.data.borrow())?;
+        let mut pool_data = PoolState::try_from_slice(&pool_state_acc.data.borrow())?;
+        check_unlocked(&pool_data)?;
         validate_program_id(token_prog_acc, &spl_token::id())?;
-        if amount_in == 0 {
+        if swap_amount.fixed_amount() == 0 {
             return Err(PoolError::ZeroAmount.into());
         }
         if user_src_acc.key == user_dst_acc.key {
@@ -698,26 +969,18 @@ impl Processor {
 
         // Validate user accounts and identify direction
         // Try validating src as Token A
-        let src_mint = if let Ok(user_src_data) =
+        let (src_mint, user_src_balance) = if let Ok(user_src_data) =
             validate_token_account_basic(user_src_acc, user_acc.key, &pool_data.token_mint_a)
         {
             // Source is Token A, Destination must be Token B
-            let _user_dst_data =
-                validate_token_account_basic(user_dst_acc, user_acc.key, &pool_data.token_mint_b)?;
-            if user_src_data.amount < amount_in {
-                return Err(PoolError::InsufficientFunds.into());
-            }
-            pool_data.token_mint_a
+            recipient.validate(user_dst_acc, user_acc.key, &pool_data.token_mint_b)?;
+            (pool_data.token_mint_a, user_src_data.amount)
         } else if let Ok(user_src_data) =
             validate_token_account_basic(user_src_acc, user_acc.key, &pool_data.token_mint_b)
         {
             // Source is Token B, Destination must be Token A
-            let _user_dst_data =
-                validate_token_account_basic(user_dst_acc, user_acc.key, &pool_data.token_mint_a)?;
-            if user_src_data.amount < amount_in {
-                return Err(PoolError::InsufficientFunds.into());
-            }
-            pool_data.token_mint_b
+            recipient.validate(user_dst_acc, user_acc.key, &pool_data.token_mint_a)?;
+            (pool_data.token_mint_b, user_src_data.amount)
         } else {
             // Source account matches neither mint A nor mint B, or fails basic validation
             msg!("Invalid user source token account or mint mismatch");
@@ -725,43 +988,53 @@ impl Processor {
         };
 
         // Identify reserve accounts based on src_mint
-        let (reserve_in_acc, reserve_out_acc) = if src_mint == pool_data.token_mint_a {
+        let a_to_b = src_mint == pool_data.token_mint_a;
+        let (reserve_in_acc, reserve_out_acc) = if a_to_b {
             (vault_a_acc, vault_b_acc)
         } else {
             (vault_b_acc, vault_a_acc)
         };
 
-        // --- Get Reserves (safe after validation) ---
-        let reserve_in_data = TokenAccount::unpack(&reserve_in_acc.data.borrow())?;
-        let reserve_out_data = TokenAccount::unpack(&reserve_out_acc.data.borrow())?;
-        let r_in = reserve_in_data.amount;
-        let r_out = reserve_out_data.amount;
-
-        // plugin cpi -- Inlined
-        let ix_data = constant_product_plugin::instruction::PluginInstruction::ComputeSwap {
-            reserve_in: r_in,
-            reserve_out: r_out,
-            amount_in,
-        }
-        .try_to_vec()?;
-        let ix = solana_program::instruction::Instruction {
-            program_id: pool_data.plugin_program_id,
-            accounts: vec![solana_program::instruction::AccountMeta::new(
-                *plugin_state_acc.key,
-                false,
-            )],
-            data: ix_data,
+        // --- Get Reserves (tracked, ignoring tokens sent straight to the vaults) ---
+        let (r_in, r_out) = swap_reserves(&pool_data, a_to_b);
+
+        // plugin cpi
+        let (amount_in, amount_out) = match swap_amount {
+            SwapAmount::ExactIn { amount_in, min_out } => {
+                let ix = plugin_instruction::compute_swap(
+                    &pool_data.plugin_program_id,
+                    plugin_state_acc.key,
+                    r_in,
+                    r_out,
+                    amount_in,
+                );
+                let plugin_calc = invoke_plugin(&ix, plugin_prog_acc, plugin_state_acc)?;
+                if plugin_calc.amount_out < min_out {
+                    return Err(PoolError::SlippageLimitExceeded.into());
+                }
+                (amount_in, plugin_calc.amount_out)
+            }
+            SwapAmount::ExactOut { amount_out, max_in } => {
+                let ix = plugin_instruction::compute_swap_exact_out(
+                    &pool_data.plugin_program_id,
+                    plugin_state_acc.key,
+                    r_in,
+                    r_out,
+                    amount_out,
+                );
+                let plugin_calc = invoke_plugin(&ix, plugin_prog_acc, plugin_state_acc)?;
+                if plugin_calc.amount_in > max_in {
+                    return Err(PoolError::SlippageLimitExceeded.into());
+                }
+                (plugin_calc.amount_in, amount_out)
+            }
         };
-        invoke(&ix, &[plugin_prog_acc.clone(), plugin_state_acc.clone()])?;
-
-        let plugin_calc = PluginCalcResult::deserialize(&mut &plugin_state_acc.data.borrow()[..])?;
-        let amount_out = plugin_calc.amount_out;
-        if amount_out < min_out {
-            return Err(PoolError::SlippageLimitExceeded.into());
-        }
-        if amount_out == 0 {
+        if amount_in == 0 || amount_out == 0 {
             return Err(PoolError::ZeroAmount.into());
         }
+        if user_src_balance < amount_in {
+            return Err(PoolError::InsufficientFunds.into());
+        }
 
         // Transfer in from user -> reserve_in
         let transfer_in_ix = spl_token::instruction::transfer(
@@ -814,8 +1087,922 @@ impl Processor {
             &[sign_seeds],
         )?;
 
+        update_oracle(program_id, pool_state_acc, &mut pool_data, oracle_acc)?;
+        record_swap(&mut pool_data, a_to_b, amount_in, amount_out)?;
+        pool_data.serialize(&mut *pool_state_acc.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    fn process_swap_route(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount_in: u64,
+        min_final_out: u64,
+        hops: u8,
+    ) -> ProgramResult {
+        msg!("Pool SwapRoute: Processing {} hops", hops);
+        let acc_iter = &mut accounts.iter();
+        let user_acc = next_account_info(acc_iter)?; // 0
+        let user_src_acc = next_account_info(acc_iter)?; // 1
+        let user_dst_acc = next_account_info(acc_iter)?; // 2
+        let token_prog_acc = next_account_info(acc_iter)?; // 3
+
+        // --- Basic Checks ---
+        if !user_acc.is_signer {
+            return Err(PoolError::MissingRequiredSignature.into());
+        }
+        validate_program_id(token_prog_acc, &spl_token::id())?;
+        if amount_in == 0 {
+            return Err(PoolError::ZeroAmount.into());
+        }
+        if hops == 0 || hops > MAX_ROUTE_HOPS {
+            msg!("Route must have between 1 and {} hops", MAX_ROUTE_HOPS);
+            return Err(PoolError::InvalidRoute.into());
+        }
+        if user_src_acc.key == user_dst_acc.key {
+            msg!("User source and destination accounts cannot be the same");
+            return Err(PoolError::InvalidArgument.into());
+        }
+
+        // --- Hop Accounts ---
+        let route = (0..hops)
+            .map(|_| RouteHop::load(program_id, acc_iter))
+            .collect::<Result<Vec<_>, _>>()?;
+        // Back-to-back hops through one pool would only swap straight back,
+        // paying the fee twice
+        if route
+            .windows(2)
+            .any(|pair| pair[0].pool_state_acc.key == pair[1].pool_state_acc.key)
+        {
+            msg!("Route goes through the same pool twice in a row");
+            return Err(PoolError::InvalidRoute.into());
+        }
+
+        // --- User Source ---
+        let src_mint = TokenAccount::unpack(&user_src_acc.data.borrow())?.mint;
+        let user_src_data = validate_token_account_basic(user_src_acc, user_acc.key, &src_mint)?;
+        if user_src_data.amount < amount_in {
+            return Err(PoolError::InsufficientFunds.into());
+        }
+
+        // --- Walk the Route ---
+        let now = Clock::get()?.unix_timestamp;
+        let mut mint_in = src_mint;
+        let mut amount = amount_in;
+        // Previous hop and the vault its output is paid from
+        let mut previous: Option<(&RouteHop, &AccountInfo)> = None;
+        for hop in &route {
+            let (vault_in_acc, vault_out_acc, mint_out) = hop.direction(&mint_in)?;
+            let a_to_b = mint_in == hop.pool_data.token_mint_a;
+            // Reloaded, as an earlier hop may have gone through the same pool
+            let mut pool_data = PoolState::try_from_slice(&hop.pool_state_acc.data.borrow())?;
+            let (r_in, r_out) = swap_reserves(&pool_data, a_to_b);
+            let ix = plugin_instruction::compute_swap(
+                &hop.pool_data.plugin_program_id,
+                hop.plugin_state_acc.key,
+                r_in,
+                r_out,
+                amount,
+            );
+            let amount_out =
+                invoke_plugin(&ix, hop.plugin_prog_acc, hop.plugin_state_acc)?.amount_out;
+            if amount_out == 0 {
+                return Err(PoolError::ZeroAmount.into());
+            }
+            oracle::accrue(&mut pool_data, now);
+            record_swap(&mut pool_data, a_to_b, amount, amount_out)?;
+            pool_data.serialize(&mut *hop.pool_state_acc.data.borrow_mut())?;
+
+            match previous {
+                // First hop: the user pays in
+                None => {
+                    let transfer_in_ix = spl_token::instruction::transfer(
+                        token_prog_acc.key,
+                        user_src_acc.key,
+                        vault_in_acc.key,
+                        user_acc.key,
+                        &[],
+                        amount,
+                    )?;
+                    invoke(
+                        &transfer_in_ix,
+                        &[
+                            user_src_acc.clone(),
+                            vault_in_acc.clone(),
+                            user_acc.clone(),
+                            token_prog_acc.clone(),
+                        ],
+                    )?;
+                }
+                // Later hops: the previous pool pays in
+                Some((previous_hop, previous_vault_out_acc)) => previous_hop.transfer_out(
+                    token_prog_acc,
+                    previous_vault_out_acc,
+                    vault_in_acc,
+                    amount,
+                )?,
+            }
+
+            msg!("Route hop: {} -> {} out", amount, amount_out);
+            previous = Some((hop, vault_out_acc));
+            mint_in = mint_out;
+            amount = amount_out;
+        }
+
+        // --- Final Output ---
+        if amount < min_final_out {
+            return Err(PoolError::SlippageLimitExceeded.into());
+        }
+        validate_token_account_basic(user_dst_acc, user_acc.key, &mint_in)?;
+        let (last_hop, last_vault_out_acc) = previous.ok_or(PoolError::InvalidRoute)?;
+        last_hop.transfer_out(token_prog_acc, last_vault_out_acc, user_dst_acc, amount)?;
+
+        Ok(())
+    }
+
+    fn process_sync(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        msg!("Pool Sync: Processing");
+        let acc_iter = &mut accounts.iter();
+        let pool_state_acc = next_account_info(acc_iter)?; // 0
+        let vault_a_acc = next_account_info(acc_iter)?; // 1
+        let vault_b_acc = next_account_info(acc_iter)?; // 2
+        let oracle_acc = acc_iter.next(); // 3 (optional)
+
+        let (mut pool_data, balance_a, balance_b) =
+            load_pool_vaults(program_id, pool_state_acc, vault_a_acc, vault_b_acc)?;
+        msg!(
+            "Reserves {}/{} synced to {}/{}",
+            pool_data.reserve_a,
+            pool_data.reserve_b,
+            balance_a,
+            balance_b
+        );
+        update_oracle(program_id, pool_state_acc, &mut pool_data, oracle_acc)?;
+        pool_data.reserve_a = balance_a;
+        pool_data.reserve_b = balance_b;
+        pool_data.serialize(&mut *pool_state_acc.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    fn process_skim(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        msg!("Pool Skim: Processing");
+        let acc_iter = &mut accounts.iter();
+        let pool_state_acc = next_account_info(acc_iter)?; // 0
+        let vault_a_acc = next_account_info(acc_iter)?; // 1
+        let vault_b_acc = next_account_info(acc_iter)?; // 2
+        let dst_a_acc = next_account_info(acc_iter)?; // 3
+        let dst_b_acc = next_account_info(acc_iter)?; // 4
+        let token_prog_acc = next_account_info(acc_iter)?; // 5
+
+        validate_program_id(token_prog_acc, &spl_token::id())?;
+        let (pool_data, balance_a, balance_b) =
+            load_pool_vaults(program_id, pool_state_acc, vault_a_acc, vault_b_acc)?;
+        validate_token_account_mint(dst_a_acc, &pool_data.token_mint_a)?;
+        validate_token_account_mint(dst_b_acc, &pool_data.token_mint_b)?;
+
+        let bump = [pool_data.bump];
+        let sign_seeds = get_pool_seeds(
+            &pool_data.token_mint_a,
+            &pool_data.token_mint_b,
+            &pool_data.plugin_program_id,
+            &pool_data.plugin_state_pubkey,
+            &bump,
+        );
+        for (vault_acc, dst_acc, surplus) in [
+            (
+                vault_a_acc,
+                dst_a_acc,
+                balance_a.saturating_sub(pool_data.reserve_a),
+            ),
+            (
+                vault_b_acc,
+                dst_b_acc,
+                balance_b.saturating_sub(pool_data.reserve_b),
+            ),
+        ] {
+            if surplus == 0 {
+                continue;
+            }
+            msg!("Skimming {} from vault {}", surplus, vault_acc.key);
+            let transfer_ix = spl_token::instruction::transfer(
+                token_prog_acc.key,
+                vault_acc.key,
+                dst_acc.key,
+                pool_state_acc.key,
+                &[],
+                surplus,
+            )?;
+            invoke_signed(
+                &transfer_ix,
+                &[
+                    vault_acc.clone(),
+                    dst_acc.clone(),
+                    pool_state_acc.clone(),
+                    token_prog_acc.clone(),
+                ],
+                &[&sign_seeds],
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn process_flash_swap(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount_out: u64,
+        callback_data: Vec<u8>,
+    ) -> ProgramResult {
+        msg!("Pool FlashSwap: Processing");
+        let acc_iter = &mut accounts.iter();
+        let user_acc = next_account_info(acc_iter)?; // 0
+        let pool_state_acc = next_account_info(acc_iter)?; // 1
+        let vault_a_acc = next_account_info(acc_iter)?; // 2
+        let vault_b_acc = next_account_info(acc_iter)?; // 3
+        let borrower_acc = next_account_info(acc_iter)?; // 4
+        let token_prog_acc = next_account_info(acc_iter)?; // 5
+        let plugin_prog_acc = next_account_info(acc_iter)?; // 6
+        let plugin_state_acc = next_account_info(acc_iter)?; // 7
+        let callback_prog_acc = next_account_info(acc_iter)?; // 8
+        let callback_accs = acc_iter.as_slice(); // 9.. (passed to the callback)
+
+        // --- Load State & Basic Checks ---
+        if !user_acc.is_signer {
+            return Err(PoolError::MissingRequiredSignature.into());
+        }
+        validate_program_id(token_prog_acc, &spl_token::id())?;
+        if amount_out == 0 {
+            return Err(PoolError::ZeroAmount.into());
+        }
+        let (mut pool_data, _balance_a, _balance_b) =
+            load_pool_vaults(program_id, pool_state_acc, vault_a_acc, vault_b_acc)?;
+        if plugin_prog_acc.key != &pool_data.plugin_program_id {
+            return Err(PoolError::PluginProgramIdMismatch.into());
+        }
+        if plugin_state_acc.key != &pool_data.plugin_state_pubkey {
+            return Err(PoolError::PluginStatePubkeyMismatch.into());
+        }
+
+        // --- Borrowed Token ---
+        let (vault_out_acc, reserve_out, a_out) =
+            if validate_token_account_mint(borrower_acc, &pool_data.token_mint_a).is_ok() {
+                (vault_a_acc, pool_data.reserve_a, true)
+            } else if validate_token_account_mint(borrower_acc, &pool_data.token_mint_b).is_ok() {
+                (vault_b_acc, pool_data.reserve_b, false)
+            } else {
+                msg!("Borrower token account matches neither pool mint");
+                return Err(PoolError::TokenMintMismatch.into());
+            };
+        if borrower_acc.key == vault_out_acc.key {
+            msg!("Borrower token account cannot be the pool's vault");
+            return Err(PoolError::InvalidArgument.into());
+        }
+        // The reserve can't be lent out in full
+        if amount_out >= reserve_out {
+            return Err(PoolError::InsufficientFunds.into());
+        }
+
+        // --- Lend ---
+        // Cumulative prices accrue at the reserves before the flash swap
+        update_oracle(program_id, pool_state_acc, &mut pool_data, None)?;
+        pool_data.locked = true;
+        pool_data.serialize(&mut *pool_state_acc.data.borrow_mut())?;
+
+        let bump = [pool_data.bump];
+        let sign_seeds = get_pool_seeds(
+            &pool_data.token_mint_a,
+            &pool_data.token_mint_b,
+            &pool_data.plugin_program_id,
+            &pool_data.plugin_state_pubkey,
+            &bump,
+        );
+        let transfer_out_ix = spl_token::instruction::transfer(
+            token_prog_acc.key,
+            vault_out_acc.key,
+            borrower_acc.key,
+            pool_state_acc.key,
+            &[],
+            amount_out,
+        )?;
+        invoke_signed(
+            &transfer_out_ix,
+            &[
+                vault_out_acc.clone(),
+                borrower_acc.clone(),
+                pool_state_acc.clone(),
+                token_prog_acc.clone(),
+            ],
+            &[&sign_seeds],
+        )?;
+
+        // --- Callback (the pool does not sign) ---
+        msg!(
+            "Lent {} from vault {}, calling back {}",
+            amount_out,
+            vault_out_acc.key,
+            callback_prog_acc.key
+        );
+        let callback_ix = Instruction {
+            program_id: *callback_prog_acc.key,
+            accounts: callback_accs
+                .iter()
+                .map(|acc| AccountMeta {
+                    pubkey: *acc.key,
+                    is_signer: acc.is_signer,
+                    is_writable: acc.is_writable,
+                })
+                .collect(),
+            data: callback_data,
+        };
+        let mut callback_infos = callback_accs.to_vec();
+        callback_infos.push(callback_prog_acc.clone());
+        invoke(&callback_ix, &callback_infos)?;
+
+        // --- Settle ---
+        let balance_a = TokenAccount::unpack(&vault_a_acc.data.borrow())?.amount;
+        let balance_b = TokenAccount::unpack(&vault_b_acc.data.borrow())?.amount;
+        let (amount_a_out, amount_b_out) = if a_out {
+            (amount_out, 0)
+        } else {
+            (0, amount_out)
+        };
+        // Whatever the vaults hold above the reserves left after lending was paid in
+        let amount_a_in = balance_a.saturating_sub(pool_data.reserve_a - amount_a_out);
+        let amount_b_in = balance_b.saturating_sub(pool_data.reserve_b - amount_b_out);
+        msg!(
+            "Paid back {}/{}, vault balances {}/{}",
+            amount_a_in,
+            amount_b_in,
+            balance_a,
+            balance_b
+        );
+        invoke(
+            &plugin_instruction::check_swap_invariant(
+                plugin_prog_acc.key,
+                plugin_state_acc.key,
+                pool_data.reserve_a,
+                pool_data.reserve_b,
+                balance_a,
+                balance_b,
+                amount_a_in,
+                amount_b_in,
+            ),
+            &[plugin_prog_acc.clone(), plugin_state_acc.clone()],
+        )?;
+
+        pool_data.reserve_a = balance_a;
+        pool_data.reserve_b = balance_b;
+        pool_data.locked = false;
+        pool_data.serialize(&mut *pool_state_acc.data.borrow_mut())?;
+
         Ok(())
     }
+
+    fn process_initialize_oracle(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        msg!("Pool InitOracle: Processing");
+        let acc_iter = &mut accounts.iter();
+        let payer_acc = next_account_info(acc_iter)?; // 0
+        let pool_state_acc = next_account_info(acc_iter)?; // 1
+        let oracle_acc = next_account_info(acc_iter)?; // 2
+        let system_acc = next_account_info(acc_iter)?; // 3
+
+        if !payer_acc.is_signer {
+            return Err(PoolError::MissingRequiredSignature.into());
+        }
+        validate_program_id(system_acc, &solana_program::system_program::id())?;
+        let pool_data = load_pool(program_id, pool_state_acc)?;
+
+        let (expected_oracle, bump) = find_oracle_address(program_id, pool_state_acc.key);
+        if &expected_oracle != oracle_acc.key {
+            return Err(PoolError::IncorrectOraclePDA.into());
+        }
+        if oracle_acc.owner == program_id {
+            return Err(PoolError::OracleAlreadyInitialized.into());
+        }
+
+        let now = Clock::get()?.unix_timestamp;
+        let buffer = ObservationBuffer::new(
+            *pool_state_acc.key,
+            bump,
+            Observation::of_pool(&pool_data, now),
+        );
+        let rent = Rent::get()?;
+        invoke_signed(
+            &system_instruction::create_account(
+                payer_acc.key,
+                oracle_acc.key,
+                rent.minimum_balance(ObservationBuffer::LEN),
+                ObservationBuffer::LEN as u64,
+                program_id,
+            ),
+            &[payer_acc.clone(), oracle_acc.clone(), system_acc.clone()],
+            &[&[ORACLE_SEED, pool_state_acc.key.as_ref(), &[bump]]],
+        )?;
+        buffer.serialize(&mut *oracle_acc.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    fn process_observe_price(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        seconds_ago: u32,
+    ) -> ProgramResult {
+        msg!("Pool ObservePrice: Processing");
+        let acc_iter = &mut accounts.iter();
+        let pool_state_acc = next_account_info(acc_iter)?; // 0
+        let oracle_acc = next_account_info(acc_iter)?; // 1
+
+        if seconds_ago == 0 {
+            msg!("Averaging window must be at least one second");
+            return Err(PoolError::InvalidArgument.into());
+        }
+        let pool_data = load_pool(program_id, pool_state_acc)?;
+        let buffer = load_oracle(program_id, pool_state_acc.key, oracle_acc)?;
+
+        let now = Clock::get()?.unix_timestamp;
+        let start = buffer
+            .at_or_before(now - seconds_ago as i64)
+            .ok_or(PoolError::InsufficientOracleHistory)?;
+        let price = oracle::twap(start, &Observation::of_pool(&pool_data, now))
+            .ok_or(PoolError::InsufficientOracleHistory)?;
+        msg!(
+            "TWAP over {}s: price_a {} price_b {} (UQ64.64)",
+            price.window_seconds,
+            price.price_a,
+            price.price_b
+        );
+        set_return_data(&price.try_to_vec()?);
+
+        Ok(())
+    }
+}
+
+/// Validated accounts of one pool along a `SwapRoute`.
+struct RouteHop<'a, 'b> {
+    pool_state_acc: &'b AccountInfo<'a>,
+    vault_a_acc: &'b AccountInfo<'a>,
+    vault_b_acc: &'b AccountInfo<'a>,
+    plugin_prog_acc: &'b AccountInfo<'a>,
+    plugin_state_acc: &'b AccountInfo<'a>,
+    pool_data: PoolState,
+}
+
+impl<'a, 'b> RouteHop<'a, 'b> {
+    /// Reads the next hop's accounts and checks them against its pool state,
+    /// as `Swap` does for a single pool.
+    fn load(
+        program_id: &Pubkey,
+        acc_iter: &mut std::slice::Iter<'b, AccountInfo<'a>>,
+    ) -> Result<Self, ProgramError> {
+        let pool_state_acc = next_account_info(acc_iter)?; // 0
+        let vault_a_acc = next_account_info(acc_iter)?; // 1
+        let vault_b_acc = next_account_info(acc_iter)?; // 2
+        let plugin_prog_acc = next_account_info(acc_iter)?; // 3
+        let plugin_state_acc = next_account_info(acc_iter)?; // 4
+
+        if pool_state_acc.owner != program_id {
+            return Err(PoolError::InvalidPoolStateOwner.into());
+        }
+        let pool_data = PoolState::try_from_slice(&pool_state_acc.data.borrow())?;
+        check_unlocked(&pool_data)?;
+        let (expected_pda, _bump) = find_pool_address(
+            program_id,
+            &pool_data.token_mint_a,
+            &pool_data.token_mint_b,
+            &pool_data.plugin_program_id,
+            &pool_data.plugin_state_pubkey,
+        );
+        if &expected_pda != pool_state_acc.key {
+            return Err(PoolError::IncorrectPoolPDA.into());
+        }
+        if vault_a_acc.key != &pool_data.vault_a || vault_b_acc.key != &pool_data.vault_b {
+            return Err(PoolError::VaultMismatch.into());
+        }
+        if plugin_prog_acc.key != &pool_data.plugin_program_id {
+            return Err(PoolError::PluginProgramIdMismatch.into());
+        }
+        if plugin_state_acc.key != &pool_data.plugin_state_pubkey {
+            return Err(PoolError::PluginStatePubkeyMismatch.into());
+        }
+        validate_pool_vault(vault_a_acc, &expected_pda, &pool_data.token_mint_a)?;
+        validate_pool_vault(vault_b_acc, &expected_pda, &pool_data.token_mint_b)?;
+
+        Ok(Self {
+            pool_state_acc,
+            vault_a_acc,
+            vault_b_acc,
+            plugin_prog_acc,
+            plugin_state_acc,
+            pool_data,
+        })
+    }
+
+    /// `(vault in, vault out, mint out)` when swapping `mint_in` through this pool.
+    fn direction(
+        &self,
+        mint_in: &Pubkey,
+    ) -> Result<(&'b AccountInfo<'a>, &'b AccountInfo<'a>, Pubkey), ProgramError> {
+        if mint_in == &self.pool_data.token_mint_a {
+            Ok((
+                self.vault_a_acc,
+                self.vault_b_acc,
+                self.pool_data.token_mint_b,
+            ))
+        } else if mint_in == &self.pool_data.token_mint_b {
+            Ok((
+                self.vault_b_acc,
+                self.vault_a_acc,
+                self.pool_data.token_mint_a,
+            ))
+        } else {
+            msg!(
+                "Route: mint {} is not part of pool {}",
+                mint_in,
+                self.pool_state_acc.key
+            );
+            Err(PoolError::TokenMintMismatch.into())
+        }
+    }
+
+    /// Transfers `amount` out of one of this pool's vaults (pool PDA signs).
+    fn transfer_out(
+        &self,
+        token_prog_acc: &AccountInfo<'a>,
+        vault_acc: &AccountInfo<'a>,
+        destination_acc: &AccountInfo<'a>,
+        amount: u64,
+    ) -> ProgramResult {
+        let bump = [self.pool_data.bump];
+        let sign_seeds = get_pool_seeds(
+            &self.pool_data.token_mint_a,
+            &self.pool_data.token_mint_b,
+            &self.pool_data.plugin_program_id,
+            &self.pool_data.plugin_state_pubkey,
+            &bump,
+        );
+        let transfer_ix = spl_token::instruction::transfer(
+            token_prog_acc.key,
+            vault_acc.key,
+            destination_acc.key,
+            self.pool_state_acc.key,
+            &[],
+            amount,
+        )?;
+        invoke_signed(
+            &transfer_ix,
+            &[
+                vault_acc.clone(),
+                destination_acc.clone(),
+                self.pool_state_acc.clone(),
+                token_prog_acc.clone(),
+            ],
+            &[&sign_seeds],
+        )
+    }
+}
+
+/// The side of a swap fixed by the user; the plugin computes the other one.
+enum SwapAmount {
+    /// Spend exactly `amount_in`, receive at least `min_out`
+    ExactIn { amount_in: u64, min_out: u64 },
+    /// Receive exactly `amount_out`, spend at most `max_in`
+    ExactOut { amount_out: u64, max_in: u64 },
+}
+
+impl SwapAmount {
+    fn fixed_amount(&self) -> u64 {
+        match self {
+            Self::ExactIn { amount_in, .. } => *amount_in,
+            Self::ExactOut { amount_out, .. } => *amount_out,
+        }
+    }
+}
+
+/// Who may own the token accounts receiving the output of a swap or withdrawal.
+#[derive(Clone, Copy)]
+enum Recipient {
+    /// The signer, as for `Swap` and `RemoveLiquidity`
+    Signer,
+    /// Anyone; only the mint is checked
+    Any,
+}
+
+impl Recipient {
+    fn validate(
+        self,
+        account: &AccountInfo,
+        signer: &Pubkey,
+        mint: &Pubkey,
+    ) -> Result<TokenAccount, ProgramError> {
+        match self {
+            Self::Signer => validate_token_account_basic(account, signer, mint),
+            Self::Any => validate_token_account_mint(account, mint),
+        }
+    }
+}
+
+/// Loads the protocol config PDA. Returns `None` while it has not been created.
+fn load_protocol_config(
+    program_id: &Pubkey,
+    config_acc: &AccountInfo,
+) -> Result<Option<ProtocolConfig>, ProgramError> {
+    let (expected_config, _bump) = find_config_address(program_id);
+    if &expected_config != config_acc.key {
+        return Err(PoolError::IncorrectConfigPDA.into());
+    }
+    if config_acc.owner != program_id {
+        return Ok(None);
+    }
+    let config = ProtocolConfig::try_from_slice(&config_acc.data.borrow())?;
+    Ok(config.is_initialized.then_some(config))
+}
+
+/// Creates a program-owned PDA with `space` bytes, funded by `payer`.
+/// Unlike `create_account`, this still works if someone sent lamports to the address first.
+fn create_pda_account<'a>(
+    program_id: &Pubkey,
+    payer: &AccountInfo<'a>,
+    account: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    space: usize,
+    seeds: &[&[u8]],
+) -> ProgramResult {
+    let required_lamports = Rent::get()?
+        .minimum_balance(space)
+        .saturating_sub(account.lamports());
+    if required_lamports > 0 {
+        invoke(
+            &system_instruction::transfer(payer.key, account.key, required_lamports),
+            &[payer.clone(), account.clone(), system_program.clone()],
+        )?;
+    }
+    invoke_signed(
+        &system_instruction::allocate(account.key, space as u64),
+        &[account.clone(), system_program.clone()],
+        &[seeds],
+    )?;
+    invoke_signed(
+        &system_instruction::assign(account.key, program_id),
+        &[account.clone(), system_program.clone()],
+        &[seeds],
+    )
+}
+
+/// How a liquidity event settles the protocol fee accrued since the pool's last one.
+enum ProtocolFee<'b, 'a> {
+    /// The fee is off: nothing accrues and `k_last` is cleared.
+    Off,
+    /// `shares` are minted to the treasury LP account and `k_last` is checkpointed.
+    On {
+        shares: u64,
+        treasury_lp_acc: &'b AccountInfo<'a>,
+    },
+}
+
+impl<'b, 'a> ProtocolFee<'b, 'a> {
+    /// Resolves the fee from the config and the treasury LP account.
+    /// The treasury LP account is only required while the fee is on.
+    fn load(
+        program_id: &Pubkey,
+        config_acc: &AccountInfo,
+        treasury_lp_acc: Option<&'b AccountInfo<'a>>,
+        pool_data: &PoolState,
+        reserve_a: u64,
+        reserve_b: u64,
+    ) -> Result<Self, ProgramError> {
+        let Some(config) = load_protocol_config(program_id, config_acc)?.filter(|c| c.fee_on())
+        else {
+            return Ok(Self::Off);
+        };
+        let Some(treasury_lp_acc) = treasury_lp_acc else {
+            msg!("The protocol fee is on, so the treasury LP account is required");
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+        validate_token_account_basic(treasury_lp_acc, &config.treasury, &pool_data.lp_mint)
+            .map_err(|_| PoolError::TreasuryMismatch)?;
+        let shares = calculate_protocol_fee_shares(
+            reserve_a,
+            reserve_b,
+            pool_data.k_last,
+            pool_data.total_lp_supply,
+            config.protocol_fee_share_bps,
+        )
+        .ok_or(PoolError::ArithmeticOverflow)?;
+        Ok(Self::On {
+            shares,
+            treasury_lp_acc,
+        })
+    }
+
+    /// LP shares minted to the treasury by this liquidity event.
+    fn shares(&self) -> u64 {
+        match self {
+            Self::On { shares, .. } => *shares,
+            Self::Off => 0,
+        }
+    }
+
+    /// Mints the protocol fee shares to the treasury LP account (pool PDA signs).
+    fn mint(
+        &self,
+        lp_mint_acc: &AccountInfo<'a>,
+        pool_state_acc: &AccountInfo<'a>,
+        token_prog_acc: &AccountInfo<'a>,
+        sign_seeds: &[&[u8]],
+    ) -> ProgramResult {
+        let Self::On {
+            shares,
+            treasury_lp_acc,
+        } = self
+        else {
+            return Ok(());
+        };
+        if *shares == 0 {
+            return Ok(());
+        }
+        msg!("Pool: Minting {} protocol fee shares", shares);
+        let mint_ix = spl_token::instruction::mint_to(
+            token_prog_acc.key,
+            lp_mint_acc.key,
+            treasury_lp_acc.key,
+            pool_state_acc.key,
+            &[],
+            *shares,
+        )?;
+        invoke_signed(
+            &mint_ix,
+            &[
+                lp_mint_acc.clone(),
+                (*treasury_lp_acc).clone(),
+                pool_state_acc.clone(),
+                token_prog_acc.clone(),
+            ],
+            &[sign_seeds],
+        )
+    }
+
+    /// `k_last` after a liquidity event: the new `reserve_a * reserve_b` while the fee is on,
+    /// else 0.
+    fn k_last(&self, reserve_a: u64, reserve_b: u64) -> u128 {
+        match self {
+            Self::Off => 0,
+            Self::On { .. } => reserve_a as u128 * reserve_b as u128,
+        }
+    }
+}
+
+/// Next optional trailing account. The program ID stands in for an omitted account
+/// that is followed by one that is passed.
+fn next_optional_account<'b, 'a>(
+    acc_iter: &mut std::slice::Iter<'b, AccountInfo<'a>>,
+    program_id: &Pubkey,
+) -> Option<&'b AccountInfo<'a>> {
+    acc_iter.next().filter(|acc| acc.key != program_id)
+}
+
+/// Tracked `(reserve_in, reserve_out)` for a swap paying in token A (`a_to_b`) or token B.
+fn swap_reserves(pool_data: &PoolState, a_to_b: bool) -> (u64, u64) {
+    if a_to_b {
+        (pool_data.reserve_a, pool_data.reserve_b)
+    } else {
+        (pool_data.reserve_b, pool_data.reserve_a)
+    }
+}
+
+/// Moves a swap's `amount_in` and `amount_out` into the tracked reserves.
+fn record_swap(
+    pool_data: &mut PoolState,
+    a_to_b: bool,
+    amount_in: u64,
+    amount_out: u64,
+) -> ProgramResult {
+    let (reserve_in, reserve_out) = if a_to_b {
+        (&mut pool_data.reserve_a, &mut pool_data.reserve_b)
+    } else {
+        (&mut pool_data.reserve_b, &mut pool_data.reserve_a)
+    };
+    *reserve_in = reserve_in
+        .checked_add(amount_in)
+        .ok_or(PoolError::ArithmeticOverflow)?;
+    *reserve_out = reserve_out
+        .checked_sub(amount_out)
+        .ok_or(PoolError::ArithmeticOverflow)?;
+    Ok(())
+}
+
+/// Loads a pool state account owned by this program at its expected PDA.
+fn load_pool(program_id: &Pubkey, pool_state_acc: &AccountInfo) -> Result<PoolState, ProgramError> {
+    if pool_state_acc.owner != program_id {
+        return Err(PoolError::InvalidPoolStateOwner.into());
+    }
+    let pool_data = PoolState::try_from_slice(&pool_state_acc.data.borrow())?;
+    let (expected_pda, _bump) = find_pool_address(
+        program_id,
+        &pool_data.token_mint_a,
+        &pool_data.token_mint_b,
+        &pool_data.plugin_program_id,
+        &pool_data.plugin_state_pubkey,
+    );
+    if &expected_pda != pool_state_acc.key {
+        return Err(PoolError::IncorrectPoolPDA.into());
+    }
+    Ok(pool_data)
+}
+
+/// Loads an unlocked pool for `Sync`, `Skim` or `FlashSwap` and checks its vaults.
+/// Returns the pool state and the vault balances.
+fn load_pool_vaults(
+    program_id: &Pubkey,
+    pool_state_acc: &AccountInfo,
+    vault_a_acc: &AccountInfo,
+    vault_b_acc: &AccountInfo,
+) -> Result<(PoolState, u64, u64), ProgramError> {
+    let pool_data = load_pool(program_id, pool_state_acc)?;
+    check_unlocked(&pool_data)?;
+    if vault_a_acc.key != &pool_data.vault_a || vault_b_acc.key != &pool_data.vault_b {
+        return Err(PoolError::VaultMismatch.into());
+    }
+    validate_pool_vault(vault_a_acc, pool_state_acc.key, &pool_data.token_mint_a)?;
+    validate_pool_vault(vault_b_acc, pool_state_acc.key, &pool_data.token_mint_b)?;
+    let balance_a = TokenAccount::unpack(&vault_a_acc.data.borrow())?.amount;
+    let balance_b = TokenAccount::unpack(&vault_b_acc.data.borrow())?.amount;
+    Ok((pool_data, balance_a, balance_b))
+}
+
+/// Rejects instructions on a pool whose flash swap callback is running.
+fn check_unlocked(pool_data: &PoolState) -> ProgramResult {
+    if pool_data.locked {
+        msg!("Pool is locked until its flash swap settles");
+        return Err(PoolError::PoolLocked.into());
+    }
+    Ok(())
+}
+
+/// Loads a pool's oracle account.
+fn load_oracle(
+    program_id: &Pubkey,
+    pool: &Pubkey,
+    oracle_acc: &AccountInfo,
+) -> Result<ObservationBuffer, ProgramError> {
+    let (expected_oracle, _bump) = find_oracle_address(program_id, pool);
+    if &expected_oracle != oracle_acc.key {
+        return Err(PoolError::IncorrectOraclePDA.into());
+    }
+    if oracle_acc.owner != program_id {
+        return Err(ProgramError::UninitializedAccount);
+    }
+    Ok(ObservationBuffer::try_from_slice(
+        &oracle_acc.data.borrow(),
+    )?)
+}
+
+/// Accrues the pool's cumulative prices up to now and, if its oracle account was passed,
+/// records an observation. Must run before the reserves change.
+fn update_oracle(
+    program_id: &Pubkey,
+    pool_state_acc: &AccountInfo,
+    pool_data: &mut PoolState,
+    oracle_acc: Option<&AccountInfo>,
+) -> ProgramResult {
+    let now = Clock::get()?.unix_timestamp;
+    oracle::accrue(pool_data, now);
+    let Some(oracle_acc) = oracle_acc else {
+        return Ok(());
+    };
+    let mut buffer = load_oracle(program_id, pool_state_acc.key, oracle_acc)?;
+    buffer.record(Observation::of_pool(pool_data, now));
+    buffer.serialize(&mut *oracle_acc.data.borrow_mut())?;
+    Ok(())
+}
+
+/// Invokes a plugin computation and decodes the `PluginCalcResult` it set as return data.
+/// Return data left by any program other than the plugin is rejected.
+fn invoke_plugin<'a>(
+    ix: &Instruction,
+    plugin_prog_acc: &AccountInfo<'a>,
+    plugin_state_acc: &AccountInfo<'a>,
+) -> Result<PluginCalcResult, ProgramError> {
+    invoke(ix, &[plugin_prog_acc.clone(), plugin_state_acc.clone()])?;
+
+    let (returning_program, data) = get_return_data().ok_or_else(|| {
+        msg!("Pool ERROR: Plugin did not set return data");
+        PoolError::PluginComputeFailed
+    })?;
+    if returning_program != ix.program_id {
+        msg!(
+            "Pool ERROR: Return data set by {}, expected plugin {}",
+            returning_program,
+            ix.program_id
+        );
+        return Err(PoolError::PluginComputeFailed.into());
+    }
+    PluginCalcResult::try_from_slice(&data).map_err(|_| {
+        msg!("Pool ERROR: Could not decode plugin return data");
+        PoolError::PluginComputeFailed.into()
+    })
 }
 
 /// Utility: sort two pubkeys consistently
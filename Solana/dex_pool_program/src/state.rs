@@ -1,14 +1,22 @@
 use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::pubkey::Pubkey;
 
+/// LP shares withheld from a pool's first deposit and minted to the pool PDA's own LP
+/// token account, which nothing can spend from. The supply can't return to zero, so a
+/// dust first deposit can't inflate the value of a share.
+pub const MINIMUM_LIQUIDITY: u64 = 1_000;
+
 /// The main state account for a liquidity pool.
 /// Convenient for retrieving pool information.
 ///
 /// It stores references to:
 /// - Mints for the two assets being pooled and the LP token mint.
 /// - Vaults (token accounts) that hold the pool's reserves of each asset.
+/// - The reserves the pool accounts for, which tokens sent straight to a vault don't change.
+/// - Cumulative prices for time-weighted averages.
+/// - A lock held while a flash swap's callback runs.
 /// - Information about the associated pricing plugin.
-/// - The total supply of LP tokens currently minted.
+/// - The total supply of LP shares, including those locked on the first deposit.
 /// - The bump seed used for the pool's PDA.
 #[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq)]
 #[repr(C)]
@@ -23,7 +31,8 @@ pub struct PoolState {
     pub vault_b: Pubkey,
     /// Mint address for the liquidity provider (LP) tokens.
     pub lp_mint: Pubkey,
-    /// The total amount of LP tokens currently minted.
+    /// The total amount of LP tokens currently minted, including the `MINIMUM_LIQUIDITY`
+    /// locked in the pool's own LP token account.
     pub total_lp_supply: u64,
     /// The bump seed used to derive the pool state's PDA.
     pub bump: u8,
@@ -33,4 +42,53 @@ pub struct PoolState {
     pub plugin_program_id: Pubkey,
     /// The account address of the plugin's specific state for this pool.
     pub plugin_state_pubkey: Pubkey,
+
+    /// `reserve_a * reserve_b` as of the last liquidity event while the protocol fee is on,
+    /// zero otherwise. Growth of `k` since then is the fee income the protocol takes a share of.
+    pub k_last: u128,
+
+    /// Token A the pool accounts for. Pricing reads this, not the vault balance, so a
+    /// donation to the vault has no effect until `Sync`.
+    pub reserve_a: u64,
+    /// Token B the pool accounts for, see `reserve_a`.
+    pub reserve_b: u64,
+
+    /// Sum of the price of token A in token B (UQ64.64) per second since the pool was
+    /// created, as of `last_update_timestamp`. Wraps on overflow, see `crate::oracle`.
+    pub price_a_cumulative: u128,
+    /// Same as `price_a_cumulative` for the price of token B in token A.
+    pub price_b_cumulative: u128,
+    /// `Clock::unix_timestamp` up to which the cumulative prices are accrued.
+    pub last_update_timestamp: i64,
+
+    /// Set while a `FlashSwap` callback runs; every instruction changing the pool's
+    /// reserves or vaults is rejected until the flash swap settles.
+    pub locked: bool,
+}
+
+/// Global protocol configuration, stored in the `[b"config"]` PDA.
+/// While it is uninitialized, or its share is zero, the protocol fee is off.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default, PartialEq)]
+#[repr(C)]
+pub struct ProtocolConfig {
+    /// Set once by `InitializeConfig`.
+    pub is_initialized: bool,
+    /// Authority allowed to change the protocol fee.
+    pub admin: Pubkey,
+    /// Wallet whose LP token accounts receive the protocol fee.
+    pub treasury: Pubkey,
+    /// Share of the swap fees credited to the treasury, in basis points.
+    pub protocol_fee_share_bps: u16,
+    /// The bump seed used to derive the config PDA.
+    pub bump: u8,
+}
+
+impl ProtocolConfig {
+    /// Serialized size of the config.
+    pub const LEN: usize = 1 + 32 + 32 + 2 + 1;
+
+    /// Whether liquidity events should mint the protocol fee.
+    pub fn fee_on(&self) -> bool {
+        self.is_initialized && self.protocol_fee_share_bps > 0
+    }
 }
@@ -0,0 +1,26 @@
+use core::fmt;
+
+/// Reasons a quote can fail. Each one corresponds to the plugin or pool rejecting the
+/// same instruction on-chain.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QuoteError {
+    /// The plugin rejects these inputs (empty reserves, zero shares, burning too much...)
+    InvalidInput,
+    /// An intermediate value overflowed
+    Overflow,
+    /// The input mint is neither of the pool mints
+    MintMismatch,
+}
+
+impl fmt::Display for QuoteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidInput => f.write_str("Invalid quote input"),
+            Self::Overflow => f.write_str("Arithmetic overflow"),
+            Self::MintMismatch => f.write_str("Mint is not part of the pool"),
+        }
+    }
+}
+
+#[cfg(feature = "pool-state")]
+impl std::error::Error for QuoteError {}
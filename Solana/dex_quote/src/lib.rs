@@ -0,0 +1,24 @@
+//! Off-chain quotes for the DEX pool and the constant product plugin.
+//!
+//! [`math`] reproduces the plugin computations bit for bit using only `core`, so it can be
+//! used from `no_std` clients. With the default `pool-state` feature, [`quote_swap`],
+//! [`quote_swap_exact_out`], [`quote_add_liquidity`] and [`quote_remove_liquidity`] quote
+//! directly from the decoded pool and plugin accounts.
+//!
+//! None of the on-chain programs depend on this crate.
+#![cfg_attr(not(feature = "pool-state"), no_std)]
+
+pub mod error;
+pub mod math;
+
+#[cfg(feature = "pool-state")]
+mod pool;
+
+#[cfg(feature = "pool-state")]
+pub use pool::{
+    quote_add_liquidity, quote_protocol_fee_shares, quote_remove_liquidity, quote_swap,
+    quote_swap_exact_out,
+};
+
+#[cfg(all(test, feature = "pool-state"))]
+mod quote_tests;
@@ -0,0 +1,181 @@
+//! `core`-only mirror of the constant product plugin computations.
+//!
+//! Every function returns exactly what `constant_product_plugin::processor::Processor`
+//! computes for the same inputs, and fails wherever the plugin does.
+
+use crate::error::QuoteError;
+
+/// Amounts taken from the depositor and LP shares minted to them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AddLiquidityQuote {
+    pub actual_a: u64,
+    pub actual_b: u64,
+    pub shares_to_mint: u64,
+}
+
+/// Amounts paid out for burning LP shares.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RemoveLiquidityQuote {
+    pub withdraw_a: u64,
+    pub withdraw_b: u64,
+}
+
+/// Mirrors `Processor::calculate_add_liquidity`.
+pub fn add_liquidity(
+    reserve_a: u64,
+    reserve_b: u64,
+    deposit_a: u64,
+    deposit_b: u64,
+    total_lp_supply: u64,
+) -> Result<AddLiquidityQuote, QuoteError> {
+    if total_lp_supply == 0 {
+        // first deposit => geometric mean
+        let minted = integer_sqrt(deposit_a as u128 * deposit_b as u128);
+        if minted == 0 {
+            return Err(QuoteError::InvalidInput);
+        }
+        return Ok(AddLiquidityQuote {
+            actual_a: deposit_a,
+            actual_b: deposit_b,
+            shares_to_mint: minted as u64,
+        });
+    }
+
+    // ratio-limited
+    if reserve_a == 0 || reserve_b == 0 {
+        return Err(QuoteError::InvalidInput);
+    }
+    let req_b = deposit_a as u128 * reserve_b as u128 / reserve_a as u128;
+    let req_a = deposit_b as u128 * reserve_a as u128 / reserve_b as u128;
+    let mut actual_a = deposit_a;
+    let mut actual_b = deposit_b;
+    if req_b <= deposit_b as u128 {
+        actual_b = req_b as u64;
+    } else if req_a <= deposit_a as u128 {
+        actual_a = req_a as u64;
+    }
+    // The plugin truncates the share count to u64
+    let shares_to_mint = total_lp_supply as u128 * actual_a as u128 / reserve_a as u128;
+    if shares_to_mint == 0 {
+        return Err(QuoteError::InvalidInput);
+    }
+    Ok(AddLiquidityQuote {
+        actual_a,
+        actual_b,
+        shares_to_mint: shares_to_mint as u64,
+    })
+}
+
+/// Mirrors `Processor::calculate_remove_liquidity`.
+pub fn remove_liquidity(
+    reserve_a: u64,
+    reserve_b: u64,
+    total_lp_supply: u64,
+    lp_amount_burning: u64,
+) -> Result<RemoveLiquidityQuote, QuoteError> {
+    if lp_amount_burning == 0 || lp_amount_burning > total_lp_supply {
+        return Err(QuoteError::InvalidInput);
+    }
+    // Floor division leaves the dust in the pool
+    let burning = lp_amount_burning as u128;
+    let total = total_lp_supply as u128;
+    Ok(RemoveLiquidityQuote {
+        withdraw_a: (reserve_a as u128 * burning / total) as u64,
+        withdraw_b: (reserve_b as u128 * burning / total) as u64,
+    })
+}
+
+/// Mirrors `Processor::calculate_swap`: the fee is taken from the input, then the output is
+/// derived from the invariant with the new output reserve rounded up.
+pub fn swap_amount_out(
+    reserve_in: u64,
+    reserve_out: u64,
+    amount_in: u64,
+    fee_numerator: u64,
+    fee_denominator: u64,
+) -> Result<u64, QuoteError> {
+    if reserve_in == 0 || reserve_out == 0 {
+        return Err(QuoteError::InvalidInput);
+    }
+
+    let effective_in = (amount_in as u128)
+        .checked_mul(fee_denominator.saturating_sub(fee_numerator) as u128)
+        .and_then(|num| num.checked_div(fee_denominator as u128))
+        .unwrap_or(0);
+    if effective_in == 0 {
+        return Ok(0);
+    }
+
+    // u64 * u64 always fits in u128
+    let invariant = reserve_in as u128 * reserve_out as u128;
+    let new_reserve_in = (reserve_in as u128)
+        .checked_add(effective_in)
+        .ok_or(QuoteError::Overflow)?;
+    let new_reserve_out = ceil_div(invariant, new_reserve_in).ok_or(QuoteError::Overflow)?;
+    let amount_out = (reserve_out as u128)
+        .checked_sub(new_reserve_out)
+        .ok_or(QuoteError::Overflow)?;
+    u64::try_from(amount_out).map_err(|_| QuoteError::Overflow)
+}
+
+/// Mirrors `Processor::calculate_swap_exact_out`: the smallest input for which
+/// [`swap_amount_out`] returns at least `amount_out`.
+pub fn swap_amount_in(
+    reserve_in: u64,
+    reserve_out: u64,
+    amount_out: u64,
+    fee_numerator: u64,
+    fee_denominator: u64,
+) -> Result<u64, QuoteError> {
+    if reserve_in == 0 || amount_out == 0 || amount_out >= reserve_out {
+        return Err(QuoteError::InvalidInput);
+    }
+    let fee_complement = fee_denominator.saturating_sub(fee_numerator);
+    if fee_complement == 0 {
+        return Err(QuoteError::InvalidInput);
+    }
+
+    let invariant = reserve_in as u128 * reserve_out as u128;
+    let new_reserve_in = ceil_div_exact(invariant, (reserve_out - amount_out) as u128)
+        .ok_or(QuoteError::Overflow)?;
+    let effective_in = new_reserve_in - reserve_in as u128;
+    let amount_in = effective_in
+        .checked_mul(fee_denominator as u128)
+        .and_then(|num| ceil_div_exact(num, fee_complement as u128))
+        .ok_or(QuoteError::Overflow)?;
+    u64::try_from(amount_in).map_err(|_| QuoteError::Overflow)
+}
+
+/// Plain division rounding up, as used by the exact-out path.
+fn ceil_div_exact(dividend: u128, divisor: u128) -> Option<u128> {
+    let quotient = dividend.checked_div(divisor)?;
+    if dividend.checked_rem(divisor)? > 0 {
+        quotient.checked_add(1)
+    } else {
+        Some(quotient)
+    }
+}
+
+/// Quotient of `spl_math::checked_ceil_div::CheckedCeilDiv` for `u128`:
+/// rounded up, and `None` when the dividend is smaller than the divisor.
+fn ceil_div(dividend: u128, divisor: u128) -> Option<u128> {
+    let quotient = dividend.checked_div(divisor)?;
+    if quotient == 0 {
+        return None;
+    }
+    if dividend.checked_rem(divisor)? > 0 {
+        return quotient.checked_add(1);
+    }
+    Some(quotient)
+}
+
+/// Integer square root (floor), matching `spl_math::approximations::sqrt` used on chain.
+pub fn integer_sqrt(v: u128) -> u128 {
+    let mut x = v;
+    let mut z = (v >> 1) + 1;
+    while z < x {
+        x = z;
+        z = ((v / z) + z) >> 1;
+    }
+    x
+}
@@ -0,0 +1,125 @@
+//! Quotes from decoded pool accounts. Like the pool, they price against the reserves
+//! tracked in `PoolState`, not the vault balances.
+
+use constant_product_plugin::state::PluginState;
+use dex_pool_program::fee::calculate_protocol_fee_shares;
+use dex_pool_program::solana_program::pubkey::Pubkey;
+use dex_pool_program::state::{PoolState, ProtocolConfig, MINIMUM_LIQUIDITY};
+
+use crate::error::QuoteError;
+use crate::math::{self, AddLiquidityQuote, RemoveLiquidityQuote};
+
+/// Output of swapping `amount_in` of `input_mint` into the pool.
+pub fn quote_swap(
+    pool: &PoolState,
+    plugin_state: &PluginState,
+    input_mint: &Pubkey,
+    amount_in: u64,
+) -> Result<u64, QuoteError> {
+    let (reserve_in, reserve_out) = swap_reserves(pool, input_mint)?;
+    math::swap_amount_out(
+        reserve_in,
+        reserve_out,
+        amount_in,
+        plugin_state.fee_numerator,
+        plugin_state.fee_denominator,
+    )
+}
+
+/// Input needed to receive exactly `amount_out` for `input_mint`.
+pub fn quote_swap_exact_out(
+    pool: &PoolState,
+    plugin_state: &PluginState,
+    input_mint: &Pubkey,
+    amount_out: u64,
+) -> Result<u64, QuoteError> {
+    let (reserve_in, reserve_out) = swap_reserves(pool, input_mint)?;
+    math::swap_amount_in(
+        reserve_in,
+        reserve_out,
+        amount_out,
+        plugin_state.fee_numerator,
+        plugin_state.fee_denominator,
+    )
+}
+
+/// LP shares the treasury is minted ahead of the next liquidity event.
+/// `protocol_config` is the decoded config PDA, `None` when it was never initialized.
+pub fn quote_protocol_fee_shares(
+    pool: &PoolState,
+    protocol_config: Option<&ProtocolConfig>,
+) -> Result<u64, QuoteError> {
+    let Some(config) = protocol_config.filter(|c| c.fee_on()) else {
+        return Ok(0);
+    };
+    calculate_protocol_fee_shares(
+        pool.reserve_a,
+        pool.reserve_b,
+        pool.k_last,
+        pool.total_lp_supply,
+        config.protocol_fee_share_bps,
+    )
+    .ok_or(QuoteError::Overflow)
+}
+
+/// Amounts taken and LP shares minted for depositing up to `amount_a` / `amount_b`.
+/// The first deposit is minted its shares less the locked `MINIMUM_LIQUIDITY`.
+pub fn quote_add_liquidity(
+    pool: &PoolState,
+    protocol_config: Option<&ProtocolConfig>,
+    amount_a: u64,
+    amount_b: u64,
+) -> Result<AddLiquidityQuote, QuoteError> {
+    let total_lp_supply = total_lp_supply_with_fee(pool, protocol_config)?;
+    let mut quote = math::add_liquidity(
+        pool.reserve_a,
+        pool.reserve_b,
+        amount_a,
+        amount_b,
+        total_lp_supply,
+    )?;
+    if total_lp_supply == 0 {
+        quote.shares_to_mint = quote
+            .shares_to_mint
+            .checked_sub(MINIMUM_LIQUIDITY)
+            .filter(|shares| *shares > 0)
+            .ok_or(QuoteError::InvalidInput)?;
+    }
+    Ok(quote)
+}
+
+/// Amounts paid out for burning `amount_lp` shares.
+pub fn quote_remove_liquidity(
+    pool: &PoolState,
+    protocol_config: Option<&ProtocolConfig>,
+    amount_lp: u64,
+) -> Result<RemoveLiquidityQuote, QuoteError> {
+    // The pool checks the burn against the supply before the protocol fee is minted
+    if amount_lp > pool.total_lp_supply {
+        return Err(QuoteError::InvalidInput);
+    }
+    let total_lp_supply = total_lp_supply_with_fee(pool, protocol_config)?;
+    math::remove_liquidity(pool.reserve_a, pool.reserve_b, total_lp_supply, amount_lp)
+}
+
+/// The supply the pool hands to the plugin: current supply plus the pending protocol fee.
+fn total_lp_supply_with_fee(
+    pool: &PoolState,
+    protocol_config: Option<&ProtocolConfig>,
+) -> Result<u64, QuoteError> {
+    let fee_shares = quote_protocol_fee_shares(pool, protocol_config)?;
+    pool.total_lp_supply
+        .checked_add(fee_shares)
+        .ok_or(QuoteError::Overflow)
+}
+
+/// `(reserve_in, reserve_out)` for a swap paying in `input_mint`.
+fn swap_reserves(pool: &PoolState, input_mint: &Pubkey) -> Result<(u64, u64), QuoteError> {
+    if *input_mint == pool.token_mint_a {
+        Ok((pool.reserve_a, pool.reserve_b))
+    } else if *input_mint == pool.token_mint_b {
+        Ok((pool.reserve_b, pool.reserve_a))
+    } else {
+        Err(QuoteError::MintMismatch)
+    }
+}
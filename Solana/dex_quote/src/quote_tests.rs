@@ -0,0 +1,216 @@
+#[cfg(test)]
+mod tests {
+    use crate::error::QuoteError;
+    use crate::math::{self, AddLiquidityQuote, RemoveLiquidityQuote};
+    use crate::{quote_add_liquidity, quote_remove_liquidity, quote_swap};
+    use constant_product_plugin::processor::Processor;
+    use constant_product_plugin::state::PluginState;
+    use dex_pool_program::solana_program::pubkey::Pubkey;
+    use dex_pool_program::state::{PoolState, ProtocolConfig};
+    use proptest::prelude::*;
+
+    fn pool_state(total_lp_supply: u64, k_last: u128, reserves: (u64, u64)) -> PoolState {
+        PoolState {
+            token_mint_a: Pubkey::new_unique(),
+            token_mint_b: Pubkey::new_unique(),
+            vault_a: Pubkey::new_unique(),
+            vault_b: Pubkey::new_unique(),
+            lp_mint: Pubkey::new_unique(),
+            total_lp_supply,
+            bump: 255,
+            plugin_program_id: Pubkey::new_unique(),
+            plugin_state_pubkey: Pubkey::new_unique(),
+            k_last,
+            reserve_a: reserves.0,
+            reserve_b: reserves.1,
+            price_a_cumulative: 0,
+            price_b_cumulative: 0,
+            last_update_timestamp: 0,
+            locked: false,
+        }
+    }
+
+    /// Reserves and amounts across the whole range, biased towards the small values
+    /// where the rounding and error paths live.
+    fn amount() -> impl Strategy<Value = u64> {
+        prop_oneof![0u64..=1_000, 0u64..=u32::MAX as u64, any::<u64>()]
+    }
+
+    fn fee() -> impl Strategy<Value = (u64, u64)> {
+        prop_oneof![
+            Just((3, 1_000)),
+            Just((0, 1_000)),
+            (0u64..=10_000).prop_flat_map(|den| (0u64..=den + 1, Just(den))),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn swap_matches_plugin(
+            reserve_in in amount(),
+            reserve_out in amount(),
+            amount_in in amount(),
+            (fee_numerator, fee_denominator) in fee(),
+        ) {
+            let quote = math::swap_amount_out(
+                reserve_in, reserve_out, amount_in, fee_numerator, fee_denominator,
+            );
+            let plugin = Processor::calculate_swap(
+                reserve_in, reserve_out, amount_in, fee_numerator, fee_denominator,
+            );
+            match plugin {
+                Ok(result) => prop_assert_eq!(quote, Ok(result.amount_out)),
+                Err(_) => prop_assert!(quote.is_err()),
+            }
+        }
+
+        #[test]
+        fn swap_exact_out_matches_plugin(
+            reserve_in in amount(),
+            reserve_out in amount(),
+            amount_out in amount(),
+            (fee_numerator, fee_denominator) in fee(),
+        ) {
+            let quote = math::swap_amount_in(
+                reserve_in, reserve_out, amount_out, fee_numerator, fee_denominator,
+            );
+            let plugin = Processor::calculate_swap_exact_out(
+                reserve_in, reserve_out, amount_out, fee_numerator, fee_denominator,
+            );
+            match plugin {
+                Ok(result) => prop_assert_eq!(quote, Ok(result.amount_in)),
+                Err(_) => prop_assert!(quote.is_err()),
+            }
+        }
+
+        #[test]
+        fn add_liquidity_matches_plugin(
+            reserve_a in amount(),
+            reserve_b in amount(),
+            deposit_a in amount(),
+            deposit_b in amount(),
+            total_lp_supply in amount(),
+        ) {
+            let quote = math::add_liquidity(
+                reserve_a, reserve_b, deposit_a, deposit_b, total_lp_supply,
+            );
+            let plugin = Processor::calculate_add_liquidity(
+                reserve_a, reserve_b, deposit_a, deposit_b, total_lp_supply,
+            );
+            match plugin {
+                Ok(result) => prop_assert_eq!(quote, Ok(AddLiquidityQuote {
+                    actual_a: result.actual_a,
+                    actual_b: result.actual_b,
+                    shares_to_mint: result.shares_to_mint,
+                })),
+                Err(_) => prop_assert!(quote.is_err()),
+            }
+        }
+
+        #[test]
+        fn remove_liquidity_matches_plugin(
+            reserve_a in amount(),
+            reserve_b in amount(),
+            total_lp_supply in amount(),
+            lp_amount_burning in amount(),
+        ) {
+            let quote = math::remove_liquidity(
+                reserve_a, reserve_b, total_lp_supply, lp_amount_burning,
+            );
+            let plugin = Processor::calculate_remove_liquidity(
+                reserve_a, reserve_b, total_lp_supply, lp_amount_burning,
+            );
+            match plugin {
+                Ok(result) => prop_assert_eq!(quote, Ok(RemoveLiquidityQuote {
+                    withdraw_a: result.withdraw_a,
+                    withdraw_b: result.withdraw_b,
+                })),
+                Err(_) => prop_assert!(quote.is_err()),
+            }
+        }
+    }
+
+    #[test]
+    fn test_quote_swap_direction() {
+        let pool = pool_state(1_000, 0, (1_000, 4_000));
+        let plugin_state = PluginState {
+            is_initialized: true,
+            fee_numerator: 3,
+            fee_denominator: 1_000,
+        };
+
+        // A -> B and B -> A read the reserves in opposite order
+        let a_to_b = quote_swap(&pool, &plugin_state, &pool.token_mint_a, 100);
+        assert_eq!(a_to_b, math::swap_amount_out(1_000, 4_000, 100, 3, 1_000));
+        let b_to_a = quote_swap(&pool, &plugin_state, &pool.token_mint_b, 100);
+        assert_eq!(b_to_a, math::swap_amount_out(4_000, 1_000, 100, 3, 1_000));
+        assert_ne!(a_to_b, b_to_a);
+
+        assert_eq!(
+            quote_swap(&pool, &plugin_state, &Pubkey::new_unique(), 100),
+            Err(QuoteError::MintMismatch)
+        );
+    }
+
+    #[test]
+    fn test_quote_first_deposit_locks_minimum_liquidity() {
+        let pool = pool_state(0, 0, (0, 0));
+        assert_eq!(
+            quote_add_liquidity(&pool, None, 4_000, 1_000),
+            Ok(AddLiquidityQuote {
+                actual_a: 4_000,
+                actual_b: 1_000,
+                shares_to_mint: 1_000,
+            })
+        );
+        // Nothing left for the depositor once the minimum is locked
+        assert_eq!(
+            quote_add_liquidity(&pool, None, 1_000, 1_000),
+            Err(QuoteError::InvalidInput)
+        );
+    }
+
+    #[test]
+    fn test_quote_liquidity_with_protocol_fee() {
+        // √k grew from 1000 to 1100 since the last liquidity event
+        let pool = pool_state(1_000, 1_000_000, (1_100, 1_100));
+        let config = ProtocolConfig {
+            is_initialized: true,
+            admin: Pubkey::new_unique(),
+            treasury: Pubkey::new_unique(),
+            protocol_fee_share_bps: 5_000,
+            bump: 255,
+        };
+
+        // Fee off: the plugin sees the stored supply
+        assert_eq!(
+            quote_add_liquidity(&pool, None, 110, 110),
+            Ok(AddLiquidityQuote {
+                actual_a: 110,
+                actual_b: 110,
+                shares_to_mint: 100,
+            })
+        );
+        // Fee on: 47 treasury shares are minted first, diluting the deposit
+        assert_eq!(
+            quote_add_liquidity(&pool, Some(&config), 110, 110),
+            Ok(AddLiquidityQuote {
+                actual_a: 110,
+                actual_b: 110,
+                shares_to_mint: 104,
+            })
+        );
+        assert_eq!(
+            quote_remove_liquidity(&pool, Some(&config), 1_000),
+            Ok(RemoveLiquidityQuote {
+                withdraw_a: 1_050,
+                withdraw_b: 1_050,
+            })
+        );
+        // Burning more than the stored supply is rejected before the fee is minted
+        assert_eq!(
+            quote_remove_liquidity(&pool, Some(&config), 1_001),
+            Err(QuoteError::InvalidInput)
+        );
+    }
+}
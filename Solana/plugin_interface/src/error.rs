@@ -0,0 +1,24 @@
+use solana_program::program_error::ProgramError;
+use thiserror::Error;
+
+/// Errors shared by every plugin implementing the interface.
+#[derive(Error, Debug, Copy, Clone, PartialEq)]
+pub enum PluginInterfaceError {
+    /// Instruction data did not match any interface instruction.
+    #[error("Invalid plugin instruction")]
+    InvalidInstruction,
+
+    /// The caller speaks an interface version the plugin does not implement.
+    #[error("Unsupported plugin interface version")]
+    UnsupportedInterfaceVersion,
+
+    /// A flash swap left the pool's balances below what its invariant requires.
+    #[error("Swap invariant violated")]
+    SwapInvariantViolated,
+}
+
+impl From<PluginInterfaceError> for ProgramError {
+    fn from(e: PluginInterfaceError) -> Self {
+        ProgramError::Custom(e as u32)
+    }
+}
@@ -0,0 +1,479 @@
+#![allow(clippy::doc_lazy_continuation)]
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+use crate::error::PluginInterfaceError;
+
+/// Length of the discriminator prefixing every interface instruction.
+pub const DISCRIMINATOR_LEN: usize = 8;
+
+/// Instruction discriminators: first 8 bytes of `sha256("dopple-plugin-interface:<name>")`.
+/// Plugins are free to define their own instructions as long as they don't collide with these.
+pub mod discriminator {
+    use super::DISCRIMINATOR_LEN;
+
+    pub const COMPUTE_ADD_LIQUIDITY: [u8; DISCRIMINATOR_LEN] =
+        [133, 187, 57, 123, 245, 199, 20, 217];
+    pub const COMPUTE_REMOVE_LIQUIDITY: [u8; DISCRIMINATOR_LEN] =
+        [139, 2, 137, 147, 140, 189, 136, 150];
+    pub const COMPUTE_SWAP: [u8; DISCRIMINATOR_LEN] = [232, 8, 252, 113, 150, 112, 239, 118];
+    pub const CHECK_INTERFACE_VERSION: [u8; DISCRIMINATOR_LEN] =
+        [10, 103, 85, 254, 168, 109, 152, 124];
+    pub const COMPUTE_SWAP_EXACT_OUT: [u8; DISCRIMINATOR_LEN] =
+        [245, 72, 249, 85, 121, 45, 148, 65];
+    pub const CHECK_SWAP_INVARIANT: [u8; DISCRIMINATOR_LEN] = [68, 121, 119, 47, 164, 20, 165, 85];
+}
+
+/// Instructions every pool plugin must implement.
+/// `Compute*` instructions answer with a borsh-serialized [`crate::result::PluginCalcResult`]
+/// set as return data.
+///
+/// Encoding: `discriminator (8 bytes) || little-endian arguments`.
+#[derive(Debug, PartialEq)]
+pub enum PluginInstruction {
+    /// Computes the amounts actually deposited and the LP shares to mint.
+    ///
+    /// Accounts:
+    /// 0. [read] plugin state: The plugin state account of the pool
+    ComputeAddLiquidity {
+        reserve_a: u64,
+        reserve_b: u64,
+        deposit_a: u64,
+        deposit_b: u64,
+        total_lp_supply: u64,
+    },
+
+    /// Computes the amounts of token A and B returned for burning LP shares.
+    ///
+    /// Accounts:
+    /// 0. [read] plugin state: The plugin state account of the pool
+    ComputeRemoveLiquidity {
+        reserve_a: u64,
+        reserve_b: u64,
+        total_lp_supply: u64,
+        lp_amount_burning: u64,
+    },
+
+    /// Computes the output amount of a swap.
+    ///
+    /// Accounts:
+    /// 0. [read] plugin state: The plugin state account of the pool
+    ComputeSwap {
+        reserve_in: u64,
+        reserve_out: u64,
+        amount_in: u64,
+    },
+
+    /// Handshake sent by the pool when a pool is created.
+    /// Must fail with `UnsupportedInterfaceVersion` unless the plugin implements `version`,
+    /// and must fail unless the plugin state is an initialized state of this plugin.
+    ///
+    /// Accounts:
+    /// 0. [read] plugin state: The plugin state account of the new pool
+    CheckInterfaceVersion { version: u16 },
+
+    /// Computes the input amount needed to receive exactly `amount_out`, rounded up
+    /// in the pool's favour. The result carries both `amount_in` and `amount_out`.
+    ///
+    /// Accounts:
+    /// 0. [read] plugin state: The plugin state account of the pool
+    ComputeSwapExactOut {
+        reserve_in: u64,
+        reserve_out: u64,
+        amount_out: u64,
+    },
+
+    /// Checks the pool's balances after a flash swap against its reserves before it.
+    /// Must fail with `SwapInvariantViolated` unless the balances, net of the fee charged
+    /// on `amount_a_in` and `amount_b_in`, keep the plugin's invariant.
+    ///
+    /// Accounts:
+    /// 0. [read] plugin state: The plugin state account of the pool
+    CheckSwapInvariant {
+        reserve_a: u64,
+        reserve_b: u64,
+        balance_a: u64,
+        balance_b: u64,
+        amount_a_in: u64,
+        amount_b_in: u64,
+    },
+}
+
+impl PluginInstruction {
+    /// Serializes the instruction into its wire format.
+    pub fn pack(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(DISCRIMINATOR_LEN + 6 * 8);
+        match self {
+            Self::ComputeAddLiquidity {
+                reserve_a,
+                reserve_b,
+                deposit_a,
+                deposit_b,
+                total_lp_supply,
+            } => {
+                buf.extend_from_slice(&discriminator::COMPUTE_ADD_LIQUIDITY);
+                for v in [reserve_a, reserve_b, deposit_a, deposit_b, total_lp_supply] {
+                    buf.extend_from_slice(&v.to_le_bytes());
+                }
+            }
+            Self::ComputeRemoveLiquidity {
+                reserve_a,
+                reserve_b,
+                total_lp_supply,
+                lp_amount_burning,
+            } => {
+                buf.extend_from_slice(&discriminator::COMPUTE_REMOVE_LIQUIDITY);
+                for v in [reserve_a, reserve_b, total_lp_supply, lp_amount_burning] {
+                    buf.extend_from_slice(&v.to_le_bytes());
+                }
+            }
+            Self::ComputeSwap {
+                reserve_in,
+                reserve_out,
+                amount_in,
+            } => {
+                buf.extend_from_slice(&discriminator::COMPUTE_SWAP);
+                for v in [reserve_in, reserve_out, amount_in] {
+                    buf.extend_from_slice(&v.to_le_bytes());
+                }
+            }
+            Self::CheckInterfaceVersion { version } => {
+                buf.extend_from_slice(&discriminator::CHECK_INTERFACE_VERSION);
+                buf.extend_from_slice(&version.to_le_bytes());
+            }
+            Self::ComputeSwapExactOut {
+                reserve_in,
+                reserve_out,
+                amount_out,
+            } => {
+                buf.extend_from_slice(&discriminator::COMPUTE_SWAP_EXACT_OUT);
+                for v in [reserve_in, reserve_out, amount_out] {
+                    buf.extend_from_slice(&v.to_le_bytes());
+                }
+            }
+            Self::CheckSwapInvariant {
+                reserve_a,
+                reserve_b,
+                balance_a,
+                balance_b,
+                amount_a_in,
+                amount_b_in,
+            } => {
+                buf.extend_from_slice(&discriminator::CHECK_SWAP_INVARIANT);
+                for v in [
+                    reserve_a,
+                    reserve_b,
+                    balance_a,
+                    balance_b,
+                    amount_a_in,
+                    amount_b_in,
+                ] {
+                    buf.extend_from_slice(&v.to_le_bytes());
+                }
+            }
+        }
+        buf
+    }
+
+    /// Deserializes instruction data. Trailing bytes are rejected.
+    pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
+        if input.len() < DISCRIMINATOR_LEN {
+            return Err(PluginInterfaceError::InvalidInstruction.into());
+        }
+        let (disc, rest) = input.split_at(DISCRIMINATOR_LEN);
+        let rest = &mut &rest[..];
+        let instruction = match <[u8; DISCRIMINATOR_LEN]>::try_from(disc).unwrap() {
+            discriminator::COMPUTE_ADD_LIQUIDITY => Self::ComputeAddLiquidity {
+                reserve_a: unpack_u64(rest)?,
+                reserve_b: unpack_u64(rest)?,
+                deposit_a: unpack_u64(rest)?,
+                deposit_b: unpack_u64(rest)?,
+                total_lp_supply: unpack_u64(rest)?,
+            },
+            discriminator::COMPUTE_REMOVE_LIQUIDITY => Self::ComputeRemoveLiquidity {
+                reserve_a: unpack_u64(rest)?,
+                reserve_b: unpack_u64(rest)?,
+                total_lp_supply: unpack_u64(rest)?,
+                lp_amount_burning: unpack_u64(rest)?,
+            },
+            discriminator::COMPUTE_SWAP => Self::ComputeSwap {
+                reserve_in: unpack_u64(rest)?,
+                reserve_out: unpack_u64(rest)?,
+                amount_in: unpack_u64(rest)?,
+            },
+            discriminator::CHECK_INTERFACE_VERSION => Self::CheckInterfaceVersion {
+                version: unpack_u16(rest)?,
+            },
+            discriminator::COMPUTE_SWAP_EXACT_OUT => Self::ComputeSwapExactOut {
+                reserve_in: unpack_u64(rest)?,
+                reserve_out: unpack_u64(rest)?,
+                amount_out: unpack_u64(rest)?,
+            },
+            discriminator::CHECK_SWAP_INVARIANT => Self::CheckSwapInvariant {
+                reserve_a: unpack_u64(rest)?,
+                reserve_b: unpack_u64(rest)?,
+                balance_a: unpack_u64(rest)?,
+                balance_b: unpack_u64(rest)?,
+                amount_a_in: unpack_u64(rest)?,
+                amount_b_in: unpack_u64(rest)?,
+            },
+            _ => return Err(PluginInterfaceError::InvalidInstruction.into()),
+        };
+        if !rest.is_empty() {
+            return Err(PluginInterfaceError::InvalidInstruction.into());
+        }
+        Ok(instruction)
+    }
+}
+
+fn unpack_u64(input: &mut &[u8]) -> Result<u64, ProgramError> {
+    if input.len() < 8 {
+        return Err(PluginInterfaceError::InvalidInstruction.into());
+    }
+    let (bytes, rest) = input.split_at(8);
+    *input = rest;
+    Ok(u64::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+fn unpack_u16(input: &mut &[u8]) -> Result<u16, ProgramError> {
+    if input.len() < 2 {
+        return Err(PluginInterfaceError::InvalidInstruction.into());
+    }
+    let (bytes, rest) = input.split_at(2);
+    *input = rest;
+    Ok(u16::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+/// Builds a `ComputeAddLiquidity` instruction.
+pub fn compute_add_liquidity(
+    plugin_program_id: &Pubkey,
+    plugin_state: &Pubkey,
+    reserve_a: u64,
+    reserve_b: u64,
+    deposit_a: u64,
+    deposit_b: u64,
+    total_lp_supply: u64,
+) -> Instruction {
+    Instruction {
+        program_id: *plugin_program_id,
+        accounts: vec![AccountMeta::new_readonly(*plugin_state, false)],
+        data: PluginInstruction::ComputeAddLiquidity {
+            reserve_a,
+            reserve_b,
+            deposit_a,
+            deposit_b,
+            total_lp_supply,
+        }
+        .pack(),
+    }
+}
+
+/// Builds a `ComputeRemoveLiquidity` instruction.
+pub fn compute_remove_liquidity(
+    plugin_program_id: &Pubkey,
+    plugin_state: &Pubkey,
+    reserve_a: u64,
+    reserve_b: u64,
+    total_lp_supply: u64,
+    lp_amount_burning: u64,
+) -> Instruction {
+    Instruction {
+        program_id: *plugin_program_id,
+        accounts: vec![AccountMeta::new_readonly(*plugin_state, false)],
+        data: PluginInstruction::ComputeRemoveLiquidity {
+            reserve_a,
+            reserve_b,
+            total_lp_supply,
+            lp_amount_burning,
+        }
+        .pack(),
+    }
+}
+
+/// Builds a `ComputeSwap` instruction.
+pub fn compute_swap(
+    plugin_program_id: &Pubkey,
+    plugin_state: &Pubkey,
+    reserve_in: u64,
+    reserve_out: u64,
+    amount_in: u64,
+) -> Instruction {
+    Instruction {
+        program_id: *plugin_program_id,
+        accounts: vec![AccountMeta::new_readonly(*plugin_state, false)],
+        data: PluginInstruction::ComputeSwap {
+            reserve_in,
+            reserve_out,
+            amount_in,
+        }
+        .pack(),
+    }
+}
+
+/// Builds a `ComputeSwapExactOut` instruction.
+pub fn compute_swap_exact_out(
+    plugin_program_id: &Pubkey,
+    plugin_state: &Pubkey,
+    reserve_in: u64,
+    reserve_out: u64,
+    amount_out: u64,
+) -> Instruction {
+    Instruction {
+        program_id: *plugin_program_id,
+        accounts: vec![AccountMeta::new_readonly(*plugin_state, false)],
+        data: PluginInstruction::ComputeSwapExactOut {
+            reserve_in,
+            reserve_out,
+            amount_out,
+        }
+        .pack(),
+    }
+}
+
+/// Builds a `CheckSwapInvariant` instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn check_swap_invariant(
+    plugin_program_id: &Pubkey,
+    plugin_state: &Pubkey,
+    reserve_a: u64,
+    reserve_b: u64,
+    balance_a: u64,
+    balance_b: u64,
+    amount_a_in: u64,
+    amount_b_in: u64,
+) -> Instruction {
+    Instruction {
+        program_id: *plugin_program_id,
+        accounts: vec![AccountMeta::new_readonly(*plugin_state, false)],
+        data: PluginInstruction::CheckSwapInvariant {
+            reserve_a,
+            reserve_b,
+            balance_a,
+            balance_b,
+            amount_a_in,
+            amount_b_in,
+        }
+        .pack(),
+    }
+}
+
+/// Builds a `CheckInterfaceVersion` instruction.
+pub fn check_interface_version(
+    plugin_program_id: &Pubkey,
+    plugin_state: &Pubkey,
+    version: u16,
+) -> Instruction {
+    Instruction {
+        program_id: *plugin_program_id,
+        accounts: vec![AccountMeta::new_readonly(*plugin_state, false)],
+        data: PluginInstruction::CheckInterfaceVersion { version }.pack(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_program::hash::hash;
+
+    fn expected_discriminator(name: &str) -> [u8; DISCRIMINATOR_LEN] {
+        let preimage = format!("dopple-plugin-interface:{}", name);
+        hash(preimage.as_bytes()).to_bytes()[..DISCRIMINATOR_LEN]
+            .try_into()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_discriminators() {
+        assert_eq!(
+            discriminator::COMPUTE_ADD_LIQUIDITY,
+            expected_discriminator("compute_add_liquidity")
+        );
+        assert_eq!(
+            discriminator::COMPUTE_REMOVE_LIQUIDITY,
+            expected_discriminator("compute_remove_liquidity")
+        );
+        assert_eq!(
+            discriminator::COMPUTE_SWAP,
+            expected_discriminator("compute_swap")
+        );
+        assert_eq!(
+            discriminator::CHECK_INTERFACE_VERSION,
+            expected_discriminator("check_interface_version")
+        );
+        assert_eq!(
+            discriminator::COMPUTE_SWAP_EXACT_OUT,
+            expected_discriminator("compute_swap_exact_out")
+        );
+        assert_eq!(
+            discriminator::CHECK_SWAP_INVARIANT,
+            expected_discriminator("check_swap_invariant")
+        );
+    }
+
+    #[test]
+    fn test_pack_unpack_roundtrip() {
+        let instructions = [
+            PluginInstruction::ComputeAddLiquidity {
+                reserve_a: 1,
+                reserve_b: 2,
+                deposit_a: 3,
+                deposit_b: 4,
+                total_lp_supply: 5,
+            },
+            PluginInstruction::ComputeRemoveLiquidity {
+                reserve_a: u64::MAX,
+                reserve_b: 0,
+                total_lp_supply: 7,
+                lp_amount_burning: 8,
+            },
+            PluginInstruction::ComputeSwap {
+                reserve_in: 10,
+                reserve_out: 20,
+                amount_in: 30,
+            },
+            PluginInstruction::CheckInterfaceVersion { version: 1 },
+            PluginInstruction::ComputeSwapExactOut {
+                reserve_in: 40,
+                reserve_out: 50,
+                amount_out: 60,
+            },
+            PluginInstruction::CheckSwapInvariant {
+                reserve_a: 70,
+                reserve_b: 80,
+                balance_a: 90,
+                balance_b: u64::MAX,
+                amount_a_in: 0,
+                amount_b_in: 100,
+            },
+        ];
+        for ix in instructions {
+            let packed = ix.pack();
+            assert_eq!(PluginInstruction::unpack(&packed).unwrap(), ix);
+        }
+    }
+
+    #[test]
+    fn test_unpack_rejects_malformed_data() {
+        let err = Err(PluginInterfaceError::InvalidInstruction.into());
+        // Too short for a discriminator
+        assert_eq!(PluginInstruction::unpack(&[1, 2, 3]), err);
+        // Unknown discriminator
+        assert_eq!(PluginInstruction::unpack(&[0u8; 16]), err);
+        // Truncated arguments
+        let mut packed = PluginInstruction::ComputeSwap {
+            reserve_in: 1,
+            reserve_out: 2,
+            amount_in: 3,
+        }
+        .pack();
+        packed.pop();
+        assert_eq!(PluginInstruction::unpack(&packed), err);
+        // Trailing bytes
+        let mut packed = PluginInstruction::CheckInterfaceVersion { version: 1 }.pack();
+        packed.push(0);
+        assert_eq!(PluginInstruction::unpack(&packed), err);
+    }
+}
@@ -0,0 +1,18 @@
+//! Shared ABI between `dex_pool_program` and its pricing plugins.
+//!
+//! Any program that implements the instructions in [`instruction::PluginInstruction`]
+//! and answers with a [`result::PluginCalcResult`] can back a Dopple pool, without
+//! depending on (or forking) the constant product plugin.
+
+pub mod error;
+pub mod instruction;
+pub mod result;
+
+pub use solana_program;
+
+/// Version of the plugin interface spoken by this crate.
+/// The pool sends it to the plugin in a `CheckInterfaceVersion` handshake at pool creation.
+///
+/// Version 2 added `ComputeSwapExactOut` and `PluginCalcResult::amount_in`.
+/// Version 3 added `CheckSwapInvariant`.
+pub const INTERFACE_VERSION: u16 = 3;
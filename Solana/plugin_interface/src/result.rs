@@ -0,0 +1,38 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+
+/// Result of a plugin computation, returned through `set_return_data` and read back
+/// by the pool with `get_return_data` after the CPI.
+/// Fields not relevant to the computed instruction are left at zero.
+#[derive(BorshDeserialize, BorshSerialize, Debug, Default, PartialEq)]
+pub struct PluginCalcResult {
+    /// Actual amount of token A deposited (relevant for Add Liquidity)
+    pub actual_a: u64,
+    /// Actual amount of token B deposited (relevant for Add Liquidity)
+    pub actual_b: u64,
+    /// Number of LP shares minted (relevant for Add Liquidity)
+    pub shares_to_mint: u64,
+    /// Amount of token A withdrawn (relevant for Remove Liquidity)
+    pub withdraw_a: u64,
+    /// Amount of token B withdrawn (relevant for Remove Liquidity)
+    pub withdraw_b: u64,
+    /// Amount of output token calculated (relevant for Swap and Swap Exact Out)
+    pub amount_out: u64,
+    /// Amount of input token required (relevant for Swap Exact Out)
+    pub amount_in: u64,
+}
+
+impl PluginCalcResult {
+    /// Serialized size of the result.
+    pub const LEN: usize = 7 * 8;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serialized_len() {
+        let bytes = PluginCalcResult::default().try_to_vec().unwrap();
+        assert_eq!(bytes.len(), PluginCalcResult::LEN);
+    }
+}
@@ -1,8 +1,14 @@
 use {
     borsh::{BorshDeserialize, BorshSerialize},
-    dex_pool_program::instruction::PoolInstruction,
-    dex_pool_program::processor::PluginCalcResult,
-    dex_pool_program::state::PoolState,
+    constant_product_plugin::{
+        instruction::initialize_plugin_state, processor::Processor, state::PluginState,
+    },
+    dex_pool_program::error::PoolError,
+    dex_pool_program::fee::calculate_protocol_fee_shares,
+    dex_pool_program::instruction::{Deadline, PoolInstruction},
+    dex_pool_program::oracle::{price_q64, TwapPrice},
+    dex_pool_program::pda::{find_config_address, find_oracle_address, find_program_data_address},
+    dex_pool_program::state::{PoolState, MINIMUM_LIQUIDITY},
     litesvm::{
         types::{FailedTransactionMetadata, TransactionMetadata},
         LiteSVM,
@@ -10,7 +16,9 @@ use {
     solana_program::{program_option::COption, system_instruction},
     solana_sdk::{
         account::Account,
-        instruction::{AccountMeta, Instruction},
+        bpf_loader_upgradeable,
+        clock::Clock,
+        instruction::{AccountMeta, Instruction, InstructionError},
         message::Message,
         native_token::LAMPORTS_PER_SOL,
         pubkey::Pubkey,
@@ -18,7 +26,7 @@ use {
         signer::keypair::Keypair,
         system_program,
         sysvar::{self, rent::Rent},
-        transaction::Transaction,
+        transaction::{Transaction, TransactionError},
     },
     spl_associated_token_account::{
         self, get_associated_token_address, instruction::create_associated_token_account,
@@ -26,7 +34,6 @@ use {
     spl_token::{self, solana_program::program_pack::Pack},
     std::env,
     std::error::Error,
-    std::mem::size_of,
 };
 
 struct TestSetup {
@@ -43,6 +50,10 @@ struct TestSetup {
     pool_bump: u8,
     vault_a_pk: Pubkey,
     vault_b_pk: Pubkey,
+    config_pda: Pubkey,
+    treasury: Pubkey,
+    treasury_lp: Pubkey,
+    locked_lp: Pubkey,
 }
 
 // Helper function to handle litesvm errors
@@ -50,6 +61,28 @@ fn map_litesvm_err<T, E: std::fmt::Debug>(res: Result<T, E>) -> Result<T, Box<dy
     res.map_err(|e| Box::<dyn Error>::from(format!("LiteSVM Error: {:?}", e)))
 }
 
+// Helper function to record `authority` as the upgrade authority of a program.
+// LiteSVM loads programs without the upgradeable loader, so the ProgramData is faked.
+fn set_upgrade_authority(svm: &mut LiteSVM, program_id: &Pubkey, authority: &Pubkey) {
+    // Bincode `UpgradeableLoaderState::ProgramData { slot: 0, upgrade_authority_address }`
+    let mut data = 3u32.to_le_bytes().to_vec();
+    data.extend_from_slice(&0u64.to_le_bytes());
+    data.push(1);
+    data.extend_from_slice(authority.as_ref());
+    let lamports = svm.minimum_balance_for_rent_exemption(data.len());
+    svm.set_account(
+        find_program_data_address(program_id),
+        Account {
+            lamports,
+            data,
+            owner: bpf_loader_upgradeable::id(),
+            executable: false,
+            rent_epoch: 0,
+        },
+    )
+    .expect("Failed to set ProgramData account");
+}
+
 // Helper function to create mint accounts
 fn create_mint(
     svm: &mut LiteSVM,
@@ -321,9 +354,19 @@ fn execute_add_liquidity(
             AccountMeta::new(*user_ata_lp, false),
             AccountMeta::new_readonly(spl_token::id(), false),
             AccountMeta::new_readonly(setup.plugin_pid, false),
-            AccountMeta::new(setup.plugin_state_pk, false),
+            AccountMeta::new_readonly(setup.plugin_state_pk, false),
+            AccountMeta::new_readonly(setup.config_pda, false),
+            AccountMeta::new(setup.treasury_lp, false),
+            AccountMeta::new_readonly(setup.dex_pid, false), // no oracle
+            AccountMeta::new(setup.locked_lp, false),
         ],
-        data: PoolInstruction::AddLiquidity { amount_a, amount_b }.try_to_vec()?,
+        data: PoolInstruction::AddLiquidity {
+            amount_a,
+            amount_b,
+            deadline: None,
+            min_shares_out: None,
+        }
+        .try_to_vec()?,
     };
     let tx = Transaction::new_signed_with_payer(
         &[add_liq_ix],
@@ -344,6 +387,13 @@ fn get_pool_state(svm: &LiteSVM, pool_pda: &Pubkey) -> Result<PoolState, Box<dyn
 }
 
 fn setup_test_environment() -> Result<TestSetup, Box<dyn Error>> {
+    setup_test_environment_with_fee(3, 1000)
+}
+
+fn setup_test_environment_with_fee(
+    fee_numerator: u64,
+    fee_denominator: u64,
+) -> Result<TestSetup, Box<dyn Error>> {
     let dex_pid = Pubkey::new_unique();
     let plugin_pid = Pubkey::new_unique();
     println!("Using DEX Program ID: {}", dex_pid);
@@ -394,7 +444,7 @@ fn setup_test_environment() -> Result<TestSetup, Box<dyn Error>> {
 
     let plugin_state_kp = Keypair::new();
     let plugin_state_pk = plugin_state_kp.pubkey();
-    let plugin_state_size = size_of::<PluginCalcResult>();
+    let plugin_state_size = PluginState::LEN;
     println!("Plugin State Account Size: {}", plugin_state_size);
     let rent = svm.get_sysvar::<Rent>();
     let plugin_state_rent = rent.minimum_balance(plugin_state_size);
@@ -406,8 +456,14 @@ fn setup_test_environment() -> Result<TestSetup, Box<dyn Error>> {
         plugin_state_size as u64,
         &plugin_pid,
     );
+    let init_plugin_state_ix = initialize_plugin_state(
+        &plugin_pid,
+        &plugin_state_pk,
+        fee_numerator,
+        fee_denominator,
+    );
     let tx_plugin_state = Transaction::new_signed_with_payer(
-        &[create_plugin_state_ix],
+        &[create_plugin_state_ix, init_plugin_state_ix],
         Some(&payer.pubkey()),
         &[&payer, &plugin_state_kp],
         svm.latest_blockhash(),
@@ -476,7 +532,7 @@ fn setup_test_environment() -> Result<TestSetup, Box<dyn Error>> {
             AccountMeta::new_readonly(mint_a, false),
             AccountMeta::new_readonly(mint_b, false),
             AccountMeta::new_readonly(plugin_pid, false),
-            AccountMeta::new(plugin_state_pk, false),
+            AccountMeta::new_readonly(plugin_state_pk, false),
             AccountMeta::new_readonly(system_program::id(), false),
             AccountMeta::new_readonly(sysvar::rent::id(), false),
             AccountMeta::new_readonly(spl_token::id(), false),
@@ -510,6 +566,11 @@ fn setup_test_environment() -> Result<TestSetup, Box<dyn Error>> {
     map_litesvm_err(svm.send_transaction(tx))?;
     println!("Pool Initialization successful during setup.");
 
+    let (config_pda, _config_bump) = find_config_address(&dex_pid);
+    let treasury = Pubkey::new_unique();
+    let treasury_lp = create_user_ata(&mut svm, &payer, &treasury, &lp_mint)?;
+    let locked_lp = create_user_ata(&mut svm, &payer, &pool_pda, &lp_mint)?;
+
     Ok(TestSetup {
         svm,
         payer,
@@ -524,10 +585,15 @@ fn setup_test_environment() -> Result<TestSetup, Box<dyn Error>> {
         pool_bump,
         vault_a_pk,
         vault_b_pk,
+        config_pda,
+        treasury,
+        treasury_lp,
+        locked_lp,
     })
 }
 
 fn setup_wsol_test_environment() -> Result<TestSetup, Box<dyn Error>> {
+    let (fee_numerator, fee_denominator) = (3, 1000);
     let dex_pid = Pubkey::new_unique();
     let plugin_pid = Pubkey::new_unique();
     println!("Using DEX Program ID: {}", dex_pid);
@@ -615,7 +681,7 @@ fn setup_wsol_test_environment() -> Result<TestSetup, Box<dyn Error>> {
 
     let plugin_state_kp = Keypair::new();
     let plugin_state_pk = plugin_state_kp.pubkey();
-    let plugin_state_size = size_of::<PluginCalcResult>();
+    let plugin_state_size = PluginState::LEN;
     let rent = svm.get_sysvar::<Rent>();
     let plugin_state_rent = rent.minimum_balance(plugin_state_size);
     let create_plugin_state_ix = system_instruction::create_account(
@@ -625,8 +691,14 @@ fn setup_wsol_test_environment() -> Result<TestSetup, Box<dyn Error>> {
         plugin_state_size as u64,
         &plugin_pid,
     );
+    let init_plugin_state_ix = initialize_plugin_state(
+        &plugin_pid,
+        &plugin_state_pk,
+        fee_numerator,
+        fee_denominator,
+    );
     let tx_plugin_state = Transaction::new_signed_with_payer(
-        &[create_plugin_state_ix],
+        &[create_plugin_state_ix, init_plugin_state_ix],
         Some(&payer.pubkey()),
         &[&payer, &plugin_state_kp],
         svm.latest_blockhash(),
@@ -738,7 +810,7 @@ fn setup_wsol_test_environment() -> Result<TestSetup, Box<dyn Error>> {
             AccountMeta::new_readonly(mint_a, false),
             AccountMeta::new_readonly(mint_b, false),
             AccountMeta::new_readonly(plugin_pid, false),
-            AccountMeta::new(plugin_state_pk, false),
+            AccountMeta::new_readonly(plugin_state_pk, false),
             AccountMeta::new_readonly(system_program::id(), false),
             AccountMeta::new_readonly(sysvar::rent::id(), false),
             AccountMeta::new_readonly(spl_token::id(), false),
@@ -755,6 +827,11 @@ fn setup_wsol_test_environment() -> Result<TestSetup, Box<dyn Error>> {
     map_litesvm_err(svm.send_transaction(init_pool_tx))?;
     println!("Pool Initialization successful during setup.");
 
+    let (config_pda, _config_bump) = find_config_address(&dex_pid);
+    let treasury = Pubkey::new_unique();
+    let treasury_lp = create_user_ata(&mut svm, &payer, &treasury, &lp_mint)?;
+    let locked_lp = create_user_ata(&mut svm, &payer, &pool_pda, &lp_mint)?;
+
     Ok(TestSetup {
         svm,
         payer,
@@ -769,6 +846,10 @@ fn setup_wsol_test_environment() -> Result<TestSetup, Box<dyn Error>> {
         pool_bump,
         vault_a_pk,
         vault_b_pk,
+        config_pda,
+        treasury,
+        treasury_lp,
+        locked_lp,
     })
 }
 
@@ -780,6 +861,27 @@ fn execute_swap(
     destination_ata: &Pubkey, // User's destination ATA (e.g., SPL B or wSOL)
     amount_in: u64,
     min_out: u64,
+) -> Result<(), Box<dyn Error>> {
+    execute_swap_with_deadline(
+        setup,
+        swapper_kp,
+        source_ata,
+        destination_ata,
+        amount_in,
+        min_out,
+        None,
+    )
+}
+
+// Same as `execute_swap`, expiring at `deadline`
+fn execute_swap_with_deadline(
+    setup: &mut TestSetup,
+    swapper_kp: &Keypair,
+    source_ata: &Pubkey,
+    destination_ata: &Pubkey,
+    amount_in: u64,
+    min_out: u64,
+    deadline: Option<Deadline>,
 ) -> Result<(), Box<dyn Error>> {
     // Determine if it's A->B or B->A based on source ATA mint
     // NOTE: This relies on TestSetup having mint_a/mint_b correctly assigned
@@ -811,10 +913,15 @@ fn execute_swap(
             AccountMeta::new(*destination_ata, false),   // 5 User Destination ATA
             AccountMeta::new_readonly(spl_token::id(), false), // 6 Token Program
             AccountMeta::new_readonly(setup.plugin_pid, false), // 7 Plugin Program
-            AccountMeta::new(setup.plugin_state_pk, false), // 8 Plugin State
+            AccountMeta::new_readonly(setup.plugin_state_pk, false), // 8 Plugin State
         ],
         // Use correct fields for Swap instruction
-        data: PoolInstruction::Swap { amount_in, min_out }.try_to_vec()?,
+        data: PoolInstruction::Swap {
+            amount_in,
+            min_out,
+            deadline,
+        }
+        .try_to_vec()?,
     };
 
     let tx = Transaction::new_signed_with_payer(
@@ -913,7 +1020,7 @@ fn test_initialize_pool_already_exists() -> Result<(), Box<dyn Error>> {
             AccountMeta::new_readonly(setup.mint_a, false),
             AccountMeta::new_readonly(setup.mint_b, false),
             AccountMeta::new_readonly(setup.plugin_pid, false),
-            AccountMeta::new(setup.plugin_state_pk, false), // Writable=false OK
+            AccountMeta::new_readonly(setup.plugin_state_pk, false),
             AccountMeta::new_readonly(system_program::id(), false),
             AccountMeta::new_readonly(sysvar::rent::id(), false),
             AccountMeta::new_readonly(spl_token::id(), false), // ADDED
@@ -1040,10 +1147,15 @@ fn test_remove_liquidity_zero() -> Result<(), Box<dyn Error>> {
             AccountMeta::new(user_ata_lp, false),      // 7 user LP
             AccountMeta::new_readonly(spl_token::id(), false), // 8 token program
             AccountMeta::new_readonly(setup.plugin_pid, false), // 9 plugin program
-            AccountMeta::new(setup.plugin_state_pk, false), // 10 plugin state
+            AccountMeta::new_readonly(setup.plugin_state_pk, false), // 10 plugin state
+            AccountMeta::new_readonly(setup.config_pda, false), // 11 protocol config
+            AccountMeta::new(setup.treasury_lp, false), // 12 treasury LP
         ],
         data: PoolInstruction::RemoveLiquidity {
             amount_lp: remove_amount_lp,
+            deadline: None,
+            min_a: None,
+            min_b: None,
         }
         .try_to_vec()?,
     };
@@ -1272,9 +1384,23 @@ fn test_add_liquidity_simple() -> Result<(), Box<dyn Error>> {
     // Use helper to get pool state
     let pool_state = get_pool_state(&setup.svm, &setup.pool_pda)?;
     assert_eq!(
-        pool_state.total_lp_supply, user_lp_balance,
+        pool_state.total_lp_supply,
+        user_lp_balance + MINIMUM_LIQUIDITY,
         "Pool total LP supply mismatch"
     );
+    // The locked shares are real tokens, held by the pool itself
+    assert_eq!(
+        get_token_balance(&setup.svm, &setup.locked_lp),
+        MINIMUM_LIQUIDITY
+    );
+    let lp_mint_account = setup
+        .svm
+        .get_account(&setup.lp_mint)
+        .ok_or("LP mint not found")?;
+    assert_eq!(
+        spl_token::state::Mint::unpack(&lp_mint_account.data)?.supply,
+        pool_state.total_lp_supply
+    );
 
     println!("Add Liquidity Test Passed!");
     Ok(())
@@ -1341,10 +1467,15 @@ fn test_remove_liquidity_simple() -> Result<(), Box<dyn Error>> {
             AccountMeta::new(user_ata_lp, false),      // user LP
             AccountMeta::new_readonly(spl_token::id(), false), // token program
             AccountMeta::new_readonly(setup.plugin_pid, false), // plugin program
-            AccountMeta::new(setup.plugin_state_pk, false), // plugin state
+            AccountMeta::new_readonly(setup.plugin_state_pk, false), // plugin state
+            AccountMeta::new_readonly(setup.config_pda, false), // protocol config
+            AccountMeta::new(setup.treasury_lp, false), // treasury LP
         ],
         data: PoolInstruction::RemoveLiquidity {
             amount_lp: remove_amount_lp,
+            deadline: None,
+            min_a: None,
+            min_b: None,
         }
         .try_to_vec()?,
     };
@@ -1358,10 +1489,13 @@ fn test_remove_liquidity_simple() -> Result<(), Box<dyn Error>> {
     // Correct: Expect success, map error if it fails unexpectedly
     map_litesvm_err(setup.svm.send_transaction(remove_tx))?;
 
-    // Restore original assertions for this test
+    // The locked minimum liquidity keeps its part of the reserves
+    let total_lp = remove_amount_lp + MINIMUM_LIQUIDITY;
+    let locked_a = initial_vault_a - initial_vault_a * remove_amount_lp / total_lp;
+    let locked_b = initial_vault_b - initial_vault_b * remove_amount_lp / total_lp;
     assert_eq!(get_token_balance(&setup.svm, &user_ata_lp), 0);
-    assert_eq!(get_token_balance(&setup.svm, &setup.vault_a_pk), 0);
-    assert_eq!(get_token_balance(&setup.svm, &setup.vault_b_pk), 0);
+    assert_eq!(get_token_balance(&setup.svm, &setup.vault_a_pk), locked_a);
+    assert_eq!(get_token_balance(&setup.svm, &setup.vault_b_pk), locked_b);
     let final_user_a = get_token_balance(&setup.svm, &user_ata_a);
     let final_user_b = get_token_balance(&setup.svm, &user_ata_b);
     println!(
@@ -1369,11 +1503,11 @@ fn test_remove_liquidity_simple() -> Result<(), Box<dyn Error>> {
         final_user_a - initial_user_a,
         final_user_b - initial_user_b
     );
-    assert_eq!(final_user_a, initial_user_a + initial_vault_a);
-    assert_eq!(final_user_b, initial_user_b + initial_vault_b);
+    assert_eq!(final_user_a, initial_user_a + initial_vault_a - locked_a);
+    assert_eq!(final_user_b, initial_user_b + initial_vault_b - locked_b);
 
     let pool_state = get_pool_state(&setup.svm, &setup.pool_pda)?;
-    assert_eq!(pool_state.total_lp_supply, 0);
+    assert_eq!(pool_state.total_lp_supply, MINIMUM_LIQUIDITY);
 
     println!("Remove Liquidity Test Passed!");
     Ok(())
@@ -1390,8 +1524,8 @@ fn test_remove_liquidity_partial() -> Result<(), Box<dyn Error>> {
         &setup.mint_b,
         &setup.lp_mint,
     )?;
-    let deposit_a = 100;
-    let deposit_b = 200;
+    let deposit_a = 10_000;
+    let deposit_b = 20_000;
     mint_to_ata(
         &mut setup.svm,
         &setup.payer,
@@ -1446,10 +1580,15 @@ fn test_remove_liquidity_partial() -> Result<(), Box<dyn Error>> {
             AccountMeta::new(user_ata_lp, false),      // user LP
             AccountMeta::new_readonly(spl_token::id(), false), // token program
             AccountMeta::new_readonly(setup.plugin_pid, false), // plugin program
-            AccountMeta::new(setup.plugin_state_pk, false), // plugin state
+            AccountMeta::new_readonly(setup.plugin_state_pk, false), // plugin state
+            AccountMeta::new_readonly(setup.config_pda, false), // protocol config
+            AccountMeta::new(setup.treasury_lp, false), // treasury LP
         ],
         data: PoolInstruction::RemoveLiquidity {
             amount_lp: remove_amount_lp,
+            deadline: None,
+            min_a: None,
+            min_b: None,
         }
         .try_to_vec()?,
     };
@@ -1674,9 +1813,50 @@ fn test_swap_a_to_b() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+// Helper function to execute an exact-output swap
+fn execute_swap_exact_out(
+    setup: &mut TestSetup,
+    swapper_kp: &Keypair,
+    source_ata: &Pubkey,
+    destination_ata: &Pubkey,
+    amount_out: u64,
+    max_in: u64,
+) -> Result<(), Box<dyn Error>> {
+    let swap_ix = Instruction {
+        program_id: setup.dex_pid,
+        accounts: vec![
+            AccountMeta::new(swapper_kp.pubkey(), true), // 0 User swapper signer
+            AccountMeta::new(setup.pool_pda, false),     // 1 Pool state
+            AccountMeta::new(setup.vault_a_pk, false),   // 2 Vault A
+            AccountMeta::new(setup.vault_b_pk, false),   // 3 Vault B
+            AccountMeta::new(*source_ata, false),        // 4 User Source ATA
+            AccountMeta::new(*destination_ata, false),   // 5 User Destination ATA
+            AccountMeta::new_readonly(spl_token::id(), false), // 6 Token Program
+            AccountMeta::new_readonly(setup.plugin_pid, false), // 7 Plugin Program
+            AccountMeta::new_readonly(setup.plugin_state_pk, false), // 8 Plugin State
+        ],
+        data: PoolInstruction::SwapExactOut {
+            amount_out,
+            max_in,
+            deadline: None,
+        }
+        .try_to_vec()?,
+    };
+
+    let tx = Transaction::new_signed_with_payer(
+        &[swap_ix],
+        Some(&setup.payer.pubkey()),
+        &[&setup.payer, swapper_kp],
+        setup.svm.latest_blockhash(),
+    );
+    map_litesvm_err(setup.svm.send_transaction(tx))?;
+    Ok(())
+}
+
 #[test]
-fn test_swap_b_to_a() -> Result<(), Box<dyn Error>> {
+fn test_swap_exact_out_a_to_b() -> Result<(), Box<dyn Error>> {
     let mut setup = setup_test_environment()?;
+
     // --- Initial Liquidity Setup (using setup.payer) ---
     let deposit_a = 123_456;
     let deposit_b = 654_321;
@@ -1725,7 +1905,6 @@ fn test_swap_b_to_a() -> Result<(), Box<dyn Error>> {
         deposit_a,
         deposit_b,
     )?;
-    // --- End Initial Liquidity ---
 
     // Setup Swapper User
     let (swapper_kp, swapper_ata_a, swapper_ata_b, _swapper_ata_lp) = setup_user_accounts(
@@ -1736,7 +1915,6 @@ fn test_swap_b_to_a() -> Result<(), Box<dyn Error>> {
         &setup.lp_mint,
     )?;
     let initial_swapper_a = 1_234_567;
-    let initial_swapper_b = 7_654_321;
     mint_to_ata(
         &mut setup.svm,
         &setup.payer,
@@ -1745,98 +1923,92 @@ fn test_swap_b_to_a() -> Result<(), Box<dyn Error>> {
         &swapper_ata_a,
         initial_swapper_a,
     )?;
-    mint_to_ata(
-        &mut setup.svm,
-        &setup.payer,
-        &setup.mint_authority,
-        &setup.mint_b,
-        &swapper_ata_b,
-        initial_swapper_b,
-    )?;
 
-    let initial_user_a = get_token_balance(&setup.svm, &swapper_ata_a);
-    let initial_user_b = get_token_balance(&setup.svm, &swapper_ata_b);
     let initial_vault_a = get_token_balance(&setup.svm, &setup.vault_a_pk);
     let initial_vault_b = get_token_balance(&setup.svm, &setup.vault_b_pk);
 
-    // Build Swap IX (B -> A)
-    let amount_in = 22_222;
-    let min_out = 1;
-    // Use generic swap helper
-    execute_swap(
+    // Receive exactly 50_000 B
+    let amount_out = 50_000;
+    let expected_amount_in =
+        Processor::calculate_swap_exact_out(initial_vault_a, initial_vault_b, amount_out, 3, 1000)?
+            .amount_in;
+    // Rounded in the pool's favour: the same input swapped exact-in buys at least amount_out
+    let exact_in_out = Processor::calculate_swap(
+        initial_vault_a,
+        initial_vault_b,
+        expected_amount_in,
+        3,
+        1000,
+    )?
+    .amount_out;
+    assert!(exact_in_out >= amount_out);
+
+    // max_in one below the required input fails the slippage check
+    let result = execute_swap_exact_out(
         &mut setup,
         &swapper_kp,
-        &swapper_ata_b, // Source is B
-        &swapper_ata_a, // Dest is A
-        amount_in,
-        min_out,
-    )?;
-
-    // Assert balances
-    let final_user_a = get_token_balance(&setup.svm, &swapper_ata_a);
-    let final_user_b = get_token_balance(&setup.svm, &swapper_ata_b);
-    let final_vault_a = get_token_balance(&setup.svm, &setup.vault_a_pk);
-    let final_vault_b = get_token_balance(&setup.svm, &setup.vault_b_pk);
-
-    // Calculate expected output (simple CPMM with 0.3% fee)
-    let effective_in = (amount_in as u128) * 997 / 1000;
-    let expected_amount_out =
-        (initial_vault_a as u128 * effective_in) / (initial_vault_b as u128 + effective_in);
-
-    println!("--- Swap B to A ---");
-    println!("Initial User A: {}", initial_user_a);
-    println!("Initial User B: {}", initial_user_b);
-    println!("Initial Vault A: {}", initial_vault_a);
-    println!("Initial Vault B: {}", initial_vault_b);
-    println!("Amount In (B): {}", amount_in);
-    println!("Effective In (B): {}", effective_in);
-    println!("Expected Amount Out (A): {}", expected_amount_out);
-    println!(
-        "Actual Amount Out (User A change): {}",
-        final_user_a - initial_user_a
+        &swapper_ata_a,
+        &swapper_ata_b,
+        amount_out,
+        expected_amount_in - 1,
     );
-    println!("Final User A: {}", final_user_a);
-    println!("Final User B: {}", final_user_b);
-    println!("Final Vault A: {}", final_vault_a);
-    println!("Final Vault B: {}", final_vault_b);
-
-    // Assertions
+    assert!(result.is_err(), "Swap above max_in should fail");
     assert_eq!(
-        final_user_b,
-        initial_user_b - amount_in,
-        "User B balance mismatch"
+        get_token_balance(&setup.svm, &swapper_ata_a),
+        initial_swapper_a
     );
+
+    execute_swap_exact_out(
+        &mut setup,
+        &swapper_kp,
+        &swapper_ata_a,
+        &swapper_ata_b,
+        amount_out,
+        expected_amount_in,
+    )?;
+
     assert_eq!(
-        final_user_a,
-        initial_user_a + expected_amount_out as u64,
+        get_token_balance(&setup.svm, &swapper_ata_a),
+        initial_swapper_a - expected_amount_in,
         "User A balance mismatch"
     );
     assert_eq!(
-        final_vault_b,
-        initial_vault_b + amount_in,
-        "Vault B balance mismatch"
+        get_token_balance(&setup.svm, &swapper_ata_b),
+        amount_out,
+        "User B balance mismatch"
     );
     assert_eq!(
-        final_vault_a,
-        initial_vault_a - expected_amount_out as u64,
+        get_token_balance(&setup.svm, &setup.vault_a_pk),
+        initial_vault_a + expected_amount_in,
         "Vault A balance mismatch"
     );
-    // Check minimum out constraint
-    assert!(
-        final_user_a - initial_user_a >= min_out,
-        "Swap output less than minimum required"
+    assert_eq!(
+        get_token_balance(&setup.svm, &setup.vault_b_pk),
+        initial_vault_b - amount_out,
+        "Vault B balance mismatch"
     );
 
-    println!("Swap B->A Test Passed!");
+    // Draining the output vault is rejected
+    let result = execute_swap_exact_out(
+        &mut setup,
+        &swapper_kp,
+        &swapper_ata_a,
+        &swapper_ata_b,
+        initial_vault_b - amount_out,
+        u64::MAX,
+    );
+    assert!(result.is_err(), "Swap draining the vault should fail");
+
     Ok(())
 }
 
 #[test]
-fn test_add_liquidity_refund() -> Result<(), Box<dyn Error>> {
+fn test_swap_deadline() -> Result<(), Box<dyn Error>> {
     let mut setup = setup_test_environment()?;
-    // --- Initial deposit (using setup.payer) ---
-    let initial_deposit_a = 100_000;
-    let initial_deposit_b = 500_000;
+
+    // --- Initial Liquidity Setup (using setup.payer) ---
+    let deposit_a = 123_456;
+    let deposit_b = 654_321;
     let payer_ata_a = create_user_ata(
         &mut setup.svm,
         &setup.payer,
@@ -1861,7 +2033,7 @@ fn test_add_liquidity_refund() -> Result<(), Box<dyn Error>> {
         &setup.mint_authority,
         &setup.mint_a,
         &payer_ata_a,
-        initial_deposit_a,
+        deposit_a,
     )?;
     mint_to_ata(
         &mut setup.svm,
@@ -1869,7 +2041,7 @@ fn test_add_liquidity_refund() -> Result<(), Box<dyn Error>> {
         &setup.mint_authority,
         &setup.mint_b,
         &payer_ata_b,
-        initial_deposit_b,
+        deposit_b,
     )?;
     let payer_kp_clone =
         Keypair::from_bytes(&setup.payer.to_bytes()).expect("Failed to clone payer keypair");
@@ -1879,12 +2051,108 @@ fn test_add_liquidity_refund() -> Result<(), Box<dyn Error>> {
         &payer_ata_a,
         &payer_ata_b,
         &payer_ata_lp,
-        initial_deposit_a,
-        initial_deposit_b,
+        deposit_a,
+        deposit_b,
     )?;
-    // --- End Initial Deposit ---
 
-    // --- Second deposit (user tries to add too much A) ---
+    // Setup Swapper User
+    let (swapper_kp, swapper_ata_a, swapper_ata_b, _swapper_ata_lp) = setup_user_accounts(
+        &mut setup.svm,
+        &setup.payer,
+        &setup.mint_a,
+        &setup.mint_b,
+        &setup.lp_mint,
+    )?;
+    let initial_swapper_a = 1_234_567;
+    mint_to_ata(
+        &mut setup.svm,
+        &setup.payer,
+        &setup.mint_authority,
+        &setup.mint_a,
+        &swapper_ata_a,
+        initial_swapper_a,
+    )?;
+
+    let mut clock = setup.svm.get_sysvar::<Clock>();
+    clock.unix_timestamp = 1_700_000_000;
+    clock.slot = 500;
+    setup.svm.set_sysvar::<Clock>(&clock);
+
+    // Both kinds of deadline are rejected once passed
+    for deadline in [
+        Deadline::UnixTimestamp(clock.unix_timestamp - 1),
+        Deadline::Slot(clock.slot - 1),
+    ] {
+        let result = execute_swap_with_deadline(
+            &mut setup,
+            &swapper_kp,
+            &swapper_ata_a,
+            &swapper_ata_b,
+            10_000,
+            1,
+            Some(deadline),
+        );
+        assert!(result.is_err(), "Swap past {:?} should fail", deadline);
+        assert_eq!(
+            get_token_balance(&setup.svm, &swapper_ata_a),
+            initial_swapper_a
+        );
+    }
+
+    // A deadline equal to the current time still executes
+    execute_swap_with_deadline(
+        &mut setup,
+        &swapper_kp,
+        &swapper_ata_a,
+        &swapper_ata_b,
+        10_000,
+        1,
+        Some(Deadline::UnixTimestamp(clock.unix_timestamp)),
+    )?;
+    execute_swap_with_deadline(
+        &mut setup,
+        &swapper_kp,
+        &swapper_ata_a,
+        &swapper_ata_b,
+        10_000,
+        1,
+        Some(Deadline::Slot(clock.slot + 10)),
+    )?;
+    assert_eq!(
+        get_token_balance(&setup.svm, &swapper_ata_a),
+        initial_swapper_a - 20_000
+    );
+    Ok(())
+}
+
+// Helper to build a `Swap` / `SwapTo` instruction for the setup pool
+fn swap_to_ix(
+    setup: &TestSetup,
+    user: &Pubkey,
+    source_ata: &Pubkey,
+    recipient_ata: &Pubkey,
+    instruction: PoolInstruction,
+) -> Result<Instruction, Box<dyn Error>> {
+    Ok(Instruction {
+        program_id: setup.dex_pid,
+        accounts: vec![
+            AccountMeta::new(*user, true),
+            AccountMeta::new(setup.pool_pda, false),
+            AccountMeta::new(setup.vault_a_pk, false),
+            AccountMeta::new(setup.vault_b_pk, false),
+            AccountMeta::new(*source_ata, false),
+            AccountMeta::new(*recipient_ata, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(setup.plugin_pid, false),
+            AccountMeta::new_readonly(setup.plugin_state_pk, false),
+        ],
+        data: instruction.try_to_vec()?,
+    })
+}
+
+#[test]
+fn test_swap_and_remove_liquidity_to_recipient() -> Result<(), Box<dyn Error>> {
+    let mut setup = setup_test_environment()?;
     let (user_kp, user_ata_a, user_ata_b, user_ata_lp) = setup_user_accounts(
         &mut setup.svm,
         &setup.payer,
@@ -1892,15 +2160,15 @@ fn test_add_liquidity_refund() -> Result<(), Box<dyn Error>> {
         &setup.mint_b,
         &setup.lp_mint,
     )?;
-    let user_initial_a = 500_000;
-    let user_initial_b = 500_000;
+    let deposit_a = 123_456;
+    let deposit_b = 654_321;
     mint_to_ata(
         &mut setup.svm,
         &setup.payer,
         &setup.mint_authority,
         &setup.mint_a,
         &user_ata_a,
-        user_initial_a,
+        deposit_a + 10_000,
     )?;
     mint_to_ata(
         &mut setup.svm,
@@ -1908,111 +2176,2020 @@ fn test_add_liquidity_refund() -> Result<(), Box<dyn Error>> {
         &setup.mint_authority,
         &setup.mint_b,
         &user_ata_b,
-        user_initial_b,
+        deposit_b,
     )?;
-
-    let user_balance_a_before = get_token_balance(&setup.svm, &user_ata_a);
-    let user_balance_b_before = get_token_balance(&setup.svm, &user_ata_b);
-    let vault_a_before = get_token_balance(&setup.svm, &setup.vault_a_pk);
-    let vault_b_before = get_token_balance(&setup.svm, &setup.vault_b_pk);
-    let pool_state_before = get_pool_state(&setup.svm, &setup.pool_pda)?;
-    let total_lp_before = pool_state_before.total_lp_supply;
-    let user_lp_before = get_token_balance(&setup.svm, &user_ata_lp);
-
-    // User tries to deposit 30k A (excess) and 50k B.
-    let deposit_a_attempt = 30_000;
-    let deposit_b_attempt = 50_000;
     execute_add_liquidity(
         &mut setup,
         &user_kp,
         &user_ata_a,
         &user_ata_b,
         &user_ata_lp,
-        deposit_a_attempt,
-        deposit_b_attempt,
+        deposit_a,
+        deposit_b,
     )?;
 
-    // --- Assertions ---
-    let user_balance_a_after = get_token_balance(&setup.svm, &user_ata_a);
-    let user_balance_b_after = get_token_balance(&setup.svm, &user_ata_b);
-    let vault_a_after = get_token_balance(&setup.svm, &setup.vault_a_pk);
-    let vault_b_after = get_token_balance(&setup.svm, &setup.vault_b_pk);
-    let user_lp_after = get_token_balance(&setup.svm, &user_ata_lp);
-    let pool_state_after = get_pool_state(&setup.svm, &setup.pool_pda)?;
-    let total_lp_after = pool_state_after.total_lp_supply;
+    // The merchant never signs; only its token accounts are passed
+    let merchant = Keypair::new();
+    let merchant_ata_a = create_user_ata(
+        &mut setup.svm,
+        &setup.payer,
+        &merchant.pubkey(),
+        &setup.mint_a,
+    )?;
+    let merchant_ata_b = create_user_ata(
+        &mut setup.svm,
+        &setup.payer,
+        &merchant.pubkey(),
+        &setup.mint_b,
+    )?;
 
-    // Calculate expected amounts based on pool ratio and deposit_b_attempt
-    let actual_deposit_a =
-        (vault_a_before as u128 * deposit_b_attempt as u128 / vault_b_before as u128) as u64;
-    let actual_deposit_b = deposit_b_attempt; // B is the limiting factor
+    // Plain Swap still requires the destination to belong to the signer
+    let swap_ix = swap_to_ix(
+        &setup,
+        &user_kp.pubkey(),
+        &user_ata_a,
+        &merchant_ata_b,
+        PoolInstruction::Swap {
+            amount_in: 10_000,
+            min_out: 1,
+            deadline: None,
+        },
+    )?;
+    let tx = Transaction::new_signed_with_payer(
+        &[swap_ix],
+        Some(&setup.payer.pubkey()),
+        &[&setup.payer, &user_kp],
+        setup.svm.latest_blockhash(),
+    );
+    assert!(setup.svm.send_transaction(tx).is_err());
+
+    // SwapTo delivers to the merchant; a wrong-mint destination is still rejected
+    let vault_a = get_token_balance(&setup.svm, &setup.vault_a_pk);
+    let vault_b = get_token_balance(&setup.svm, &setup.vault_b_pk);
+    let expected_out = Processor::calculate_swap(vault_a, vault_b, 10_000, 3, 1000)?.amount_out;
+    let swap_to = || PoolInstruction::SwapTo {
+        amount_in: 10_000,
+        min_out: expected_out,
+        deadline: None,
+    };
+    let wrong_mint_ix = swap_to_ix(
+        &setup,
+        &user_kp.pubkey(),
+        &user_ata_a,
+        &merchant_ata_a,
+        swap_to(),
+    )?;
+    let tx = Transaction::new_signed_with_payer(
+        &[wrong_mint_ix],
+        Some(&setup.payer.pubkey()),
+        &[&setup.payer, &user_kp],
+        setup.svm.latest_blockhash(),
+    );
+    assert!(setup.svm.send_transaction(tx).is_err());
+    let swap_to_merchant_ix = swap_to_ix(
+        &setup,
+        &user_kp.pubkey(),
+        &user_ata_a,
+        &merchant_ata_b,
+        swap_to(),
+    )?;
+    let tx = Transaction::new_signed_with_payer(
+        &[swap_to_merchant_ix],
+        Some(&setup.payer.pubkey()),
+        &[&setup.payer, &user_kp],
+        setup.svm.latest_blockhash(),
+    );
+    map_litesvm_err(setup.svm.send_transaction(tx))?;
+    assert_eq!(get_token_balance(&setup.svm, &user_ata_a), 0);
+    assert_eq!(get_token_balance(&setup.svm, &merchant_ata_b), expected_out);
+
+    // RemoveLiquidityTo burns the user's LP and pays the merchant
+    let amount_lp = get_token_balance(&setup.svm, &user_ata_lp) / 2;
+    let pool_state = get_pool_state(&setup.svm, &setup.pool_pda)?;
+    let vault_a = get_token_balance(&setup.svm, &setup.vault_a_pk);
+    let vault_b = get_token_balance(&setup.svm, &setup.vault_b_pk);
+    let expected = Processor::calculate_remove_liquidity(
+        vault_a,
+        vault_b,
+        pool_state.total_lp_supply,
+        amount_lp,
+    )?;
+    let user_b_before = get_token_balance(&setup.svm, &user_ata_b);
+    let merchant_b_before = get_token_balance(&setup.svm, &merchant_ata_b);
+    let remove_ix = Instruction {
+        program_id: setup.dex_pid,
+        accounts: vec![
+            AccountMeta::new(user_kp.pubkey(), true),
+            AccountMeta::new(setup.pool_pda, false),
+            AccountMeta::new(setup.vault_a_pk, false),
+            AccountMeta::new(setup.vault_b_pk, false),
+            AccountMeta::new(setup.lp_mint, false),
+            AccountMeta::new(merchant_ata_a, false),
+            AccountMeta::new(merchant_ata_b, false),
+            AccountMeta::new(user_ata_lp, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(setup.plugin_pid, false),
+            AccountMeta::new_readonly(setup.plugin_state_pk, false),
+            AccountMeta::new_readonly(setup.config_pda, false),
+            AccountMeta::new(setup.treasury_lp, false),
+        ],
+        data: PoolInstruction::RemoveLiquidityTo {
+            amount_lp,
+            deadline: None,
+            min_a: None,
+            min_b: None,
+        }
+        .try_to_vec()?,
+    };
+    let tx = Transaction::new_signed_with_payer(
+        &[remove_ix],
+        Some(&setup.payer.pubkey()),
+        &[&setup.payer, &user_kp],
+        setup.svm.latest_blockhash(),
+    );
+    map_litesvm_err(setup.svm.send_transaction(tx))?;
+    assert_eq!(
+        get_token_balance(&setup.svm, &merchant_ata_a),
+        expected.withdraw_a
+    );
+    assert_eq!(
+        get_token_balance(&setup.svm, &merchant_ata_b),
+        merchant_b_before + expected.withdraw_b
+    );
+    assert_eq!(get_token_balance(&setup.svm, &user_ata_b), user_b_before);
+    Ok(())
+}
+
+/// Accounts of an additional pool sharing the setup's programs.
+struct RoutePool {
+    pool_pda: Pubkey,
+    vault_a_pk: Pubkey,
+    vault_b_pk: Pubkey,
+    plugin_state_pk: Pubkey,
+}
+
+/// Creates and seeds a second pool for `mint_a`/`mint_b` with its own plugin state (0.3% fee).
+fn create_route_pool(
+    setup: &mut TestSetup,
+    mint_a: &Pubkey,
+    mint_b: &Pubkey,
+    deposit_a: u64,
+    deposit_b: u64,
+) -> Result<RoutePool, Box<dyn Error>> {
+    let lp_mint =
+        create_mint(&mut setup.svm, &setup.payer, &setup.mint_authority.pubkey())?.pubkey();
+
+    let plugin_state_kp = Keypair::new();
+    let plugin_state_pk = plugin_state_kp.pubkey();
+    let rent = setup.svm.get_sysvar::<Rent>();
+    let create_plugin_state_ix = solana_sdk::system_instruction::create_account(
+        &setup.payer.pubkey(),
+        &plugin_state_pk,
+        rent.minimum_balance(PluginState::LEN),
+        PluginState::LEN as u64,
+        &setup.plugin_pid,
+    );
+    let init_plugin_state_ix =
+        initialize_plugin_state(&setup.plugin_pid, &plugin_state_pk, 3, 1000);
+    let tx = Transaction::new_signed_with_payer(
+        &[create_plugin_state_ix, init_plugin_state_ix],
+        Some(&setup.payer.pubkey()),
+        &[&setup.payer, &plugin_state_kp],
+        setup.svm.latest_blockhash(),
+    );
+    map_litesvm_err(setup.svm.send_transaction(tx))?;
+
+    let (pool_pda, _pool_bump) = dex_pool_program::pda::find_pool_address(
+        &setup.dex_pid,
+        mint_a,
+        mint_b,
+        &setup.plugin_pid,
+        &plugin_state_pk,
+    );
+    let vault_a_pk = get_associated_token_address(&pool_pda, mint_a);
+    let vault_b_pk = get_associated_token_address(&pool_pda, mint_b);
+    let setup_tx = Transaction::new_signed_with_payer(
+        &[
+            create_associated_token_account(
+                &setup.payer.pubkey(),
+                &pool_pda,
+                mint_a,
+                &spl_token::id(),
+            ),
+            create_associated_token_account(
+                &setup.payer.pubkey(),
+                &pool_pda,
+                mint_b,
+                &spl_token::id(),
+            ),
+            spl_token::instruction::set_authority(
+                &spl_token::id(),
+                &lp_mint,
+                Some(&pool_pda),
+                spl_token::instruction::AuthorityType::MintTokens,
+                &setup.mint_authority.pubkey(),
+                &[&setup.mint_authority.pubkey()],
+            )?,
+        ],
+        Some(&setup.payer.pubkey()),
+        &[&setup.payer, &setup.mint_authority],
+        setup.svm.latest_blockhash(),
+    );
+    map_litesvm_err(setup.svm.send_transaction(setup_tx))?;
+
+    let init_ix = Instruction {
+        program_id: setup.dex_pid,
+        accounts: vec![
+            AccountMeta::new(setup.payer.pubkey(), true),
+            AccountMeta::new(pool_pda, false),
+            AccountMeta::new(vault_a_pk, false),
+            AccountMeta::new(vault_b_pk, false),
+            AccountMeta::new(lp_mint, false),
+            AccountMeta::new_readonly(*mint_a, false),
+            AccountMeta::new_readonly(*mint_b, false),
+            AccountMeta::new_readonly(setup.plugin_pid, false),
+            AccountMeta::new_readonly(plugin_state_pk, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data: PoolInstruction::InitializePool.try_to_vec()?,
+    };
+    let tx = Transaction::new_signed_with_payer(
+        &[init_ix],
+        Some(&setup.payer.pubkey()),
+        &[&setup.payer],
+        setup.svm.latest_blockhash(),
+    );
+    map_litesvm_err(setup.svm.send_transaction(tx))?;
+
+    // Seed the pool from a fresh depositor
+    let (lp_kp, lp_ata_a, lp_ata_b, lp_ata_lp) =
+        setup_user_accounts(&mut setup.svm, &setup.payer, mint_a, mint_b, &lp_mint)?;
+    let locked_lp = create_user_ata(&mut setup.svm, &setup.payer, &pool_pda, &lp_mint)?;
+    mint_to_ata(
+        &mut setup.svm,
+        &setup.payer,
+        &setup.mint_authority,
+        mint_a,
+        &lp_ata_a,
+        deposit_a,
+    )?;
+    mint_to_ata(
+        &mut setup.svm,
+        &setup.payer,
+        &setup.mint_authority,
+        mint_b,
+        &lp_ata_b,
+        deposit_b,
+    )?;
+    let add_liq_ix = Instruction {
+        program_id: setup.dex_pid,
+        accounts: vec![
+            AccountMeta::new(lp_kp.pubkey(), true),
+            AccountMeta::new(pool_pda, false),
+            AccountMeta::new(vault_a_pk, false),
+            AccountMeta::new(vault_b_pk, false),
+            AccountMeta::new(lp_mint, false),
+            AccountMeta::new(lp_ata_a, false),
+            AccountMeta::new(lp_ata_b, false),
+            AccountMeta::new(lp_ata_lp, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(setup.plugin_pid, false),
+            AccountMeta::new_readonly(plugin_state_pk, false),
+            AccountMeta::new_readonly(setup.config_pda, false),
+            // Ignored while the protocol fee is off
+            AccountMeta::new(setup.treasury_lp, false),
+            AccountMeta::new_readonly(setup.dex_pid, false), // no oracle
+            AccountMeta::new(locked_lp, false),
+        ],
+        data: PoolInstruction::AddLiquidity {
+            amount_a: deposit_a,
+            amount_b: deposit_b,
+            deadline: None,
+            min_shares_out: None,
+        }
+        .try_to_vec()?,
+    };
+    let tx = Transaction::new_signed_with_payer(
+        &[add_liq_ix],
+        Some(&setup.payer.pubkey()),
+        &[&setup.payer, &lp_kp],
+        setup.svm.latest_blockhash(),
+    );
+    map_litesvm_err(setup.svm.send_transaction(tx))?;
+
+    Ok(RoutePool {
+        pool_pda,
+        vault_a_pk,
+        vault_b_pk,
+        plugin_state_pk,
+    })
+}
+
+// Helper function to execute a multi-hop swap through `pools`, in order
+fn execute_swap_route(
+    setup: &mut TestSetup,
+    swapper_kp: &Keypair,
+    source_ata: &Pubkey,
+    destination_ata: &Pubkey,
+    amount_in: u64,
+    min_final_out: u64,
+    pools: &[&RoutePool],
+) -> Result<(), Box<dyn Error>> {
+    let mut accounts = vec![
+        AccountMeta::new(swapper_kp.pubkey(), true), // 0 User swapper signer
+        AccountMeta::new(*source_ata, false),        // 1 User Source ATA
+        AccountMeta::new(*destination_ata, false),   // 2 User Destination ATA
+        AccountMeta::new_readonly(spl_token::id(), false), // 3 Token Program
+    ];
+    for pool in pools {
+        accounts.extend([
+            AccountMeta::new(pool.pool_pda, false),
+            AccountMeta::new(pool.vault_a_pk, false),
+            AccountMeta::new(pool.vault_b_pk, false),
+            AccountMeta::new_readonly(setup.plugin_pid, false),
+            AccountMeta::new_readonly(pool.plugin_state_pk, false),
+        ]);
+    }
+    let route_ix = Instruction {
+        program_id: setup.dex_pid,
+        accounts,
+        data: PoolInstruction::SwapRoute {
+            amount_in,
+            min_final_out,
+            hops: pools.len() as u8,
+            deadline: None,
+        }
+        .try_to_vec()?,
+    };
+
+    let tx = Transaction::new_signed_with_payer(
+        &[route_ix],
+        Some(&setup.payer.pubkey()),
+        &[&setup.payer, swapper_kp],
+        setup.svm.latest_blockhash(),
+    );
+    map_litesvm_err(setup.svm.send_transaction(tx))?;
+    Ok(())
+}
+
+#[test]
+fn test_swap_route_a_to_b_to_c() -> Result<(), Box<dyn Error>> {
+    let mut setup = setup_test_environment()?;
+    let mint_a = setup.mint_a;
+    let mint_b = setup.mint_b;
+    let mint_c =
+        create_mint(&mut setup.svm, &setup.payer, &setup.mint_authority.pubkey())?.pubkey();
+
+    // Seed the setup's A/B pool, then an independent B/C pool
+    let (payer_kp, payer_ata_a, payer_ata_b, payer_ata_lp) = setup_user_accounts(
+        &mut setup.svm,
+        &setup.payer,
+        &setup.mint_a,
+        &setup.mint_b,
+        &setup.lp_mint,
+    )?;
+    mint_to_ata(
+        &mut setup.svm,
+        &setup.payer,
+        &setup.mint_authority,
+        &mint_a,
+        &payer_ata_a,
+        1_000_000,
+    )?;
+    mint_to_ata(
+        &mut setup.svm,
+        &setup.payer,
+        &setup.mint_authority,
+        &mint_b,
+        &payer_ata_b,
+        2_000_000,
+    )?;
+    execute_add_liquidity(
+        &mut setup,
+        &payer_kp,
+        &payer_ata_a,
+        &payer_ata_b,
+        &payer_ata_lp,
+        1_000_000,
+        2_000_000,
+    )?;
+    let pool_ab = RoutePool {
+        pool_pda: setup.pool_pda,
+        vault_a_pk: setup.vault_a_pk,
+        vault_b_pk: setup.vault_b_pk,
+        plugin_state_pk: setup.plugin_state_pk,
+    };
+    let pool_bc = create_route_pool(&mut setup, &mint_b, &mint_c, 2_000_000, 500_000)?;
+
+    // Swapper holds A and wants C
+    let swapper_kp = Keypair::new();
+    map_litesvm_err(setup.svm.airdrop(&swapper_kp.pubkey(), 1_000_000_000))?;
+    let swapper_ata_a =
+        create_user_ata(&mut setup.svm, &setup.payer, &swapper_kp.pubkey(), &mint_a)?;
+    let swapper_ata_c =
+        create_user_ata(&mut setup.svm, &setup.payer, &swapper_kp.pubkey(), &mint_c)?;
+    let amount_in = 10_000;
+    mint_to_ata(
+        &mut setup.svm,
+        &setup.payer,
+        &setup.mint_authority,
+        &mint_a,
+        &swapper_ata_a,
+        amount_in,
+    )?;
+
+    // Expected output: both legs priced by the plugin against the pre-swap reserves
+    let ab_vault_a = get_token_balance(&setup.svm, &pool_ab.vault_a_pk);
+    let ab_vault_b = get_token_balance(&setup.svm, &pool_ab.vault_b_pk);
+    let bc_vault_b = get_token_balance(&setup.svm, &pool_bc.vault_a_pk);
+    let bc_vault_c = get_token_balance(&setup.svm, &pool_bc.vault_b_pk);
+    let amount_b =
+        Processor::calculate_swap(ab_vault_a, ab_vault_b, amount_in, 3, 1000)?.amount_out;
+    let amount_c = Processor::calculate_swap(bc_vault_b, bc_vault_c, amount_b, 3, 1000)?.amount_out;
+    assert!(amount_c > 0);
+
+    // The final minimum is enforced for the whole route
+    let result = execute_swap_route(
+        &mut setup,
+        &swapper_kp,
+        &swapper_ata_a,
+        &swapper_ata_c,
+        amount_in,
+        amount_c + 1,
+        &[&pool_ab, &pool_bc],
+    );
+    assert!(result.is_err(), "Route below min_final_out should fail");
+    assert_eq!(get_token_balance(&setup.svm, &swapper_ata_a), amount_in);
+    assert_eq!(
+        get_token_balance(&setup.svm, &pool_ab.vault_b_pk),
+        ab_vault_b
+    );
+
+    execute_swap_route(
+        &mut setup,
+        &swapper_kp,
+        &swapper_ata_a,
+        &swapper_ata_c,
+        amount_in,
+        amount_c,
+        &[&pool_ab, &pool_bc],
+    )?;
+
+    assert_eq!(get_token_balance(&setup.svm, &swapper_ata_a), 0);
+    assert_eq!(get_token_balance(&setup.svm, &swapper_ata_c), amount_c);
+    // The intermediate B moved straight from the A/B vault into the B/C vault
+    assert_eq!(
+        get_token_balance(&setup.svm, &pool_ab.vault_a_pk),
+        ab_vault_a + amount_in
+    );
+    assert_eq!(
+        get_token_balance(&setup.svm, &pool_ab.vault_b_pk),
+        ab_vault_b - amount_b
+    );
+    assert_eq!(
+        get_token_balance(&setup.svm, &pool_bc.vault_a_pk),
+        bc_vault_b + amount_b
+    );
+    assert_eq!(
+        get_token_balance(&setup.svm, &pool_bc.vault_b_pk),
+        bc_vault_c - amount_c
+    );
+
+    // A route whose hops don't connect is rejected
+    mint_to_ata(
+        &mut setup.svm,
+        &setup.payer,
+        &setup.mint_authority,
+        &mint_a,
+        &swapper_ata_a,
+        amount_in,
+    )?;
+    let result = execute_swap_route(
+        &mut setup,
+        &swapper_kp,
+        &swapper_ata_a,
+        &swapper_ata_c,
+        amount_in,
+        1,
+        &[&pool_bc],
+    );
+    assert!(
+        result.is_err(),
+        "Route not starting with the input mint should fail"
+    );
+    assert_eq!(get_token_balance(&setup.svm, &swapper_ata_a), amount_in);
+
+    Ok(())
+}
+
+#[test]
+fn test_swap_custom_fee_tier() -> Result<(), Box<dyn Error>> {
+    // 1 bp pool
+    let mut setup = setup_test_environment_with_fee(1, 10_000)?;
+
+    let plugin_state = setup
+        .svm
+        .get_account(&setup.plugin_state_pk)
+        .ok_or("Plugin state account not found")?;
+    let state = PluginState::try_from_slice(&plugin_state.data)?;
+    assert!(state.is_initialized);
+    assert_eq!((state.fee_numerator, state.fee_denominator), (1, 10_000));
+
+    // --- Initial Liquidity Setup (using setup.payer) ---
+    let deposit_a = 1_000_000;
+    let deposit_b = 2_000_000;
+    let payer_ata_a = create_user_ata(
+        &mut setup.svm,
+        &setup.payer,
+        &setup.payer.pubkey(),
+        &setup.mint_a,
+    )?;
+    let payer_ata_b = create_user_ata(
+        &mut setup.svm,
+        &setup.payer,
+        &setup.payer.pubkey(),
+        &setup.mint_b,
+    )?;
+    let payer_ata_lp = create_user_ata(
+        &mut setup.svm,
+        &setup.payer,
+        &setup.payer.pubkey(),
+        &setup.lp_mint,
+    )?;
+    mint_to_ata(
+        &mut setup.svm,
+        &setup.payer,
+        &setup.mint_authority,
+        &setup.mint_a,
+        &payer_ata_a,
+        deposit_a,
+    )?;
+    mint_to_ata(
+        &mut setup.svm,
+        &setup.payer,
+        &setup.mint_authority,
+        &setup.mint_b,
+        &payer_ata_b,
+        deposit_b,
+    )?;
+    let payer_kp_clone =
+        Keypair::from_bytes(&setup.payer.to_bytes()).expect("Failed to clone payer keypair");
+    execute_add_liquidity(
+        &mut setup,
+        &payer_kp_clone,
+        &payer_ata_a,
+        &payer_ata_b,
+        &payer_ata_lp,
+        deposit_a,
+        deposit_b,
+    )?;
+
+    // Setup Swapper User
+    let (swapper_kp, swapper_ata_a, swapper_ata_b, _swapper_ata_lp) = setup_user_accounts(
+        &mut setup.svm,
+        &setup.payer,
+        &setup.mint_a,
+        &setup.mint_b,
+        &setup.lp_mint,
+    )?;
+    let amount_in = 50_000;
+    mint_to_ata(
+        &mut setup.svm,
+        &setup.payer,
+        &setup.mint_authority,
+        &setup.mint_a,
+        &swapper_ata_a,
+        amount_in,
+    )?;
+
+    let initial_vault_a = get_token_balance(&setup.svm, &setup.vault_a_pk);
+    let initial_vault_b = get_token_balance(&setup.svm, &setup.vault_b_pk);
+
+    execute_swap(
+        &mut setup,
+        &swapper_kp,
+        &swapper_ata_a,
+        &swapper_ata_b,
+        amount_in,
+        1,
+    )?;
+
+    // Expected output with a 0.01% fee
+    let effective_in = (amount_in as u128) * 9_999 / 10_000;
+    let expected_amount_out =
+        (initial_vault_b as u128 * effective_in) / (initial_vault_a as u128 + effective_in);
+
+    assert_eq!(get_token_balance(&setup.svm, &swapper_ata_a), 0);
+    assert_eq!(
+        get_token_balance(&setup.svm, &swapper_ata_b),
+        expected_amount_out as u64,
+        "Swap output does not reflect the 1 bp fee"
+    );
+    assert_eq!(
+        get_token_balance(&setup.svm, &setup.vault_b_pk),
+        initial_vault_b - expected_amount_out as u64
+    );
+
+    Ok(())
+}
+
+fn execute_set_protocol_fee(
+    setup: &mut TestSetup,
+    admin_kp: &Keypair,
+    protocol_fee_share_bps: u16,
+    initialize: bool,
+) -> Result<(), Box<dyn Error>> {
+    let (accounts, data) = if initialize {
+        (
+            vec![
+                AccountMeta::new(admin_kp.pubkey(), true),
+                AccountMeta::new(setup.config_pda, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new_readonly(find_program_data_address(&setup.dex_pid), false),
+            ],
+            PoolInstruction::InitializeConfig {
+                treasury: setup.treasury,
+                protocol_fee_share_bps,
+            },
+        )
+    } else {
+        (
+            vec![
+                AccountMeta::new_readonly(admin_kp.pubkey(), true),
+                AccountMeta::new(setup.config_pda, false),
+            ],
+            PoolInstruction::SetProtocolFee {
+                treasury: setup.treasury,
+                protocol_fee_share_bps,
+            },
+        )
+    };
+    let ix = Instruction {
+        program_id: setup.dex_pid,
+        accounts,
+        data: data.try_to_vec()?,
+    };
+    let tx = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&setup.payer.pubkey()),
+        &[&setup.payer, admin_kp],
+        setup.svm.latest_blockhash(),
+    );
+    map_litesvm_err(setup.svm.send_transaction(tx))?;
+    Ok(())
+}
+
+#[test]
+fn test_protocol_fee_minted_to_treasury() -> Result<(), Box<dyn Error>> {
+    let mut setup = setup_test_environment()?;
+    let admin_kp =
+        Keypair::from_bytes(&setup.payer.to_bytes()).expect("Failed to clone payer keypair");
+
+    set_upgrade_authority(&mut setup.svm, &setup.dex_pid, &admin_kp.pubkey());
+    let (user_kp, user_ata_a, user_ata_b, user_ata_lp) = setup_user_accounts(
+        &mut setup.svm,
+        &setup.payer,
+        &setup.mint_a,
+        &setup.mint_b,
+        &setup.lp_mint,
+    )?;
+
+    // Only the upgrade authority can create the config, even if the PDA was prefunded
+    map_litesvm_err(setup.svm.airdrop(&setup.config_pda, 1))?;
+    assert!(execute_set_protocol_fee(&mut setup, &user_kp, 1_667, true).is_err());
+
+    // Protocol takes 1/6 of the swap fees
+    execute_set_protocol_fee(&mut setup, &admin_kp, 1_667, true)?;
+    mint_to_ata(
+        &mut setup.svm,
+        &setup.payer,
+        &setup.mint_authority,
+        &setup.mint_a,
+        &user_ata_a,
+        3_000_000,
+    )?;
+    mint_to_ata(
+        &mut setup.svm,
+        &setup.payer,
+        &setup.mint_authority,
+        &setup.mint_b,
+        &user_ata_b,
+        2_000_000,
+    )?;
+    execute_add_liquidity(
+        &mut setup,
+        &user_kp,
+        &user_ata_a,
+        &user_ata_b,
+        &user_ata_lp,
+        1_000_000,
+        1_000_000,
+    )?;
+    let pool_after_first_deposit = get_pool_state(&setup.svm, &setup.pool_pda)?;
+    assert_eq!(pool_after_first_deposit.k_last, 1_000_000u128 * 1_000_000);
+    assert_eq!(get_token_balance(&setup.svm, &setup.treasury_lp), 0);
+
+    // Fee income grows k
+    execute_swap(&mut setup, &user_kp, &user_ata_a, &user_ata_b, 500_000, 1)?;
+    let expected_fee_shares = calculate_protocol_fee_shares(
+        get_token_balance(&setup.svm, &setup.vault_a_pk),
+        get_token_balance(&setup.svm, &setup.vault_b_pk),
+        pool_after_first_deposit.k_last,
+        pool_after_first_deposit.total_lp_supply,
+        1_667,
+    )
+    .unwrap();
+    assert!(expected_fee_shares > 0);
+
+    // A withdrawal can't skip settling the fee by leaving out the config or treasury LP
+    for accounts_passed in [11, 12] {
+        let mut withdraw_ix = liquidity_ix(
+            &setup,
+            &user_kp.pubkey(),
+            &user_ata_a,
+            &user_ata_b,
+            &user_ata_lp,
+            PoolInstruction::RemoveLiquidity {
+                amount_lp: 100_000,
+                deadline: None,
+                min_a: None,
+                min_b: None,
+            },
+        )?;
+        withdraw_ix.accounts.truncate(accounts_passed);
+        let tx = Transaction::new_signed_with_payer(
+            &[withdraw_ix],
+            Some(&setup.payer.pubkey()),
+            &[&setup.payer, &user_kp],
+            setup.svm.latest_blockhash(),
+        );
+        assert!(setup.svm.send_transaction(tx).is_err());
+    }
+    assert_eq!(get_token_balance(&setup.svm, &setup.treasury_lp), 0);
+
+    // The next liquidity event mints the accrued protocol fee
+    execute_add_liquidity(
+        &mut setup,
+        &user_kp,
+        &user_ata_a,
+        &user_ata_b,
+        &user_ata_lp,
+        1_000,
+        1_000,
+    )?;
+    assert_eq!(
+        get_token_balance(&setup.svm, &setup.treasury_lp),
+        expected_fee_shares
+    );
+    let pool = get_pool_state(&setup.svm, &setup.pool_pda)?;
+    assert_eq!(
+        pool.total_lp_supply,
+        get_token_balance(&setup.svm, &user_ata_lp) + expected_fee_shares + MINIMUM_LIQUIDITY
+    );
+    assert_eq!(
+        pool.k_last,
+        get_token_balance(&setup.svm, &setup.vault_a_pk) as u128
+            * get_token_balance(&setup.svm, &setup.vault_b_pk) as u128
+    );
+
+    // Only the admin can change the fee
+    assert!(execute_set_protocol_fee(&mut setup, &user_kp, 0, false).is_err());
+
+    // Switching the fee off resets k_last on the next liquidity event
+    execute_set_protocol_fee(&mut setup, &admin_kp, 0, false)?;
+    execute_add_liquidity(
+        &mut setup,
+        &user_kp,
+        &user_ata_a,
+        &user_ata_b,
+        &user_ata_lp,
+        1_000,
+        1_000,
+    )?;
+    assert_eq!(get_pool_state(&setup.svm, &setup.pool_pda)?.k_last, 0);
+    assert_eq!(
+        get_token_balance(&setup.svm, &setup.treasury_lp),
+        expected_fee_shares
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_swap_b_to_a() -> Result<(), Box<dyn Error>> {
+    let mut setup = setup_test_environment()?;
+    // --- Initial Liquidity Setup (using setup.payer) ---
+    let deposit_a = 123_456;
+    let deposit_b = 654_321;
+    let payer_ata_a = create_user_ata(
+        &mut setup.svm,
+        &setup.payer,
+        &setup.payer.pubkey(),
+        &setup.mint_a,
+    )?;
+    let payer_ata_b = create_user_ata(
+        &mut setup.svm,
+        &setup.payer,
+        &setup.payer.pubkey(),
+        &setup.mint_b,
+    )?;
+    let payer_ata_lp = create_user_ata(
+        &mut setup.svm,
+        &setup.payer,
+        &setup.payer.pubkey(),
+        &setup.lp_mint,
+    )?;
+    mint_to_ata(
+        &mut setup.svm,
+        &setup.payer,
+        &setup.mint_authority,
+        &setup.mint_a,
+        &payer_ata_a,
+        deposit_a,
+    )?;
+    mint_to_ata(
+        &mut setup.svm,
+        &setup.payer,
+        &setup.mint_authority,
+        &setup.mint_b,
+        &payer_ata_b,
+        deposit_b,
+    )?;
+    let payer_kp_clone =
+        Keypair::from_bytes(&setup.payer.to_bytes()).expect("Failed to clone payer keypair");
+    execute_add_liquidity(
+        &mut setup,
+        &payer_kp_clone,
+        &payer_ata_a,
+        &payer_ata_b,
+        &payer_ata_lp,
+        deposit_a,
+        deposit_b,
+    )?;
+    // --- End Initial Liquidity ---
+
+    // Setup Swapper User
+    let (swapper_kp, swapper_ata_a, swapper_ata_b, _swapper_ata_lp) = setup_user_accounts(
+        &mut setup.svm,
+        &setup.payer,
+        &setup.mint_a,
+        &setup.mint_b,
+        &setup.lp_mint,
+    )?;
+    let initial_swapper_a = 1_234_567;
+    let initial_swapper_b = 7_654_321;
+    mint_to_ata(
+        &mut setup.svm,
+        &setup.payer,
+        &setup.mint_authority,
+        &setup.mint_a,
+        &swapper_ata_a,
+        initial_swapper_a,
+    )?;
+    mint_to_ata(
+        &mut setup.svm,
+        &setup.payer,
+        &setup.mint_authority,
+        &setup.mint_b,
+        &swapper_ata_b,
+        initial_swapper_b,
+    )?;
+
+    let initial_user_a = get_token_balance(&setup.svm, &swapper_ata_a);
+    let initial_user_b = get_token_balance(&setup.svm, &swapper_ata_b);
+    let initial_vault_a = get_token_balance(&setup.svm, &setup.vault_a_pk);
+    let initial_vault_b = get_token_balance(&setup.svm, &setup.vault_b_pk);
+
+    // Build Swap IX (B -> A)
+    let amount_in = 22_222;
+    let min_out = 1;
+    // Use generic swap helper
+    execute_swap(
+        &mut setup,
+        &swapper_kp,
+        &swapper_ata_b, // Source is B
+        &swapper_ata_a, // Dest is A
+        amount_in,
+        min_out,
+    )?;
+
+    // Assert balances
+    let final_user_a = get_token_balance(&setup.svm, &swapper_ata_a);
+    let final_user_b = get_token_balance(&setup.svm, &swapper_ata_b);
+    let final_vault_a = get_token_balance(&setup.svm, &setup.vault_a_pk);
+    let final_vault_b = get_token_balance(&setup.svm, &setup.vault_b_pk);
+
+    // Calculate expected output (simple CPMM with 0.3% fee)
+    let effective_in = (amount_in as u128) * 997 / 1000;
+    let expected_amount_out =
+        (initial_vault_a as u128 * effective_in) / (initial_vault_b as u128 + effective_in);
+
+    println!("--- Swap B to A ---");
+    println!("Initial User A: {}", initial_user_a);
+    println!("Initial User B: {}", initial_user_b);
+    println!("Initial Vault A: {}", initial_vault_a);
+    println!("Initial Vault B: {}", initial_vault_b);
+    println!("Amount In (B): {}", amount_in);
+    println!("Effective In (B): {}", effective_in);
+    println!("Expected Amount Out (A): {}", expected_amount_out);
+    println!(
+        "Actual Amount Out (User A change): {}",
+        final_user_a - initial_user_a
+    );
+    println!("Final User A: {}", final_user_a);
+    println!("Final User B: {}", final_user_b);
+    println!("Final Vault A: {}", final_vault_a);
+    println!("Final Vault B: {}", final_vault_b);
+
+    // Assertions
+    assert_eq!(
+        final_user_b,
+        initial_user_b - amount_in,
+        "User B balance mismatch"
+    );
+    assert_eq!(
+        final_user_a,
+        initial_user_a + expected_amount_out as u64,
+        "User A balance mismatch"
+    );
+    assert_eq!(
+        final_vault_b,
+        initial_vault_b + amount_in,
+        "Vault B balance mismatch"
+    );
+    assert_eq!(
+        final_vault_a,
+        initial_vault_a - expected_amount_out as u64,
+        "Vault A balance mismatch"
+    );
+    // Check minimum out constraint
+    assert!(
+        final_user_a - initial_user_a >= min_out,
+        "Swap output less than minimum required"
+    );
+
+    println!("Swap B->A Test Passed!");
+    Ok(())
+}
+
+#[test]
+fn test_add_liquidity_refund() -> Result<(), Box<dyn Error>> {
+    let mut setup = setup_test_environment()?;
+    // --- Initial deposit (using setup.payer) ---
+    let initial_deposit_a = 100_000;
+    let initial_deposit_b = 500_000;
+    let payer_ata_a = create_user_ata(
+        &mut setup.svm,
+        &setup.payer,
+        &setup.payer.pubkey(),
+        &setup.mint_a,
+    )?;
+    let payer_ata_b = create_user_ata(
+        &mut setup.svm,
+        &setup.payer,
+        &setup.payer.pubkey(),
+        &setup.mint_b,
+    )?;
+    let payer_ata_lp = create_user_ata(
+        &mut setup.svm,
+        &setup.payer,
+        &setup.payer.pubkey(),
+        &setup.lp_mint,
+    )?;
+    mint_to_ata(
+        &mut setup.svm,
+        &setup.payer,
+        &setup.mint_authority,
+        &setup.mint_a,
+        &payer_ata_a,
+        initial_deposit_a,
+    )?;
+    mint_to_ata(
+        &mut setup.svm,
+        &setup.payer,
+        &setup.mint_authority,
+        &setup.mint_b,
+        &payer_ata_b,
+        initial_deposit_b,
+    )?;
+    let payer_kp_clone =
+        Keypair::from_bytes(&setup.payer.to_bytes()).expect("Failed to clone payer keypair");
+    execute_add_liquidity(
+        &mut setup,
+        &payer_kp_clone,
+        &payer_ata_a,
+        &payer_ata_b,
+        &payer_ata_lp,
+        initial_deposit_a,
+        initial_deposit_b,
+    )?;
+    // --- End Initial Deposit ---
+
+    // --- Second deposit (user tries to add too much A) ---
+    let (user_kp, user_ata_a, user_ata_b, user_ata_lp) = setup_user_accounts(
+        &mut setup.svm,
+        &setup.payer,
+        &setup.mint_a,
+        &setup.mint_b,
+        &setup.lp_mint,
+    )?;
+    let user_initial_a = 500_000;
+    let user_initial_b = 500_000;
+    mint_to_ata(
+        &mut setup.svm,
+        &setup.payer,
+        &setup.mint_authority,
+        &setup.mint_a,
+        &user_ata_a,
+        user_initial_a,
+    )?;
+    mint_to_ata(
+        &mut setup.svm,
+        &setup.payer,
+        &setup.mint_authority,
+        &setup.mint_b,
+        &user_ata_b,
+        user_initial_b,
+    )?;
+
+    let user_balance_a_before = get_token_balance(&setup.svm, &user_ata_a);
+    let user_balance_b_before = get_token_balance(&setup.svm, &user_ata_b);
+    let vault_a_before = get_token_balance(&setup.svm, &setup.vault_a_pk);
+    let vault_b_before = get_token_balance(&setup.svm, &setup.vault_b_pk);
+    let pool_state_before = get_pool_state(&setup.svm, &setup.pool_pda)?;
+    let total_lp_before = pool_state_before.total_lp_supply;
+    let user_lp_before = get_token_balance(&setup.svm, &user_ata_lp);
+
+    // User tries to deposit 30k A (excess) and 50k B.
+    let deposit_a_attempt = 30_000;
+    let deposit_b_attempt = 50_000;
+    execute_add_liquidity(
+        &mut setup,
+        &user_kp,
+        &user_ata_a,
+        &user_ata_b,
+        &user_ata_lp,
+        deposit_a_attempt,
+        deposit_b_attempt,
+    )?;
+
+    // --- Assertions ---
+    let user_balance_a_after = get_token_balance(&setup.svm, &user_ata_a);
+    let user_balance_b_after = get_token_balance(&setup.svm, &user_ata_b);
+    let vault_a_after = get_token_balance(&setup.svm, &setup.vault_a_pk);
+    let vault_b_after = get_token_balance(&setup.svm, &setup.vault_b_pk);
+    let user_lp_after = get_token_balance(&setup.svm, &user_ata_lp);
+    let pool_state_after = get_pool_state(&setup.svm, &setup.pool_pda)?;
+    let total_lp_after = pool_state_after.total_lp_supply;
+
+    // Calculate expected amounts based on pool ratio and deposit_b_attempt
+    let actual_deposit_a =
+        (vault_a_before as u128 * deposit_b_attempt as u128 / vault_b_before as u128) as u64;
+    let actual_deposit_b = deposit_b_attempt; // B is the limiting factor
     let refunded_a = deposit_a_attempt - actual_deposit_a;
 
-    // Calculate expected LP minted based on existing pool ratio
-    let expected_lp_minted =
-        (actual_deposit_a as u128 * total_lp_before as u128 / vault_a_before as u128) as u64;
+    // Calculate expected LP minted based on existing pool ratio
+    let expected_lp_minted =
+        (actual_deposit_a as u128 * total_lp_before as u128 / vault_a_before as u128) as u64;
+
+    println!("--- Add Liquidity Refund ---");
+    println!("Vaults Before: A={}, B={}", vault_a_before, vault_b_before);
+    println!(
+        "User Before: A={}, B={}, LP={}",
+        user_balance_a_before, user_balance_b_before, user_lp_before
+    );
+    println!(
+        "Attempted Deposit: A={}, B={}",
+        deposit_a_attempt, deposit_b_attempt
+    );
+    println!(
+        "Actual Deposit: A={}, B={}",
+        actual_deposit_a, actual_deposit_b
+    );
+    println!("Refunded A: {}", refunded_a);
+    println!("Expected LP Minted: {}", expected_lp_minted);
+    println!("Vaults After: A={}, B={}", vault_a_after, vault_b_after);
+    println!(
+        "User After: A={}, B={}, LP={}",
+        user_balance_a_after, user_balance_b_after, user_lp_after
+    );
+    println!(
+        "Total LP Before: {}, After: {}",
+        total_lp_before, total_lp_after
+    );
+
+    // Assert user balances
+    assert_eq!(
+        user_balance_a_after,
+        user_balance_a_before - actual_deposit_a,
+        "User A balance mismatch"
+    );
+    assert_eq!(
+        user_balance_b_after,
+        user_balance_b_before - actual_deposit_b,
+        "User B balance mismatch"
+    );
+    assert_eq!(
+        user_lp_after,
+        user_lp_before + expected_lp_minted,
+        "User LP balance mismatch"
+    );
+
+    // Assert vault balances
+    assert_eq!(
+        vault_a_after,
+        vault_a_before + actual_deposit_a,
+        "Vault A balance mismatch"
+    );
+    assert_eq!(
+        vault_b_after,
+        vault_b_before + actual_deposit_b,
+        "Vault B balance mismatch"
+    );
+
+    // Assert total LP supply
+    assert_eq!(
+        total_lp_after,
+        total_lp_before + expected_lp_minted,
+        "Total LP supply mismatch"
+    );
+
+    println!("Add Liquidity Refund Test Passed!");
+    Ok(())
+}
+
+// Helper to build an `AddLiquidity` / `RemoveLiquidity` instruction for the setup pool
+fn liquidity_ix(
+    setup: &TestSetup,
+    user: &Pubkey,
+    user_ata_a: &Pubkey,
+    user_ata_b: &Pubkey,
+    user_ata_lp: &Pubkey,
+    instruction: PoolInstruction,
+) -> Result<Instruction, Box<dyn Error>> {
+    let mut accounts = vec![
+        AccountMeta::new(*user, true),
+        AccountMeta::new(setup.pool_pda, false),
+        AccountMeta::new(setup.vault_a_pk, false),
+        AccountMeta::new(setup.vault_b_pk, false),
+        AccountMeta::new(setup.lp_mint, false),
+        AccountMeta::new(*user_ata_a, false),
+        AccountMeta::new(*user_ata_b, false),
+        AccountMeta::new(*user_ata_lp, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(setup.plugin_pid, false),
+        AccountMeta::new_readonly(setup.plugin_state_pk, false),
+        AccountMeta::new_readonly(setup.config_pda, false),
+        AccountMeta::new(setup.treasury_lp, false),
+    ];
+    if matches!(instruction, PoolInstruction::AddLiquidity { .. }) {
+        accounts.push(AccountMeta::new_readonly(setup.dex_pid, false)); // no oracle
+        accounts.push(AccountMeta::new(setup.locked_lp, false));
+    }
+    Ok(Instruction {
+        program_id: setup.dex_pid,
+        accounts,
+        data: instruction.try_to_vec()?,
+    })
+}
+
+#[test]
+fn test_liquidity_slippage_limits() -> Result<(), Box<dyn Error>> {
+    let mut setup = setup_test_environment()?;
+
+    // --- Initial Liquidity Setup (LP user) ---
+    let (lp_kp, lp_ata_a, lp_ata_b, lp_ata_lp) = setup_user_accounts(
+        &mut setup.svm,
+        &setup.payer,
+        &setup.mint_a,
+        &setup.mint_b,
+        &setup.lp_mint,
+    )?;
+    let lp_initial = 2_000_000;
+    mint_to_ata(
+        &mut setup.svm,
+        &setup.payer,
+        &setup.mint_authority,
+        &setup.mint_a,
+        &lp_ata_a,
+        lp_initial,
+    )?;
+    mint_to_ata(
+        &mut setup.svm,
+        &setup.payer,
+        &setup.mint_authority,
+        &setup.mint_b,
+        &lp_ata_b,
+        lp_initial,
+    )?;
+    execute_add_liquidity(
+        &mut setup, &lp_kp, &lp_ata_a, &lp_ata_b, &lp_ata_lp, 1_000_000, 1_000_000,
+    )?;
+
+    // Swapper moving the price between each quote and its execution
+    let (swapper_kp, swapper_ata_a, swapper_ata_b, _swapper_ata_lp) = setup_user_accounts(
+        &mut setup.svm,
+        &setup.payer,
+        &setup.mint_a,
+        &setup.mint_b,
+        &setup.lp_mint,
+    )?;
+    mint_to_ata(
+        &mut setup.svm,
+        &setup.payer,
+        &setup.mint_authority,
+        &setup.mint_a,
+        &swapper_ata_a,
+        1_000_000,
+    )?;
+
+    // --- Add liquidity: quote, move the pool, execute with the quoted minimum ---
+    let deposit = 100_000;
+    let pool_state = get_pool_state(&setup.svm, &setup.pool_pda)?;
+    let quoted_shares = Processor::calculate_add_liquidity(
+        get_token_balance(&setup.svm, &setup.vault_a_pk),
+        get_token_balance(&setup.svm, &setup.vault_b_pk),
+        deposit,
+        deposit,
+        pool_state.total_lp_supply,
+    )?
+    .shares_to_mint;
+    execute_swap(
+        &mut setup,
+        &swapper_kp,
+        &swapper_ata_a,
+        &swapper_ata_b,
+        200_000,
+        1,
+    )?;
+
+    let add_ix = |setup: &TestSetup, min_shares_out| {
+        liquidity_ix(
+            setup,
+            &lp_kp.pubkey(),
+            &lp_ata_a,
+            &lp_ata_b,
+            &lp_ata_lp,
+            PoolInstruction::AddLiquidity {
+                amount_a: deposit,
+                amount_b: deposit,
+                deadline: None,
+                min_shares_out: Some(min_shares_out),
+            },
+        )
+    };
+    let lp_balance_before = get_token_balance(&setup.svm, &lp_ata_lp);
+    let tx = Transaction::new_signed_with_payer(
+        &[add_ix(&setup, quoted_shares)?],
+        Some(&setup.payer.pubkey()),
+        &[&setup.payer, &lp_kp],
+        setup.svm.latest_blockhash(),
+    );
+    assert!(
+        setup.svm.send_transaction(tx).is_err(),
+        "AddLiquidity below min_shares_out should fail"
+    );
+    assert_eq!(get_token_balance(&setup.svm, &lp_ata_lp), lp_balance_before);
+    assert_eq!(
+        get_token_balance(&setup.svm, &lp_ata_a),
+        lp_initial - 1_000_000
+    );
+
+    // A looser minimum still goes through
+    let tx = Transaction::new_signed_with_payer(
+        &[add_ix(&setup, quoted_shares / 2)?],
+        Some(&setup.payer.pubkey()),
+        &[&setup.payer, &lp_kp],
+        setup.svm.latest_blockhash(),
+    );
+    map_litesvm_err(setup.svm.send_transaction(tx))?;
+    let minted = get_token_balance(&setup.svm, &lp_ata_lp) - lp_balance_before;
+    assert!(minted >= quoted_shares / 2 && minted < quoted_shares);
+
+    // --- Remove liquidity: quote, move the pool, execute with the quoted minimums ---
+    let amount_lp = get_token_balance(&setup.svm, &lp_ata_lp) / 2;
+    let pool_state = get_pool_state(&setup.svm, &setup.pool_pda)?;
+    let quote = Processor::calculate_remove_liquidity(
+        get_token_balance(&setup.svm, &setup.vault_a_pk),
+        get_token_balance(&setup.svm, &setup.vault_b_pk),
+        pool_state.total_lp_supply,
+        amount_lp,
+    )?;
+    execute_swap(
+        &mut setup,
+        &swapper_kp,
+        &swapper_ata_a,
+        &swapper_ata_b,
+        200_000,
+        1,
+    )?;
+
+    let remove_ix = |setup: &TestSetup, min_a, min_b| {
+        liquidity_ix(
+            setup,
+            &lp_kp.pubkey(),
+            &lp_ata_a,
+            &lp_ata_b,
+            &lp_ata_lp,
+            PoolInstruction::RemoveLiquidity {
+                amount_lp,
+                deadline: None,
+                min_a,
+                min_b,
+            },
+        )
+    };
+    let lp_balance_before = get_token_balance(&setup.svm, &lp_ata_lp);
+    let b_balance_before = get_token_balance(&setup.svm, &lp_ata_b);
+    let tx = Transaction::new_signed_with_payer(
+        &[remove_ix(
+            &setup,
+            Some(quote.withdraw_a),
+            Some(quote.withdraw_b),
+        )?],
+        Some(&setup.payer.pubkey()),
+        &[&setup.payer, &lp_kp],
+        setup.svm.latest_blockhash(),
+    );
+    assert!(
+        setup.svm.send_transaction(tx).is_err(),
+        "RemoveLiquidity below min_b should fail"
+    );
+    assert_eq!(get_token_balance(&setup.svm, &lp_ata_lp), lp_balance_before);
+    assert_eq!(get_token_balance(&setup.svm, &lp_ata_b), b_balance_before);
+
+    // Only bounding token A, which the swap added to the pool, succeeds
+    let tx = Transaction::new_signed_with_payer(
+        &[remove_ix(&setup, Some(quote.withdraw_a), None)?],
+        Some(&setup.payer.pubkey()),
+        &[&setup.payer, &lp_kp],
+        setup.svm.latest_blockhash(),
+    );
+    map_litesvm_err(setup.svm.send_transaction(tx))?;
+    assert_eq!(
+        get_token_balance(&setup.svm, &lp_ata_lp),
+        lp_balance_before - amount_lp
+    );
+    assert!(get_token_balance(&setup.svm, &lp_ata_b) - b_balance_before < quote.withdraw_b);
+    Ok(())
+}
+
+#[test]
+fn test_first_deposit_inflation_attack() -> Result<(), Box<dyn Error>> {
+    let mut setup = setup_test_environment()?;
+    let (attacker_kp, attacker_ata_a, attacker_ata_b, attacker_ata_lp) = setup_user_accounts(
+        &mut setup.svm,
+        &setup.payer,
+        &setup.mint_a,
+        &setup.mint_b,
+        &setup.lp_mint,
+    )?;
+    let (victim_kp, victim_ata_a, victim_ata_b, victim_ata_lp) = setup_user_accounts(
+        &mut setup.svm,
+        &setup.payer,
+        &setup.mint_a,
+        &setup.mint_b,
+        &setup.lp_mint,
+    )?;
+    for (mint, ata) in [
+        (setup.mint_a, attacker_ata_a),
+        (setup.mint_b, attacker_ata_b),
+        (setup.mint_a, victim_ata_a),
+        (setup.mint_b, victim_ata_b),
+    ] {
+        mint_to_ata(
+            &mut setup.svm,
+            &setup.payer,
+            &setup.mint_authority,
+            &mint,
+            &ata,
+            10_000_000,
+        )?;
+    }
+
+    // A dust first deposit worth a single share is no longer possible
+    assert!(execute_add_liquidity(
+        &mut setup,
+        &attacker_kp,
+        &attacker_ata_a,
+        &attacker_ata_b,
+        &attacker_ata_lp,
+        1,
+        1,
+    )
+    .is_err());
+    assert!(execute_add_liquidity(
+        &mut setup,
+        &attacker_kp,
+        &attacker_ata_a,
+        &attacker_ata_b,
+        &attacker_ata_lp,
+        MINIMUM_LIQUIDITY,
+        MINIMUM_LIQUIDITY,
+    )
+    .is_err());
+
+    // The first deposit must pass the pool's LP account to lock the minimum in
+    let mut first_deposit_ix = liquidity_ix(
+        &setup,
+        &attacker_kp.pubkey(),
+        &attacker_ata_a,
+        &attacker_ata_b,
+        &attacker_ata_lp,
+        PoolInstruction::AddLiquidity {
+            amount_a: MINIMUM_LIQUIDITY + 1,
+            amount_b: MINIMUM_LIQUIDITY + 1,
+            deadline: None,
+            min_shares_out: None,
+        },
+    )?;
+    first_deposit_ix.accounts.pop();
+    let tx = Transaction::new_signed_with_payer(
+        &[first_deposit_ix],
+        Some(&setup.payer.pubkey()),
+        &[&setup.payer, &attacker_kp],
+        setup.svm.latest_blockhash(),
+    );
+    assert!(setup.svm.send_transaction(tx).is_err());
+
+    // The smallest allowed first deposit mints one share
+    execute_add_liquidity(
+        &mut setup,
+        &attacker_kp,
+        &attacker_ata_a,
+        &attacker_ata_b,
+        &attacker_ata_lp,
+        MINIMUM_LIQUIDITY + 1,
+        MINIMUM_LIQUIDITY + 1,
+    )?;
+    assert_eq!(get_token_balance(&setup.svm, &attacker_ata_lp), 1);
+    assert_eq!(
+        get_pool_state(&setup.svm, &setup.pool_pda)?.total_lp_supply,
+        MINIMUM_LIQUIDITY + 1
+    );
+
+    // Donating to the vaults and syncing inflates the share price...
+    let donation = 1_000_000;
+    for (mint, vault) in [
+        (setup.mint_a, setup.vault_a_pk),
+        (setup.mint_b, setup.vault_b_pk),
+    ] {
+        mint_to_ata(
+            &mut setup.svm,
+            &setup.payer,
+            &setup.mint_authority,
+            &mint,
+            &vault,
+            donation,
+        )?;
+    }
+    execute_sync(&mut setup)?;
 
-    println!("--- Add Liquidity Refund ---");
-    println!("Vaults Before: A={}, B={}", vault_a_before, vault_b_before);
-    println!(
-        "User Before: A={}, B={}, LP={}",
-        user_balance_a_before, user_balance_b_before, user_lp_before
+    // ...but the victim's deposit still mints shares worth about what it paid
+    let deposit = 1_500_000;
+    execute_add_liquidity(
+        &mut setup,
+        &victim_kp,
+        &victim_ata_a,
+        &victim_ata_b,
+        &victim_ata_lp,
+        deposit,
+        deposit,
+    )?;
+    let victim_lp = get_token_balance(&setup.svm, &victim_ata_lp);
+    assert!(victim_lp > 0);
+
+    let remove = |setup: &mut TestSetup,
+                  user_kp: &Keypair,
+                  user_ata_a: &Pubkey,
+                  user_ata_b: &Pubkey,
+                  user_ata_lp: &Pubkey,
+                  amount_lp|
+     -> Result<(), Box<dyn Error>> {
+        let ix = liquidity_ix(
+            setup,
+            &user_kp.pubkey(),
+            user_ata_a,
+            user_ata_b,
+            user_ata_lp,
+            PoolInstruction::RemoveLiquidity {
+                amount_lp,
+                deadline: None,
+                min_a: None,
+                min_b: None,
+            },
+        )?;
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&setup.payer.pubkey()),
+            &[&setup.payer, user_kp],
+            setup.svm.latest_blockhash(),
+        );
+        map_litesvm_err(setup.svm.send_transaction(tx))?;
+        Ok(())
+    };
+    let victim_a_before = get_token_balance(&setup.svm, &victim_ata_a);
+    remove(
+        &mut setup,
+        &victim_kp,
+        &victim_ata_a,
+        &victim_ata_b,
+        &victim_ata_lp,
+        victim_lp,
+    )?;
+    let victim_received = get_token_balance(&setup.svm, &victim_ata_a) - victim_a_before;
+    assert!(victim_received >= deposit - deposit / 1_000);
+
+    // The attacker loses nearly all of the donation to the locked shares
+    let attacker_a_before = get_token_balance(&setup.svm, &attacker_ata_a);
+    remove(
+        &mut setup,
+        &attacker_kp,
+        &attacker_ata_a,
+        &attacker_ata_b,
+        &attacker_ata_lp,
+        1,
+    )?;
+    let attacker_received = get_token_balance(&setup.svm, &attacker_ata_a) - attacker_a_before;
+    assert!(attacker_received < donation / 100);
+    Ok(())
+}
+
+// Helper function to sync the pool's reserves to its vault balances
+fn execute_sync(setup: &mut TestSetup) -> Result<(), Box<dyn Error>> {
+    let sync_ix = Instruction {
+        program_id: setup.dex_pid,
+        accounts: vec![
+            AccountMeta::new(setup.pool_pda, false),
+            AccountMeta::new_readonly(setup.vault_a_pk, false),
+            AccountMeta::new_readonly(setup.vault_b_pk, false),
+        ],
+        data: PoolInstruction::Sync.try_to_vec()?,
+    };
+    let tx = Transaction::new_signed_with_payer(
+        &[sync_ix],
+        Some(&setup.payer.pubkey()),
+        &[&setup.payer],
+        setup.svm.latest_blockhash(),
     );
-    println!(
-        "Attempted Deposit: A={}, B={}",
-        deposit_a_attempt, deposit_b_attempt
+    map_litesvm_err(setup.svm.send_transaction(tx))?;
+    Ok(())
+}
+
+// Helper function to skim the vault surplus into `dst_a` / `dst_b`
+fn execute_skim(
+    setup: &mut TestSetup,
+    dst_a: &Pubkey,
+    dst_b: &Pubkey,
+) -> Result<(), Box<dyn Error>> {
+    let skim_ix = Instruction {
+        program_id: setup.dex_pid,
+        accounts: vec![
+            AccountMeta::new_readonly(setup.pool_pda, false),
+            AccountMeta::new(setup.vault_a_pk, false),
+            AccountMeta::new(setup.vault_b_pk, false),
+            AccountMeta::new(*dst_a, false),
+            AccountMeta::new(*dst_b, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data: PoolInstruction::Skim.try_to_vec()?,
+    };
+    let tx = Transaction::new_signed_with_payer(
+        &[skim_ix],
+        Some(&setup.payer.pubkey()),
+        &[&setup.payer],
+        setup.svm.latest_blockhash(),
     );
-    println!(
-        "Actual Deposit: A={}, B={}",
-        actual_deposit_a, actual_deposit_b
+    map_litesvm_err(setup.svm.send_transaction(tx))?;
+    Ok(())
+}
+
+#[test]
+fn test_vault_donation_sync_and_skim() -> Result<(), Box<dyn Error>> {
+    let mut setup = setup_test_environment()?;
+    let (user_kp, user_ata_a, user_ata_b, user_ata_lp) = setup_user_accounts(
+        &mut setup.svm,
+        &setup.payer,
+        &setup.mint_a,
+        &setup.mint_b,
+        &setup.lp_mint,
+    )?;
+    for (mint, ata) in [(setup.mint_a, user_ata_a), (setup.mint_b, user_ata_b)] {
+        mint_to_ata(
+            &mut setup.svm,
+            &setup.payer,
+            &setup.mint_authority,
+            &mint,
+            &ata,
+            10_000_000,
+        )?;
+    }
+    execute_add_liquidity(
+        &mut setup,
+        &user_kp,
+        &user_ata_a,
+        &user_ata_b,
+        &user_ata_lp,
+        1_000_000,
+        2_000_000,
+    )?;
+    let pool_state = get_pool_state(&setup.svm, &setup.pool_pda)?;
+    assert_eq!(
+        (pool_state.reserve_a, pool_state.reserve_b),
+        (1_000_000, 2_000_000)
     );
-    println!("Refunded A: {}", refunded_a);
-    println!("Expected LP Minted: {}", expected_lp_minted);
-    println!("Vaults After: A={}, B={}", vault_a_after, vault_b_after);
-    println!(
-        "User After: A={}, B={}, LP={}",
-        user_balance_a_after, user_balance_b_after, user_lp_after
+
+    // Tokens sent straight to the vaults are not reserves
+    let (donation_a, donation_b) = (500_000, 70_000);
+    for (mint, vault, amount) in [
+        (setup.mint_a, setup.vault_a_pk, donation_a),
+        (setup.mint_b, setup.vault_b_pk, donation_b),
+    ] {
+        mint_to_ata(
+            &mut setup.svm,
+            &setup.payer,
+            &setup.mint_authority,
+            &mint,
+            &vault,
+            amount,
+        )?;
+    }
+
+    // The swap is priced from the tracked reserves, not the vault balances
+    let amount_in = 10_000;
+    let expected_out =
+        Processor::calculate_swap(1_000_000, 2_000_000, amount_in, 3, 1000)?.amount_out;
+    let user_b_before = get_token_balance(&setup.svm, &user_ata_b);
+    execute_swap(&mut setup, &user_kp, &user_ata_a, &user_ata_b, amount_in, 1)?;
+    assert_eq!(
+        get_token_balance(&setup.svm, &user_ata_b) - user_b_before,
+        expected_out
     );
-    println!(
-        "Total LP Before: {}, After: {}",
-        total_lp_before, total_lp_after
+    let pool_state = get_pool_state(&setup.svm, &setup.pool_pda)?;
+    assert_eq!(
+        (pool_state.reserve_a, pool_state.reserve_b),
+        (1_000_000 + amount_in, 2_000_000 - expected_out)
     );
 
-    // Assert user balances
+    // Skim pays out exactly the donations and leaves the reserves alone
+    let (_recipient_kp, recipient_ata_a, recipient_ata_b, _recipient_ata_lp) = setup_user_accounts(
+        &mut setup.svm,
+        &setup.payer,
+        &setup.mint_a,
+        &setup.mint_b,
+        &setup.lp_mint,
+    )?;
+    execute_skim(&mut setup, &recipient_ata_a, &recipient_ata_b)?;
+    assert_eq!(get_token_balance(&setup.svm, &recipient_ata_a), donation_a);
+    assert_eq!(get_token_balance(&setup.svm, &recipient_ata_b), donation_b);
     assert_eq!(
-        user_balance_a_after,
-        user_balance_a_before - actual_deposit_a,
-        "User A balance mismatch"
+        get_token_balance(&setup.svm, &setup.vault_a_pk),
+        pool_state.reserve_a
     );
     assert_eq!(
-        user_balance_b_after,
-        user_balance_b_before - actual_deposit_b,
-        "User B balance mismatch"
+        get_token_balance(&setup.svm, &setup.vault_b_pk),
+        pool_state.reserve_b
+    );
+    assert_eq!(get_pool_state(&setup.svm, &setup.pool_pda)?, pool_state);
+
+    // Sync takes a later donation into the reserves instead
+    mint_to_ata(
+        &mut setup.svm,
+        &setup.payer,
+        &setup.mint_authority,
+        &setup.mint_a,
+        &setup.vault_a_pk,
+        donation_a,
+    )?;
+    execute_sync(&mut setup)?;
+    let synced = get_pool_state(&setup.svm, &setup.pool_pda)?;
+    assert_eq!(synced.reserve_a, pool_state.reserve_a + donation_a);
+    assert_eq!(synced.reserve_b, pool_state.reserve_b);
+    assert_eq!(synced.total_lp_supply, pool_state.total_lp_supply);
+    Ok(())
+}
+
+// Helper function to create the pool's oracle account
+fn execute_initialize_oracle(setup: &mut TestSetup) -> Result<Pubkey, Box<dyn Error>> {
+    let (oracle_pda, _bump) = find_oracle_address(&setup.dex_pid, &setup.pool_pda);
+    let init_ix = Instruction {
+        program_id: setup.dex_pid,
+        accounts: vec![
+            AccountMeta::new(setup.payer.pubkey(), true),
+            AccountMeta::new_readonly(setup.pool_pda, false),
+            AccountMeta::new(oracle_pda, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: PoolInstruction::InitializeOracle.try_to_vec()?,
+    };
+    let tx = Transaction::new_signed_with_payer(
+        &[init_ix],
+        Some(&setup.payer.pubkey()),
+        &[&setup.payer],
+        setup.svm.latest_blockhash(),
+    );
+    map_litesvm_err(setup.svm.send_transaction(tx))?;
+    Ok(oracle_pda)
+}
+
+// Helper function to read the TWAP over at least `seconds_ago` seconds
+fn execute_observe_price(
+    setup: &mut TestSetup,
+    oracle_pda: &Pubkey,
+    seconds_ago: u32,
+) -> Result<TwapPrice, Box<dyn Error>> {
+    let observe_ix = Instruction {
+        program_id: setup.dex_pid,
+        accounts: vec![
+            AccountMeta::new_readonly(setup.pool_pda, false),
+            AccountMeta::new_readonly(*oracle_pda, false),
+        ],
+        data: PoolInstruction::ObservePrice { seconds_ago }.try_to_vec()?,
+    };
+    let tx = Transaction::new_signed_with_payer(
+        &[observe_ix],
+        Some(&setup.payer.pubkey()),
+        &[&setup.payer],
+        setup.svm.latest_blockhash(),
+    );
+    let meta = map_litesvm_err(setup.svm.send_transaction(tx))?;
+    Ok(TwapPrice::try_from_slice(&meta.return_data.data)?)
+}
+
+#[test]
+fn test_twap_oracle() -> Result<(), Box<dyn Error>> {
+    let mut setup = setup_test_environment()?;
+    let (user_kp, user_ata_a, user_ata_b, user_ata_lp) = setup_user_accounts(
+        &mut setup.svm,
+        &setup.payer,
+        &setup.mint_a,
+        &setup.mint_b,
+        &setup.lp_mint,
+    )?;
+    for (mint, ata) in [(setup.mint_a, user_ata_a), (setup.mint_b, user_ata_b)] {
+        mint_to_ata(
+            &mut setup.svm,
+            &setup.payer,
+            &setup.mint_authority,
+            &mint,
+            &ata,
+            10_000_000,
+        )?;
+    }
+    let mut clock = setup.svm.get_sysvar::<Clock>();
+    let start = 1_700_000_000;
+    clock.unix_timestamp = start;
+    setup.svm.set_sysvar::<Clock>(&clock);
+
+    // Price of A is 2 B
+    execute_add_liquidity(
+        &mut setup,
+        &user_kp,
+        &user_ata_a,
+        &user_ata_b,
+        &user_ata_lp,
+        1_000_000,
+        2_000_000,
+    )?;
+    let oracle_pda = execute_initialize_oracle(&mut setup)?;
+    assert!(execute_initialize_oracle(&mut setup).is_err());
+    let initial_price_a = price_q64(1_000_000, 2_000_000).unwrap();
+
+    // 100 seconds later a swap moves the price and records an observation
+    clock.unix_timestamp = start + 100;
+    setup.svm.set_sysvar::<Clock>(&clock);
+    let swap_ix = Instruction {
+        program_id: setup.dex_pid,
+        accounts: vec![
+            AccountMeta::new(user_kp.pubkey(), true),
+            AccountMeta::new(setup.pool_pda, false),
+            AccountMeta::new(setup.vault_a_pk, false),
+            AccountMeta::new(setup.vault_b_pk, false),
+            AccountMeta::new(user_ata_a, false),
+            AccountMeta::new(user_ata_b, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(setup.plugin_pid, false),
+            AccountMeta::new_readonly(setup.plugin_state_pk, false),
+            AccountMeta::new(oracle_pda, false),
+        ],
+        data: PoolInstruction::Swap {
+            amount_in: 100_000,
+            min_out: 1,
+            deadline: None,
+        }
+        .try_to_vec()?,
+    };
+    let tx = Transaction::new_signed_with_payer(
+        &[swap_ix],
+        Some(&setup.payer.pubkey()),
+        &[&setup.payer, &user_kp],
+        setup.svm.latest_blockhash(),
     );
+    map_litesvm_err(setup.svm.send_transaction(tx))?;
+    let pool_state = get_pool_state(&setup.svm, &setup.pool_pda)?;
+    assert_eq!(pool_state.last_update_timestamp, start + 100);
+    assert_eq!(pool_state.price_a_cumulative, initial_price_a * 100);
+    let swapped_price_a = price_q64(pool_state.reserve_a, pool_state.reserve_b).unwrap();
+    assert!(swapped_price_a < initial_price_a);
+
+    // A donation does not move the price
+    mint_to_ata(
+        &mut setup.svm,
+        &setup.payer,
+        &setup.mint_authority,
+        &setup.mint_b,
+        &setup.vault_b_pk,
+        5_000_000,
+    )?;
+
+    // 300 seconds later: averaging from the swap covers the new price only...
+    clock.unix_timestamp = start + 400;
+    setup.svm.set_sysvar::<Clock>(&clock);
+    let recent = execute_observe_price(&mut setup, &oracle_pda, 300)?;
+    assert_eq!(recent.window_seconds, 300);
+    assert_eq!(recent.price_a, swapped_price_a);
+
+    // ...while a longer window falls back to the oracle's first observation
+    let full = execute_observe_price(&mut setup, &oracle_pda, 350)?;
+    assert_eq!(full.window_seconds, 400);
     assert_eq!(
-        user_lp_after,
-        user_lp_before + expected_lp_minted,
-        "User LP balance mismatch"
+        full.price_a,
+        (initial_price_a * 100 + swapped_price_a * 300) / 400
     );
 
-    // Assert vault balances
+    // Nothing was observed before the oracle was created
+    assert!(execute_observe_price(&mut setup, &oracle_pda, 401).is_err());
+    assert!(execute_observe_price(&mut setup, &oracle_pda, 0).is_err());
+    Ok(())
+}
+
+// Builds a `FlashSwap` lending `amount_out` to `borrower_ata` and invoking `callback`
+// (its program, accounts and data) once the tokens are lent
+fn flash_swap_ix(
+    setup: &TestSetup,
+    user: &Pubkey,
+    borrower_ata: &Pubkey,
+    amount_out: u64,
+    callback: Instruction,
+) -> Result<Instruction, Box<dyn Error>> {
+    let mut accounts = vec![
+        AccountMeta::new(*user, true),
+        AccountMeta::new(setup.pool_pda, false),
+        AccountMeta::new(setup.vault_a_pk, false),
+        AccountMeta::new(setup.vault_b_pk, false),
+        AccountMeta::new(*borrower_ata, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(setup.plugin_pid, false),
+        AccountMeta::new_readonly(setup.plugin_state_pk, false),
+        AccountMeta::new_readonly(callback.program_id, false),
+    ];
+    accounts.extend(callback.accounts);
+    Ok(Instruction {
+        program_id: setup.dex_pid,
+        accounts,
+        data: PoolInstruction::FlashSwap {
+            amount_out,
+            callback_data: callback.data,
+            deadline: None,
+        }
+        .try_to_vec()?,
+    })
+}
+
+#[test]
+fn test_flash_swap() -> Result<(), Box<dyn Error>> {
+    let mut setup = setup_test_environment()?;
+    let (user_kp, user_ata_a, user_ata_b, user_ata_lp) = setup_user_accounts(
+        &mut setup.svm,
+        &setup.payer,
+        &setup.mint_a,
+        &setup.mint_b,
+        &setup.lp_mint,
+    )?;
+    for (mint, ata) in [(setup.mint_a, user_ata_a), (setup.mint_b, user_ata_b)] {
+        mint_to_ata(
+            &mut setup.svm,
+            &setup.payer,
+            &setup.mint_authority,
+            &mint,
+            &ata,
+            10_000_000,
+        )?;
+    }
+    execute_add_liquidity(
+        &mut setup,
+        &user_kp,
+        &user_ata_a,
+        &user_ata_b,
+        &user_ata_lp,
+        1_000_000,
+        2_000_000,
+    )?;
+
+    // The SPL Token program stands in for the borrower's callback: it pays token B back
+    // from the user's account, under the user's signature
+    let vault_b = setup.vault_b_pk;
+    let repay_b = |amount: u64| {
+        spl_token::instruction::transfer(
+            &spl_token::id(),
+            &user_ata_b,
+            &vault_b,
+            &user_kp.pubkey(),
+            &[],
+            amount,
+        )
+    };
+    let flash_swap = |setup: &mut TestSetup, borrower_ata: &Pubkey, amount_out, callback| {
+        let ix = flash_swap_ix(setup, &user_kp.pubkey(), borrower_ata, amount_out, callback)?;
+        let tx = Transaction::new_signed_with_payer(
+            &[ix],
+            Some(&setup.payer.pubkey()),
+            &[&setup.payer, &user_kp],
+            setup.svm.latest_blockhash(),
+        );
+        Ok::<_, Box<dyn Error>>(setup.svm.send_transaction(tx))
+    };
+
+    // Borrowing B and paying back in B: 10_000 out needs ceil(10_000 * 1000 / 997) in
+    let amount_out = 10_000;
+    let repayment = 10_031;
+    let pool_state = get_pool_state(&setup.svm, &setup.pool_pda)?;
+    assert!(flash_swap(&mut setup, &user_ata_b, amount_out, repay_b(repayment - 1)?)?.is_err());
+    assert_eq!(get_pool_state(&setup.svm, &setup.pool_pda)?, pool_state);
+
+    let user_b_before = get_token_balance(&setup.svm, &user_ata_b);
+    map_litesvm_err(flash_swap(
+        &mut setup,
+        &user_ata_b,
+        amount_out,
+        repay_b(repayment)?,
+    )?)?;
     assert_eq!(
-        vault_a_after,
-        vault_a_before + actual_deposit_a,
-        "Vault A balance mismatch"
+        user_b_before - get_token_balance(&setup.svm, &user_ata_b),
+        repayment - amount_out
     );
+    let pool_state = get_pool_state(&setup.svm, &setup.pool_pda)?;
+    assert!(!pool_state.locked);
     assert_eq!(
-        vault_b_after,
-        vault_b_before + actual_deposit_b,
-        "Vault B balance mismatch"
+        (pool_state.reserve_a, pool_state.reserve_b),
+        (1_000_000, 2_000_000 + repayment - amount_out)
+    );
+    assert_eq!(
+        get_token_balance(&setup.svm, &setup.vault_b_pk),
+        pool_state.reserve_b
     );
 
-    // Assert total LP supply
+    // Borrowing A and paying back in B costs what SwapExactOut charges
+    let amount_out = 5_000;
+    let amount_in = Processor::calculate_swap_exact_out(
+        pool_state.reserve_b,
+        pool_state.reserve_a,
+        amount_out,
+        3,
+        1000,
+    )?
+    .amount_in;
+    assert!(flash_swap(&mut setup, &user_ata_a, amount_out, repay_b(amount_in - 1)?)?.is_err());
+    let user_a_before = get_token_balance(&setup.svm, &user_ata_a);
+    map_litesvm_err(flash_swap(
+        &mut setup,
+        &user_ata_a,
+        amount_out,
+        repay_b(amount_in)?,
+    )?)?;
     assert_eq!(
-        total_lp_after,
-        total_lp_before + expected_lp_minted,
-        "Total LP supply mismatch"
+        get_token_balance(&setup.svm, &user_ata_a) - user_a_before,
+        amount_out
+    );
+    let swapped = get_pool_state(&setup.svm, &setup.pool_pda)?;
+    assert_eq!(
+        (swapped.reserve_a, swapped.reserve_b),
+        (
+            pool_state.reserve_a - amount_out,
+            pool_state.reserve_b + amount_in
+        )
     );
 
-    println!("Add Liquidity Refund Test Passed!");
+    // The callback can't re-enter the pool while it is locked
+    let sync_ix = Instruction {
+        program_id: setup.dex_pid,
+        accounts: vec![
+            AccountMeta::new(setup.pool_pda, false),
+            AccountMeta::new_readonly(setup.vault_a_pk, false),
+            AccountMeta::new_readonly(setup.vault_b_pk, false),
+        ],
+        data: PoolInstruction::Sync.try_to_vec()?,
+    };
+    let failed = flash_swap(&mut setup, &user_ata_b, 1_000, sync_ix)?.unwrap_err();
+    assert_eq!(
+        failed.err,
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(PoolError::PoolLocked as u32)
+        )
+    );
+    assert_eq!(get_pool_state(&setup.svm, &setup.pool_pda)?, swapped);
     Ok(())
 }